@@ -0,0 +1,14 @@
+#version 330 core
+
+in VS_OUTPUT {
+    vec2 Uv;
+} IN;
+
+uniform sampler2D Image;
+
+out vec4 Color;
+
+void main()
+{
+    Color = texture(Image, IN.Uv);
+}
@@ -0,0 +1,13 @@
+#version 330 core
+
+out VS_OUTPUT {
+    vec2 Uv;
+} OUT;
+
+void main()
+{
+    // (0, 0), (2, 0), (0, 2) in uv, a triangle that covers the viewport
+    vec2 Uv = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    gl_Position = vec4(Uv * 2.0 - 1.0, 0.0, 1.0);
+    OUT.Uv = vec2(Uv.x, 1.0 - Uv.y);
+}
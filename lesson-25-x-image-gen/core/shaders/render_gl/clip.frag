@@ -0,0 +1,8 @@
+#version 330 core
+
+out vec4 Color;
+
+void main()
+{
+    Color = vec4(1.0);
+}
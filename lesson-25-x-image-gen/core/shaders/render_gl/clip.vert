@@ -0,0 +1,10 @@
+#version 330 core
+
+layout (location = 0) in vec2 Position;
+
+uniform mat4 ModelViewProjection;
+
+void main()
+{
+    gl_Position = ModelViewProjection * vec4(Position, 0.0, 1.0);
+}
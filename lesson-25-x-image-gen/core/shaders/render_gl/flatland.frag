@@ -2,11 +2,44 @@
 
 in VS_OUTPUT {
     vec4 Color;
+    vec2 Fringe;
+    vec4 Paint;
 } IN;
 
+uniform sampler2D GradientRamps;
+// 0 - opaque pass, keeps only fully opaque pixels, 1 - translucent pass, keeps the rest
+uniform int TranslucentPass;
+
 out vec4 Color;
 
+const float RAMP_WIDTH = 256.0;
+const float OPAQUE_ALPHA = 254.5 / 255.0;
+
+// Paint.w: 0 - solid, 1 - linear gradient with position in Paint.x,
+// 2 - radial gradient with position relative to the unit circle in Paint.xy.
+// Paint.z is the ramp row texture coordinate.
+vec4 PaintColor()
+{
+    if (IN.Paint.w < 0.5) {
+        return vec4(1.0);
+    }
+
+    float T = IN.Paint.w < 1.5 ? IN.Paint.x : length(IN.Paint.xy);
+    float U = (clamp(T, 0.0, 1.0) * (RAMP_WIDTH - 1.0) + 0.5) / RAMP_WIDTH;
+    return texture(GradientRamps, vec2(U, IN.Paint.z));
+}
+
 void main()
 {
-    Color = IN.Color;
+    // Fringe is zero on the outline and grows to unit length at the outer fringe edge
+    float Coverage = 1.0 - clamp(length(IN.Fringe), 0.0, 1.0);
+    vec4 Painted = IN.Color * PaintColor();
+    float Alpha = Painted.a * Coverage;
+
+    if ((Alpha >= OPAQUE_ALPHA) == (TranslucentPass != 0)) {
+        discard;
+    }
+
+    // premultiplied alpha
+    Color = vec4(Painted.rgb * Alpha, Alpha);
 }
\ No newline at end of file
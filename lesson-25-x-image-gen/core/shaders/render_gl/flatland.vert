@@ -9,11 +9,17 @@ layout (location = 5) in vec4 ModelCol1;
 layout (location = 6) in vec4 ModelCol2;
 layout (location = 7) in vec4 ModelCol3;
 layout (location = 8) in vec4 Color;
+layout (location = 9) in vec4 VertexColor;
+layout (location = 10) in vec4 Paint;
 
 uniform mat4 ViewProjection;
+uniform vec2 ViewportSize;
+uniform float FringeWidth;
 
 out VS_OUTPUT {
     vec4 Color;
+    vec2 Fringe;
+    vec4 Paint;
 } OUT;
 
 void main()
@@ -24,7 +30,32 @@ void main()
     Model[2] = ModelCol2;
     Model[3] = ModelCol3;
 
-    vec4 OutPos = ViewProjection * Model * vec4(Position.x + OffsetX, Position.y + OffsetY, 0.0, 1.0);
-    gl_Position = vec4(OutPos.x, OutPos.y, OutPos.z, OutPos.w);
-    OUT.Color = Color;
+    mat4 ModelViewProjection = ViewProjection * Model;
+    vec2 Pos = vec2(Position.x + OffsetX, Position.y + OffsetY);
+
+    vec4 OutPos = ModelViewProjection * vec4(Pos, 0.0, 1.0);
+    OUT.Fringe = vec2(0.0, 0.0);
+
+    // fill vertices have zero normal, fringe vertices are extruded along the outline normal
+    float NormalLength = length(Normal);
+    if (NormalLength > 0.0) {
+        vec2 Direction = Normal / NormalLength;
+        vec4 NormalPos = ModelViewProjection * vec4(Pos + Direction, 0.0, 1.0);
+
+        vec2 HalfViewport = ViewportSize * 0.5;
+        vec2 ScreenDirection = (NormalPos.xy / NormalPos.w - OutPos.xy / OutPos.w) * HalfViewport;
+        float ScreenLength = length(ScreenDirection);
+
+        if (ScreenLength > 0.0) {
+            // longer normals at sharp corners keep the fringe width constant, up to a miter limit
+            vec2 OffsetPixels = ScreenDirection / ScreenLength * FringeWidth * min(NormalLength, 4.0);
+            OutPos.xy += OffsetPixels / HalfViewport * OutPos.w;
+        }
+
+        OUT.Fringe = Direction;
+    }
+
+    gl_Position = OutPos;
+    OUT.Color = Color * VertexColor;
+    OUT.Paint = Paint;
 }
\ No newline at end of file
@@ -0,0 +1,74 @@
+#version 330 core
+
+in VS_OUTPUT {
+    vec2 Uv;
+    vec4 UvBounds;
+} IN;
+
+uniform sampler2D Atlas;
+uniform float DistanceScale;
+
+uniform vec4 TextColor;
+uniform float OutlineWidth;
+uniform vec4 OutlineColor;
+uniform vec2 ShadowOffset;
+uniform float ShadowSoftness;
+uniform vec4 ShadowColor;
+uniform float GlowRadius;
+uniform vec4 GlowColor;
+
+out vec4 Color;
+
+// signed distance to the glyph edge in ems, positive inside
+float Distance(vec2 Uv)
+{
+    return (texture(Atlas, Uv).r - 0.5) * DistanceScale;
+}
+
+// premultiplied "over" operator
+vec4 Over(vec4 Top, vec4 Bottom)
+{
+    return Top + Bottom * (1.0 - Top.a);
+}
+
+vec4 Premultiply(vec4 C, float Coverage)
+{
+    float Alpha = C.a * Coverage;
+    return vec4(C.rgb * Alpha, Alpha);
+}
+
+void main()
+{
+    float D = Distance(IN.Uv);
+    float AntiAlias = max(fwidth(D) * 0.5, 1e-5);
+
+    vec4 Result = vec4(0.0);
+
+    if (GlowRadius > 0.0) {
+        float Glow = 1.0 - smoothstep(0.0, GlowRadius, -D);
+        Result = Over(Premultiply(GlowColor, Glow), Result);
+    }
+
+    if (ShadowColor.a > 0.0) {
+        // samples outside the glyph cell would read neighbouring glyphs
+        float ShadowD = Distance(clamp(IN.Uv - ShadowOffset, IN.UvBounds.xy, IN.UvBounds.zw));
+        float Spread = AntiAlias + ShadowSoftness;
+        float Shadow = smoothstep(-Spread, Spread, ShadowD + OutlineWidth);
+        Result = Over(Premultiply(ShadowColor, Shadow), Result);
+    }
+
+    if (OutlineWidth > 0.0) {
+        float Outline = smoothstep(-AntiAlias, AntiAlias, D + OutlineWidth);
+        Result = Over(Premultiply(OutlineColor, Outline), Result);
+    }
+
+    float Fill = smoothstep(-AntiAlias, AntiAlias, D);
+    Result = Over(Premultiply(TextColor, Fill), Result);
+
+    if (Result.a <= 0.0) {
+        discard;
+    }
+
+    // blending expects straight alpha
+    Color = vec4(Result.rgb / Result.a, Result.a);
+}
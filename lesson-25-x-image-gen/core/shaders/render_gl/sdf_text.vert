@@ -0,0 +1,20 @@
+#version 330 core
+
+layout (location = 0) in vec2 Position;
+layout (location = 1) in vec2 Uv;
+layout (location = 2) in vec4 UvBounds;
+
+uniform mat4 ViewProjection;
+uniform mat4 Model;
+
+out VS_OUTPUT {
+    vec2 Uv;
+    vec4 UvBounds;
+} OUT;
+
+void main()
+{
+    gl_Position = ViewProjection * Model * vec4(Position, 0.0, 1.0);
+    OUT.Uv = Uv;
+    OUT.UvBounds = UvBounds;
+}
@@ -0,0 +1,77 @@
+use gl;
+use failure;
+use resources::Resources;
+use crate::Program;
+use crate::buffer::VertexArray;
+use crate::texture::{Texture2D, TextureLoadOptions, Sampler, SamplerOptions};
+
+/// Image stretched over the whole viewport, drawn before everything else.
+pub struct Backdrop {
+    gl: gl::Gl,
+    program: Program,
+    program_image_location: Option<i32>,
+    texture: Texture2D,
+    sampler: Sampler,
+    vao: VertexArray,
+    draw_enabled: bool,
+}
+
+impl Backdrop {
+    pub fn new(gl: &gl::Gl, res: &Resources, image: &str) -> Result<Backdrop, failure::Error> {
+        let program = Program::from_res(gl, res, "shaders/render_gl/backdrop")?;
+        let texture = Texture2D::from_res(gl, res, image, TextureLoadOptions::rgba())?;
+
+        Ok(Backdrop {
+            gl: gl.clone(),
+            program_image_location: program.get_uniform_location("Image"),
+            program,
+            texture,
+            sampler: Sampler::new(gl, &SamplerOptions::linear()),
+            vao: VertexArray::new(gl),
+            draw_enabled: true,
+        })
+    }
+
+    pub fn toggle(&mut self) {
+        self.draw_enabled = !self.draw_enabled;
+    }
+
+    /// Reloads the image if its resource has changed. The previous image is kept if the new one fails to load.
+    pub fn reload_if_modified(&mut self) {
+        match self.texture.reload_if_modified() {
+            Ok(true) => info!("reloaded backdrop image"),
+            Ok(false) => (),
+            Err(e) => error!("failed to reload backdrop image: {}", e),
+        }
+    }
+
+    pub fn render(&self) {
+        if !self.draw_enabled {
+            return;
+        }
+
+        let gl = &self.gl;
+
+        self.program.set_used();
+        if let Some(loc) = self.program_image_location {
+            self.program.set_uniform_1i(loc, 0);
+        }
+
+        self.texture.bind(0);
+        self.sampler.bind(0);
+        self.vao.bind();
+
+        unsafe {
+            gl.Disable(gl::DEPTH_TEST);
+            gl.DepthMask(gl::FALSE);
+            // one triangle covering the viewport, positions are generated in the vertex shader
+            gl.DrawArrays(gl::TRIANGLES, 0, 3);
+            gl.DepthMask(gl::TRUE);
+            gl.Enable(gl::DEPTH_TEST);
+        }
+
+        self.vao.unbind();
+        self.sampler.unbind(0);
+        self.texture.unbind(0);
+    }
+}
@@ -82,25 +82,331 @@ impl Buffer where {
         offset: usize,
         size: usize,
     ) -> Option<MappedBuffer<'r, T>> {
-        let ptr = self.gl.MapBufferRange(
-            self.buffer_type,                                               // target
-            (offset * ::std::mem::size_of::<T>()) as gl::types::GLsizeiptr, // offset
-            (size * ::std::mem::size_of::<T>()) as gl::types::GLsizeiptr,   //  length
-            gl::MAP_WRITE_BIT | gl::MAP_INVALIDATE_RANGE_BIT,               // usage
+        map_buffer_range_write_invalidate(&self.gl, self.buffer_type, offset, size)
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.DeleteBuffers(1, &mut self.vbo);
+        }
+    }
+}
+
+pub trait BufferType {
+    const BUFFER_TYPE: gl::types::GLuint;
+}
+
+pub struct BufferTypeArray;
+impl BufferType for BufferTypeArray {
+    const BUFFER_TYPE: gl::types::GLuint = gl::ARRAY_BUFFER;
+}
+
+pub struct BufferTypeElementArray;
+impl BufferType for BufferTypeElementArray {
+    const BUFFER_TYPE: gl::types::GLuint = gl::ELEMENT_ARRAY_BUFFER;
+}
+
+pub struct BufferTypeDrawIndirect;
+impl BufferType for BufferTypeDrawIndirect {
+    const BUFFER_TYPE: gl::types::GLuint = gl::DRAW_INDIRECT_BUFFER;
+}
+
+pub struct BufferTypeUniform;
+impl BufferType for BufferTypeUniform {
+    const BUFFER_TYPE: gl::types::GLuint = gl::UNIFORM_BUFFER;
+}
+
+/// Element index type usable in `ElementBuffer`.
+pub trait Index: Copy {
+    const INDEX_TYPE: gl::types::GLenum;
+}
+
+impl Index for u8 {
+    const INDEX_TYPE: gl::types::GLenum = gl::UNSIGNED_BYTE;
+}
+
+impl Index for u16 {
+    const INDEX_TYPE: gl::types::GLenum = gl::UNSIGNED_SHORT;
+}
+
+impl Index for u32 {
+    const INDEX_TYPE: gl::types::GLenum = gl::UNSIGNED_INT;
+}
+
+pub type ArrayBuffer<T> = TypedBuffer<BufferTypeArray, T>;
+pub type ElementBuffer<I> = TypedBuffer<BufferTypeElementArray, I>;
+pub type IndirectBuffer<T> = TypedBuffer<BufferTypeDrawIndirect, T>;
+pub type UniformBuffer<T> = TypedBuffer<BufferTypeUniform, T>;
+
+/// Buffer that remembers its element type, allocated capacity and length.
+///
+/// Data transfers (`upload`, `sub_data`, `copy_to`, `read`) go through the
+/// `COPY_READ_BUFFER` and `COPY_WRITE_BUFFER` targets, so they do not disturb
+/// the element array binding of a currently bound vertex array.
+pub struct TypedBuffer<B, T>
+where
+    B: BufferType,
+    T: Copy,
+{
+    gl: gl::Gl,
+    vbo: gl::types::GLuint,
+    len: usize,
+    capacity: usize,
+    _marker: ::std::marker::PhantomData<(B, T)>,
+}
+
+impl<B, T> TypedBuffer<B, T>
+where
+    B: BufferType,
+    T: Copy,
+{
+    pub fn new(gl: &gl::Gl) -> TypedBuffer<B, T> {
+        let mut vbo: gl::types::GLuint = 0;
+        unsafe {
+            gl.GenBuffers(1, &mut vbo);
+        }
+
+        TypedBuffer {
+            gl: gl.clone(),
+            vbo,
+            len: 0,
+            capacity: 0,
+            _marker: ::std::marker::PhantomData,
+        }
+    }
+
+    /// Number of valid elements.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of elements the buffer storage can hold.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            self.gl.BindBuffer(B::BUFFER_TYPE, self.vbo);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            self.gl.BindBuffer(B::BUFFER_TYPE, 0);
+        }
+    }
+
+    pub fn static_draw_data(&mut self, data: &[T]) {
+        self.buffer_data(data.len(), data.as_ptr(), gl::STATIC_DRAW);
+        self.len = data.len();
+    }
+
+    pub fn stream_draw_data(&mut self, data: &[T]) {
+        self.buffer_data(data.len(), data.as_ptr(), gl::STREAM_DRAW);
+        self.len = data.len();
+    }
+
+    /// Reallocates storage for `capacity` elements, discarding the contents.
+    pub fn stream_draw_data_null(&mut self, capacity: usize) {
+        self.buffer_data(capacity, ::std::ptr::null(), gl::STREAM_DRAW);
+        self.len = 0;
+    }
+
+    /// Reallocates storage for `capacity` elements, keeping the valid elements.
+    ///
+    /// The contents are moved through a temporary buffer, so the buffer object stays the same
+    /// and vertex arrays that refer to it remain valid.
+    pub fn grow(&mut self, capacity: usize) {
+        assert!(
+            capacity >= self.len,
+            "grow capacity {} is less than buffer length {}",
+            capacity,
+            self.len
+        );
+
+        if self.len == 0 {
+            self.stream_draw_data_null(capacity);
+            return;
+        }
+
+        let mut kept = TypedBuffer::<B, T>::new(&self.gl);
+        kept.stream_draw_data_null(self.len);
+        self.copy_to(&mut kept, 0, 0, self.len);
+
+        self.stream_draw_data_null(capacity);
+        kept.copy_to(self, 0, 0, kept.len);
+    }
+
+    /// Replaces buffer contents with `items_len` items, growing the storage if needed.
+    /// Length counts only items that were written, previous contents are kept if mapping fails without growing.
+    pub fn upload(&mut self, items_len: usize, items: impl Iterator<Item = T>) {
+        if items_len == 0 {
+            self.len = 0;
+            return;
+        }
+
+        if self.capacity < items_len {
+            self.buffer_data(items_len, ::std::ptr::null(), gl::STREAM_DRAW);
+            // reallocated buffer has undefined contents until the write below succeeds
+            self.len = 0;
+        }
+
+        unsafe {
+            self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, self.vbo);
+
+            match map_buffer_range_write_invalidate::<T>(&self.gl, gl::COPY_WRITE_BUFFER, 0, items_len) {
+                Some(mut buffer) => {
+                    let mut written = 0;
+                    for (index, item) in items.enumerate().take(items_len) {
+                        *buffer.get_unchecked_mut(index) = item;
+                        written = index + 1;
+                    }
+                    self.len = written;
+                }
+                None => error!("failed to map buffer to upload {} items", items_len),
+            }
+
+            self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+        }
+    }
+
+    /// Overwrites elements starting at `offset`. The range must fit into capacity.
+    pub fn sub_data(&mut self, offset: usize, data: &[T]) {
+        assert!(
+            offset + data.len() <= self.capacity,
+            "sub_data range {}..{} is out of buffer capacity {}",
+            offset,
+            offset + data.len(),
+            self.capacity
+        );
+
+        if data.len() == 0 {
+            return;
+        }
+
+        unsafe {
+            self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, self.vbo);
+            self.gl.BufferSubData(
+                gl::COPY_WRITE_BUFFER,
+                byte_len::<T>(offset) as gl::types::GLintptr,
+                byte_len::<T>(data.len()),
+                data.as_ptr() as *const gl::types::GLvoid,
+            );
+            self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+        }
+
+        self.len = ::std::cmp::max(self.len, offset + data.len());
+    }
+
+    /// Copies `count` elements from `src_offset` in this buffer to `dst_offset` in `target`.
+    pub fn copy_to<TargetB: BufferType>(
+        &self,
+        target: &mut TypedBuffer<TargetB, T>,
+        src_offset: usize,
+        dst_offset: usize,
+        count: usize,
+    ) {
+        assert!(
+            src_offset + count <= self.len,
+            "copy source range {}..{} is out of buffer length {}",
+            src_offset,
+            src_offset + count,
+            self.len
         );
-        if ptr == ::std::ptr::null_mut() {
-            return None;
+        assert!(
+            dst_offset + count <= target.capacity,
+            "copy target range {}..{} is out of buffer capacity {}",
+            dst_offset,
+            dst_offset + count,
+            target.capacity
+        );
+
+        if count == 0 {
+            return;
         }
-        return Some(MappedBuffer {
-            gl: self.gl.clone(),
-            buffer_type: self.buffer_type,
-            data: ::std::slice::from_raw_parts_mut(ptr as *mut T, size),
-            position: 0,
-        });
+
+        unsafe {
+            self.gl.BindBuffer(gl::COPY_READ_BUFFER, self.vbo);
+            self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, target.vbo);
+            self.gl.CopyBufferSubData(
+                gl::COPY_READ_BUFFER,
+                gl::COPY_WRITE_BUFFER,
+                byte_len::<T>(src_offset) as gl::types::GLintptr,
+                byte_len::<T>(dst_offset) as gl::types::GLintptr,
+                byte_len::<T>(count),
+            );
+            self.gl.BindBuffer(gl::COPY_READ_BUFFER, 0);
+            self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+        }
+
+        target.len = ::std::cmp::max(target.len, dst_offset + count);
+    }
+
+    /// Reads the valid elements back from GPU memory. Slow, meant for tests and debugging.
+    pub fn read(&self) -> Vec<T> {
+        let mut data = Vec::with_capacity(self.len);
+
+        if self.len > 0 {
+            unsafe {
+                self.gl.BindBuffer(gl::COPY_READ_BUFFER, self.vbo);
+                self.gl.GetBufferSubData(
+                    gl::COPY_READ_BUFFER,
+                    0,
+                    byte_len::<T>(self.len),
+                    data.as_mut_ptr() as *mut gl::types::GLvoid,
+                );
+                self.gl.BindBuffer(gl::COPY_READ_BUFFER, 0);
+                data.set_len(self.len);
+            }
+        }
+
+        data
+    }
+
+    fn buffer_data(&mut self, capacity: usize, data: *const T, usage: gl::types::GLenum) {
+        unsafe {
+            self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, self.vbo);
+            self.gl.BufferData(
+                gl::COPY_WRITE_BUFFER,
+                byte_len::<T>(capacity),
+                data as *const gl::types::GLvoid,
+                usage,
+            );
+            self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+        }
+
+        self.capacity = capacity;
     }
 }
 
-impl Drop for Buffer {
+impl<I: Index> TypedBuffer<BufferTypeElementArray, I> {
+    /// GL index type to pass to `DrawElements*` calls.
+    pub fn index_type(&self) -> gl::types::GLenum {
+        I::INDEX_TYPE
+    }
+}
+
+impl<T: Copy> TypedBuffer<BufferTypeUniform, T> {
+    /// Binds the whole buffer to uniform block binding point `index`.
+    pub fn bind_base(&self, index: u32) {
+        unsafe {
+            self.gl.BindBufferBase(gl::UNIFORM_BUFFER, index, self.vbo);
+        }
+    }
+}
+
+impl<B, T> Drop for TypedBuffer<B, T>
+where
+    B: BufferType,
+    T: Copy,
+{
     fn drop(&mut self) {
         unsafe {
             self.gl.DeleteBuffers(1, &mut self.vbo);
@@ -108,6 +414,37 @@ impl Drop for Buffer {
     }
 }
 
+fn byte_len<T>(count: usize) -> gl::types::GLsizeiptr {
+    let bytes = count
+        .checked_mul(::std::mem::size_of::<T>())
+        .expect("buffer size overflow");
+    assert!(bytes <= gl::types::GLsizeiptr::max_value() as usize, "buffer size overflow");
+    bytes as gl::types::GLsizeiptr
+}
+
+unsafe fn map_buffer_range_write_invalidate<'r, T>(
+    gl: &gl::Gl,
+    buffer_type: gl::types::GLuint,
+    offset: usize,
+    size: usize,
+) -> Option<MappedBuffer<'r, T>> {
+    let ptr = gl.MapBufferRange(
+        buffer_type,                                      // target
+        byte_len::<T>(offset) as gl::types::GLintptr,     // offset
+        byte_len::<T>(size),                              // length
+        gl::MAP_WRITE_BIT | gl::MAP_INVALIDATE_RANGE_BIT, // usage
+    );
+    if ptr == ::std::ptr::null_mut() {
+        return None;
+    }
+    Some(MappedBuffer {
+        gl: gl.clone(),
+        buffer_type,
+        data: ::std::slice::from_raw_parts_mut(ptr as *mut T, size),
+        position: 0,
+    })
+}
+
 pub struct MappedBuffer<'a, DataT: 'a> {
     gl: gl::Gl,
     buffer_type: gl::types::GLuint,
@@ -0,0 +1,409 @@
+use gl;
+use crate::na;
+use failure;
+use resources::Resources;
+use crate::Program;
+use crate::data;
+use crate::buffer::{ArrayBuffer, VertexArray};
+
+/// Allowed deviation in pixels for a projected rectangle to still count as axis aligned.
+const AXIS_ALIGNED_TOLERANCE_PX: f32 = 0.5;
+
+#[derive(VertexAttribPointers, Copy, Clone, Debug)]
+#[repr(C, packed)]
+struct ClipVertex {
+    #[location = 0]
+    pos: data::f32_f32,
+}
+
+/// Rectangle from `(0, 0)` to `size` in the space of `transform`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ClipRect {
+    pub transform: na::Projective3<f32>,
+    pub size: na::Vector2<f32>,
+}
+
+/// Nested clip rectangles, outermost first. Drawing is limited to their intersection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clip {
+    rects: Vec<ClipRect>,
+}
+
+impl Clip {
+    pub fn new(transform: &na::Projective3<f32>, size: na::Vector2<f32>) -> Clip {
+        Clip {
+            rects: vec![ClipRect { transform: *transform, size }],
+        }
+    }
+
+    /// Clip limited by one more rectangle inside this one.
+    pub fn nested(&self, transform: &na::Projective3<f32>, size: na::Vector2<f32>) -> Clip {
+        let mut rects = self.rects.clone();
+        rects.push(ClipRect { transform: *transform, size });
+        Clip { rects }
+    }
+
+    pub fn rects(&self) -> &[ClipRect] {
+        &self.rects
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum ClipMode {
+    Scissor,
+    Stencil {
+        depth_test: bool,
+        cull_face: bool,
+    },
+}
+
+/// Applies a `Clip` to the following draw calls.
+///
+/// Clips that stay axis aligned on screen use the scissor test. Rectangles under rotation
+/// or perspective are drawn into the stencil buffer instead, which requires the target
+/// to have one.
+pub struct Clipper {
+    gl: gl::Gl,
+    program: Program,
+    program_model_view_projection_location: Option<i32>,
+    quad: ArrayBuffer<ClipVertex>,
+    quad_vao: VertexArray,
+    active: Option<ClipMode>,
+}
+
+impl Clipper {
+    pub fn new(gl: &gl::Gl, res: &Resources) -> Result<Clipper, failure::Error> {
+        let program = Program::from_res(gl, res, "shaders/render_gl/clip")?;
+        let program_model_view_projection_location = program.get_uniform_location("ModelViewProjection");
+
+        let mut quad = ArrayBuffer::new(gl);
+        quad.static_draw_data(&[
+            ClipVertex { pos: (0.0, 0.0).into() },
+            ClipVertex { pos: (1.0, 0.0).into() },
+            ClipVertex { pos: (0.0, 1.0).into() },
+            ClipVertex { pos: (1.0, 1.0).into() },
+        ]);
+
+        let quad_vao = VertexArray::new(gl);
+        quad_vao.bind();
+        quad.bind();
+        ClipVertex::vertex_attrib_pointers(gl);
+        quad.unbind();
+        quad_vao.unbind();
+
+        Ok(Clipper {
+            gl: gl.clone(),
+            program,
+            program_model_view_projection_location,
+            quad,
+            quad_vao,
+            active: None,
+        })
+    }
+
+    /// Limits the following draw calls to the clip, until `end`.
+    ///
+    /// The stencil path changes the current program and vertex array, so callers must bind
+    /// theirs again. Returns `false` if nothing inside the clip is visible.
+    pub fn begin(&mut self, vp_matrix: &na::Matrix4<f32>, clip: &Clip) -> bool {
+        self.end();
+
+        let gl = &self.gl;
+        let viewport = viewport(gl);
+
+        match scissor_rect(vp_matrix, &viewport, clip) {
+            Some(None) => false,
+            Some(Some((x, y, w, h))) => {
+                unsafe {
+                    gl.Enable(gl::SCISSOR_TEST);
+                    gl.Scissor(x, y, w, h);
+                }
+                self.active = Some(ClipMode::Scissor);
+                true
+            },
+            None => {
+                let (x, y, w, h) = match stencil_box(vp_matrix, &viewport, clip) {
+                    Some(stencil_box) => stencil_box,
+                    None => return false,
+                };
+
+                let mode = unsafe {
+                    ClipMode::Stencil {
+                        depth_test: gl.IsEnabled(gl::DEPTH_TEST) == gl::TRUE,
+                        cull_face: gl.IsEnabled(gl::CULL_FACE) == gl::TRUE,
+                    }
+                };
+
+                // the stencil is cleared and tested only in the box around the clip, values left
+                // by earlier clips outside of it are never compared
+                unsafe {
+                    gl.Enable(gl::SCISSOR_TEST);
+                    gl.Scissor(x, y, w, h);
+                    gl.Disable(gl::DEPTH_TEST);
+                    gl.Disable(gl::CULL_FACE);
+                    gl.Enable(gl::STENCIL_TEST);
+                    gl.StencilMask(0xFF);
+                    gl.ClearStencil(0);
+                    gl.Clear(gl::STENCIL_BUFFER_BIT);
+
+                    gl.ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+                    gl.DepthMask(gl::FALSE);
+                    gl.StencilFunc(gl::ALWAYS, 0, 0xFF);
+                    gl.StencilOp(gl::KEEP, gl::KEEP, gl::INCR);
+                }
+
+                // every rectangle covers a pixel at most once, so pixels inside all of them
+                // end up equal to the rectangle count
+                self.program.set_used();
+                self.quad_vao.bind();
+                for rect in clip.rects() {
+                    if let Some(loc) = self.program_model_view_projection_location {
+                        let model = na::convert::<_, na::Matrix4<f32>>(rect.transform)
+                            * na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(rect.size.x, rect.size.y, 1.0));
+                        self.program.set_uniform_matrix_4fv(loc, &(vp_matrix * model));
+                    }
+                    unsafe {
+                        gl.DrawArrays(gl::TRIANGLE_STRIP, 0, self.quad.len() as i32);
+                    }
+                }
+                self.quad_vao.unbind();
+
+                unsafe {
+                    gl.ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+                    gl.DepthMask(gl::TRUE);
+                    gl.StencilFunc(gl::EQUAL, clip.rects().len() as i32, 0xFF);
+                    gl.StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+                    gl.StencilMask(0);
+
+                    if let ClipMode::Stencil { depth_test: true, .. } = mode {
+                        gl.Enable(gl::DEPTH_TEST);
+                    }
+                }
+
+                self.active = Some(mode);
+                true
+            },
+        }
+    }
+
+    /// Removes the active clip, if any.
+    pub fn end(&mut self) {
+        let gl = &self.gl;
+        match self.active.take() {
+            None => (),
+            Some(ClipMode::Scissor) => unsafe {
+                gl.Disable(gl::SCISSOR_TEST);
+            },
+            Some(ClipMode::Stencil { cull_face, .. }) => unsafe {
+                gl.StencilMask(0xFF);
+                gl.Disable(gl::STENCIL_TEST);
+                gl.Disable(gl::SCISSOR_TEST);
+                if cull_face {
+                    gl.Enable(gl::CULL_FACE);
+                }
+            },
+        }
+    }
+}
+
+fn viewport(gl: &gl::Gl) -> [i32; 4] {
+    let mut viewport = [0i32; 4];
+    unsafe {
+        gl.GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+    }
+    viewport
+}
+
+/// Scissor box for the clip, `Some(None)` if the box is empty.
+///
+/// Returns `None` if any rectangle is not axis aligned on screen or crosses the camera plane.
+fn scissor_rect(vp_matrix: &na::Matrix4<f32>, viewport: &[i32; 4], clip: &Clip) -> Option<Option<(i32, i32, i32, i32)>> {
+    let bounds = clip.rects()
+        .iter()
+        .map(|rect| screen_rect(vp_matrix, viewport, rect))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(pixel_box(viewport, bounds))
+}
+
+/// Box around the pixels a stencil clip can cover, `None` if the box is empty.
+///
+/// Rectangles that cross the camera plane do not limit the box.
+fn stencil_box(vp_matrix: &na::Matrix4<f32>, viewport: &[i32; 4], clip: &Clip) -> Option<(i32, i32, i32, i32)> {
+    let bounds = clip.rects()
+        .iter()
+        .filter_map(|rect| screen_corners(vp_matrix, viewport, rect))
+        .map(|corners| corner_bounds(&corners));
+
+    pixel_box(viewport, bounds)
+}
+
+/// Pixels inside the viewport and all window space bounds, `None` if there are none.
+///
+/// Bounds are rounded to the pixels whose centers they contain, as in rasterization.
+fn pixel_box<I>(viewport: &[i32; 4], bounds: I) -> Option<(i32, i32, i32, i32)>
+    where I: IntoIterator<Item = (na::Vector2<f32>, na::Vector2<f32>)>
+{
+    let mut min = na::Vector2::new(viewport[0] as f32, viewport[1] as f32);
+    let mut max = na::Vector2::new((viewport[0] + viewport[2]) as f32, (viewport[1] + viewport[3]) as f32);
+
+    for (rect_min, rect_max) in bounds {
+        min = na::Vector2::new(min.x.max(rect_min.x), min.y.max(rect_min.y));
+        max = na::Vector2::new(max.x.min(rect_max.x), max.y.min(rect_max.y));
+    }
+
+    let (x, y) = (min.x.round() as i32, min.y.round() as i32);
+    let (w, h) = (max.x.round() as i32 - x, max.y.round() as i32 - y);
+
+    if w <= 0 || h <= 0 {
+        None
+    } else {
+        Some((x, y, w, h))
+    }
+}
+
+/// Window space bounds of an axis aligned rectangle.
+fn screen_rect(vp_matrix: &na::Matrix4<f32>, viewport: &[i32; 4], rect: &ClipRect) -> Option<(na::Vector2<f32>, na::Vector2<f32>)> {
+    let corners = screen_corners(vp_matrix, viewport, rect)?;
+
+    let same = |a: f32, b: f32| (a - b).abs() <= AXIS_ALIGNED_TOLERANCE_PX;
+    let [a, b, c, d] = corners;
+    let axis_aligned = (same(a.y, b.y) && same(b.x, c.x) && same(c.y, d.y) && same(d.x, a.x))
+        || (same(a.x, b.x) && same(b.y, c.y) && same(c.x, d.x) && same(d.y, a.y));
+
+    if !axis_aligned {
+        return None;
+    }
+
+    Some(corner_bounds(&corners))
+}
+
+/// Window space corners of the rectangle, `None` if it crosses the camera plane.
+fn screen_corners(vp_matrix: &na::Matrix4<f32>, viewport: &[i32; 4], rect: &ClipRect) -> Option<[na::Vector2<f32>; 4]> {
+    let mvp = vp_matrix * na::convert::<_, na::Matrix4<f32>>(rect.transform);
+
+    let mut corners = [na::Vector2::new(0.0, 0.0); 4];
+    let local = [(0.0, 0.0), (rect.size.x, 0.0), (rect.size.x, rect.size.y), (0.0, rect.size.y)];
+
+    for (corner, &(x, y)) in corners.iter_mut().zip(local.iter()) {
+        let p = mvp * na::Vector4::new(x, y, 0.0, 1.0);
+        if p.w <= ::std::f32::EPSILON {
+            return None;
+        }
+        *corner = na::Vector2::new(
+            viewport[0] as f32 + (p.x / p.w + 1.0) * 0.5 * viewport[2] as f32,
+            viewport[1] as f32 + (p.y / p.w + 1.0) * 0.5 * viewport[3] as f32,
+        );
+    }
+
+    Some(corners)
+}
+
+fn corner_bounds(corners: &[na::Vector2<f32>; 4]) -> (na::Vector2<f32>, na::Vector2<f32>) {
+    let [a, b, c, d] = *corners;
+    let min = na::Vector2::new(a.x.min(b.x).min(c.x).min(d.x), a.y.min(b.y).min(c.y).min(d.y));
+    let max = na::Vector2::new(a.x.max(b.x).max(c.x).max(d.x), a.y.max(b.y).max(c.y).max(d.y));
+    (min, max)
+}
+
+#[cfg(test)]
+mod clip_tests {
+    use super::*;
+
+    const VIEWPORT: [i32; 4] = [0, 0, 800, 600];
+
+    /// Maps root coordinates with y pointing down to the viewport, like the window camera.
+    fn y_down_projection(viewport: &[i32; 4]) -> na::Matrix4<f32> {
+        na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(2.0 / viewport[2] as f32, -2.0 / viewport[3] as f32, 1.0))
+            .append_translation(&na::Vector3::new(-1.0, 1.0, 0.0))
+    }
+
+    fn translation(x: f32, y: f32) -> na::Projective3<f32> {
+        na::convert(na::Translation3::new(x, y, 0.0))
+    }
+
+    fn assert_near(expected: na::Vector2<f32>, actual: na::Vector2<f32>) {
+        assert!((expected - actual).norm() < 0.001, "expected {:?}, got {:?}", expected, actual);
+    }
+
+    fn rect(transform: na::Projective3<f32>, w: f32, h: f32) -> ClipRect {
+        ClipRect { transform, size: na::Vector2::new(w, h) }
+    }
+
+    #[test]
+    fn y_down_rect_is_flipped_to_window_space() {
+        let vp = y_down_projection(&VIEWPORT);
+
+        let (min, max) = screen_rect(&vp, &VIEWPORT, &rect(translation(10.0, 20.0), 100.0, 50.0)).unwrap();
+
+        assert_near(na::Vector2::new(10.0, 530.0), min);
+        assert_near(na::Vector2::new(110.0, 580.0), max);
+
+        let clip = Clip::new(&translation(10.0, 20.0), na::Vector2::new(100.0, 50.0));
+        assert_eq!(Some(Some((10, 530, 100, 50))), scissor_rect(&vp, &VIEWPORT, &clip));
+    }
+
+    #[test]
+    fn scaled_rect_is_scaled_on_screen() {
+        let vp = y_down_projection(&VIEWPORT);
+        let scale = na::Projective3::from_matrix_unchecked(na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(2.0, 3.0, 1.0)));
+
+        let clip = Clip::new(&scale, na::Vector2::new(100.0, 50.0));
+
+        assert_eq!(Some(Some((0, 450, 200, 150))), scissor_rect(&vp, &VIEWPORT, &clip));
+    }
+
+    #[test]
+    fn viewport_offset_moves_rect() {
+        let viewport = [100, 50, 800, 600];
+        let vp = y_down_projection(&viewport);
+
+        let clip = Clip::new(&translation(10.0, 20.0), na::Vector2::new(100.0, 50.0));
+
+        assert_eq!(Some(Some((110, 580, 100, 50))), scissor_rect(&vp, &viewport, &clip));
+    }
+
+    #[test]
+    fn nested_rects_intersect() {
+        let vp = y_down_projection(&VIEWPORT);
+
+        let clip = Clip::new(&translation(0.0, 0.0), na::Vector2::new(100.0, 100.0))
+            .nested(&translation(50.0, 60.0), na::Vector2::new(100.0, 100.0));
+
+        assert_eq!(Some(Some((50, 500, 50, 40))), scissor_rect(&vp, &VIEWPORT, &clip));
+    }
+
+    #[test]
+    fn rect_is_limited_to_viewport() {
+        let vp = y_down_projection(&VIEWPORT);
+
+        let clip = Clip::new(&translation(-50.0, 550.0), na::Vector2::new(100.0, 100.0));
+
+        assert_eq!(Some(Some((0, 0, 50, 50))), scissor_rect(&vp, &VIEWPORT, &clip));
+    }
+
+    #[test]
+    fn disjoint_rects_leave_nothing_visible() {
+        let vp = y_down_projection(&VIEWPORT);
+
+        let clip = Clip::new(&translation(0.0, 0.0), na::Vector2::new(100.0, 100.0))
+            .nested(&translation(200.0, 0.0), na::Vector2::new(100.0, 100.0));
+
+        assert_eq!(Some(None), scissor_rect(&vp, &VIEWPORT, &clip));
+        assert_eq!(None, stencil_box(&vp, &VIEWPORT, &clip));
+    }
+
+    #[test]
+    fn rotated_rect_needs_stencil_limited_to_its_bounds() {
+        let vp = y_down_projection(&VIEWPORT);
+        let rotation: na::Projective3<f32> = na::convert(
+            na::Isometry3::new(na::Vector3::new(200.0, 100.0, 0.0), na::Vector3::z() * ::std::f32::consts::FRAC_PI_4)
+        );
+
+        let clip = Clip::new(&rotation, na::Vector2::new(100.0, 100.0));
+
+        assert_eq!(None, scissor_rect(&vp, &VIEWPORT, &clip));
+        // corners at x 200 - 70.7 ..= 200 + 70.7 and y 100 ..= 241.4 in root coordinates
+        assert_eq!(Some((129, 359, 142, 141)), stencil_box(&vp, &VIEWPORT, &clip));
+    }
+}
@@ -26,6 +26,37 @@ impl ColorBuffer {
         }
     }
 
+    /// Blending for colors that are already multiplied by their alpha.
+    pub fn set_premultiplied_blend_func(&self, gl: &gl::Gl) {
+        unsafe {
+            gl.BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+        }
+    }
+
+    pub fn enable_depth_write(&self, gl: &gl::Gl) {
+        unsafe {
+            gl.DepthMask(gl::TRUE);
+        }
+    }
+
+    pub fn disable_depth_write(&self, gl: &gl::Gl) {
+        unsafe {
+            gl.DepthMask(gl::FALSE);
+        }
+    }
+
+    pub fn depth_func_less(&self, gl: &gl::Gl) {
+        unsafe {
+            gl.DepthFunc(gl::LESS);
+        }
+    }
+
+    pub fn depth_func_less_equal(&self, gl: &gl::Gl) {
+        unsafe {
+            gl.DepthFunc(gl::LEQUAL);
+        }
+    }
+
     pub fn clear(&self, gl: &gl::Gl) {
         unsafe {
             gl.Clear(gl::COLOR_BUFFER_BIT);
@@ -28,6 +28,15 @@ impl i8_ {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        i8_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<i8> for i8_ {
@@ -65,6 +74,15 @@ impl i8_i8 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        i8_i8_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(i8, i8)> for i8_i8 {
@@ -104,6 +122,15 @@ impl i8_i8_i8 {
 
         gl.EnableVertexAttribArray(location as gl::types::GLuint);
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        i8_i8_i8_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(i8, i8, i8)> for i8_i8_i8 {
@@ -143,6 +170,15 @@ impl i8_i8_i8_i8 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        i8_i8_i8_i8_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(i8, i8, i8, i8)> for i8_i8_i8_i8 {
@@ -333,6 +369,15 @@ impl i16_ {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        i16_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<i16> for i16_ {
@@ -370,6 +415,15 @@ impl i16_i16 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        i16_i16_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(i16, i16)> for i16_i16 {
@@ -408,6 +462,15 @@ impl i16_i16_i16 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        i16_i16_i16_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(i16, i16, i16)> for i16_i16_i16 {
@@ -447,6 +510,15 @@ impl i16_i16_i16_i16 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        i16_i16_i16_i16_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(i16, i16, i16, i16)> for i16_i16_i16_i16 {
@@ -637,6 +709,15 @@ impl i32_ {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        i32_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<i32> for i32_ {
@@ -674,6 +755,15 @@ impl i32_i32 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        i32_i32_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(i32, i32)> for i32_i32 {
@@ -712,6 +802,15 @@ impl i32_i32_i32 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        i32_i32_i32_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(i32, i32, i32)> for i32_i32_i32 {
@@ -751,6 +850,15 @@ impl i32_i32_i32_i32 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        i32_i32_i32_i32_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(i32, i32, i32, i32)> for i32_i32_i32_i32 {
@@ -941,6 +1049,15 @@ impl u8_ {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        u8_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<u8> for u8_ {
@@ -978,6 +1095,15 @@ impl u8_u8 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        u8_u8_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(u8, u8)> for u8_u8 {
@@ -1016,6 +1142,15 @@ impl u8_u8_u8 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        u8_u8_u8_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(u8, u8, u8)> for u8_u8_u8 {
@@ -1055,6 +1190,15 @@ impl u8_u8_u8_u8 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        u8_u8_u8_u8_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(u8, u8, u8, u8)> for u8_u8_u8_u8 {
@@ -1245,6 +1389,15 @@ impl u16_ {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        u16_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<u16> for u16_ {
@@ -1282,6 +1435,15 @@ impl u16_u16 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        u16_u16_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(u16, u16)> for u16_u16 {
@@ -1320,6 +1482,15 @@ impl u16_u16_u16 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        u16_u16_u16_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(u16, u16, u16)> for u16_u16_u16 {
@@ -1359,6 +1530,15 @@ impl u16_u16_u16_u16 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        u16_u16_u16_u16_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(u16, u16, u16, u16)> for u16_u16_u16_u16 {
@@ -1549,6 +1729,15 @@ impl u32_ {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        u32_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<u32> for u32_ {
@@ -1586,6 +1775,15 @@ impl u32_u32 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        u32_u32_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(u32, u32)> for u32_u32 {
@@ -1624,6 +1822,15 @@ impl u32_u32_u32 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        u32_u32_u32_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(u32, u32, u32)> for u32_u32_u32 {
@@ -1663,6 +1870,15 @@ impl u32_u32_u32_u32 {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        u32_u32_u32_u32_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(u32, u32, u32, u32)> for u32_u32_u32_u32 {
@@ -2353,6 +2569,15 @@ impl i2_i10_i10_i10_rev {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        i2_i10_i10_i10_rev_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 // -----------------------------------------
@@ -2384,6 +2609,15 @@ impl u2_u10_u10_u10_rev {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        u2_u10_u10_u10_rev_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 impl From<(f32, f32, f32, f32)> for u2_u10_u10_u10_rev {
@@ -2423,6 +2657,15 @@ impl u10_u11_u11_rev {
             offset as *const gl::types::GLvoid,
         );
     }
+
+    pub unsafe fn vertex_attrib_pointer_normalized(
+        gl: &gl::Gl,
+        stride: usize,
+        location: usize,
+        offset: usize,
+    ) {
+        u10_u11_u11_rev_float::vertex_attrib_pointer(gl, stride, location, offset)
+    }
 }
 
 // -----------------------------------------
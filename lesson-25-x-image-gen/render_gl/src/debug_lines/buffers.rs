@@ -1,70 +1,81 @@
 use crate::data;
 use gl;
 use crate::na;
+use crate::clip::Clip;
 
 #[derive(VertexAttribPointers, Copy, Clone, Debug)]
 #[repr(C, packed)]
 pub struct LinePoint {
-    #[location = "0"]
+    #[location = 0]
     pub pos: data::f32_f32_f32,
-    #[location = "1"]
+    #[location = 1]
     pub color: data::u2_u10_u10_u10_rev_float,
 }
 
-use crate::buffer::{Buffer, VertexArray};
+use crate::buffer::VertexArray;
+use crate::ring_buffer::RingBuffer;
 
 pub struct MultiDrawItem {
     pub model_matrix: na::Matrix4<f32>,
     pub starting_index: i32,
     pub index_count: i32,
+    pub clip: Option<Clip>,
 }
 
 pub struct Buffers {
-    pub vbo_capacity: usize,
+    gl: gl::Gl,
     pub multi_draw_items: Vec<MultiDrawItem>,
-    lines_vbo: Buffer,
+    lines: RingBuffer<LinePoint>,
+    lines_generation: usize,
     pub lines_vao: VertexArray,
 }
 
 impl Buffers {
     pub fn new(gl: &gl::Gl, vbo_capacity: usize) -> Buffers {
-        let lines_vbo = Buffer::new_array(&gl);
+        let lines = RingBuffer::new_array(gl, vbo_capacity);
         let lines_vao = VertexArray::new(gl);
 
-        lines_vao.bind();
+        let mut buffers = Buffers {
+            gl: gl.clone(),
+            lines_generation: lines.generation(),
+            lines,
+            multi_draw_items: Vec::new(),
+            lines_vao,
+        };
 
-        lines_vbo.bind();
-        LinePoint::vertex_attrib_pointers(gl);
-        lines_vbo.unbind();
+        buffers.set_up_vao();
+        buffers
+    }
 
-        lines_vao.unbind();
+    fn set_up_vao(&mut self) {
+        self.lines_vao.bind();
 
-        if vbo_capacity > 0 {
-            lines_vbo.bind();
-            lines_vbo.stream_draw_data_null::<LinePoint>(vbo_capacity);
-            lines_vbo.unbind();
-        }
+        self.lines.bind();
+        LinePoint::vertex_attrib_pointers(&self.gl);
+        self.lines.unbind();
 
-        Buffers {
-            vbo_capacity,
-            lines_vbo,
-            multi_draw_items: Vec::new(),
-            lines_vao,
-        }
+        self.lines_vao.unbind();
+
+        self.lines_generation = self.lines.generation();
     }
 
-    pub fn upload_vertices(&self, items: impl Iterator<Item = LinePoint>) {
-        if self.vbo_capacity > 0 {
-            self.lines_vbo.bind();
-            if let Some(mut buffer) = unsafe {
-                self.lines_vbo
-                    .map_buffer_range_write_invalidate::<LinePoint>(0, self.vbo_capacity)
-            } {
-                for (index, item) in items.enumerate().take(self.vbo_capacity) {
-                    *unsafe { buffer.get_unchecked_mut(index) } = item;
-                }
-            }
-            self.lines_vbo.unbind();
+    pub fn upload_vertices(&mut self, items: impl Iterator<Item = LinePoint>) {
+        self.lines.begin();
+        self.lines.extend(items);
+        self.lines.end();
+
+        if self.lines_generation != self.lines.generation() {
+            self.set_up_vao();
         }
     }
+
+    /// Index of the first uploaded vertex, `MultiDrawItem::starting_index` is relative to it.
+    pub fn first_vertex(&self) -> i32 {
+        self.lines.first() as i32
+    }
+
+    /// Marks the uploaded vertices as used by the submitted draw calls.
+    pub fn fence(&mut self) {
+        self.lines.fence();
+    }
 }
@@ -6,6 +6,7 @@ use ncollide3d;
 use resources::Resources;
 use crate::ColorBuffer;
 use crate::Program;
+use crate::clip::{Clip, Clipper};
 
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -22,6 +23,7 @@ pub struct DebugLines {
     program_model_matrix_location: Option<i32>,
     containers: Rc<RefCell<SharedDebugLines>>,
     buffers: Option<Buffers>,
+    clipper: Clipper,
     draw_enabled: bool,
 }
 
@@ -37,6 +39,7 @@ impl DebugLines {
             program_model_matrix_location,
             containers: Rc::new(RefCell::new(SharedDebugLines::new())),
             buffers: None,
+            clipper: Clipper::new(gl, res)?,
             draw_enabled: true,
         })
     }
@@ -49,19 +52,13 @@ impl DebugLines {
         let mut shared_debug_lines = self.containers.borrow_mut();
 
         if shared_debug_lines.invalidated {
-            let num_items = shared_debug_lines
-                .containers
-                .values()
-                .flat_map(|v| v.data.iter())
-                .count();
-
-            let should_recreate_buffer = match self.buffers {
-                None => true,
-                Some(ref buffers) if buffers.vbo_capacity < num_items => true,
-                _ => false,
-            };
-
-            if should_recreate_buffer {
+            if self.buffers.is_none() {
+                let num_items = shared_debug_lines
+                    .containers
+                    .values()
+                    .flat_map(|v| v.data.iter())
+                    .count();
+
                 self.buffers = Some(Buffers::new(gl, num_items));
             }
 
@@ -81,6 +78,7 @@ impl DebugLines {
                         model_matrix: na::convert(container.transform),
                         starting_index: offset,
                         index_count: container.data.len() as i32,
+                        clip: container.clip.clone(),
                     });
                     offset += container.data.len() as i32;
                 }
@@ -94,7 +92,7 @@ impl DebugLines {
         if self.draw_enabled {
             self.check_if_invalidated_and_reinitialize(gl);
 
-            if let Some(ref buffers) = self.buffers {
+            if let Some(ref mut buffers) = self.buffers {
                 if buffers.multi_draw_items.len() > 0 {
                     self.program.set_used();
                     if let Some(loc) = self.program_view_projection_location {
@@ -105,25 +103,47 @@ impl DebugLines {
                         .program_model_matrix_location
                         .expect("Debug lines Model uniform must exist");
 
+                    let first_vertex = buffers.first_vertex();
+
                     buffers.lines_vao.bind();
 
                     unsafe {
                         target.set_default_blend_func(gl);
                         target.enable_blend(gl);
 
+                        let mut active_clip = None;
+                        let mut active_clip_visible = true;
+
                         for instance in buffers.multi_draw_items.iter() {
+                            if instance.clip.as_ref() != active_clip {
+                                active_clip = instance.clip.as_ref();
+                                self.clipper.end();
+                                active_clip_visible = match active_clip {
+                                    Some(clip) => self.clipper.begin(vp_matrix, clip),
+                                    None => true,
+                                };
+                                self.program.set_used();
+                                buffers.lines_vao.bind();
+                            }
+                            if !active_clip_visible {
+                                continue;
+                            }
+
                             self.program.set_uniform_matrix_4fv(
                                 program_model_matrix_location,
                                 &instance.model_matrix,
                             );
 
-                            gl.DrawArrays(gl::LINES, instance.starting_index, instance.index_count);
+                            gl.DrawArrays(gl::LINES, first_vertex + instance.starting_index, instance.index_count);
                         }
 
+                        self.clipper.end();
+
                         target.disable_blend(gl);
                     }
 
                     buffers.lines_vao.unbind();
+                    buffers.fence();
                 }
             }
         }
@@ -576,6 +596,16 @@ impl RectMarker {
     pub fn update_size_and_color(&self, size: na::Vector2<f32>, color: na::Vector4<f32>) {
         self.update_lines(size, color);
     }
+
+    pub fn update_clip(&self, clip: Option<&Clip>) {
+        let mut containers = self.containers.borrow_mut();
+        let unchanged = containers.containers.get(&self.id).map(|c| c.clip.as_ref() == clip).unwrap_or(true);
+        if !unchanged {
+            if let Some(data) = containers.get_container_mut(self.id) {
+                data.clip = clip.cloned();
+            }
+        }
+    }
 }
 
 impl Drop for RectMarker {
@@ -1,10 +1,12 @@
 use super::buffers::LinePoint;
 use crate::na;
+use crate::clip::Clip;
 use std::collections::BTreeMap;
 
 pub struct Container {
     pub transform: na::Projective3<f32>,
     pub data: Vec<LinePoint>,
+    pub clip: Option<Clip>,
 }
 
 pub struct SharedDebugLines {
@@ -31,7 +33,7 @@ impl SharedDebugLines {
     pub fn new_container(&mut self, transform: na::Projective3<f32>, data: Vec<LinePoint>) -> i32 {
         let next_id = self.get_next_id();
         self.containers
-            .insert(next_id, Container { transform, data });
+            .insert(next_id, Container { transform, data, clip: None });
         self.invalidated = true;
         next_id
     }
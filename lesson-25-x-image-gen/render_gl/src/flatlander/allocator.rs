@@ -0,0 +1,172 @@
+use std::ops::Range;
+
+/// First fit allocator of element ranges in a buffer.
+///
+/// Freed ranges are kept in a free list and reused, so live allocations never move
+/// and only newly allocated ranges have to be uploaded.
+pub struct RangeAllocator {
+    /// Free ranges sorted by start, adjacent ranges are merged.
+    free: Vec<Range<usize>>,
+    end: usize,
+}
+
+impl RangeAllocator {
+    pub fn new() -> RangeAllocator {
+        RangeAllocator {
+            free: Vec::new(),
+            end: 0,
+        }
+    }
+
+    /// Number of elements the buffer needs to hold all live allocations.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Returns the start of a free range of `len` elements.
+    pub fn alloc(&mut self, len: usize) -> usize {
+        if len == 0 {
+            return 0;
+        }
+
+        if let Some(index) = self.free.iter().position(|r| r.end - r.start >= len) {
+            let start = self.free[index].start;
+            self.free[index].start += len;
+            if self.free[index].start == self.free[index].end {
+                self.free.remove(index);
+            }
+            return start;
+        }
+
+        let start = self.end;
+        self.end += len;
+        start
+    }
+
+    pub fn free(&mut self, start: usize, len: usize) {
+        if len == 0 {
+            return;
+        }
+
+        let end = start + len;
+        let index = self.free.iter().position(|r| r.start > start).unwrap_or(self.free.len());
+
+        let merge_previous = index > 0 && self.free[index - 1].end == start;
+        let merge_next = index < self.free.len() && self.free[index].start == end;
+
+        match (merge_previous, merge_next) {
+            (true, true) => {
+                self.free[index - 1].end = self.free[index].end;
+                self.free.remove(index);
+            },
+            (true, false) => self.free[index - 1].end = end,
+            (false, true) => self.free[index].start = start,
+            (false, false) => self.free.insert(index, start..end),
+        }
+
+        // a free range at the end only shrinks the used part of the buffer
+        let trailing = match self.free.last() {
+            Some(last) if last.end == self.end => Some(last.start),
+            _ => None,
+        };
+        if let Some(start) = trailing {
+            self.free.pop();
+            self.end = start;
+        }
+    }
+}
+
+#[cfg(test)]
+mod allocator_tests {
+    use super::*;
+
+    /// Allocates ranges of the given lengths one after another.
+    fn allocated(lens: &[usize]) -> (RangeAllocator, Vec<usize>) {
+        let mut allocator = RangeAllocator::new();
+        let starts = lens.iter().map(|&len| allocator.alloc(len)).collect();
+        (allocator, starts)
+    }
+
+    #[test]
+    fn ranges_are_allocated_one_after_another() {
+        let (allocator, starts) = allocated(&[3, 5, 2]);
+
+        assert_eq!(vec![0, 3, 8], starts);
+        assert_eq!(10, allocator.end());
+    }
+
+    #[test]
+    fn freed_range_merges_with_previous() {
+        let (mut allocator, _) = allocated(&[2, 3, 4, 5]);
+
+        allocator.free(2, 3);
+        allocator.free(5, 4);
+        assert_eq!(vec![2..9], allocator.free);
+        assert_eq!(14, allocator.end());
+    }
+
+    #[test]
+    fn freed_range_merges_with_next() {
+        let (mut allocator, _) = allocated(&[2, 3, 4, 5]);
+
+        allocator.free(5, 4);
+        allocator.free(2, 3);
+        assert_eq!(vec![2..9], allocator.free);
+        assert_eq!(14, allocator.end());
+    }
+
+    #[test]
+    fn freed_range_merges_with_both_neighbours() {
+        let (mut allocator, _) = allocated(&[2, 3, 4, 5, 6]);
+
+        allocator.free(2, 3);
+        allocator.free(9, 5);
+        assert_eq!(vec![2..5, 9..14], allocator.free);
+
+        allocator.free(5, 4);
+        assert_eq!(vec![2..14], allocator.free);
+        assert_eq!(20, allocator.end());
+    }
+
+    #[test]
+    fn freeing_trailing_range_shrinks_end() {
+        let (mut allocator, _) = allocated(&[2, 3, 4]);
+
+        allocator.free(5, 4);
+        assert!(allocator.free.is_empty());
+        assert_eq!(5, allocator.end());
+
+        // the free range before the trailing one is released with it
+        allocator.free(0, 2);
+        allocator.free(2, 3);
+        assert!(allocator.free.is_empty());
+        assert_eq!(0, allocator.end());
+    }
+
+    #[test]
+    fn freed_middle_range_is_reused() {
+        let (mut allocator, _) = allocated(&[2, 6, 4]);
+        allocator.free(2, 6);
+
+        // first fit splits the free range
+        assert_eq!(2, allocator.alloc(4));
+        assert_eq!(vec![6..8], allocator.free);
+        assert_eq!(6, allocator.alloc(2));
+        assert!(allocator.free.is_empty());
+
+        // too large for any free range
+        allocator.free(2, 4);
+        assert_eq!(12, allocator.alloc(5));
+        assert_eq!(17, allocator.end());
+    }
+
+    #[test]
+    fn empty_ranges_are_ignored() {
+        let (mut allocator, _) = allocated(&[2]);
+
+        assert_eq!(0, allocator.alloc(0));
+        allocator.free(1, 0);
+        assert!(allocator.free.is_empty());
+        assert_eq!(2, allocator.end());
+    }
+}
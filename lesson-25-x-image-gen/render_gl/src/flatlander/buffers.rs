@@ -1,40 +1,51 @@
 use crate::na;
 use crate::data;
-use crate::buffer::Buffer;
+use crate::buffer::{ArrayBuffer, ElementBuffer, IndirectBuffer, BufferType, TypedBuffer};
 use crate::buffer::VertexArray;
+use super::IndexWidth;
 
 #[derive(VertexAttribPointers, Copy, Clone, Debug)]
 #[repr(C, packed)]
 pub struct FlatlanderVertex {
-    #[location = "0"]
-    pub pos: data::f16_f16,
-    #[location = "1"]
+    /// Full precision, glyph outlines are in font units.
+    #[location = 0]
+    pub pos: data::f32_f32,
+    /// Zero for fill vertices. For anti-aliasing fringe vertices, the outline normal
+    /// along which the vertex is extruded in screen space.
+    #[location = 1]
     pub normal: data::f16_f16,
+    /// Multiplied with the group color, white for glyphs.
+    #[location = 9]
+    pub color: data::u8_u8_u8_u8_float,
+    /// Gradient coordinates, gradient ramp row and paint kind, see `FlatlanderPaint`.
+    #[location = 10]
+    pub paint: data::f32_f32_f32_f32,
 }
 
 #[derive(VertexAttribPointers, Copy, Clone, Debug)]
 #[repr(C, packed)]
 pub struct FlatlanderVertexDrawId {
-    #[location = "2"]
-    #[divisor = "1"]
-    pub x_offset: data::f16_,
-    #[location = "3"]
-    #[divisor = "1"]
-    pub y_offset: data::f16_,
-    #[location = "4"]
-    #[divisor = "1"]
+    #[location = 2]
+    #[divisor = 1]
+    pub x_offset: data::f32_,
+    #[location = 3]
+    #[divisor = 1]
+    pub y_offset: data::f32_,
+    #[location = 4]
+    #[divisor = 1]
     pub model_col0: data::f16_f16_f16_f16,
-    #[location = "5"]
-    #[divisor = "1"]
+    #[location = 5]
+    #[divisor = 1]
     pub model_col1: data::f16_f16_f16_f16,
-    #[location = "6"]
-    #[divisor = "1"]
+    #[location = 6]
+    #[divisor = 1]
     pub model_col2: data::f16_f16_f16_f16,
-    #[location = "7"]
-    #[divisor = "1"]
-    pub model_col3: data::f16_f16_f16_f16,
-    #[location = "8"]
-    #[divisor = "1"]
+    /// Translation, kept in full precision for positions far from the origin.
+    #[location = 7]
+    #[divisor = 1]
+    pub model_col3: data::f32_f32_f32_f32,
+    #[location = 8]
+    #[divisor = 1]
     pub color: data::u8_u8_u8_u8_float,
 }
 
@@ -50,7 +61,6 @@ pub struct DrawIndirectCmd {
 
 #[derive(Copy, Clone, Debug)]
 pub struct FlatlanderGroupDrawData {
-    pub cmd: DrawIndirectCmd,
     pub x_offset: f32,
     pub y_offset: f32,
     pub transform: na::Projective3<f32>,
@@ -58,98 +68,117 @@ pub struct FlatlanderGroupDrawData {
 }
 
 pub struct Buffers {
-    vertices: Storage,
-    indices: Storage,
-    draw_id: Storage,
-    pub indirect: Storage,
-
-    pub groups_simple: Vec<FlatlanderGroupDrawData>,
-
-    pub lines_vao: VertexArray,
+    vertices: ArrayBuffer<FlatlanderVertex>,
+    indices_u16: ElementBuffer<u16>,
+    indices_u32: ElementBuffer<u32>,
+    draw_id: ArrayBuffer<FlatlanderVertexDrawId>,
+    pub indirect: IndirectBuffer<DrawIndirectCmd>,
+
+    indices_scratch: Vec<u16>,
+    draw_id_scratch: Vec<FlatlanderVertexDrawId>,
+    commands_scratch: Vec<DrawIndirectCmd>,
+    uploaded_bytes: usize,
+
+    /// Vertex arrays with the same vertex and instance data, but different element buffers.
+    vao_u16: VertexArray,
+    vao_u32: VertexArray,
 }
 
-pub struct Storage {
-    pub buffer: Buffer,
-    pub len: usize,
-}
+impl Buffers {
+    pub fn new(gl: &gl::Gl) -> Buffers {
+        let vertices = ArrayBuffer::new(gl);
+        let indices_u16 = ElementBuffer::new(gl);
+        let indices_u32 = ElementBuffer::new(gl);
+        let indirect = IndirectBuffer::new(gl);
+        let draw_id = ArrayBuffer::new(gl);
+
+        let vao_u16 = VertexArray::new(gl);
+        let vao_u32 = VertexArray::new(gl);
+
+        let bind_vertex_attribs = || {
+            vertices.bind();
+            FlatlanderVertex::vertex_attrib_pointers(gl);
+            draw_id.bind();
+            FlatlanderVertexDrawId::vertex_attrib_pointers(gl);
+            draw_id.unbind();
+        };
+
+        vao_u16.bind();
+        bind_vertex_attribs();
+        indices_u16.bind();
+        vao_u16.unbind();
+        indices_u16.unbind();
+
+        vao_u32.bind();
+        bind_vertex_attribs();
+        indices_u32.bind();
+        vao_u32.unbind();
+        indices_u32.unbind();
 
-impl Storage {
-    pub fn new(buffer: Buffer, len: usize) -> Storage {
-        Storage {
-            buffer,
-            len,
+        Buffers {
+            vertices,
+            indices_u16,
+            indices_u32,
+            indirect,
+            draw_id,
+            vao_u16,
+            vao_u32,
+            indices_scratch: Vec::new(),
+            draw_id_scratch: Vec::new(),
+            commands_scratch: Vec::new(),
+            uploaded_bytes: 0,
         }
     }
 
-    pub fn upload<T, I: Iterator<Item = T>>(&mut self, items_len: usize, items: I) {
-        if items_len > 0 {
-            let should_recreate_buffer = self.len < items_len;
-
-            self.buffer.bind();
-
-            if should_recreate_buffer {
-                self.buffer.stream_draw_data_null::<T>(items_len);
-            }
-
-            if let Some(mut buffer) = unsafe {
-                self.buffer
-                    .map_buffer_range_write_invalidate::<T>(0, items_len)
-            } {
-                for (index, item) in items.enumerate().take(items_len) {
-                    *unsafe { buffer.get_unchecked_mut(index) } = item;
-                }
-            }
-
-            self.buffer.unbind();
-        }
-
-        self.len = items_len;
+    /// Bytes uploaded since the last call.
+    pub fn take_uploaded_bytes(&mut self) -> usize {
+        ::std::mem::replace(&mut self.uploaded_bytes, 0)
     }
-}
-
-impl Buffers {
-    pub fn new(gl: &gl::Gl) -> Buffers {
-        let vertices = Buffer::new_array(&gl);
-        let indices = Buffer::new_element_array(&gl);
-        let indirect = Buffer::new_draw_indirect(&gl);
-        let draw_id = Buffer::new_array(&gl);
 
-        let lines_vao = VertexArray::new(gl);
-
-        lines_vao.bind();
-
-        vertices.bind();
-        FlatlanderVertex::vertex_attrib_pointers(gl);
-        draw_id.bind();
-        FlatlanderVertexDrawId::vertex_attrib_pointers(gl);
-        draw_id.unbind();
-
-        indices.bind();
-        lines_vao.unbind();
-
-        indices.unbind();
-
-        Buffers {
-            vertices: Storage::new(vertices, 0),
-            indices: Storage::new(indices, 0),
-            indirect: Storage::new(indirect, 0),
-            draw_id: Storage::new(draw_id, 0),
-            lines_vao,
-            groups_simple: Vec::new(),
+    /// Vertex array to draw alphabets with the index width.
+    pub fn vertex_array(&self, index_width: IndexWidth) -> &VertexArray {
+        match index_width {
+            IndexWidth::U16 => &self.vao_u16,
+            IndexWidth::U32 => &self.vao_u32,
         }
     }
 
+    /// Grows vertex and index storage to fit, keeping the uploaded entries.
+    pub fn reserve_alphabets(&mut self, vertices_len: usize, indices_u16_len: usize, indices_u32_len: usize) {
+        reserve(&mut self.vertices, vertices_len);
+        reserve(&mut self.indices_u16, indices_u16_len);
+        reserve(&mut self.indices_u32, indices_u32_len);
+    }
 
-    pub fn upload_vertices(&mut self, items_len: usize, items: impl Iterator<Item = FlatlanderVertex>) {
-        self.vertices.upload(items_len, items);
+    /// Grows instance storage to fit, keeping the uploaded instances.
+    pub fn reserve_instances(&mut self, instances_len: usize) {
+        reserve(&mut self.draw_id, instances_len);
     }
 
-    pub fn upload_indices(&mut self, items_len: usize, items: impl Iterator<Item = u16>) {
-        self.indices.upload(items_len, items);
+    /// Writes a part of an alphabet entry. Indices must fit into the index width.
+    pub fn upload_alphabet_part(&mut self, index_width: IndexWidth, first_vertex: usize, vertices: &[FlatlanderVertex], first_index: usize, indices: &[u32]) {
+        self.vertices.sub_data(first_vertex, vertices);
+        self.uploaded_bytes += ::std::mem::size_of_val(vertices);
+
+        match index_width {
+            IndexWidth::U16 => {
+                self.indices_scratch.clear();
+                self.indices_scratch.extend(indices.iter().map(|i| *i as u16));
+                self.indices_u16.sub_data(first_index, &self.indices_scratch);
+                self.uploaded_bytes += ::std::mem::size_of_val(&self.indices_scratch[..]);
+            },
+            IndexWidth::U32 => {
+                self.indices_u32.sub_data(first_index, indices);
+                self.uploaded_bytes += ::std::mem::size_of_val(indices);
+            },
+        }
     }
 
-    pub fn upload_groups(&mut self, items_len: usize, items: impl Iterator<Item = FlatlanderGroupDrawData>) {
-        self.draw_id.upload(items_len, items
+    /// Writes instance data of consecutive group items starting at `first_instance`.
+    pub fn upload_instances(&mut self, first_instance: usize, items: &[FlatlanderGroupDrawData]) {
+        self.draw_id_scratch.clear();
+        self.draw_id_scratch.extend(items
+            .iter()
             .map(|i| {
                 let mat: na::Matrix4<f32> = na::convert::<_, na::Matrix4<f32>>(i.transform) *
                     na::Matrix4::<f32>::new_nonuniform_scaling(&na::Vector3::new(1.0, -1.0, 1.0));
@@ -164,14 +193,37 @@ impl Buffers {
                     model_col0: data::f16_f16_f16_f16::from((col0[0], col0[1], col0[2], col0[3])),
                     model_col1: data::f16_f16_f16_f16::from((col1[0], col1[1], col1[2], col1[3])),
                     model_col2: data::f16_f16_f16_f16::from((col2[0], col2[1], col2[2], col2[3])),
-                    model_col3: data::f16_f16_f16_f16::from((col3[0], col3[1], col3[2], col3[3])),
+                    model_col3: data::f32_f32_f32_f32::from((col3[0], col3[1], col3[2], col3[3])),
                     color: (i.color.x, i.color.y, i.color.z, i.color.w).into(),
                 }
             }));
+
+        self.draw_id.sub_data(first_instance, &self.draw_id_scratch);
+        self.uploaded_bytes += ::std::mem::size_of_val(&self.draw_id_scratch[..]);
     }
 
-    pub fn upload_draw_commands(&mut self, items_len: usize, items: impl Iterator<Item = FlatlanderGroupDrawData>) {
-        self.indirect.upload(items_len, items
-            .map(|i| i.cmd));
+    /// Overwrites draw commands starting at `first_command`, within the current commands.
+    pub fn upload_draw_commands(&mut self, first_command: usize, commands: impl Iterator<Item = DrawIndirectCmd>) {
+        self.commands_scratch.clear();
+        self.commands_scratch.extend(commands);
+
+        self.indirect.sub_data(first_command, &self.commands_scratch);
+        self.uploaded_bytes += ::std::mem::size_of_val(&self.commands_scratch[..]);
+    }
+
+    /// Replaces all draw commands.
+    pub fn replace_draw_commands(&mut self, commands: &[DrawIndirectCmd]) {
+        self.indirect.upload(commands.len(), commands.iter().cloned());
+        self.uploaded_bytes += ::std::mem::size_of_val(commands);
+    }
+}
+
+/// Grows the buffer to at least double capacity if `len` does not fit.
+fn reserve<B: BufferType, T: Copy>(buffer: &mut TypedBuffer<B, T>, len: usize) {
+    if len <= buffer.capacity() {
+        return;
     }
-}
\ No newline at end of file
+
+    let capacity = ::std::cmp::max(len, buffer.capacity() * 2);
+    buffer.grow(capacity);
+}
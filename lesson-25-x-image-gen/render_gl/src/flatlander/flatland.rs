@@ -1,7 +1,10 @@
 use metrohash::MetroHashMap;
 use slotmap;
 use crate::na;
-use super::{FlatlanderVertex, FlatlanderGroupDrawData, DrawIndirectCmd, FlatlandItem};
+use crate::clip::Clip;
+use super::{FlatlanderVertex, FlatlanderGroupDrawData, DrawIndirectCmd, FlatlandItem, IndexWidth};
+use super::allocator::RangeAllocator;
+use super::buffers::Buffers;
 
 #[derive(Copy, Clone)]
 pub struct AlphabetSlotData {
@@ -11,17 +14,15 @@ pub struct AlphabetSlotData {
 pub struct AlphabetData {
     pub map: MetroHashMap<u32, usize>,
     pub entries: Vec<AlphabetEntry>,
-    total_vertices: usize,
-    total_indices: usize,
+    pub index_width: IndexWidth,
 }
 
 impl AlphabetData {
-    pub fn new() -> AlphabetData {
+    pub fn new(index_width: IndexWidth) -> AlphabetData {
         AlphabetData {
             map: MetroHashMap::default(),
             entries: Vec::with_capacity(4096),
-            total_vertices: 0,
-            total_indices: 0,
+            index_width,
         }
     }
 
@@ -29,30 +30,78 @@ impl AlphabetData {
         self.map.get(&id).map(|v| *v)
     }
 
-    pub fn add(&mut self, id: u32, vertices: Vec<FlatlanderVertex>, indices: Vec<u16>) -> usize {
+    pub fn add(&mut self, id: u32, entry: AlphabetEntry) -> usize {
         let index = self.entries.len();
 
-        let previous_indices = self.total_indices;
-
-        self.total_vertices += vertices.len();
-        self.total_indices += indices.len();
-
-        self.entries.push(AlphabetEntry { vertices, indices, previous_indices });
+        self.entries.push(entry);
         self.map.insert(id, index);
 
         index
     }
 }
 
+/// Tessellated glyph or shape, drawn with one command per part.
 pub struct AlphabetEntry {
+    pub parts: Vec<EntryPart>,
+}
+
+/// Geometry that fits the index width of the alphabet, with its place in the vertex and
+/// index buffers.
+///
+/// Indices are relative to the first vertex of the part.
+pub struct EntryPart {
     pub vertices: Vec<FlatlanderVertex>,
-    pub indices: Vec<u16>,
-    pub previous_indices: usize,
+    pub indices: Vec<u32>,
+    pub first_vertex: usize,
+    pub first_index: usize,
 }
 
-#[derive(Debug)]
-struct AlphabetDataIndexOffset {
-    first_index: usize,
+/// Splits indexed triangles into parts that address at most `max_vertices` vertices each.
+///
+/// Vertices shared by triangles in different parts are duplicated.
+fn split_into_parts(vertices: Vec<FlatlanderVertex>, indices: Vec<u32>, max_vertices: usize) -> Vec<(Vec<FlatlanderVertex>, Vec<u32>)> {
+    if vertices.len() <= max_vertices {
+        return vec![(vertices, indices)];
+    }
+
+    let mut parts = Vec::new();
+    let mut part_vertices = Vec::new();
+    let mut part_indices = Vec::new();
+    let mut remap: MetroHashMap<u32, u32> = MetroHashMap::default();
+
+    for triangle in indices.chunks(3) {
+        let new_vertices = triangle.iter()
+            .enumerate()
+            .filter(|&(i, index)| !remap.contains_key(index) && !triangle[..i].contains(index))
+            .count();
+
+        if part_vertices.len() + new_vertices > max_vertices {
+            parts.push((
+                ::std::mem::replace(&mut part_vertices, Vec::new()),
+                ::std::mem::replace(&mut part_indices, Vec::new()),
+            ));
+            remap.clear();
+        }
+
+        for index in triangle {
+            let local = match remap.get(index) {
+                Some(local) => *local,
+                None => {
+                    let local = part_vertices.len() as u32;
+                    part_vertices.push(vertices[*index as usize]);
+                    remap.insert(*index, local);
+                    local
+                }
+            };
+            part_indices.push(local);
+        }
+    }
+
+    if !part_indices.is_empty() {
+        parts.push((part_vertices, part_indices));
+    }
+
+    parts
 }
 
 #[derive(Copy, Clone)]
@@ -64,6 +113,59 @@ pub struct GroupData {
     pub color: na::Vector4<u8>,
     pub alphabet_slot: AlphabetSlot,
     pub items: Vec<FlatlandItem>,
+    pub clip: Option<Clip>,
+    pub opacity: f32,
+    /// Start of the instance data range of the group, one instance per item.
+    first_instance: usize,
+    /// Position of the first group command in the draw command buffer.
+    first_command: usize,
+    /// One command for every part of every item entry.
+    command_count: usize,
+    /// Instance data and draw commands need uploading.
+    dirty: bool,
+}
+
+impl GroupData {
+    /// Group color with the alpha multiplied by the group opacity.
+    fn draw_color(&self) -> na::Vector4<u8> {
+        let opacity = na::clamp(self.opacity, 0.0, 1.0);
+        na::Vector4::new(self.color.x, self.color.y, self.color.z, (self.color.w as f32 * opacity).round() as u8)
+    }
+
+    /// Whether nothing drawn by the group can be fully opaque.
+    fn is_translucent(&self) -> bool {
+        self.color.w < 255 || self.opacity < 1.0
+    }
+}
+
+/// Consecutive draw commands that share the same clip and index width.
+///
+/// Commands of groups that may contain opaque pixels come first, the rest of the
+/// batch is only drawn in the translucent pass.
+pub struct DrawBatch {
+    pub clip: Option<Clip>,
+    pub index_width: IndexWidth,
+    pub first_command: usize,
+    pub command_count: usize,
+    pub opaque_command_count: usize,
+}
+
+/// Draw commands of a single group, used to sort groups for the translucent pass.
+pub struct GroupCommands {
+    pub slot: GroupSlot,
+    pub index_width: IndexWidth,
+    pub first_command: usize,
+    pub command_count: usize,
+}
+
+/// Number of texels in a gradient ramp.
+pub const GRADIENT_RAMP_WIDTH: u32 = 256;
+/// Maximum number of gradients that can exist at the same time, one texture row each.
+pub const GRADIENT_RAMP_ROWS: u32 = 256;
+
+struct GradientRamp {
+    count: isize,
+    pixels: Vec<u8>,
 }
 
 new_key_type! { pub struct AlphabetSlot; }
@@ -73,18 +175,30 @@ pub struct Flatland {
     pub alphabet_slots: slotmap::SlotMap<AlphabetSlot, AlphabetSlotData>,
     pub alphabet_data: slotmap::SecondaryMap<AlphabetSlot, AlphabetData>,
 
-    alphabet_data_index_offsets: slotmap::SecondaryMap<AlphabetSlot, AlphabetDataIndexOffset>,
-    alphabet_data_index_offsets_invalidated: bool,
-
     pub group_slots: slotmap::SlotMap<GroupSlot, GroupSlotData>,
     pub group_data: slotmap::SecondaryMap<GroupSlot, GroupData>,
 
-    pub alphabets_invalidated: bool,
-    pub groups_invalidated: bool,
-    pub draw_invalidated: bool,
-
-    total_alphabet_vertices: usize,
-    total_alphabet_indices: usize,
+    /// Groups ordered so that groups with the same clip and index width are drawn together.
+    draw_order: Vec<GroupSlot>,
+    /// Draw batches of the current draw order.
+    pub draw_batches: Vec<DrawBatch>,
+    /// Per group commands of the current draw order.
+    pub group_commands: Vec<GroupCommands>,
+
+    gradient_ramps: Vec<Option<GradientRamp>>,
+
+    vertex_ranges: RangeAllocator,
+    index_ranges_u16: RangeAllocator,
+    index_ranges_u32: RangeAllocator,
+    instance_ranges: RangeAllocator,
+
+    /// Alphabet entries that are not uploaded yet.
+    dirty_entries: Vec<(AlphabetSlot, usize)>,
+    /// Groups with `dirty` flag set, may contain removed groups.
+    dirty_groups: Vec<GroupSlot>,
+    /// Groups were added, removed or moved between batches, all commands are rewritten.
+    draw_order_invalidated: bool,
+    pub invalidated_gradient_rows: Vec<u32>,
 }
 
 impl Flatland {
@@ -93,165 +207,249 @@ impl Flatland {
             alphabet_slots: slotmap::SlotMap::with_key(),
             alphabet_data: slotmap::SecondaryMap::new(),
 
-            alphabet_data_index_offsets: slotmap::SecondaryMap::new(),
-            alphabet_data_index_offsets_invalidated: false,
-
             group_slots: slotmap::SlotMap::with_key(),
             group_data: slotmap::SecondaryMap::new(),
 
-            alphabets_invalidated: false,
-            groups_invalidated: false,
-            draw_invalidated: false,
+            draw_order: Vec::new(),
+            draw_batches: Vec::new(),
+            group_commands: Vec::new(),
+
+            gradient_ramps: Vec::new(),
 
-            total_alphabet_vertices: 0,
-            total_alphabet_indices: 0,
+            vertex_ranges: RangeAllocator::new(),
+            index_ranges_u16: RangeAllocator::new(),
+            index_ranges_u32: RangeAllocator::new(),
+            instance_ranges: RangeAllocator::new(),
+
+            dirty_entries: Vec::new(),
+            dirty_groups: Vec::new(),
+            draw_order_invalidated: false,
+            invalidated_gradient_rows: Vec::new(),
         }
     }
 
-    pub fn alphabet_vertices_len(&self) -> usize {
-        self.total_alphabet_vertices
-    }
+    /// Uploads changed alphabet entries, group instance data and draw commands.
+    pub fn upload(&mut self, buffers: &mut Buffers) {
+        // grown storage keeps uploaded entries and instances, only changes are uploaded
+        buffers.reserve_alphabets(self.vertex_ranges.end(), self.index_ranges_u16.end(), self.index_ranges_u32.end());
+
+        for (slot, index) in self.dirty_entries.drain(..) {
+            if let Some(data) = self.alphabet_data.get(slot) {
+                if let Some(entry) = data.entries.get(index) {
+                    for part in entry.parts.iter() {
+                        buffers.upload_alphabet_part(data.index_width, part.first_vertex, &part.vertices, part.first_index, &part.indices);
+                    }
+                }
+            }
+        }
 
-    pub fn alphabet_vertices<'r>(&'r self) -> impl Iterator<Item = FlatlanderVertex> + 'r {
-        self.alphabet_data
-            .values()
-            .flat_map(|data|
-                data.entries.iter()
-            )
-            .flat_map(|entry|
-                entry.vertices.iter().map(|v| *v)
-            )
-    }
+        buffers.reserve_instances(self.instance_ranges.end());
 
-    pub fn alphabet_indices_len(&self) -> usize {
-        self.total_alphabet_indices
-    }
+        if self.draw_order_invalidated {
+            self.update_draw_order();
+        }
 
-    pub fn alphabet_indices<'r>(&'r self) -> impl Iterator<Item = u16> + 'r {
-        self.alphabet_data
-            .values()
-            .flat_map(|data|
-                data.entries.iter()
-            )
-            .scan(0, |base_index, AlphabetEntry { ref vertices, ref indices, .. }| {
-                let previous_base_index = *base_index;
-                *base_index += vertices.len() as u16;
-                Some((previous_base_index, indices))
-            })
-            .flat_map(|(base_index, indices)|
-                indices.iter().map(move |index| *index + base_index)
-            )
+        let mut draw_data = Vec::new();
+        for slot in self.dirty_groups.drain(..) {
+            let group = match self.group_data.get_mut(slot) {
+                Some(group) => group,
+                None => continue,
+            };
+            group.dirty = false;
+
+            draw_data.clear();
+            draw_data.extend(group_instances(group));
+            buffers.upload_instances(group.first_instance, &draw_data);
+
+            if !self.draw_order_invalidated {
+                buffers.upload_draw_commands(group.first_command, group_draw_commands(group, &self.alphabet_data));
+            }
+        }
+
+        if self.draw_order_invalidated {
+            let commands: Vec<DrawIndirectCmd> = self.draw_order
+                .iter()
+                .map(|slot| &self.group_data[*slot])
+                .flat_map(|group| group_draw_commands(group, &self.alphabet_data))
+                .collect();
+            buffers.replace_draw_commands(&commands);
+
+            self.draw_order_invalidated = false;
+        }
     }
 
-    fn ensure_alphabet_data_index_offsets(&mut self) {
-        if self.alphabet_data_index_offsets_invalidated {
+    fn update_draw_order(&mut self) {
+        self.draw_batches.clear();
+        let mut batch_slots: Vec<(Vec<GroupSlot>, Vec<GroupSlot>)> = Vec::new();
+
+        for (slot, group) in self.group_data.iter() {
+            let index_width = self.alphabet_data[group.alphabet_slot].index_width;
+            let batch_index = match self.draw_batches.iter().position(|b| b.clip == group.clip && b.index_width == index_width) {
+                Some(index) => index,
+                None => {
+                    self.draw_batches.push(DrawBatch {
+                        clip: group.clip.clone(),
+                        index_width,
+                        first_command: 0,
+                        command_count: 0,
+                        opaque_command_count: 0,
+                    });
+                    batch_slots.push((Vec::new(), Vec::new()));
+                    self.draw_batches.len() - 1
+                }
+            };
+
+            let batch = &mut self.draw_batches[batch_index];
+            batch.command_count += group.command_count;
+            if group.is_translucent() {
+                batch_slots[batch_index].1.push(slot);
+            } else {
+                batch.opaque_command_count += group.command_count;
+                batch_slots[batch_index].0.push(slot);
+            }
+        }
 
-            self.alphabet_data_index_offsets.clear();
-            self.alphabet_data_index_offsets.extend(self.alphabet_data
-                .iter()
-                .scan(0, |previous_indices, (slot, AlphabetData { ref total_indices, .. })| {
-                    let first_index = *previous_indices;
-                    *previous_indices += total_indices;
-                    Some((slot, first_index))
-                })
-                .map(|(slot, first_index)|
-                    (slot, AlphabetDataIndexOffset {
-                        first_index,
-                    })
-                ));
-
-            self.alphabet_data_index_offsets_invalidated = false;
-        }
-    }
-
-    pub fn groups_len(&self) -> usize {
-        self.group_data.values().map(|g| g.items.len()).sum()
-    }
-
-    pub fn groups_draw_data<'r>(&'r mut self) -> impl Iterator<Item = FlatlanderGroupDrawData> + 'r {
-        self.ensure_alphabet_data_index_offsets();
-
-        fn unpack<'p>(
-            group_data: &'p slotmap::SecondaryMap<GroupSlot, GroupData>,
-            alphabet_data: &'p slotmap::SecondaryMap<AlphabetSlot, AlphabetData>,
-            alphabet_data_index_offsets: &'p slotmap::SecondaryMap<AlphabetSlot, AlphabetDataIndexOffset>
-        ) -> impl Iterator<Item = FlatlanderGroupDrawData> + 'p {
-            group_data
-                .values()
-                .flat_map(move |group| group.items.iter().map(move |i| {
-                    let alphabet_slot = group.alphabet_slot;
-                    let (previous_indices, num_indices) = alphabet_data[alphabet_slot].entries.get(i.alphabet_entry_index)
-                        .map(|e| (e.previous_indices as u32, e.indices.len() as u32))
-                        .expect("expected alphabet entry to exist");
-                    let first_alphabet_index = alphabet_data_index_offsets[alphabet_slot].first_index as u32;
-
-                    (num_indices, first_alphabet_index + previous_indices, i.x_offset, i.y_offset, group.transform, group.color)
-                }))
-                .enumerate()
-                .map(|(i, (num_indices, first_index, x_offset, y_offset, transform, color))| FlatlanderGroupDrawData {
-                    cmd: DrawIndirectCmd {
-                        count: num_indices,
-                        prim_count: 1,
-                        first_index,
-                        base_vertex: 0,
-                        base_instance: i as u32
-                    },
-                    x_offset: x_offset as f32,
-                    y_offset: y_offset as f32,
-                    transform,
-                    color
-                })
-        }
-
-        unpack(&self.group_data, &self.alphabet_data, &self.alphabet_data_index_offsets)
+        let mut first_command = 0;
+        for batch in self.draw_batches.iter_mut() {
+            batch.first_command = first_command;
+            first_command += batch.command_count;
+        }
+
+        self.draw_order.clear();
+        self.draw_order.extend(batch_slots.into_iter().flat_map(|(opaque, translucent)| opaque.into_iter().chain(translucent.into_iter())));
+
+        self.group_commands.clear();
+        let mut first_command = 0;
+        for slot in self.draw_order.iter() {
+            let group = &mut self.group_data[*slot];
+            group.first_command = first_command;
+            self.group_commands.push(GroupCommands {
+                slot: *slot,
+                index_width: self.alphabet_data[group.alphabet_slot].index_width,
+                first_command,
+                command_count: group.command_count,
+            });
+            first_command += group.command_count;
+        }
+    }
+
+    fn invalidate_group(&mut self, slot: GroupSlot) {
+        let group = &mut self.group_data[slot];
+        if !group.dirty {
+            group.dirty = true;
+            self.dirty_groups.push(slot);
+        }
     }
 
     pub fn create_flatland_group_with_items(&mut self, &transform: &na::Projective3<f32>, color: na::Vector4<u8>, alphabet_slot: AlphabetSlot, items: Vec<FlatlandItem>) -> GroupSlot {
         let slot = self.group_slots.insert(GroupSlotData {});
+        let first_instance = self.instance_ranges.alloc(items.len());
+        let command_count = command_count(&items, &self.alphabet_data[alphabet_slot]);
         self.group_data.insert(slot, GroupData {
             transform,
             alphabet_slot,
             items,
             color,
+            clip: None,
+            opacity: 1.0,
+            first_instance,
+            first_command: 0,
+            command_count,
+            dirty: false,
         });
 
-        self.groups_invalidated = true;
-        self.draw_invalidated = true;
+        self.invalidate_group(slot);
+        self.draw_order_invalidated = true;
 
         slot
     }
 
     pub fn update_items<'p>(&mut self, slot: GroupSlot, items: impl Iterator<Item = &'p FlatlandItem>) {
-        self.group_data[slot].items.clear();
-        self.group_data[slot].items.extend(items);
+        let items: Vec<FlatlandItem> = items.cloned().collect();
+        if self.group_data[slot].items == items {
+            return;
+        }
+
+        let (first_instance, previous_len, previous_command_count, alphabet_slot) = {
+            let group = &self.group_data[slot];
+            (group.first_instance, group.items.len(), group.command_count, group.alphabet_slot)
+        };
+
+        if items.len() != previous_len {
+            self.instance_ranges.free(first_instance, previous_len);
+            self.group_data[slot].first_instance = self.instance_ranges.alloc(items.len());
+        }
 
-        self.groups_invalidated = true;
-        self.draw_invalidated = true;
+        let command_count = command_count(&items, &self.alphabet_data[alphabet_slot]);
+        if command_count != previous_command_count {
+            self.draw_order_invalidated = true;
+        }
+
+        let group = &mut self.group_data[slot];
+        group.items = items;
+        group.command_count = command_count;
+        self.invalidate_group(slot);
     }
 
     pub fn update_transform(&mut self, slot: GroupSlot, &transform: &na::Projective3<f32>) {
+        if self.group_data[slot].transform == transform {
+            return;
+        }
+
         self.group_data[slot].transform = transform;
 
-        self.draw_invalidated = true;
+        // the transform is part of the per instance data
+        self.invalidate_group(slot);
     }
 
     pub fn update_color(&mut self, slot: GroupSlot, color: na::Vector4<u8>) {
+        if self.group_data[slot].color == color {
+            return;
+        }
+
+        let was_translucent = self.group_data[slot].is_translucent();
         self.group_data[slot].color = color;
 
-        self.draw_invalidated = true;
+        // color alpha decides whether the group is drawn in the opaque pass
+        self.draw_order_invalidated |= was_translucent != self.group_data[slot].is_translucent();
+        self.invalidate_group(slot);
+    }
+
+    pub fn update_opacity(&mut self, slot: GroupSlot, opacity: f32) {
+        if self.group_data[slot].opacity == opacity {
+            return;
+        }
+
+        let was_translucent = self.group_data[slot].is_translucent();
+        self.group_data[slot].opacity = opacity;
+
+        self.draw_order_invalidated |= was_translucent != self.group_data[slot].is_translucent();
+        self.invalidate_group(slot);
+    }
+
+    pub fn update_clip(&mut self, slot: GroupSlot, clip: Option<&Clip>) {
+        if self.group_data[slot].clip.as_ref() == clip {
+            return;
+        }
+
+        self.group_data[slot].clip = clip.cloned();
+
+        // groups are reordered into clip batches
+        self.draw_order_invalidated = true;
     }
 
     pub fn delete_flatland_group(&mut self, slot: GroupSlot) {
         self.group_slots.remove(slot);
-        self.group_data.remove(slot);
+        if let Some(group) = self.group_data.remove(slot) {
+            self.instance_ranges.free(group.first_instance, group.items.len());
+        }
 
-        self.groups_invalidated = true;
-        self.draw_invalidated = true;
+        self.draw_order_invalidated = true;
     }
 
-    pub fn create_alphabet(&mut self) -> AlphabetSlot {
+    pub fn create_alphabet(&mut self, index_width: IndexWidth) -> AlphabetSlot {
         let slot = self.alphabet_slots.insert(AlphabetSlotData { count: 1 });
-        self.alphabet_data.insert(slot, AlphabetData::new());
+        self.alphabet_data.insert(slot, AlphabetData::new(index_width));
         slot
     }
 
@@ -259,15 +457,26 @@ impl Flatland {
         self.alphabet_data[slot].get_index(id)
     }
 
-    pub fn add_alphabet_entry(&mut self, slot: AlphabetSlot, id: u32, vertices: Vec<FlatlanderVertex>, indices: Vec<u16>) -> usize {
-        self.alphabets_invalidated = true;
-        self.alphabet_data_index_offsets_invalidated = true;
-        self.groups_invalidated = true;
-        self.draw_invalidated = true;
+    pub fn add_alphabet_entry(&mut self, slot: AlphabetSlot, id: u32, vertices: Vec<FlatlanderVertex>, indices: Vec<u32>) -> usize {
+        let index_width = self.alphabet_data[slot].index_width;
+
+        let mut parts = Vec::new();
+        for (vertices, indices) in split_into_parts(vertices, indices, index_width.max_vertices()) {
+            let first_vertex = self.vertex_ranges.alloc(vertices.len());
+            let first_index = self.index_ranges(index_width).alloc(indices.len());
+            parts.push(EntryPart { vertices, indices, first_vertex, first_index });
+        }
 
-        self.total_alphabet_vertices += vertices.len();
-        self.total_alphabet_indices += indices.len();
-        self.alphabet_data[slot].add(id, vertices, indices)
+        let index = self.alphabet_data[slot].add(id, AlphabetEntry { parts });
+        self.dirty_entries.push((slot, index));
+        index
+    }
+
+    fn index_ranges(&mut self, index_width: IndexWidth) -> &mut RangeAllocator {
+        match index_width {
+            IndexWidth::U16 => &mut self.index_ranges_u16,
+            IndexWidth::U32 => &mut self.index_ranges_u32,
+        }
     }
 
     pub fn inc_alphabet(&mut self, slot: AlphabetSlot) {
@@ -285,12 +494,305 @@ impl Flatland {
     pub fn delete_alphabet(&mut self, slot: AlphabetSlot) {
         self.alphabet_slots.remove(slot);
         let data = self.alphabet_data.remove(slot).expect("expected to remove data when removing the alphabet");
-        self.total_alphabet_vertices -= data.total_vertices;
-        self.total_alphabet_indices -= data.total_indices;
 
-        self.alphabets_invalidated = true;
-        self.alphabet_data_index_offsets_invalidated = true;
-        self.groups_invalidated = true;
-        self.draw_invalidated = true;
+        // groups using the alphabet hold a reference to it, so none are left to draw these ranges
+        for part in data.entries.iter().flat_map(|e| e.parts.iter()) {
+            self.vertex_ranges.free(part.first_vertex, part.vertices.len());
+            self.index_ranges(data.index_width).free(part.first_index, part.indices.len());
+        }
+    }
+
+    /// Stores RGBA ramp pixels in the first free row, `None` if all rows are used.
+    pub fn create_gradient(&mut self, pixels: Vec<u8>) -> Option<u32> {
+        let ramp = GradientRamp { count: 1, pixels };
+
+        let row = match self.gradient_ramps.iter().position(|r| r.is_none()) {
+            Some(row) => {
+                self.gradient_ramps[row] = Some(ramp);
+                row
+            }
+            None if self.gradient_ramps.len() < GRADIENT_RAMP_ROWS as usize => {
+                self.gradient_ramps.push(Some(ramp));
+                self.gradient_ramps.len() - 1
+            }
+            None => return None,
+        };
+
+        self.invalidated_gradient_rows.push(row as u32);
+        Some(row as u32)
+    }
+
+    pub fn gradient_ramp_pixels(&self, row: u32) -> Option<&[u8]> {
+        self.gradient_ramps.get(row as usize)
+            .and_then(|r| r.as_ref())
+            .map(|r| &r.pixels[..])
+    }
+
+    pub fn inc_gradient(&mut self, row: u32) {
+        if let Some(ramp) = self.gradient_ramps[row as usize].as_mut() {
+            ramp.count += 1;
+        }
+    }
+
+    pub fn dec_gradient(&mut self, row: u32) {
+        let delete = match self.gradient_ramps[row as usize].as_mut() {
+            Some(ramp) => {
+                ramp.count -= 1;
+                ramp.count <= 0
+            }
+            None => false,
+        };
+
+        if delete {
+            self.gradient_ramps[row as usize] = None;
+        }
+    }
+}
+
+/// Instance data of every group item.
+fn group_instances<'p>(group: &'p GroupData) -> impl Iterator<Item = FlatlanderGroupDrawData> + 'p {
+    let color = group.draw_color();
+
+    group.items.iter().map(move |item| FlatlanderGroupDrawData {
+        x_offset: item.x_offset as f32,
+        y_offset: item.y_offset as f32,
+        transform: group.transform,
+        color,
+    })
+}
+
+/// Draw commands of every part of every group item, all parts of an item share its instance.
+fn group_draw_commands<'p>(
+    group: &'p GroupData,
+    alphabet_data: &'p slotmap::SecondaryMap<AlphabetSlot, AlphabetData>,
+) -> impl Iterator<Item = DrawIndirectCmd> + 'p {
+    let entries = &alphabet_data[group.alphabet_slot].entries;
+
+    group.items.iter().enumerate().flat_map(move |(i, item)| {
+        let entry = entries.get(item.alphabet_entry_index).expect("expected alphabet entry to exist");
+
+        entry.parts.iter().map(move |part| DrawIndirectCmd {
+            count: part.indices.len() as u32,
+            prim_count: 1,
+            first_index: part.first_index as u32,
+            base_vertex: part.first_vertex as u32,
+            base_instance: (group.first_instance + i) as u32,
+        })
+    })
+}
+
+fn command_count(items: &[FlatlandItem], alphabet: &AlphabetData) -> usize {
+    items.iter()
+        .map(|item| alphabet.entries.get(item.alphabet_entry_index).map(|e| e.parts.len()).unwrap_or(0))
+        .sum()
+}
+
+#[cfg(test)]
+mod flatland_tests {
+    use super::*;
+    use crate::na;
+
+    fn vertex(x: f32, y: f32) -> FlatlanderVertex {
+        FlatlanderVertex {
+            pos: (x, y).into(),
+            normal: (0.0, 0.0).into(),
+            color: (255, 255, 255, 255).into(),
+            paint: (0.0, 0.0, 0.0, 0.0).into(),
+        }
+    }
+
+    /// Glyph made of separate unit quads in a row, 4 vertices and 2 triangles each.
+    fn synthetic_glyph(quads: usize) -> (Vec<FlatlanderVertex>, Vec<u32>) {
+        let mut vertices = Vec::with_capacity(quads * 4);
+        let mut indices = Vec::with_capacity(quads * 6);
+
+        for i in 0..quads {
+            let x = i as f32 * 2.0;
+            let first = vertices.len() as u32;
+            vertices.extend_from_slice(&[vertex(x, 0.0), vertex(x + 1.0, 0.0), vertex(x + 1.0, 1.0), vertex(x, 1.0)]);
+            indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+        }
+
+        (vertices, indices)
+    }
+
+    fn triangles(vertices: &[FlatlanderVertex], indices: &[u32]) -> Vec<(f32, f32)> {
+        indices.iter()
+            .map(|i| {
+                let pos = vertices[*i as usize].pos;
+                (pos.d0, pos.d1)
+            })
+            .collect()
+    }
+
+    fn entry_triangles(entry: &AlphabetEntry) -> Vec<(f32, f32)> {
+        entry.parts.iter()
+            .flat_map(|part| triangles(&part.vertices, &part.indices))
+            .collect()
+    }
+
+    #[test]
+    fn small_entry_is_single_part() {
+        let mut flatland = Flatland::new();
+        let alphabet = flatland.create_alphabet(IndexWidth::U16);
+
+        let (vertices, indices) = synthetic_glyph(10);
+        let index = flatland.add_alphabet_entry(alphabet, 1, vertices, indices);
+
+        let entry = &flatland.alphabet_data[alphabet].entries[index];
+        assert_eq!(1, entry.parts.len());
+        assert_eq!(40, entry.parts[0].vertices.len());
+        assert_eq!(60, entry.parts[0].indices.len());
+    }
+
+    #[test]
+    fn huge_u16_entry_is_split_into_addressable_parts() {
+        let mut flatland = Flatland::new();
+        let alphabet = flatland.create_alphabet(IndexWidth::U16);
+
+        let (vertices, indices) = synthetic_glyph(40_000);
+        let expected = triangles(&vertices, &indices);
+        let index = flatland.add_alphabet_entry(alphabet, 1, vertices, indices);
+
+        let entry = &flatland.alphabet_data[alphabet].entries[index];
+        assert_eq!(3, entry.parts.len());
+        for part in entry.parts.iter() {
+            assert!(part.vertices.len() <= IndexWidth::U16.max_vertices());
+            assert!(part.indices.iter().all(|i| (*i as usize) < part.vertices.len()));
+        }
+        assert_eq!(expected, entry_triangles(entry));
+    }
+
+    #[test]
+    fn split_duplicates_vertices_shared_across_parts() {
+        // triangle fan around a single center vertex
+        let mut vertices = vec![vertex(0.0, 0.0)];
+        let mut indices = Vec::new();
+        for i in 0..100_000u32 {
+            vertices.push(vertex(i as f32, 1.0));
+            if i > 0 {
+                indices.extend_from_slice(&[0, i, i + 1]);
+            }
+        }
+        let expected = triangles(&vertices, &indices);
+
+        let parts = split_into_parts(vertices, indices, IndexWidth::U16.max_vertices());
+
+        assert_eq!(2, parts.len());
+        let actual: Vec<_> = parts.iter().flat_map(|&(ref v, ref i)| triangles(v, i)).collect();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn huge_u32_entry_is_single_part() {
+        let mut flatland = Flatland::new();
+        let alphabet = flatland.create_alphabet(IndexWidth::U32);
+
+        let (vertices, indices) = synthetic_glyph(40_000);
+        let index = flatland.add_alphabet_entry(alphabet, 1, vertices, indices);
+
+        let entry = &flatland.alphabet_data[alphabet].entries[index];
+        assert_eq!(1, entry.parts.len());
+        assert_eq!(160_000, entry.parts[0].vertices.len());
+    }
+
+    #[test]
+    fn many_glyphs_get_disjoint_ranges() {
+        let mut flatland = Flatland::new();
+        let alphabet = flatland.create_alphabet(IndexWidth::U16);
+
+        for id in 0..300 {
+            let (vertices, indices) = synthetic_glyph(300);
+            flatland.add_alphabet_entry(alphabet, id, vertices, indices);
+        }
+
+        let mut vertex_ranges: Vec<_> = flatland.alphabet_data[alphabet].entries.iter()
+            .flat_map(|e| e.parts.iter())
+            .map(|p| (p.first_vertex, p.first_vertex + p.vertices.len()))
+            .collect();
+        vertex_ranges.sort();
+
+        assert_eq!(300, vertex_ranges.len());
+        for pair in vertex_ranges.windows(2) {
+            assert!(pair[0].1 <= pair[1].0);
+        }
+        assert_eq!(300 * 1200, flatland.vertex_ranges.end());
+        assert_eq!(300 * 1800, flatland.index_ranges_u16.end());
+    }
+
+    #[test]
+    fn deleted_alphabet_ranges_are_reused() {
+        let mut flatland = Flatland::new();
+
+        let first = flatland.create_alphabet(IndexWidth::U16);
+        let (vertices, indices) = synthetic_glyph(1000);
+        flatland.add_alphabet_entry(first, 1, vertices, indices);
+        flatland.delete_alphabet(first);
+
+        let second = flatland.create_alphabet(IndexWidth::U16);
+        let (vertices, indices) = synthetic_glyph(500);
+        let index = flatland.add_alphabet_entry(second, 1, vertices, indices);
+
+        let part = &flatland.alphabet_data[second].entries[index].parts[0];
+        assert_eq!(0, part.first_vertex);
+        assert_eq!(0, part.first_index);
+        assert_eq!(2000, flatland.vertex_ranges.end());
+    }
+
+    #[test]
+    fn group_has_command_for_every_part() {
+        let mut flatland = Flatland::new();
+        let alphabet = flatland.create_alphabet(IndexWidth::U16);
+
+        let (vertices, indices) = synthetic_glyph(40_000);
+        let huge = flatland.add_alphabet_entry(alphabet, 1, vertices, indices);
+        let (vertices, indices) = synthetic_glyph(1);
+        let small = flatland.add_alphabet_entry(alphabet, 2, vertices, indices);
+
+        let items = vec![
+            FlatlandItem { alphabet_entry_index: small, x_offset: 0, y_offset: 0 },
+            FlatlandItem { alphabet_entry_index: huge, x_offset: 10, y_offset: 0 },
+        ];
+        let slot = flatland.create_flatland_group_with_items(&na::Projective3::identity(), [0, 0, 0, 255].into(), alphabet, items);
+
+        let group = &flatland.group_data[slot];
+        assert_eq!(4, group.command_count);
+
+        let commands: Vec<_> = group_draw_commands(group, &flatland.alphabet_data).collect();
+        assert_eq!(4, commands.len());
+        assert_eq!(group.first_instance as u32, commands[0].base_instance);
+
+        let huge_parts = &flatland.alphabet_data[alphabet].entries[huge].parts;
+        for (command, part) in commands[1..].iter().zip(huge_parts.iter()) {
+            assert_eq!(group.first_instance as u32 + 1, command.base_instance);
+            assert_eq!(part.first_vertex as u32, command.base_vertex);
+            assert_eq!(part.first_index as u32, command.first_index);
+            assert_eq!(part.indices.len() as u32, command.count);
+        }
+    }
+
+    #[test]
+    fn index_widths_are_drawn_in_separate_batches() {
+        let mut flatland = Flatland::new();
+        let narrow = flatland.create_alphabet(IndexWidth::U16);
+        let wide = flatland.create_alphabet(IndexWidth::U32);
+
+        let (vertices, indices) = synthetic_glyph(40_000);
+        let narrow_entry = flatland.add_alphabet_entry(narrow, 1, vertices, indices);
+        let (vertices, indices) = synthetic_glyph(40_000);
+        let wide_entry = flatland.add_alphabet_entry(wide, 1, vertices, indices);
+
+        let item = |alphabet_entry_index| vec![FlatlandItem { alphabet_entry_index, x_offset: 0, y_offset: 0 }];
+        flatland.create_flatland_group_with_items(&na::Projective3::identity(), [0, 0, 0, 255].into(), narrow, item(narrow_entry));
+        flatland.create_flatland_group_with_items(&na::Projective3::identity(), [0, 0, 0, 255].into(), wide, item(wide_entry));
+
+        flatland.update_draw_order();
+
+        assert_eq!(2, flatland.draw_batches.len());
+        let narrow_batch = flatland.draw_batches.iter().find(|b| b.index_width == IndexWidth::U16).unwrap();
+        let wide_batch = flatland.draw_batches.iter().find(|b| b.index_width == IndexWidth::U32).unwrap();
+        assert_eq!(3, narrow_batch.command_count);
+        assert_eq!(1, wide_batch.command_count);
+        assert_eq!(4, narrow_batch.command_count + wide_batch.command_count);
     }
 }
@@ -4,35 +4,85 @@ use failure;
 use resources::Resources;
 use crate::ColorBuffer;
 use crate::Program;
+use crate::data;
+use crate::clip::{Clip, Clipper};
+use crate::texture::{Texture2D, TextureFormat, Sampler, SamplerOptions};
 use std::rc::Rc;
 use std::cell::RefCell;
 
+mod allocator;
 mod buffers;
 mod flatland;
 
 pub use self::buffers::{FlatlanderVertex, FlatlanderGroupDrawData, DrawIndirectCmd};
+pub use self::flatland::{GRADIENT_RAMP_WIDTH, GRADIENT_RAMP_ROWS};
+
+/// Element index type of an alphabet.
+///
+/// Entries of `U16` alphabets with more vertices than the indices can address are split
+/// into several parts, each drawn by its own command.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum IndexWidth {
+    U16,
+    U32,
+}
+
+impl IndexWidth {
+    /// Maximum number of vertices a single draw command can address.
+    pub fn max_vertices(&self) -> usize {
+        match *self {
+            IndexWidth::U16 => ::std::u16::MAX as usize + 1,
+            IndexWidth::U32 => ::std::u32::MAX as usize,
+        }
+    }
+
+    fn gl_type(&self) -> gl::types::GLenum {
+        match *self {
+            IndexWidth::U16 => gl::UNSIGNED_SHORT,
+            IndexWidth::U32 => gl::UNSIGNED_INT,
+        }
+    }
+}
 
 pub struct Flatlander {
     program: Program,
     program_view_projection_location: Option<i32>,
+    program_viewport_size_location: Option<i32>,
+    program_fringe_width_location: Option<i32>,
+    program_gradient_ramps_location: Option<i32>,
+    program_translucent_pass_location: Option<i32>,
     flatland: Rc<RefCell<flatland::Flatland>>,
     buffers: Option<buffers::Buffers>,
+    gradient_ramps: Option<(Texture2D, Sampler)>,
+    clipper: Clipper,
     draw_enabled: bool,
     wireframe: bool,
+    fringe_width: f32,
 }
 
 impl Flatlander {
     pub fn new(gl: &gl::Gl, res: &Resources) -> Result<Flatlander, failure::Error> {
         let program = Program::from_res(gl, res, "shaders/render_gl/flatland")?;
         let program_view_projection_location = program.get_uniform_location("ViewProjection");
+        let program_viewport_size_location = program.get_uniform_location("ViewportSize");
+        let program_fringe_width_location = program.get_uniform_location("FringeWidth");
+        let program_gradient_ramps_location = program.get_uniform_location("GradientRamps");
+        let program_translucent_pass_location = program.get_uniform_location("TranslucentPass");
 
         Ok(Flatlander {
             program,
             program_view_projection_location,
+            program_viewport_size_location,
+            program_fringe_width_location,
+            program_gradient_ramps_location,
+            program_translucent_pass_location,
             flatland: Rc::new(RefCell::new(flatland::Flatland::new())),
             buffers: None,
+            gradient_ramps: None,
+            clipper: Clipper::new(gl, res)?,
             draw_enabled: true,
             wireframe: false,
+            fringe_width: 1.0,
         })
     }
 
@@ -43,50 +93,78 @@ impl Flatlander {
         self.wireframe = !self.wireframe;
     }
 
+    pub fn fringe_width(&self) -> f32 {
+        self.fringe_width
+    }
+
+    /// Width in pixels of the anti-aliasing fringe around shapes, 0 disables it.
+    pub fn set_fringe_width(&mut self, fringe_width: f32) {
+        self.fringe_width = fringe_width;
+    }
+
     fn check_if_invalidated_and_reinitialize(&mut self, gl: &gl::Gl) {
         let mut flatland = self.flatland.borrow_mut();
 
-        if flatland.alphabets_invalidated {
-            if self.buffers.is_none() {
-                self.buffers = Some(buffers::Buffers::new(gl));
+        if !flatland.invalidated_gradient_rows.is_empty() {
+            if self.gradient_ramps.is_none() {
+                self.gradient_ramps = Some((
+                    Texture2D::new_empty(gl, TextureFormat::Rgba8, GRADIENT_RAMP_WIDTH, GRADIENT_RAMP_ROWS),
+                    Sampler::new(gl, &SamplerOptions::linear()),
+                ));
             }
 
-            if let Some(ref mut buffers) = self.buffers {
-                buffers.upload_vertices(flatland.alphabet_vertices_len(), flatland.alphabet_vertices());
-                buffers.upload_indices(flatland.alphabet_indices_len(), flatland.alphabet_indices());
+            if let Some((ref texture, _)) = self.gradient_ramps {
+                let rows = ::std::mem::replace(&mut flatland.invalidated_gradient_rows, Vec::new());
+                for row in rows {
+                    if let Some(pixels) = flatland.gradient_ramp_pixels(row) {
+                        texture.sub_image(0, row, GRADIENT_RAMP_WIDTH, 1, pixels);
+                    }
+                }
             }
+        }
 
-            flatland.alphabets_invalidated = false;
+        if self.buffers.is_none() {
+            self.buffers = Some(buffers::Buffers::new(gl));
         }
 
-        if flatland.groups_invalidated {
-            if self.buffers.is_none() {
-                return;
-            }
+        if let Some(ref mut buffers) = self.buffers {
+            flatland.upload(buffers);
+        }
+    }
 
-            if let Some(ref mut buffers) = self.buffers {
-                buffers.upload_groups(flatland.groups_len(), flatland.groups_draw_data());
-            }
+    /// Bytes of vertex, index, instance and draw command data uploaded since the last call.
+    pub fn take_uploaded_bytes(&mut self) -> usize {
+        self.buffers.as_mut().map(|b| b.take_uploaded_bytes()).unwrap_or(0)
+    }
 
-            flatland.groups_invalidated = false;
+    /// Creates a gradient ramp from color stops sorted by offset in `[0, 1]` range.
+    ///
+    /// Returns `None` if there are no stops, or `GRADIENT_RAMP_ROWS` gradients already exist.
+    pub fn create_gradient(&self, stops: &[GradientStop]) -> Option<Gradient> {
+        if stops.is_empty() {
+            return None;
         }
 
-        if flatland.draw_invalidated {
-            if self.buffers.is_none() {
-                return;
-            }
-
-            if let Some(ref mut buffers) = self.buffers {
-                buffers.upload_draw_commands(flatland.groups_len(), flatland.groups_draw_data());
+        let row = self.flatland.borrow_mut().create_gradient(gradient_ramp_pixels(stops));
+        match row {
+            Some(row) => Some(Gradient {
+                row,
+                flatland: self.flatland.clone(),
+            }),
+            None => {
+                warn!("no free gradient ramp rows left, maximum is {}", GRADIENT_RAMP_ROWS);
+                None
             }
-
-            flatland.draw_invalidated = false;
         }
     }
 
     pub fn create_alphabet(&self) -> Alphabet {
+        self.create_alphabet_with_index_width(IndexWidth::U16)
+    }
+
+    pub fn create_alphabet_with_index_width(&self, index_width: IndexWidth) -> Alphabet {
         let mut flatland = self.flatland.borrow_mut();
-        let slot = flatland.create_alphabet();
+        let slot = flatland.create_alphabet(index_width);
         Alphabet {
             slot,
             flatland: self.flatland.clone(),
@@ -102,54 +180,168 @@ impl Flatlander {
                 if let Some(loc) = self.program_view_projection_location {
                     self.program.set_uniform_matrix_4fv(loc, &vp_matrix);
                 }
+                if let Some(loc) = self.program_viewport_size_location {
+                    let mut viewport = [0i32; 4];
+                    unsafe {
+                        gl.GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+                    }
+                    self.program.set_uniform_2f(loc, &na::Vector2::new(viewport[2] as f32, viewport[3] as f32));
+                }
+                if let Some(loc) = self.program_fringe_width_location {
+                    self.program.set_uniform_1f(loc, self.fringe_width);
+                }
+                if let Some(loc) = self.program_gradient_ramps_location {
+                    self.program.set_uniform_1i(loc, 0);
+                }
+                if let Some((ref texture, ref sampler)) = self.gradient_ramps {
+                    texture.bind(0);
+                    sampler.bind(0);
+                }
 
-                buffers.lines_vao.bind();
-                buffers.indirect.buffer.bind();
+                buffers.indirect.bind();
 
                 unsafe {
-                    target.set_default_blend_func(gl);
-//                    target.enable_blend(gl);
+                    target.set_premultiplied_blend_func(gl);
+                    target.enable_blend(gl);
+                    target.depth_func_less_equal(gl);
                     target.front_face_cw(gl);
                     if self.wireframe {
                         target.polygon_mode_line(gl);
                     }
+                }
 
-                    if gl.MultiDrawElementsIndirect.is_loaded() {
-                        // open gl 4.3
-                        gl.MultiDrawElementsIndirect(
-                            gl::TRIANGLES,
-                            gl::UNSIGNED_SHORT,
-                            0 as *const ::std::ffi::c_void,
-                            buffers.indirect.len as i32,
-                            ::std::mem::size_of::<DrawIndirectCmd>() as i32
-                        );
-                    } else {
-                        // open gl 4.1
-                        // manual implementation of MultiDrawElementsIndirect
-
-                        for i in 0..buffers.indirect.len {
-                            gl.DrawElementsIndirect(
-                                gl::TRIANGLES,
-                                gl::UNSIGNED_SHORT,
-                                (i as u32 * ::std::mem::size_of::<DrawIndirectCmd>() as u32) as *const ::std::ffi::c_void
-                            );
+                let flatland = self.flatland.borrow();
+
+                // opaque pass: fully covered pixels of groups that can be opaque, with depth writes
+
+                if let Some(loc) = self.program_translucent_pass_location {
+                    self.program.set_uniform_1i(loc, 0);
+                }
+
+                for batch in flatland.draw_batches.iter().filter(|b| b.opaque_command_count > 0) {
+                    if let Some(ref clip) = batch.clip {
+                        if !self.clipper.begin(vp_matrix, clip) {
+                            continue;
                         }
+                        self.program.set_used();
+                    }
+
+                    buffers.vertex_array(batch.index_width).bind();
+                    draw_commands(gl, batch.index_width, batch.first_command, batch.opaque_command_count);
+
+                    self.clipper.end();
+                }
+
+                // translucent pass: remaining pixels of all groups, back to front without depth writes
+
+                if let Some(loc) = self.program_translucent_pass_location {
+                    self.program.set_uniform_1i(loc, 1);
+                }
+
+                let mut sorted: Vec<_> = flatland.group_commands.iter()
+                    .filter(|g| g.command_count > 0)
+                    .map(|g| (view_depth(vp_matrix, &flatland.group_data[g.slot].transform), g))
+                    .collect();
+                // stable sort keeps the draw order of groups at the same depth
+                sorted.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(::std::cmp::Ordering::Equal));
+
+                target.disable_depth_write(gl);
+
+                let mut active_clip: Option<(&Clip, bool)> = None;
+                for (_, commands) in sorted {
+                    let group = &flatland.group_data[commands.slot];
+                    let same_clip = match (active_clip, group.clip.as_ref()) {
+                        (Some((active, _)), Some(clip)) => active == clip,
+                        (None, None) => true,
+                        _ => false,
+                    };
+
+                    if !same_clip {
+                        self.clipper.end();
+                        active_clip = match group.clip {
+                            Some(ref clip) => {
+                                let visible = self.clipper.begin(vp_matrix, clip);
+                                // the stencil path restores depth writes and changes the program
+                                target.disable_depth_write(gl);
+                                self.program.set_used();
+                                Some((clip, visible))
+                            },
+                            None => None,
+                        };
                     }
 
+                    if let Some((_, false)) = active_clip {
+                        continue;
+                    }
+
+                    buffers.vertex_array(commands.index_width).bind();
+                    draw_commands(gl, commands.index_width, commands.first_command, commands.command_count);
+                }
+
+                self.clipper.end();
+                target.enable_depth_write(gl);
+
+                unsafe {
                     if self.wireframe {
                         target.polygon_mode_fill(gl);
                     }
                     target.front_face_ccw(gl);
-//                    target.disable_blend(gl);
+                    target.depth_func_less(gl);
+                    target.disable_blend(gl);
                 }
 
-                buffers.indirect.buffer.unbind();
-                buffers.lines_vao.unbind();
+                buffers.indirect.unbind();
+                buffers.vertex_array(IndexWidth::U16).unbind();
+
+                if let Some((ref texture, ref sampler)) = self.gradient_ramps {
+                    sampler.unbind(0);
+                    texture.unbind(0);
+                }
+            }
+        }
+    }
+}
+
+/// Runs `count` indirect draw commands starting at `first`, from the bound indirect buffer.
+fn draw_commands(gl: &gl::Gl, index_width: IndexWidth, first: usize, count: usize) {
+    let command_size = ::std::mem::size_of::<DrawIndirectCmd>();
+
+    unsafe {
+        if gl.MultiDrawElementsIndirect.is_loaded() {
+            // open gl 4.3
+            gl.MultiDrawElementsIndirect(
+                gl::TRIANGLES,
+                index_width.gl_type(),
+                (first * command_size) as *const ::std::ffi::c_void,
+                count as i32,
+                command_size as i32
+            );
+        } else {
+            // open gl 4.1
+            // manual implementation of MultiDrawElementsIndirect
+
+            for i in first..first + count {
+                gl.DrawElementsIndirect(
+                    gl::TRIANGLES,
+                    index_width.gl_type(),
+                    (i * command_size) as *const ::std::ffi::c_void
+                );
             }
         }
     }
 }
 
+/// Normalized device depth of the transformed origin, larger is further away.
+fn view_depth(vp_matrix: &na::Matrix4<f32>, transform: &na::Projective3<f32>) -> f32 {
+    let p = vp_matrix * transform.matrix() * na::Vector4::new(0.0, 0.0, 0.0, 1.0);
+    if p.w > ::std::f32::EPSILON {
+        p.z / p.w
+    } else {
+        // behind the camera, draw first
+        ::std::f32::MAX
+    }
+}
+
 pub struct Alphabet {
     slot: flatland::AlphabetSlot,
     flatland: Rc<RefCell<flatland::Flatland>>,
@@ -172,9 +364,11 @@ impl Alphabet {
         flatland.get_alphabet_entry_index(self.slot, id)
     }
 
-    pub fn add_entry(&self, id: u32, vertices: Vec<FlatlanderVertex>, indices: Vec<u16>) -> usize {
+    /// Adds triangles with indices relative to the first vertex, split into several draw
+    /// commands if they do not fit into the index width of the alphabet.
+    pub fn add_entry<I: Copy + Into<u32>>(&self, id: u32, vertices: Vec<FlatlanderVertex>, indices: Vec<I>) -> usize {
         let mut flatland = self.flatland.borrow_mut();
-        flatland.add_alphabet_entry(self.slot, id, vertices, indices)
+        flatland.add_alphabet_entry(self.slot, id, vertices, indices.into_iter().map(|i| i.into()).collect())
     }
 }
 
@@ -185,7 +379,7 @@ impl Drop for Alphabet {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub struct FlatlandItem {
     pub alphabet_entry_index: usize,
     pub x_offset: i32,
@@ -218,10 +412,123 @@ impl FlatlandGroup {
     pub fn update_color(&self, color: na::Vector4<u8>) {
         self.alphabet.flatland.borrow_mut().update_color(self.group_slot, color);
     }
+
+    /// Multiplies the alpha of everything in the group, in `[0, 1]` range.
+    ///
+    /// Groups with opacity below 1 are drawn only in the sorted translucent pass.
+    pub fn update_opacity(&self, opacity: f32) {
+        self.alphabet.flatland.borrow_mut().update_opacity(self.group_slot, opacity);
+    }
+
+    /// Limits drawing of the group to the clip, `None` draws it unclipped.
+    pub fn update_clip(&self, clip: Option<&Clip>) {
+        self.alphabet.flatland.borrow_mut().update_clip(self.group_slot, clip);
+    }
 }
 
 impl Drop for FlatlandGroup {
     fn drop(&mut self) {
         self.alphabet.flatland.borrow_mut().delete_flatland_group(self.group_slot);
     }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct GradientStop {
+    pub offset: f32,
+    pub color: na::Vector4<u8>,
+}
+
+/// Color ramp stored in a row of the gradient texture, freed when the last clone is dropped.
+pub struct Gradient {
+    row: u32,
+    flatland: Rc<RefCell<flatland::Flatland>>,
+}
+
+impl Gradient {
+    /// Texture coordinate of the ramp row.
+    fn v(&self) -> f32 {
+        (self.row as f32 + 0.5) / GRADIENT_RAMP_ROWS as f32
+    }
+}
+
+impl Clone for Gradient {
+    fn clone(&self) -> Self {
+        self.flatland.borrow_mut().inc_gradient(self.row);
+        Gradient {
+            row: self.row,
+            flatland: self.flatland.clone(),
+        }
+    }
+}
+
+impl Drop for Gradient {
+    fn drop(&mut self) {
+        self.flatland.borrow_mut().dec_gradient(self.row);
+    }
+}
+
+/// How the vertex color is modulated, in the same coordinate space as the vertex positions.
+#[derive(Copy, Clone)]
+pub enum FlatlanderPaint<'a> {
+    /// Vertex color only.
+    Solid,
+    LinearGradient {
+        gradient: &'a Gradient,
+        start: na::Point2<f32>,
+        end: na::Point2<f32>,
+    },
+    RadialGradient {
+        gradient: &'a Gradient,
+        center: na::Point2<f32>,
+        radius: f32,
+    },
+}
+
+impl<'a> FlatlanderPaint<'a> {
+    /// Value of `FlatlanderVertex::paint` for a vertex at `x`, `y`.
+    ///
+    /// Gradient coordinates are linear in position, so they are interpolated exactly,
+    /// and the ramp is looked up in the fragment shader.
+    pub fn vertex_paint(&self, x: f32, y: f32) -> data::f32_f32_f32_f32 {
+        let p = na::Point2::new(x, y);
+
+        match *self {
+            FlatlanderPaint::Solid => (0.0, 0.0, 0.0, 0.0).into(),
+            FlatlanderPaint::LinearGradient { gradient, start, end } => {
+                let direction = end - start;
+                let length_sq = direction.norm_squared();
+                let t = if length_sq > 0.0 { (p - start).dot(&direction) / length_sq } else { 0.0 };
+                (t, 0.0, gradient.v(), 1.0).into()
+            }
+            FlatlanderPaint::RadialGradient { gradient, center, radius } => {
+                let local = if radius > 0.0 { (p - center) / radius } else { na::zero() };
+                (local.x, local.y, gradient.v(), 2.0).into()
+            }
+        }
+    }
+}
+
+fn gradient_ramp_pixels(stops: &[GradientStop]) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(GRADIENT_RAMP_WIDTH as usize * 4);
+
+    for i in 0..GRADIENT_RAMP_WIDTH {
+        let t = i as f32 / (GRADIENT_RAMP_WIDTH - 1) as f32;
+
+        let next = stops.iter().position(|s| s.offset > t);
+        let color = match next {
+            None => stops[stops.len() - 1].color,
+            Some(0) => stops[0].color,
+            Some(next) => {
+                let (a, b) = (&stops[next - 1], &stops[next]);
+                let f = (t - a.offset) / (b.offset - a.offset);
+                na::Vector4::from_fn(|c, _| {
+                    (a.color[c] as f32 + (b.color[c] as f32 - a.color[c] as f32) * f).round() as u8
+                })
+            }
+        };
+
+        pixels.extend_from_slice(&[color.x, color.y, color.z, color.w]);
+    }
+
+    pixels
 }
\ No newline at end of file
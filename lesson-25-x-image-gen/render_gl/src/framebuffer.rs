@@ -0,0 +1,469 @@
+use gl;
+use crate::na;
+use crate::Viewport;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Framebuffer is incomplete: {}", status)]
+    Incomplete { status: &'static str },
+    #[fail(display = "Framebuffer size {}x{} is not valid", width, height)]
+    InvalidSize { width: i32, height: i32 },
+    #[fail(display = "Multisampled framebuffer can not have texture attachments, use renderbuffers")]
+    MultisampledTexture,
+}
+
+/// How an attachment image is stored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AttachmentKind {
+    /// Texture that can be sampled after rendering.
+    Texture,
+    /// Renderbuffer, cheaper when the image is only rendered to or blitted.
+    Renderbuffer,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct AttachmentDesc {
+    internal_format: gl::types::GLenum,
+    kind: AttachmentKind,
+}
+
+struct Attachment {
+    desc: AttachmentDesc,
+    id: gl::types::GLuint,
+}
+
+pub struct FramebufferBuilder {
+    colors: Vec<AttachmentDesc>,
+    depth: Option<AttachmentDesc>,
+    stencil: Option<AttachmentDesc>,
+    depth_stencil: Option<AttachmentDesc>,
+    samples: i32,
+}
+
+impl FramebufferBuilder {
+    pub fn new() -> FramebufferBuilder {
+        FramebufferBuilder {
+            colors: Vec::new(),
+            depth: None,
+            stencil: None,
+            depth_stencil: None,
+            samples: 0,
+        }
+    }
+
+    /// Adds a color attachment, attached at `COLOR_ATTACHMENT0 + n` in the order of calls.
+    pub fn with_color(mut self, internal_format: gl::types::GLenum, kind: AttachmentKind) -> Self {
+        self.colors.push(AttachmentDesc { internal_format, kind });
+        self
+    }
+
+    pub fn with_depth(mut self, internal_format: gl::types::GLenum, kind: AttachmentKind) -> Self {
+        self.depth = Some(AttachmentDesc { internal_format, kind });
+        self
+    }
+
+    pub fn with_stencil(mut self, internal_format: gl::types::GLenum, kind: AttachmentKind) -> Self {
+        self.stencil = Some(AttachmentDesc { internal_format, kind });
+        self
+    }
+
+    /// Combined depth and stencil attachment, for example `DEPTH24_STENCIL8`.
+    pub fn with_depth_stencil(mut self, internal_format: gl::types::GLenum, kind: AttachmentKind) -> Self {
+        self.depth_stencil = Some(AttachmentDesc { internal_format, kind });
+        self
+    }
+
+    /// Number of MSAA samples. A multisampled framebuffer gets a single-sampled
+    /// resolve target with texture color attachments.
+    pub fn with_samples(mut self, samples: i32) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn build(self, gl: &gl::Gl, width: i32, height: i32) -> Result<Framebuffer, Error> {
+        if self.samples > 1 {
+            let uses_textures = self.colors.iter()
+                .chain(self.depth.iter())
+                .chain(self.stencil.iter())
+                .chain(self.depth_stencil.iter())
+                .any(|a| a.kind == AttachmentKind::Texture);
+
+            if uses_textures {
+                return Err(Error::MultisampledTexture);
+            }
+        }
+
+        let resolve = if self.samples > 1 {
+            let resolve_builder = FramebufferBuilder {
+                colors: self.colors.iter()
+                    .map(|a| AttachmentDesc { internal_format: a.internal_format, kind: AttachmentKind::Texture })
+                    .collect(),
+                depth: self.depth,
+                stencil: self.stencil,
+                depth_stencil: self.depth_stencil,
+                samples: 0,
+            };
+            Some(Box::new(resolve_builder.build(gl, width, height)?))
+        } else {
+            None
+        };
+
+        let mut fbo: gl::types::GLuint = 0;
+        unsafe {
+            gl.GenFramebuffers(1, &mut fbo);
+        }
+
+        let mut framebuffer = Framebuffer {
+            gl: gl.clone(),
+            fbo,
+            width: 0,
+            height: 0,
+            samples: self.samples,
+            colors: self.colors.into_iter().map(|desc| Attachment { desc, id: 0 }).collect(),
+            depth: self.depth.map(|desc| Attachment { desc, id: 0 }),
+            stencil: self.stencil.map(|desc| Attachment { desc, id: 0 }),
+            depth_stencil: self.depth_stencil.map(|desc| Attachment { desc, id: 0 }),
+            resolve,
+        };
+
+        framebuffer.allocate(width, height)?;
+        Ok(framebuffer)
+    }
+}
+
+/// Off-screen render target with color, depth and stencil attachments.
+///
+/// Multisampled framebuffers are resolved into an internal single-sampled
+/// framebuffer with `resolve`, after which its color textures can be sampled.
+pub struct Framebuffer {
+    gl: gl::Gl,
+    fbo: gl::types::GLuint,
+    width: i32,
+    height: i32,
+    samples: i32,
+    colors: Vec<Attachment>,
+    depth: Option<Attachment>,
+    stencil: Option<Attachment>,
+    depth_stencil: Option<Attachment>,
+    resolve: Option<Box<Framebuffer>>,
+}
+
+impl Framebuffer {
+    pub fn builder() -> FramebufferBuilder {
+        FramebufferBuilder::new()
+    }
+
+    /// Single RGBA8 color texture with depth renderbuffer, the common render-to-texture setup.
+    pub fn new_color_depth(gl: &gl::Gl, width: i32, height: i32, samples: i32) -> Result<Framebuffer, Error> {
+        let color_kind = if samples > 1 { AttachmentKind::Renderbuffer } else { AttachmentKind::Texture };
+
+        FramebufferBuilder::new()
+            .with_color(gl::RGBA8, color_kind)
+            .with_depth_stencil(gl::DEPTH24_STENCIL8, AttachmentKind::Renderbuffer)
+            .with_samples(samples)
+            .build(gl, width, height)
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn samples(&self) -> i32 {
+        self.samples
+    }
+
+    /// Binds framebuffer for drawing and sets the viewport to cover it.
+    pub fn bind_as_target(&self) {
+        unsafe {
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            self.gl.Viewport(0, 0, self.width, self.height);
+        }
+    }
+
+    /// Binds the default (window) framebuffer back.
+    pub fn unbind(&self) {
+        unsafe {
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    pub fn clear(&self, color: na::Vector4<f32>) {
+        unsafe {
+            self.gl.ClearColor(color.x, color.y, color.z, color.w);
+            self.gl.Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+        }
+    }
+
+    /// Recreates attachments if the size has changed, for example after the window was resized.
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<(), Error> {
+        if width == self.width && height == self.height {
+            return Ok(());
+        }
+
+        if let Some(ref mut resolve) = self.resolve {
+            resolve.resize(width, height)?;
+        }
+
+        self.allocate(width, height)
+    }
+
+    /// Matches framebuffer size to the viewport size.
+    pub fn resize_to_viewport(&mut self, viewport: &Viewport) -> Result<(), Error> {
+        self.resize(viewport.w, viewport.h)
+    }
+
+    /// Blits multisampled color and depth into the resolve framebuffer. Does nothing for
+    /// single-sampled framebuffers.
+    pub fn resolve(&self) {
+        if let Some(ref resolve) = self.resolve {
+            let mut mask = 0;
+            if self.depth.is_some() || self.depth_stencil.is_some() {
+                mask |= gl::DEPTH_BUFFER_BIT;
+            }
+            if self.stencil.is_some() || self.depth_stencil.is_some() {
+                mask |= gl::STENCIL_BUFFER_BIT;
+            }
+
+            unsafe {
+                self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+                self.gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, resolve.fbo);
+
+                // every color attachment has to be blitted separately
+                for index in 0..self.colors.len() as u32 {
+                    self.gl.ReadBuffer(gl::COLOR_ATTACHMENT0 + index);
+                    self.gl.DrawBuffer(gl::COLOR_ATTACHMENT0 + index);
+                    self.blit(gl::COLOR_BUFFER_BIT, gl::NEAREST, self.width, self.height);
+                }
+
+                if mask != 0 {
+                    self.blit(mask, gl::NEAREST, self.width, self.height);
+                }
+
+                self.gl.ReadBuffer(gl::COLOR_ATTACHMENT0);
+                resolve.set_draw_buffers();
+
+                self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+                self.gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+            }
+        }
+    }
+
+    /// Copies the first color attachment into the default framebuffer, scaling it to `w` x `h`.
+    pub fn blit_to_default(&self, w: i32, h: i32) {
+        let source = match self.resolve {
+            Some(ref resolve) => {
+                self.resolve();
+                resolve
+            }
+            None => self,
+        };
+
+        unsafe {
+            self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, source.fbo);
+            self.gl.BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+            self.gl.ReadBuffer(gl::COLOR_ATTACHMENT0);
+            source.blit(gl::COLOR_BUFFER_BIT, gl::LINEAR, w, h);
+            self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+    }
+
+    /// Texture of color attachment `index` that can be sampled, after `resolve` for
+    /// multisampled framebuffers. `None` if the attachment is a renderbuffer.
+    pub fn color_texture(&self, index: usize) -> Option<gl::types::GLuint> {
+        if let Some(ref resolve) = self.resolve {
+            return resolve.color_texture(index);
+        }
+
+        self.colors.get(index)
+            .filter(|a| a.desc.kind == AttachmentKind::Texture)
+            .map(|a| a.id)
+    }
+
+    /// Depth texture, if depth is attached as a texture.
+    pub fn depth_texture(&self) -> Option<gl::types::GLuint> {
+        if let Some(ref resolve) = self.resolve {
+            return resolve.depth_texture();
+        }
+
+        self.depth.iter()
+            .chain(self.depth_stencil.iter())
+            .find(|a| a.desc.kind == AttachmentKind::Texture)
+            .map(|a| a.id)
+    }
+
+    unsafe fn blit(&self, mask: gl::types::GLbitfield, filter: gl::types::GLenum, w: i32, h: i32) {
+        self.gl.BlitFramebuffer(
+            0, 0, self.width, self.height,
+            0, 0, w, h,
+            mask,
+            filter,
+        );
+    }
+
+    unsafe fn set_draw_buffers(&self) {
+        let buffers = (0..self.colors.len() as u32)
+            .map(|i| gl::COLOR_ATTACHMENT0 + i)
+            .collect::<Vec<_>>();
+
+        if buffers.len() > 0 {
+            self.gl.DrawBuffers(buffers.len() as i32, buffers.as_ptr());
+        } else {
+            self.gl.DrawBuffer(gl::NONE);
+        }
+    }
+
+    fn allocate(&mut self, width: i32, height: i32) -> Result<(), Error> {
+        if width <= 0 || height <= 0 {
+            return Err(Error::InvalidSize { width, height });
+        }
+
+        let gl = self.gl.clone();
+        let samples = self.samples;
+
+        unsafe {
+            gl.BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+
+            for (index, attachment) in self.colors.iter_mut().enumerate() {
+                attachment.allocate(&gl, gl::COLOR_ATTACHMENT0 + index as u32, width, height, samples);
+            }
+            if let Some(ref mut attachment) = self.depth {
+                attachment.allocate(&gl, gl::DEPTH_ATTACHMENT, width, height, samples);
+            }
+            if let Some(ref mut attachment) = self.stencil {
+                attachment.allocate(&gl, gl::STENCIL_ATTACHMENT, width, height, samples);
+            }
+            if let Some(ref mut attachment) = self.depth_stencil {
+                attachment.allocate(&gl, gl::DEPTH_STENCIL_ATTACHMENT, width, height, samples);
+            }
+
+            self.set_draw_buffers();
+
+            let status = gl.CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl.BindFramebuffer(gl::FRAMEBUFFER, 0);
+
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(Error::Incomplete { status: status_name(status) });
+            }
+        }
+
+        self.width = width;
+        self.height = height;
+
+        Ok(())
+    }
+}
+
+impl Attachment {
+    unsafe fn allocate(&mut self, gl: &gl::Gl, attachment_point: gl::types::GLenum, width: i32, height: i32, samples: i32) {
+        self.delete(gl);
+
+        match self.desc.kind {
+            AttachmentKind::Texture => {
+                let (format, ty) = texture_format_for(self.desc.internal_format);
+
+                gl.GenTextures(1, &mut self.id);
+                gl.BindTexture(gl::TEXTURE_2D, self.id);
+                gl.TexImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    self.desc.internal_format as i32,
+                    width,
+                    height,
+                    0,
+                    format,
+                    ty,
+                    ::std::ptr::null(),
+                );
+                gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+                gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+                gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+                gl.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+                gl.BindTexture(gl::TEXTURE_2D, 0);
+
+                gl.FramebufferTexture2D(gl::FRAMEBUFFER, attachment_point, gl::TEXTURE_2D, self.id, 0);
+            }
+            AttachmentKind::Renderbuffer => {
+                gl.GenRenderbuffers(1, &mut self.id);
+                gl.BindRenderbuffer(gl::RENDERBUFFER, self.id);
+                if samples > 1 {
+                    gl.RenderbufferStorageMultisample(gl::RENDERBUFFER, samples, self.desc.internal_format, width, height);
+                } else {
+                    gl.RenderbufferStorage(gl::RENDERBUFFER, self.desc.internal_format, width, height);
+                }
+                gl.BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+                gl.FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment_point, gl::RENDERBUFFER, self.id);
+            }
+        }
+    }
+
+    unsafe fn delete(&mut self, gl: &gl::Gl) {
+        if self.id == 0 {
+            return;
+        }
+
+        match self.desc.kind {
+            AttachmentKind::Texture => gl.DeleteTextures(1, &mut self.id),
+            AttachmentKind::Renderbuffer => gl.DeleteRenderbuffers(1, &mut self.id),
+        }
+
+        self.id = 0;
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        let gl = self.gl.clone();
+
+        unsafe {
+            for attachment in self.colors.iter_mut()
+                .chain(self.depth.iter_mut())
+                .chain(self.stencil.iter_mut())
+                .chain(self.depth_stencil.iter_mut())
+            {
+                attachment.delete(&gl);
+            }
+
+            gl.DeleteFramebuffers(1, &mut self.fbo);
+        }
+    }
+}
+
+/// Pixel transfer format and type compatible with a sized internal format.
+///
+/// No data is uploaded, but `TexImage2D` still validates them.
+fn texture_format_for(internal_format: gl::types::GLenum) -> (gl::types::GLenum, gl::types::GLenum) {
+    match internal_format {
+        gl::DEPTH_COMPONENT16 | gl::DEPTH_COMPONENT24 | gl::DEPTH_COMPONENT32 => (gl::DEPTH_COMPONENT, gl::UNSIGNED_INT),
+        gl::DEPTH_COMPONENT32F => (gl::DEPTH_COMPONENT, gl::FLOAT),
+        gl::DEPTH24_STENCIL8 => (gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8),
+        gl::DEPTH32F_STENCIL8 => (gl::DEPTH_STENCIL, gl::FLOAT_32_UNSIGNED_INT_24_8_REV),
+        gl::STENCIL_INDEX8 => (gl::STENCIL_INDEX, gl::UNSIGNED_BYTE),
+        gl::R8 => (gl::RED, gl::UNSIGNED_BYTE),
+        gl::RG8 => (gl::RG, gl::UNSIGNED_BYTE),
+        gl::RGB8 | gl::SRGB8 => (gl::RGB, gl::UNSIGNED_BYTE),
+        gl::R16F | gl::R32F => (gl::RED, gl::FLOAT),
+        gl::RG16F | gl::RG32F => (gl::RG, gl::FLOAT),
+        gl::RGB16F | gl::RGB32F => (gl::RGB, gl::FLOAT),
+        gl::RGBA16F | gl::RGBA32F => (gl::RGBA, gl::FLOAT),
+        _ => (gl::RGBA, gl::UNSIGNED_BYTE),
+    }
+}
+
+fn status_name(status: gl::types::GLenum) -> &'static str {
+    match status {
+        gl::FRAMEBUFFER_UNDEFINED => "FRAMEBUFFER_UNDEFINED",
+        gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "FRAMEBUFFER_INCOMPLETE_ATTACHMENT",
+        gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT",
+        gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER",
+        gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "FRAMEBUFFER_INCOMPLETE_READ_BUFFER",
+        gl::FRAMEBUFFER_UNSUPPORTED => "FRAMEBUFFER_UNSUPPORTED",
+        gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "FRAMEBUFFER_INCOMPLETE_MULTISAMPLE",
+        gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => "FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS",
+        _ => "unknown status",
+    }
+}
@@ -11,7 +11,7 @@ extern crate floating_duration;
 extern crate lyon_tessellation;
 extern crate lyon_path;
 extern crate metrohash;
-extern crate log;
+#[macro_use] extern crate log;
 #[macro_use] extern crate slotmap;
 #[macro_use] extern crate failure;
 #[macro_use] extern crate lesson_25_x_render_gl_derive as render_gl_derive;
@@ -20,16 +20,28 @@ mod flatlander;
 mod debug_lines;
 mod shader;
 mod profiler;
+mod sdf_text;
+mod clip;
+mod backdrop;
 
 pub mod buffer;
+pub mod ring_buffer;
 pub mod data;
 pub mod viewport;
 pub mod color_buffer;
+pub mod framebuffer;
+pub mod texture;
 
 pub use self::viewport::Viewport;
 pub use self::color_buffer::ColorBuffer;
+pub use self::framebuffer::{Framebuffer, FramebufferBuilder, AttachmentKind};
+pub use self::texture::{Texture2D, TextureArray, Cubemap, TextureFormat, TextureLoadOptions, Sampler, SamplerOptions};
 pub use self::debug_lines::{DebugLines, RayMarkers, AabbMarker, RectMarker};
-pub use self::flatlander::{Flatlander, FlatlandGroup, FlatlandItem, Alphabet, FlatlanderVertex};
+pub use self::flatlander::{Flatlander, FlatlandGroup, FlatlandItem, Alphabet, FlatlanderVertex, IndexWidth};
+pub use self::flatlander::{Gradient, GradientStop, FlatlanderPaint};
+pub use self::clip::{Clip, ClipRect, Clipper};
+pub use self::backdrop::Backdrop;
+pub use self::sdf_text::{SdfTextRenderer, SdfTextGroup, SdfTextItem, SdfTextEffects, SdfFontKey};
 pub use self::shader::{Shader, Program, Error};
-pub use self::profiler::{EventCountProfiler, FrameProfiler};
+pub use self::profiler::{EventCountProfiler, FrameProfiler, ProfilerScope, ScopeStats};
 
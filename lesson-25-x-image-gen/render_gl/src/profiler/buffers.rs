@@ -1,44 +1,77 @@
 use gl;
-use crate::buffer::{Buffer, VertexArray};
+use crate::buffer::VertexArray;
+use crate::ring_buffer::RingBuffer;
 use crate::data;
 
 #[derive(VertexAttribPointers, Copy, Clone, Debug)]
 #[repr(C, packed)]
 pub struct LinePoint {
-    #[location = "0"]
+    #[location = 0]
     pub pos: data::f32_f32,
-    #[location = "1"]
+    #[location = 1]
     pub color: data::u2_u10_u10_u10_rev_float,
 }
 
 pub struct Buffers {
-    pub vertex_capacity: usize,
-    pub vertex_count: usize,
-    pub lines_vbo: Buffer,
+    gl: gl::Gl,
+    lines: RingBuffer<LinePoint>,
+    lines_generation: usize,
     pub lines_vao: VertexArray,
 }
 
 impl Buffers {
     pub fn new(gl: &gl::Gl, vertex_capacity: usize) -> Buffers {
-        let lines_vbo = Buffer::new_array(&gl);
+        let lines = RingBuffer::new_array(gl, vertex_capacity);
         let lines_vao = VertexArray::new(gl);
-        lines_vao.bind();
-        lines_vbo.bind();
-        LinePoint::vertex_attrib_pointers(gl);
-        lines_vbo.unbind();
-        lines_vao.unbind();
-
-        if vertex_capacity > 0 {
-            lines_vbo.bind();
-            lines_vbo.stream_draw_data_null::<LinePoint>(vertex_capacity);
-            lines_vbo.unbind();
-        }
 
-        Buffers {
-            vertex_capacity,
-            vertex_count: 0,
-            lines_vbo,
+        let mut buffers = Buffers {
+            gl: gl.clone(),
+            lines_generation: lines.generation(),
+            lines,
             lines_vao,
+        };
+
+        buffers.set_up_vao();
+        buffers
+    }
+
+    fn set_up_vao(&mut self) {
+        self.lines_vao.bind();
+        self.lines.bind();
+        LinePoint::vertex_attrib_pointers(&self.gl);
+        self.lines.unbind();
+        self.lines_vao.unbind();
+
+        self.lines_generation = self.lines.generation();
+    }
+
+    pub fn begin(&mut self, vertex_count: usize) {
+        self.lines.begin();
+        self.lines.reserve(vertex_count);
+    }
+
+    #[inline(always)]
+    pub fn push(&mut self, point: LinePoint) {
+        self.lines.push(point);
+    }
+
+    pub fn end(&mut self) {
+        self.lines.end();
+
+        if self.lines_generation != self.lines.generation() {
+            self.set_up_vao();
         }
     }
+
+    pub fn first_vertex(&self) -> usize {
+        self.lines.first()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn fence(&mut self) {
+        self.lines.fence();
+    }
 }
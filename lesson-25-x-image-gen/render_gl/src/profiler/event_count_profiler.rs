@@ -125,53 +125,36 @@ impl EventCountProfiler {
             .count()
             * 2;
 
-        let recreate_buffer_capacity = match self.buffers {
-            None => Some(4096),
-            Some(ref buffers) if buffers.vertex_capacity < all_data_len => {
-                Some(buffers.vertex_capacity * 2)
-            }
-            _ => None,
-        };
-
-        if let Some(new_capacity) = recreate_buffer_capacity {
-            self.buffers = Some(Buffers::new(gl, new_capacity));
+        if self.buffers.is_none() {
+            self.buffers = Some(Buffers::new(gl, 4096));
         }
 
         let y_scale = self.single_item_height as f32;
         let bottom_offset = self.bottom_offset_px as f32;
 
         if let Some(ref mut buffers) = self.buffers {
-            if all_data_len > 0 {
-                buffers.lines_vbo.bind();
-                if let Some(mut buffer) = unsafe {
-                    buffers
-                        .lines_vbo
-                        .map_buffer_range_write_invalidate::<LinePoint>(0, all_data_len)
-                } {
-                    for (index, frame) in self.frame_data_history.iter().enumerate() {
-                        let mut sum: f32 = 0.0;
-                        for item in frame.iter() {
-                            let item_start_diff = sum;
-                            sum += item.count as f32;
-                            let item_end_diff = sum;
-
-                            buffer.push(LinePoint {
-                                pos: (index as f32, bottom_offset + item_start_diff * y_scale)
-                                    .into(),
-                                color: item.color,
-                            });
-
-                            buffer.push(LinePoint {
-                                pos: (index as f32, bottom_offset + item_end_diff * y_scale).into(),
-                                color: item.color,
-                            });
-                        }
-                    }
+            buffers.begin(all_data_len);
+
+            for (index, frame) in self.frame_data_history.iter().enumerate() {
+                let mut sum: f32 = 0.0;
+                for item in frame.iter() {
+                    let item_start_diff = sum;
+                    sum += item.count as f32;
+                    let item_end_diff = sum;
+
+                    buffers.push(LinePoint {
+                        pos: (index as f32, bottom_offset + item_start_diff * y_scale).into(),
+                        color: item.color,
+                    });
+
+                    buffers.push(LinePoint {
+                        pos: (index as f32, bottom_offset + item_end_diff * y_scale).into(),
+                        color: item.color,
+                    });
                 }
-                buffers.lines_vbo.unbind();
             }
 
-            buffers.vertex_count = all_data_len;
+            buffers.end();
         }
     }
 
@@ -187,8 +170,8 @@ impl EventCountProfiler {
         if self.draw_enabled {
             self.update_buffer(gl);
 
-            if let Some(ref buffers) = self.buffers {
-                if buffers.vertex_count > 0 {
+            if let Some(ref mut buffers) = self.buffers {
+                if buffers.vertex_count() > 0 {
                     self.program.set_used();
                     if let Some(loc) = self.program_view_projection_location {
                         self.program.set_uniform_matrix_4fv(loc, &vp_matrix);
@@ -200,10 +183,13 @@ impl EventCountProfiler {
                         target.set_default_blend_func(gl);
                         target.enable_blend(gl);
 
-                        gl.DrawArrays(gl::LINES, 0, buffers.vertex_count as i32);
+                        gl.DrawArrays(gl::LINES, buffers.first_vertex() as i32, buffers.vertex_count() as i32);
 
                         target.disable_blend(gl);
                     }
+
+                    buffers.lines_vao.unbind();
+                    buffers.fence();
                 }
             }
         }
@@ -1,59 +1,143 @@
 use super::buffers::{Buffers, LinePoint};
+use super::gpu_timer::GpuTimer;
 use failure;
 use floating_duration::TimeAsFloat;
 use gl;
 use crate::na;
-use crate::data;
 use crate::ColorBuffer;
 use crate::Program;
-use resources::Resources;
+use resources::{self, Resources};
+use std::cell::RefCell;
 use std::collections::VecDeque;
+use std::fmt::Write;
+use std::rc::Rc;
 use std::time::Instant;
 
-const FRAME_DATA_CAPACITY: usize = 20;
+/// Maximum number of scopes recorded per frame, scopes over it are not recorded.
+const FRAME_DATA_CAPACITY: usize = 24;
+
+/// Scope colors, picked by the hash of the scope name.
+const SCOPE_PALETTE: [(f32, f32, f32); 10] = [
+    (0.0, 0.0, 1.0),
+    (1.0, 0.0, 0.0),
+    (0.0, 0.8, 0.0),
+    (1.0, 0.5, 0.0),
+    (0.6, 0.0, 0.8),
+    (0.0, 0.7, 0.8),
+    (0.8, 0.7, 0.0),
+    (0.9, 0.0, 0.6),
+    (0.4, 0.25, 0.1),
+    (0.3, 0.3, 0.3),
+];
+
+#[derive(Copy, Clone)]
+struct ScopeRecord {
+    name: &'static str,
+    depth: usize,
+    start: Instant,
+    /// Not set while the scope is still open.
+    end: Option<Instant>,
+}
 
 #[derive(Clone)]
 struct FrameData {
+    number: u64,
     start: Instant,
-    position: usize,
-    items: [Item; FRAME_DATA_CAPACITY],
+    scopes: Vec<ScopeRecord>,
+    /// Filled in when GPU query results arrive, a few frames after the frame ends.
+    gpu_scopes: Vec<ScopeRecord>,
 }
 
 impl FrameData {
     pub fn new(start: Instant) -> FrameData {
         FrameData {
+            number: 0,
             start,
-            position: 0,
-            items: [Item {
-                time: Instant::now(),
-                color: (0.0, 0.0, 0.0, 0.0).into(),
-            }; FRAME_DATA_CAPACITY],
+            scopes: Vec::with_capacity(FRAME_DATA_CAPACITY),
+            gpu_scopes: Vec::with_capacity(FRAME_DATA_CAPACITY),
         }
     }
 
-    pub fn restart(&mut self, start: Instant) {
+    pub fn restart(&mut self, number: u64, start: Instant) {
+        self.number = number;
         self.start = start;
-        self.position = 0;
+        self.scopes.clear();
+        self.gpu_scopes.clear();
     }
 
-    pub fn push(&mut self, item: Item) {
-        if self.position >= FRAME_DATA_CAPACITY {
-            return;
-        }
-
-        self.items[self.position] = item;
-        self.position += 1;
+    /// Closed CPU scopes with their end time, in the order they were opened.
+    pub fn iter(&self) -> impl Iterator<Item = (&ScopeRecord, Instant)> {
+        closed_scopes(&self.scopes)
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &Item> {
-        self.items.iter().take(self.position)
+    pub fn gpu_iter(&self) -> impl Iterator<Item = (&ScopeRecord, Instant)> {
+        closed_scopes(&self.gpu_scopes)
     }
 }
 
+fn closed_scopes(scopes: &[ScopeRecord]) -> impl Iterator<Item = (&ScopeRecord, Instant)> {
+    scopes.iter().filter_map(|s| s.end.map(|end| (s, end)))
+}
+
+/// Current frame shared with the scope guards.
+struct Recorder {
+    frame: FrameData,
+    /// Incremented on every `begin`, so that scopes left open over the frame end are ignored.
+    frame_number: u64,
+    depth: usize,
+    gpu: GpuTimer,
+}
+
 #[derive(Copy, Clone)]
-pub struct Item {
-    time: Instant,
-    color: data::u2_u10_u10_u10_rev_float,
+enum Lane {
+    Cpu,
+    Gpu,
+}
+
+/// Guard returned by `FrameProfiler::scope` and `FrameProfiler::gpu_scope`,
+/// the scope ends when the guard is dropped.
+pub struct ProfilerScope {
+    recorder: Rc<RefCell<Recorder>>,
+    frame_number: u64,
+    lane: Lane,
+    index: Option<usize>,
+}
+
+impl Drop for ProfilerScope {
+    fn drop(&mut self) {
+        let mut recorder = self.recorder.borrow_mut();
+        if recorder.frame_number != self.frame_number {
+            return;
+        }
+
+        match self.lane {
+            Lane::Cpu => {
+                recorder.depth = recorder.depth.saturating_sub(1);
+                if let Some(index) = self.index {
+                    recorder.frame.scopes[index].end = Some(Instant::now());
+                }
+            }
+            Lane::Gpu => {
+                if let Some(index) = self.index {
+                    recorder.gpu.end_scope(index);
+                }
+            }
+        }
+    }
+}
+
+/// Timing of a named scope over the frames in the history window.
+///
+/// Time of all scope occurrences in a frame is summed, and frames without the scope
+/// are not counted.
+#[derive(Clone, Debug)]
+pub struct ScopeStats {
+    pub name: &'static str,
+    pub depth: usize,
+    pub color: na::Vector3<f32>,
+    pub min_ms: f32,
+    pub avg_ms: f32,
+    pub max_ms: f32,
 }
 
 pub struct FrameProfiler {
@@ -61,7 +145,8 @@ pub struct FrameProfiler {
     program_view_projection_location: Option<i32>,
     buffers: Option<Buffers>,
     draw_enabled: bool,
-    frame_data: FrameData,
+    epoch: Instant,
+    recorder: Rc<RefCell<Recorder>>,
     frame_data_history: VecDeque<FrameData>,
     frame_data_pool: Vec<FrameData>,
     view_width_pixels: i32,
@@ -78,14 +163,23 @@ impl FrameProfiler {
         let program = Program::from_res(gl, res, "shaders/render_gl/profiler_lines")?;
         let program_view_projection_location = program.get_uniform_location("ViewProjection");
 
+        let epoch = Instant::now();
+
         Ok(FrameProfiler {
             program,
             program_view_projection_location,
             buffers: None,
             draw_enabled: true,
-            frame_data: FrameData::new(Instant::now()),
+            epoch,
+            recorder: Rc::new(RefCell::new(Recorder {
+                frame: FrameData::new(epoch),
+                frame_number: 0,
+                depth: 0,
+                gpu: GpuTimer::new(gl),
+            })),
             frame_data_history: VecDeque::with_capacity(4000),
-            frame_data_pool: vec![FrameData::new(Instant::now()); 4000], // 4000 pixels width should be enough for everybody
+            // 4000 pixels width should be enough for everybody
+            frame_data_pool: (0..4000).map(|_| FrameData::new(epoch)).collect(),
             view_width_pixels: 4000,
             view_height_pixels: 500,
             bottom_offset_px,
@@ -101,9 +195,17 @@ impl FrameProfiler {
             .frame_data_pool
             .pop()
             .unwrap_or_else(|| FrameData::new(Instant::now()));
-        data.restart(Instant::now());
 
-        ::std::mem::swap(&mut data, &mut self.frame_data);
+        {
+            let mut recorder = self.recorder.borrow_mut();
+            recorder.frame_number += 1;
+            let frame_number = recorder.frame_number;
+
+            data.restart(frame_number, Instant::now());
+            ::std::mem::swap(&mut data, &mut recorder.frame);
+            recorder.depth = 0;
+            recorder.gpu.begin_frame(frame_number);
+        }
 
         self.frame_data_history.push_front(data);
 
@@ -112,121 +214,170 @@ impl FrameProfiler {
                 self.frame_data_pool.push(old);
             }
         }
+
+        self.collect_gpu_timings();
     }
 
-    pub fn push(&mut self, color: na::Vector3<f32>) {
-        self.frame_data.push(Item {
-            color: (color.x, color.y, color.z, 0.6).into(),
-            time: Instant::now(),
+    fn collect_gpu_timings(&mut self) {
+        let history = &mut self.frame_data_history;
+
+        self.recorder.borrow_mut().gpu.collect(|frame_number, timing| {
+            let frame = match history.iter_mut().find(|f| f.number == frame_number) {
+                Some(frame) => frame,
+                None => return,
+            };
+
+            if frame.gpu_scopes.len() < FRAME_DATA_CAPACITY {
+                frame.gpu_scopes.push(ScopeRecord {
+                    name: timing.name,
+                    depth: timing.depth,
+                    start: timing.start,
+                    end: Some(timing.end),
+                });
+            }
         });
     }
 
+    /// Returns true if GPU scopes are measured, false if timer queries are not supported.
+    pub fn is_gpu_timing_available(&self) -> bool {
+        self.recorder.borrow().gpu.is_available()
+    }
+
+    /// Starts a named scope that lasts until the returned guard is dropped.
+    ///
+    /// Scopes started while another scope is open are nested in it.
+    pub fn scope(&self, name: &'static str) -> ProfilerScope {
+        let mut recorder = self.recorder.borrow_mut();
+
+        let depth = recorder.depth;
+        recorder.depth += 1;
+
+        let index = if recorder.frame.scopes.len() < FRAME_DATA_CAPACITY {
+            recorder.frame.scopes.push(ScopeRecord {
+                name,
+                depth,
+                start: Instant::now(),
+                end: None,
+            });
+            Some(recorder.frame.scopes.len() - 1)
+        } else {
+            None
+        };
+
+        ProfilerScope {
+            recorder: self.recorder.clone(),
+            frame_number: recorder.frame_number,
+            lane: Lane::Cpu,
+            index,
+        }
+    }
+
+    /// Starts a named scope measured on the GPU, in a separate lane from CPU scopes.
+    ///
+    /// GPU scopes nest like CPU scopes, and their timings arrive a few frames later.
+    pub fn gpu_scope(&self, name: &'static str) -> ProfilerScope {
+        let mut recorder = self.recorder.borrow_mut();
+        let index = recorder.gpu.begin_scope(name);
+
+        ProfilerScope {
+            recorder: self.recorder.clone(),
+            frame_number: recorder.frame_number,
+            lane: Lane::Gpu,
+            index,
+        }
+    }
+
+    /// Returns statistics of all CPU scopes in the history window, in the order they
+    /// appear in the most recent frames.
+    pub fn scope_stats(&self) -> Vec<ScopeStats> {
+        lane_stats(&self.frame_data_history, |f| &f.scopes[..])
+    }
+
+    /// Returns statistics of all GPU scopes in the history window.
+    pub fn gpu_scope_stats(&self) -> Vec<ScopeStats> {
+        lane_stats(&self.frame_data_history, |f| &f.gpu_scopes[..])
+    }
+
+    /// Writes frames in the history window to `path` as Chrome trace event JSON,
+    /// which can be opened in `chrome://tracing` or other trace viewers.
+    pub fn export_chrome_trace(&self, res: &Resources, path: &str) -> Result<(), resources::Error> {
+        // every frame ends where the next one starts
+        let mut frames = Vec::with_capacity(self.frame_data_history.len());
+        let mut frame_end = self.recorder.borrow().frame.start;
+        for frame in self.frame_data_history.iter() {
+            frames.push((frame, frame_end));
+            frame_end = frame.start;
+        }
+
+        let json = chrome_trace_json(self.epoch, frames.into_iter().rev());
+        res.resource(path).write(json.as_bytes())
+    }
+
     fn update_buffer(&mut self, gl: &gl::Gl) {
         let fps_bar_30 = 2;
         let fps_bar_60 = 2;
         let fps_bar_144 = 2;
+
+        // GPU scopes are drawn in a second lane above the CPU scopes
+        let lane_count = if self.is_gpu_timing_available() { 2 } else { 1 };
+
         let all_data_len = self
             .frame_data_history
             .iter()
-            .flat_map(|v| v.iter())
-            .count()
+            .map(|v| v.iter().count() + v.gpu_iter().count())
+            .sum::<usize>()
             * 2
-            + fps_bar_30
-            + fps_bar_60
-            + fps_bar_144;
-
-        let recreate_buffer_capacity = match self.buffers {
-            None => Some(4096),
-            Some(ref buffers) if buffers.vertex_capacity < all_data_len => {
-                Some(buffers.vertex_capacity * 2)
-            }
-            _ => None,
-        };
+            + (fps_bar_30 + fps_bar_60 + fps_bar_144) * lane_count;
 
-        if let Some(new_capacity) = recreate_buffer_capacity {
-            self.buffers = Some(Buffers::new(gl, new_capacity));
+        if self.buffers.is_none() {
+            self.buffers = Some(Buffers::new(gl, 4096));
         }
 
-        let p90 = self.view_height_pixels as f32 * 0.80;
+        let lane_height = self.view_height_pixels as f32 / lane_count as f32;
+        let p90 = lane_height * 0.80;
         let p90_fps15_ms = 1000.0 / 30.0;
         let y_scale = p90 / p90_fps15_ms;
 
         let bottom_offset = self.bottom_offset_px as f32;
+        let view_width = self.view_width_pixels as f32;
 
         if let Some(ref mut buffers) = self.buffers {
-            if all_data_len > 0 {
-                buffers.lines_vbo.bind();
-                if let Some(mut buffer) = unsafe {
-                    buffers
-                        .lines_vbo
-                        .map_buffer_range_write_invalidate::<LinePoint>(0, all_data_len)
-                } {
-                    for (index, frame) in self.frame_data_history.iter().enumerate() {
-                        let mut previous_instant = frame.start;
-                        for item in frame.iter() {
-                            let item_start_diff =
-                                (previous_instant - frame.start).as_fractional_millis() as f32;
-                            let item_end_diff =
-                                (item.time - frame.start).as_fractional_millis() as f32;
-
-                            buffer.push(LinePoint {
-                                pos: (index as f32, bottom_offset + item_start_diff * y_scale)
-                                    .into(),
-                                color: item.color,
-                            });
-
-                            buffer.push(LinePoint {
-                                pos: (index as f32, bottom_offset + item_end_diff * y_scale).into(),
-                                color: item.color,
-                            });
-
-                            previous_instant = item.time;
-                        }
+            buffers.begin(all_data_len);
+
+            for lane in 0..lane_count {
+                let lane_offset = bottom_offset + lane as f32 * lane_height;
+
+                for (index, frame) in self.frame_data_history.iter().enumerate() {
+                    let scopes = if lane == 0 { &frame.scopes } else { &frame.gpu_scopes };
+
+                    // nested scopes are opened later and are drawn over their parents
+                    for (scope, end) in closed_scopes(scopes) {
+                        let scope_start_diff = (scope.start - frame.start).as_fractional_millis() as f32;
+                        let scope_end_diff = (end - frame.start).as_fractional_millis() as f32;
+                        let color = scope_color(scope.name);
+                        let color = (color.x, color.y, color.z, 0.6).into();
+
+                        buffers.push(LinePoint {
+                            pos: (index as f32, lane_offset + scope_start_diff * y_scale).into(),
+                            color,
+                        });
+
+                        buffers.push(LinePoint {
+                            pos: (index as f32, lane_offset + scope_end_diff * y_scale).into(),
+                            color,
+                        });
                     }
-
-                    // 30 fps bar, red
-                    let bar_height = bottom_offset + 33.333 * y_scale;
-
-                    buffer.push(LinePoint {
-                        pos: (0.0, bar_height).into(),
-                        color: (1.0, 0.0, 0.0, 0.6).into(),
-                    });
-
-                    buffer.push(LinePoint {
-                        pos: (self.view_width_pixels as f32, bar_height).into(),
-                        color: (1.0, 0.0, 0.0, 0.3).into(),
-                    });
-
-                    // 60 fps bar, yellow
-                    let bar_height = bottom_offset + 16.666 * y_scale;
-
-                    buffer.push(LinePoint {
-                        pos: (0.0, bar_height).into(),
-                        color: (1.0, 1.0, 0.0, 0.6).into(),
-                    });
-
-                    buffer.push(LinePoint {
-                        pos: (self.view_width_pixels as f32, bar_height).into(),
-                        color: (1.0, 1.0, 0.0, 0.3).into(),
-                    });
-
-                    // 144 fps bar, green
-                    let bar_height = bottom_offset + 6.9 * y_scale;
-
-                    buffer.push(LinePoint {
-                        pos: (0.0, bar_height).into(),
-                        color: (0.0, 1.0, 0.0, 0.6).into(),
-                    });
-
-                    buffer.push(LinePoint {
-                        pos: (self.view_width_pixels as f32, bar_height).into(),
-                        color: (0.0, 1.0, 0.0, 0.3).into(),
-                    });
                 }
-                buffers.lines_vbo.unbind();
+
+                // 30 fps bar, red
+                push_fps_bar(buffers, view_width, lane_offset + 33.333 * y_scale, (1.0, 0.0, 0.0));
+                // 60 fps bar, yellow
+                push_fps_bar(buffers, view_width, lane_offset + 16.666 * y_scale, (1.0, 1.0, 0.0));
+                // 144 fps bar, green
+                push_fps_bar(buffers, view_width, lane_offset + 6.9 * y_scale, (0.0, 1.0, 0.0));
             }
 
-            buffers.vertex_count = all_data_len;
+            buffers.end();
         }
     }
 
@@ -244,8 +395,8 @@ impl FrameProfiler {
         if self.draw_enabled {
             self.update_buffer(gl);
 
-            if let Some(ref buffers) = self.buffers {
-                if buffers.vertex_count > 0 {
+            if let Some(ref mut buffers) = self.buffers {
+                if buffers.vertex_count() > 0 {
                     self.program.set_used();
                     if let Some(loc) = self.program_view_projection_location {
                         self.program.set_uniform_matrix_4fv(loc, &vp_matrix);
@@ -257,12 +408,301 @@ impl FrameProfiler {
                         target.set_default_blend_func(gl);
                         target.enable_blend(gl);
 
-                        gl.DrawArrays(gl::LINES, 0, buffers.vertex_count as i32);
+                        gl.DrawArrays(gl::LINES, buffers.first_vertex() as i32, buffers.vertex_count() as i32);
 
                         target.disable_blend(gl);
                     }
+
+                    buffers.lines_vao.unbind();
+                    buffers.fence();
                 }
             }
         }
     }
 }
+
+fn push_fps_bar(buffers: &mut Buffers, view_width: f32, bar_height: f32, (r, g, b): (f32, f32, f32)) {
+    buffers.push(LinePoint {
+        pos: (0.0, bar_height).into(),
+        color: (r, g, b, 0.6).into(),
+    });
+
+    buffers.push(LinePoint {
+        pos: (view_width, bar_height).into(),
+        color: (r, g, b, 0.3).into(),
+    });
+}
+
+/// Timings of scopes in one lane, the scopes of a frame are returned by `lane`.
+fn lane_stats<F>(frames: &VecDeque<FrameData>, lane: F) -> Vec<ScopeStats>
+    where F: Fn(&FrameData) -> &[ScopeRecord]
+{
+    let mut stats: Vec<(ScopeStats, f32, usize)> = Vec::new();
+    let mut frame_totals: Vec<(&'static str, usize, f32)> = Vec::new();
+
+    for frame in frames.iter() {
+        frame_totals.clear();
+
+        for (scope, end) in closed_scopes(lane(frame)) {
+            let ms = (end - scope.start).as_fractional_millis() as f32;
+            match frame_totals.iter().position(|t| t.0 == scope.name) {
+                Some(index) => frame_totals[index].2 += ms,
+                None => frame_totals.push((scope.name, scope.depth, ms)),
+            }
+        }
+
+        for &(name, depth, ms) in frame_totals.iter() {
+            match stats.iter().position(|s| (s.0).name == name) {
+                Some(index) => {
+                    let (ref mut s, ref mut sum, ref mut frame_count) = stats[index];
+                    s.min_ms = s.min_ms.min(ms);
+                    s.max_ms = s.max_ms.max(ms);
+                    *sum += ms;
+                    *frame_count += 1;
+                }
+                None => stats.push((
+                    ScopeStats {
+                        name,
+                        depth,
+                        color: scope_color(name),
+                        min_ms: ms,
+                        avg_ms: 0.0,
+                        max_ms: ms,
+                    },
+                    ms,
+                    1,
+                )),
+            }
+        }
+    }
+
+    stats
+        .into_iter()
+        .map(|(mut s, sum, frame_count)| {
+            s.avg_ms = sum / frame_count as f32;
+            s
+        })
+        .collect()
+}
+
+/// Color of the scope in the graph and the legend, stable for the same name.
+pub fn scope_color(name: &str) -> na::Vector3<f32> {
+    // FNV-1a
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in name.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+
+    let (r, g, b) = SCOPE_PALETTE[hash as usize % SCOPE_PALETTE.len()];
+    na::Vector3::new(r, g, b)
+}
+
+fn chrome_trace_json<'a, I>(epoch: Instant, frames: I) -> String
+    where I: Iterator<Item = (&'a FrameData, Instant)>
+{
+    const CPU_THREAD: u32 = 1;
+    const GPU_THREAD: u32 = 2;
+
+    fn micros(epoch: Instant, instant: Instant) -> f64 {
+        (instant - epoch).as_fractional_micros()
+    }
+
+    let mut json = String::from("{\"traceEvents\":[");
+
+    // lanes are shown as threads of the same process
+    write!(
+        json,
+        "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":\"CPU\"}}}},\
+        {{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":\"GPU\"}}}}",
+        CPU_THREAD,
+        GPU_THREAD
+    ).expect("failed to write to string");
+
+    let push_event = |json: &mut String, thread: u32, name: &str, category: &str, start: f64, end: f64| {
+        json.push_str(",{\"name\":");
+        push_json_string(json, name);
+        write!(
+            json,
+            ",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":1,\"tid\":{}}}",
+            category,
+            start,
+            end - start,
+            thread
+        ).expect("failed to write to string");
+    };
+
+    for (frame, frame_end) in frames {
+        push_event(&mut json, CPU_THREAD, "frame", "frame", micros(epoch, frame.start), micros(epoch, frame_end));
+
+        for (scope, end) in frame.iter() {
+            push_event(&mut json, CPU_THREAD, scope.name, "scope", micros(epoch, scope.start), micros(epoch, end));
+        }
+
+        for (scope, end) in frame.gpu_iter() {
+            push_event(&mut json, GPU_THREAD, scope.name, "gpu", micros(epoch, scope.start), micros(epoch, end));
+        }
+    }
+
+    json.push_str("]}");
+    json
+}
+
+fn push_json_string(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                write!(json, "\\u{:04x}", c as u32).expect("failed to write to string");
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+#[cfg(test)]
+mod frame_profiler_tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn at(epoch: Instant, ms: u64) -> Instant {
+        epoch + Duration::from_millis(ms)
+    }
+
+    fn frame(epoch: Instant, start_ms: u64, scopes: &[(&'static str, usize, u64, Option<u64>)]) -> FrameData {
+        let mut frame = FrameData::new(at(epoch, start_ms));
+        for &(name, depth, start, end) in scopes {
+            frame.scopes.push(ScopeRecord {
+                name,
+                depth,
+                start: at(epoch, start),
+                end: end.map(|end| at(epoch, end)),
+            });
+        }
+        frame
+    }
+
+    fn cpu_stats(frames: Vec<FrameData>) -> Vec<ScopeStats> {
+        lane_stats(&frames.into_iter().collect(), |f| &f.scopes[..])
+    }
+
+    #[test]
+    fn stats_are_min_avg_max_of_frame_totals() {
+        let epoch = Instant::now();
+        let stats = cpu_stats(vec![
+            frame(epoch, 0, &[("draw", 0, 0, Some(2))]),
+            frame(epoch, 10, &[("draw", 0, 10, Some(14))]),
+            // occurrences in the same frame are summed
+            frame(epoch, 20, &[("draw", 0, 20, Some(21)), ("draw", 0, 22, Some(24))]),
+            // frames without the scope are not counted
+            frame(epoch, 30, &[("update", 0, 30, Some(31))]),
+        ]);
+
+        assert_eq!(2, stats.len());
+        assert_eq!("draw", stats[0].name);
+        assert_eq!(2.0, stats[0].min_ms);
+        assert_eq!(3.0, stats[0].avg_ms);
+        assert_eq!(4.0, stats[0].max_ms);
+        assert_eq!("update", stats[1].name);
+        assert_eq!(1.0, stats[1].min_ms);
+        assert_eq!(1.0, stats[1].avg_ms);
+        assert_eq!(1.0, stats[1].max_ms);
+    }
+
+    #[test]
+    fn open_scopes_are_not_counted() {
+        let epoch = Instant::now();
+        let stats = cpu_stats(vec![
+            frame(epoch, 0, &[("draw", 0, 0, None)]),
+            frame(epoch, 10, &[("draw", 0, 10, Some(15))]),
+        ]);
+
+        assert_eq!(1, stats.len());
+        assert_eq!(5.0, stats[0].min_ms);
+        assert_eq!(5.0, stats[0].max_ms);
+    }
+
+    #[test]
+    fn nested_scopes_keep_open_order_and_depth() {
+        let epoch = Instant::now();
+        let nested = frame(epoch, 0, &[
+            ("update", 0, 0, Some(4)),
+            ("physics", 1, 1, Some(3)),
+            ("collide", 2, 2, Some(3)),
+            ("render", 0, 5, Some(9)),
+            ("upload", 1, 5, Some(6)),
+        ]);
+
+        let order: Vec<_> = nested.iter().map(|(s, _)| (s.name, s.depth)).collect();
+        assert_eq!(vec![("update", 0), ("physics", 1), ("collide", 2), ("render", 0), ("upload", 1)], order);
+
+        let stats: Vec<_> = cpu_stats(vec![nested.clone()]).into_iter().map(|s| (s.name, s.depth)).collect();
+        assert_eq!(order, stats);
+
+        let json = chrome_trace_json(epoch, vec![(&nested, at(epoch, 10))].into_iter());
+        let positions: Vec<_> = ["\"frame\"", "\"update\"", "\"physics\"", "\"collide\"", "\"render\"", "\"upload\""]
+            .iter()
+            .map(|name| json.find(name).expect("scope missing from trace"))
+            .collect();
+        let mut sorted = positions.clone();
+        sorted.sort();
+        assert_eq!(sorted, positions);
+    }
+
+    #[test]
+    fn chrome_trace_has_frame_and_scope_events() {
+        let epoch = Instant::now();
+        let first = frame(epoch, 0, &[("draw", 0, 1, Some(3)), ("open", 0, 4, None)]);
+        let mut second = frame(epoch, 10, &[]);
+        second.gpu_scopes.push(ScopeRecord { name: "blit", depth: 0, start: at(epoch, 12), end: Some(at(epoch, 13)) });
+
+        let json = chrome_trace_json(epoch, vec![(&first, at(epoch, 10)), (&second, at(epoch, 20))].into_iter());
+
+        assert!(json.starts_with("{\"traceEvents\":["));
+        assert!(json.ends_with("]}"));
+        assert!(json.contains(
+            "{\"name\":\"frame\",\"cat\":\"frame\",\"ph\":\"X\",\"ts\":0.000,\"dur\":10000.000,\"pid\":1,\"tid\":1}"
+        ));
+        assert!(json.contains(
+            "{\"name\":\"draw\",\"cat\":\"scope\",\"ph\":\"X\",\"ts\":1000.000,\"dur\":2000.000,\"pid\":1,\"tid\":1}"
+        ));
+        assert!(json.contains(
+            "{\"name\":\"blit\",\"cat\":\"gpu\",\"ph\":\"X\",\"ts\":12000.000,\"dur\":1000.000,\"pid\":1,\"tid\":2}"
+        ));
+        assert!(!json.contains("\"open\""));
+    }
+
+    #[test]
+    fn trace_names_are_escaped() {
+        let epoch = Instant::now();
+        let frame = frame(epoch, 0, &[("say \"hi\" to C:\\dir\n\t\u{1}", 0, 0, Some(1))]);
+
+        let json = chrome_trace_json(epoch, vec![(&frame, at(epoch, 1))].into_iter());
+
+        assert!(json.contains("{\"name\":\"say \\\"hi\\\" to C:\\\\dir\\n\\t\\u0001\",\"cat\":\"scope\""));
+    }
+
+    #[test]
+    fn json_strings_escape_quotes_backslashes_and_control_chars() {
+        let mut json = String::new();
+        push_json_string(&mut json, "a\"b\\c\r\n\t\u{0}\u{1f} ž");
+
+        assert_eq!("\"a\\\"b\\\\c\\r\\n\\t\\u0000\\u001f ž\"", json);
+    }
+
+    #[test]
+    fn scope_color_is_stable_palette_color() {
+        assert_eq!(scope_color("draw"), scope_color("draw"));
+
+        for name in ["", "draw", "say \"hi\"", "\u{1}"].iter() {
+            let color = scope_color(name);
+            assert!(SCOPE_PALETTE.iter().any(|&(r, g, b)| color == na::Vector3::new(r, g, b)));
+        }
+    }
+}
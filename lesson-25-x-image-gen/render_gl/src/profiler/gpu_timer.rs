@@ -0,0 +1,250 @@
+use gl;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Frames that can wait for query results, older frames are dropped without reading them.
+const MAX_PENDING_FRAMES: usize = 6;
+
+/// Scope timing read back from the GPU, converted to the CPU clock.
+#[derive(Copy, Clone, Debug)]
+pub struct GpuScopeTiming {
+    pub name: &'static str,
+    pub depth: usize,
+    pub start: Instant,
+    pub end: Instant,
+}
+
+struct GpuScope {
+    name: &'static str,
+    depth: usize,
+    begin_query: gl::types::GLuint,
+    end_query: Option<gl::types::GLuint>,
+}
+
+struct GpuFrame {
+    number: u64,
+    /// CPU instant and GPU timestamp in nanoseconds taken at the same moment.
+    calibration: (Instant, u64),
+    scopes: Vec<GpuScope>,
+    /// Query issued last, results of earlier queries are available when it is.
+    last_query: Option<gl::types::GLuint>,
+}
+
+/// Measures scopes on the GPU with `GL_TIMESTAMP` queries.
+///
+/// Results become available a few frames later, so every frame keeps its queries
+/// until they can be read without stalling the pipeline. When timer queries are not
+/// supported, for example on software GL, all calls do nothing.
+pub struct GpuTimer {
+    gl: gl::Gl,
+    available: bool,
+    free_queries: Vec<gl::types::GLuint>,
+    /// Emptied scope lists of released frames, reused to avoid allocations.
+    free_scope_lists: Vec<Vec<GpuScope>>,
+    frame: Option<GpuFrame>,
+    pending: VecDeque<GpuFrame>,
+    depth: usize,
+}
+
+impl GpuTimer {
+    pub fn new(gl: &gl::Gl) -> GpuTimer {
+        let loaded = gl.GenQueries.is_loaded()
+            && gl.QueryCounter.is_loaded()
+            && gl.GetQueryObjectui64v.is_loaded()
+            && gl.GetInteger64v.is_loaded();
+
+        let mut counter_bits: gl::types::GLint = 0;
+        if loaded {
+            unsafe {
+                gl.GetQueryiv(gl::TIMESTAMP, gl::QUERY_COUNTER_BITS, &mut counter_bits);
+            }
+        }
+
+        let available = loaded && counter_bits > 0;
+        if !available {
+            info!("GPU timer queries are not available, GPU profiling is disabled");
+        }
+
+        GpuTimer {
+            gl: gl.clone(),
+            available,
+            free_queries: Vec::new(),
+            free_scope_lists: Vec::new(),
+            frame: None,
+            pending: VecDeque::with_capacity(MAX_PENDING_FRAMES + 1),
+            depth: 0,
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.available
+    }
+
+    /// Ends the current frame and starts recording scopes for the frame `number`.
+    pub fn begin_frame(&mut self, number: u64) {
+        if !self.available {
+            return;
+        }
+
+        if let Some(frame) = self.frame.take() {
+            if frame.last_query.is_some() {
+                self.pending.push_back(frame);
+            } else {
+                self.release(frame);
+            }
+        }
+
+        while self.pending.len() > MAX_PENDING_FRAMES {
+            if let Some(frame) = self.pending.pop_front() {
+                self.release(frame);
+            }
+        }
+
+        let mut gpu_time: gl::types::GLint64 = 0;
+        unsafe {
+            self.gl.GetInteger64v(gl::TIMESTAMP, &mut gpu_time);
+        }
+
+        self.depth = 0;
+        self.frame = Some(GpuFrame {
+            number,
+            calibration: (Instant::now(), gpu_time as u64),
+            scopes: self.free_scope_lists.pop().unwrap_or_else(Vec::new),
+            last_query: None,
+        });
+    }
+
+    /// Issues the start timestamp of a scope and returns its index in the current frame.
+    pub fn begin_scope(&mut self, name: &'static str) -> Option<usize> {
+        if !self.available || self.frame.is_none() {
+            return None;
+        }
+
+        let query = self.query_counter();
+        let depth = self.depth;
+        self.depth += 1;
+
+        let frame = self.frame.as_mut().unwrap();
+        frame.scopes.push(GpuScope {
+            name,
+            depth,
+            begin_query: query,
+            end_query: None,
+        });
+        frame.last_query = Some(query);
+
+        Some(frame.scopes.len() - 1)
+    }
+
+    pub fn end_scope(&mut self, index: usize) {
+        if self.frame.is_none() {
+            return;
+        }
+
+        let query = self.query_counter();
+        self.depth = self.depth.saturating_sub(1);
+
+        let frame = self.frame.as_mut().unwrap();
+        frame.scopes[index].end_query = Some(query);
+        frame.last_query = Some(query);
+    }
+
+    /// Reads results of finished frames, oldest first, and passes them to `output`
+    /// together with the frame number.
+    pub fn collect<F>(&mut self, mut output: F)
+        where F: FnMut(u64, GpuScopeTiming)
+    {
+        while let Some(frame) = self.pending.pop_front() {
+            if !self.is_query_available(frame.last_query) {
+                self.pending.push_front(frame);
+                return;
+            }
+
+            let (cpu_time, gpu_time) = frame.calibration;
+            let to_instant = |timestamp: u64| cpu_time + Duration::from_nanos(timestamp.saturating_sub(gpu_time));
+
+            for scope in frame.scopes.iter() {
+                if let Some(end_query) = scope.end_query {
+                    let start = self.query_result(scope.begin_query);
+                    let end = self.query_result(end_query);
+
+                    output(frame.number, GpuScopeTiming {
+                        name: scope.name,
+                        depth: scope.depth,
+                        start: to_instant(start),
+                        end: to_instant(end.max(start)),
+                    });
+                }
+            }
+
+            self.release(frame);
+        }
+    }
+
+    fn query_counter(&mut self) -> gl::types::GLuint {
+        let query = match self.free_queries.pop() {
+            Some(query) => query,
+            None => {
+                let mut query: gl::types::GLuint = 0;
+                unsafe {
+                    self.gl.GenQueries(1, &mut query);
+                }
+                query
+            }
+        };
+
+        unsafe {
+            self.gl.QueryCounter(query, gl::TIMESTAMP);
+        }
+
+        query
+    }
+
+    fn is_query_available(&self, query: Option<gl::types::GLuint>) -> bool {
+        let query = match query {
+            Some(query) => query,
+            None => return true,
+        };
+
+        let mut available: gl::types::GLint = 0;
+        unsafe {
+            self.gl.GetQueryObjectiv(query, gl::QUERY_RESULT_AVAILABLE, &mut available);
+        }
+        available != 0
+    }
+
+    fn query_result(&self, query: gl::types::GLuint) -> u64 {
+        let mut result: gl::types::GLuint64 = 0;
+        unsafe {
+            self.gl.GetQueryObjectui64v(query, gl::QUERY_RESULT, &mut result);
+        }
+        result
+    }
+
+    fn release(&mut self, mut frame: GpuFrame) {
+        for scope in frame.scopes.drain(..) {
+            self.free_queries.push(scope.begin_query);
+            if let Some(end_query) = scope.end_query {
+                self.free_queries.push(end_query);
+            }
+        }
+        self.free_scope_lists.push(frame.scopes);
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        if let Some(frame) = self.frame.take() {
+            self.release(frame);
+        }
+        while let Some(frame) = self.pending.pop_front() {
+            self.release(frame);
+        }
+
+        if !self.free_queries.is_empty() {
+            unsafe {
+                self.gl.DeleteQueries(self.free_queries.len() as i32, self.free_queries.as_ptr());
+            }
+        }
+    }
+}
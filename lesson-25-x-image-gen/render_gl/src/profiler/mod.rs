@@ -1,6 +1,7 @@
 mod buffers;
 mod event_count_profiler;
 mod frame_profiler;
+mod gpu_timer;
 
 pub use self::event_count_profiler::EventCountProfiler;
-pub use self::frame_profiler::FrameProfiler;
+pub use self::frame_profiler::{FrameProfiler, ProfilerScope, ScopeStats, scope_color};
@@ -0,0 +1,311 @@
+use gl;
+
+/// Number of frame regions a ring buffer cycles through.
+///
+/// While the GPU reads from one region, CPU can fill the next one.
+pub const DEFAULT_REGIONS: usize = 3;
+
+enum Storage<T> {
+    /// `glBufferStorage` with persistent, coherent mapping (OpenGL 4.4).
+    Persistent { ptr: *mut T },
+    /// Fallback for older contexts: data is collected on CPU and uploaded by orphaning.
+    Orphaning { staging: Vec<T> },
+}
+
+/// Streaming buffer split into `regions` frame regions of `capacity` elements each.
+///
+/// Every write goes to the next region, which is guarded by a fence placed after
+/// the last draw that used it, so the CPU never overwrites data the GPU is still reading.
+///
+/// Usage per frame:
+///
+/// - `begin` to start writing into the next region,
+/// - `push` or `extend` the data,
+/// - `end` to finish writing,
+/// - draw using `first` and `len`,
+/// - `fence` after the draw calls.
+pub struct RingBuffer<T: Copy> {
+    gl: gl::Gl,
+    buffer_type: gl::types::GLuint,
+    vbo: gl::types::GLuint,
+    storage: Storage<T>,
+    capacity: usize,
+    regions: usize,
+    fences: Vec<Option<gl::types::GLsync>>,
+    current_region: usize,
+    len: usize,
+    generation: usize,
+}
+
+impl<T: Copy> RingBuffer<T> {
+    pub fn new_array(gl: &gl::Gl, capacity: usize) -> RingBuffer<T> {
+        Self::new(gl, gl::ARRAY_BUFFER, capacity, DEFAULT_REGIONS)
+    }
+
+    pub fn new(gl: &gl::Gl, buffer_type: gl::types::GLuint, capacity: usize, regions: usize) -> RingBuffer<T> {
+        assert!(regions > 0, "ring buffer needs at least one region");
+
+        let persistent = gl.BufferStorage.is_loaded();
+
+        let mut buffer = RingBuffer {
+            gl: gl.clone(),
+            buffer_type,
+            vbo: 0,
+            storage: if persistent {
+                Storage::Persistent { ptr: ::std::ptr::null_mut() }
+            } else {
+                Storage::Orphaning { staging: Vec::with_capacity(capacity) }
+            },
+            capacity: 0,
+            regions,
+            fences: vec![None; regions],
+            current_region: 0,
+            len: 0,
+            generation: 0,
+        };
+
+        buffer.allocate(::std::cmp::max(capacity, 1));
+        buffer
+    }
+
+    /// Element capacity of a single region.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of elements written into the current region.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Index of the first element of the current region, to be used as the first vertex for draw calls.
+    pub fn first(&self) -> usize {
+        match self.storage {
+            Storage::Persistent { .. } => self.current_region * self.capacity,
+            Storage::Orphaning { .. } => 0,
+        }
+    }
+
+    /// Incremented every time the underlying GL buffer is recreated.
+    ///
+    /// Vertex array objects referencing this buffer must be set up again when it changes.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            self.gl.BindBuffer(self.buffer_type, self.vbo);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            self.gl.BindBuffer(self.buffer_type, 0);
+        }
+    }
+
+    /// Moves to the next region and waits until GPU is done reading it.
+    pub fn begin(&mut self) {
+        self.current_region = (self.current_region + 1) % self.regions;
+        self.wait_region(self.current_region);
+        self.len = 0;
+
+        if let Storage::Orphaning { ref mut staging } = self.storage {
+            staging.clear();
+        }
+    }
+
+    /// Makes sure that `additional` more elements fit into the current region.
+    pub fn reserve(&mut self, additional: usize) {
+        let required = self.len + additional;
+        if required > self.capacity {
+            self.grow(required);
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        if self.len >= self.capacity {
+            self.grow(self.len + 1);
+        }
+
+        match self.storage {
+            Storage::Persistent { ptr } => unsafe {
+                ::std::ptr::write(ptr.add(self.current_region * self.capacity + self.len), item);
+            },
+            Storage::Orphaning { ref mut staging } => staging.push(item),
+        }
+
+        self.len += 1;
+    }
+
+    pub fn extend(&mut self, items: impl Iterator<Item = T>) {
+        let (lower, _) = items.size_hint();
+        self.reserve(lower);
+
+        for item in items {
+            self.push(item);
+        }
+    }
+
+    /// Finishes writing into the current region.
+    pub fn end(&mut self) {
+        if let Storage::Orphaning { ref staging } = self.storage {
+            unsafe {
+                self.gl.BindBuffer(self.buffer_type, self.vbo);
+                self.gl.BufferData(
+                    self.buffer_type,
+                    (self.capacity * ::std::mem::size_of::<T>()) as gl::types::GLsizeiptr,
+                    ::std::ptr::null(),
+                    gl::STREAM_DRAW,
+                );
+                if staging.len() > 0 {
+                    self.gl.BufferSubData(
+                        self.buffer_type,
+                        0,
+                        (staging.len() * ::std::mem::size_of::<T>()) as gl::types::GLsizeiptr,
+                        staging.as_ptr() as *const gl::types::GLvoid,
+                    );
+                }
+                self.gl.BindBuffer(self.buffer_type, 0);
+            }
+        }
+    }
+
+    /// Places a fence for the current region. Call it after the draw calls that read the region.
+    pub fn fence(&mut self) {
+        if let Storage::Orphaning { .. } = self.storage {
+            return;
+        }
+
+        let region = self.current_region;
+        self.delete_fence(region);
+        self.fences[region] = Some(unsafe { self.gl.FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) });
+    }
+
+    fn wait_region(&mut self, region: usize) {
+        if let Some(sync) = self.fences[region].take() {
+            unsafe {
+                loop {
+                    let result = self.gl.ClientWaitSync(sync, gl::SYNC_FLUSH_COMMANDS_BIT, 1_000_000);
+                    if result == gl::ALREADY_SIGNALED || result == gl::CONDITION_SATISFIED {
+                        break;
+                    }
+                    if result == gl::WAIT_FAILED {
+                        error!("ring buffer fence wait failed");
+                        break;
+                    }
+                }
+                self.gl.DeleteSync(sync);
+            }
+        }
+    }
+
+    fn delete_fence(&mut self, region: usize) {
+        if let Some(sync) = self.fences[region].take() {
+            unsafe {
+                self.gl.DeleteSync(sync);
+            }
+        }
+    }
+
+    fn grow(&mut self, required: usize) {
+        let mut new_capacity = ::std::cmp::max(self.capacity, 1);
+        while new_capacity < required {
+            new_capacity *= 2;
+        }
+
+        trace!("grow ring buffer from {} to {} elements per region", self.capacity, new_capacity);
+
+        match self.storage {
+            Storage::Persistent { .. } => {
+                // keep the data already written into this region by copying it on the GPU
+                let old_vbo = self.vbo;
+                let old_first = self.current_region * self.capacity;
+                self.vbo = 0;
+
+                for region in 0..self.regions {
+                    self.delete_fence(region);
+                }
+
+                self.allocate(new_capacity);
+                self.current_region = 0;
+
+                unsafe {
+                    if self.len > 0 {
+                        self.gl.BindBuffer(gl::COPY_READ_BUFFER, old_vbo);
+                        self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, self.vbo);
+                        self.gl.CopyBufferSubData(
+                            gl::COPY_READ_BUFFER,
+                            gl::COPY_WRITE_BUFFER,
+                            (old_first * ::std::mem::size_of::<T>()) as gl::types::GLintptr,
+                            0,
+                            (self.len * ::std::mem::size_of::<T>()) as gl::types::GLsizeiptr,
+                        );
+                        self.gl.BindBuffer(gl::COPY_READ_BUFFER, 0);
+                        self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+                    }
+
+                    let mut old_vbo = old_vbo;
+                    self.gl.DeleteBuffers(1, &mut old_vbo);
+                }
+            }
+            Storage::Orphaning { .. } => {
+                self.allocate(new_capacity);
+            }
+        }
+    }
+
+    fn allocate(&mut self, capacity: usize) {
+        let regions = match self.storage {
+            Storage::Persistent { .. } => self.regions,
+            Storage::Orphaning { .. } => 1,
+        };
+        let bytes = (capacity * regions * ::std::mem::size_of::<T>()) as gl::types::GLsizeiptr;
+
+        unsafe {
+            if self.vbo != 0 {
+                self.gl.DeleteBuffers(1, &mut self.vbo);
+            }
+            self.gl.GenBuffers(1, &mut self.vbo);
+            self.gl.BindBuffer(self.buffer_type, self.vbo);
+
+            match self.storage {
+                Storage::Persistent { ref mut ptr } => {
+                    let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+                    self.gl.BufferStorage(self.buffer_type, bytes, ::std::ptr::null(), flags);
+                    *ptr = self.gl.MapBufferRange(self.buffer_type, 0, bytes, flags) as *mut T;
+
+                    if ptr.is_null() {
+                        panic!("failed to persistently map ring buffer of {} bytes", bytes);
+                    }
+                }
+                Storage::Orphaning { .. } => {
+                    self.gl.BufferData(self.buffer_type, bytes, ::std::ptr::null(), gl::STREAM_DRAW);
+                }
+            }
+
+            self.gl.BindBuffer(self.buffer_type, 0);
+        }
+
+        self.capacity = capacity;
+        self.generation += 1;
+    }
+}
+
+impl<T: Copy> Drop for RingBuffer<T> {
+    fn drop(&mut self) {
+        for region in 0..self.regions {
+            self.delete_fence(region);
+        }
+
+        unsafe {
+            if let Storage::Persistent { .. } = self.storage {
+                self.gl.BindBuffer(self.buffer_type, self.vbo);
+                self.gl.UnmapBuffer(self.buffer_type);
+                self.gl.BindBuffer(self.buffer_type, 0);
+            }
+            self.gl.DeleteBuffers(1, &mut self.vbo);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+/// Shelf packing allocator for glyph bitmaps.
+///
+/// Rectangles are placed left to right on horizontal shelves; a new shelf is
+/// opened below the last one when no existing shelf fits.
+pub struct ShelfPacker {
+    width: u32,
+    height: u32,
+    padding: u32,
+    shelves: Vec<Shelf>,
+}
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    next_x: u32,
+}
+
+impl ShelfPacker {
+    pub fn new(width: u32, height: u32, padding: u32) -> ShelfPacker {
+        ShelfPacker {
+            width,
+            height,
+            padding,
+            shelves: Vec::new(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns top-left corner of the allocated rectangle, `None` if the atlas is full.
+    pub fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let padded_width = width + self.padding;
+        let padded_height = height + self.padding;
+
+        if padded_width > self.width || padded_height > self.height {
+            return None;
+        }
+
+        // pick the lowest shelf the rectangle fits in, to waste less space
+        let atlas_width = self.width;
+        let best_shelf = self.shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= padded_height && shelf.next_x + padded_width <= atlas_width)
+            .min_by_key(|shelf| shelf.height);
+
+        if let Some(shelf) = best_shelf {
+            let position = (shelf.next_x, shelf.y);
+            shelf.next_x += padded_width;
+            return Some(position);
+        }
+
+        let next_y = self.shelves.last().map(|s| s.y + s.height).unwrap_or(0);
+        if next_y + padded_height > self.height {
+            return None;
+        }
+
+        self.shelves.push(Shelf {
+            y: next_y,
+            height: padded_height,
+            next_x: padded_width,
+        });
+
+        Some((0, next_y))
+    }
+}
+
+#[cfg(test)]
+mod atlas_tests {
+    use super::*;
+
+    #[test]
+    fn rectangles_fill_shelf_left_to_right_with_padding() {
+        let mut packer = ShelfPacker::new(100, 100, 1);
+
+        assert_eq!(Some((0, 0)), packer.allocate(10, 10));
+        assert_eq!(Some((11, 0)), packer.allocate(10, 10));
+        assert_eq!(Some((22, 0)), packer.allocate(5, 8));
+    }
+
+    #[test]
+    fn taller_rectangle_opens_shelf_below() {
+        let mut packer = ShelfPacker::new(100, 100, 1);
+
+        assert_eq!(Some((0, 0)), packer.allocate(10, 10));
+        assert_eq!(Some((0, 11)), packer.allocate(10, 20));
+        assert_eq!(Some((11, 11)), packer.allocate(10, 20));
+    }
+
+    #[test]
+    fn lowest_fitting_shelf_is_picked() {
+        let mut packer = ShelfPacker::new(100, 100, 1);
+
+        assert_eq!(Some((0, 0)), packer.allocate(50, 30));
+        assert_eq!(Some((0, 31)), packer.allocate(60, 10));
+        assert_eq!(Some((61, 31)), packer.allocate(10, 5));
+        assert_eq!(Some((51, 0)), packer.allocate(10, 20));
+    }
+
+    #[test]
+    fn full_shelf_opens_new_shelf() {
+        let mut packer = ShelfPacker::new(30, 100, 0);
+
+        assert_eq!(Some((0, 0)), packer.allocate(20, 10));
+        assert_eq!(Some((20, 0)), packer.allocate(10, 10));
+        assert_eq!(Some((0, 10)), packer.allocate(10, 10));
+    }
+
+    #[test]
+    fn rectangles_that_do_not_fit_are_rejected() {
+        let mut packer = ShelfPacker::new(100, 50, 1);
+
+        // padding does not fit
+        assert_eq!(None, packer.allocate(100, 10));
+        assert_eq!(None, packer.allocate(10, 50));
+
+        assert_eq!(Some((0, 0)), packer.allocate(99, 30));
+        assert_eq!(None, packer.allocate(10, 20));
+        assert_eq!(Some((0, 31)), packer.allocate(10, 18));
+        assert_eq!(None, packer.allocate(90, 1));
+    }
+}
@@ -0,0 +1,73 @@
+use crate::data;
+use gl;
+
+#[derive(VertexAttribPointers, Copy, Clone, Debug)]
+#[repr(C, packed)]
+pub struct SdfTextVertex {
+    #[location = 0]
+    pub pos: data::f32_f32,
+    #[location = 1]
+    pub uv: data::f32_f32,
+    /// Atlas cell of the glyph, `(min_u, min_v, max_u, max_v)`.
+    #[location = 2]
+    pub uv_bounds: data::f32_f32_f32_f32,
+}
+
+use crate::buffer::VertexArray;
+use crate::ring_buffer::RingBuffer;
+
+pub struct Buffers {
+    gl: gl::Gl,
+    quads: RingBuffer<SdfTextVertex>,
+    quads_generation: usize,
+    pub quads_vao: VertexArray,
+}
+
+impl Buffers {
+    pub fn new(gl: &gl::Gl, vbo_capacity: usize) -> Buffers {
+        let quads = RingBuffer::new_array(gl, vbo_capacity);
+        let quads_vao = VertexArray::new(gl);
+
+        let mut buffers = Buffers {
+            gl: gl.clone(),
+            quads_generation: quads.generation(),
+            quads,
+            quads_vao,
+        };
+
+        buffers.set_up_vao();
+        buffers
+    }
+
+    fn set_up_vao(&mut self) {
+        self.quads_vao.bind();
+
+        self.quads.bind();
+        SdfTextVertex::vertex_attrib_pointers(&self.gl);
+        self.quads.unbind();
+
+        self.quads_vao.unbind();
+
+        self.quads_generation = self.quads.generation();
+    }
+
+    pub fn upload_vertices(&mut self, items: impl Iterator<Item = SdfTextVertex>) {
+        self.quads.begin();
+        self.quads.extend(items);
+        self.quads.end();
+
+        if self.quads_generation != self.quads.generation() {
+            self.set_up_vao();
+        }
+    }
+
+    /// Index of the first uploaded vertex, group vertex ranges are relative to it.
+    pub fn first_vertex(&self) -> i32 {
+        self.quads.first() as i32
+    }
+
+    /// Marks the uploaded vertices as used by the submitted draw calls.
+    pub fn fence(&mut self) {
+        self.quads.fence();
+    }
+}
@@ -0,0 +1,718 @@
+use gl;
+use crate::na;
+use failure;
+use lyon_path::default::Path;
+use resources::Resources;
+use slotmap;
+use crate::ColorBuffer;
+use crate::Program;
+use crate::clip::{Clip, Clipper};
+use crate::texture::{Texture2D, TextureFormat, Sampler, SamplerOptions};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+mod atlas;
+mod buffers;
+mod sdf;
+
+pub use self::buffers::SdfTextVertex;
+pub use self::sdf::{SDF_EM_SIZE, SDF_SPREAD};
+
+use self::atlas::ShelfPacker;
+use self::buffers::Buffers;
+
+const ATLAS_SIZE: u32 = 1024;
+
+/// Font id and key of the variable font instance, glyphs of different instances have
+/// different outlines.
+pub type SdfFontKey = (usize, u64);
+
+/// Outline, shadow and glow drawn around glyphs, sizes are in ems.
+///
+/// None of the effects can extend further than `(SDF_SPREAD - 1) / SDF_EM_SIZE` ems from the glyph
+/// edge, the last texel of the distance field is left for antialiasing. The shadow reaches as far
+/// as the outline width, shadow softness and shadow offset added together. Larger values are clamped.
+#[derive(Copy, Clone, Debug)]
+pub struct SdfTextEffects {
+    pub outline_width: f32,
+    pub outline_color: na::Vector4<f32>,
+    /// Shadow offset, `y` goes up.
+    pub shadow_offset: na::Vector2<f32>,
+    pub shadow_softness: f32,
+    pub shadow_color: na::Vector4<f32>,
+    pub glow_radius: f32,
+    pub glow_color: na::Vector4<f32>,
+}
+
+impl Default for SdfTextEffects {
+    fn default() -> Self {
+        SdfTextEffects {
+            outline_width: 0.0,
+            outline_color: na::Vector4::new(0.0, 0.0, 0.0, 0.0),
+            shadow_offset: na::Vector2::new(0.0, 0.0),
+            shadow_softness: 0.0,
+            shadow_color: na::Vector4::new(0.0, 0.0, 0.0, 0.0),
+            glow_radius: 0.0,
+            glow_color: na::Vector4::new(0.0, 0.0, 0.0, 0.0),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct SdfTextItem {
+    pub glyph_id: u32,
+    pub x_offset: i32,
+    pub y_offset: i32,
+}
+
+#[derive(Copy, Clone)]
+struct SdfGlyph {
+    uv_min: na::Vector2<f32>,
+    uv_max: na::Vector2<f32>,
+    plane_min: na::Vector2<f32>,
+    plane_max: na::Vector2<f32>,
+}
+
+/// Distance field of a glyph placed into the atlas, kept to place it again when the atlas is repacked.
+struct GlyphBitmap {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    plane_min: na::Vector2<f32>,
+    plane_max: na::Vector2<f32>,
+}
+
+struct PendingUpload {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+new_key_type! { pub struct GroupSlot; }
+
+struct GroupData {
+    font_key: SdfFontKey,
+    items: Vec<SdfTextItem>,
+    transform: na::Projective3<f32>,
+    color: na::Vector4<u8>,
+    effects: SdfTextEffects,
+    clip: Option<Clip>,
+    first_vertex: i32,
+    vertex_count: i32,
+}
+
+struct SharedSdfText {
+    /// `None` for glyphs without outline or glyphs that did not fit into the atlas.
+    glyphs: HashMap<(SdfFontKey, u32), Option<SdfGlyph>>,
+    bitmaps: HashMap<(SdfFontKey, u32), GlyphBitmap>,
+    packer: ShelfPacker,
+    pending_uploads: Vec<PendingUpload>,
+    groups: slotmap::SlotMap<GroupSlot, GroupData>,
+    invalidated: bool,
+}
+
+impl SharedSdfText {
+    fn new() -> SharedSdfText {
+        SharedSdfText {
+            glyphs: HashMap::new(),
+            bitmaps: HashMap::new(),
+            packer: ShelfPacker::new(ATLAS_SIZE, ATLAS_SIZE, 1),
+            pending_uploads: Vec::new(),
+            groups: slotmap::SlotMap::with_key(),
+            invalidated: false,
+        }
+    }
+
+    fn add_glyph(&mut self, key: (SdfFontKey, u32), bitmap: Option<sdf::SdfBitmap>) -> bool {
+        let bitmap = match bitmap {
+            Some(bitmap) => bitmap,
+            None => {
+                self.glyphs.insert(key, None);
+                return true;
+            }
+        };
+
+        self.bitmaps.insert(key, GlyphBitmap {
+            width: bitmap.width,
+            height: bitmap.height,
+            plane_min: na::Vector2::new(bitmap.origin_x, bitmap.origin_y),
+            plane_max: na::Vector2::new(
+                bitmap.origin_x + bitmap.width as f32 * bitmap.units_per_pixel,
+                bitmap.origin_y + bitmap.height as f32 * bitmap.units_per_pixel,
+            ),
+            pixels: bitmap.pixels,
+        });
+
+        self.place(key)
+    }
+
+    /// Allocates atlas space for a kept glyph bitmap and queues its upload.
+    ///
+    /// If the atlas is full, the bitmap is dropped and the glyph is skipped when drawing.
+    fn place(&mut self, key: (SdfFontKey, u32)) -> bool {
+        let position = match self.bitmaps.get(&key) {
+            Some(bitmap) => self.packer.allocate(bitmap.width, bitmap.height),
+            None => return false,
+        };
+
+        let (x, y) = match position {
+            Some(position) => position,
+            None => {
+                warn!("SDF atlas is full, glyph {} of font {} will not be drawn", key.1, (key.0).0);
+                self.bitmaps.remove(&key);
+                self.glyphs.insert(key, None);
+                return false;
+            }
+        };
+
+        let bitmap = &self.bitmaps[&key];
+        let atlas_size = ATLAS_SIZE as f32;
+        self.glyphs.insert(key, Some(SdfGlyph {
+            uv_min: na::Vector2::new(x as f32 / atlas_size, y as f32 / atlas_size),
+            uv_max: na::Vector2::new((x + bitmap.width) as f32 / atlas_size, (y + bitmap.height) as f32 / atlas_size),
+            plane_min: bitmap.plane_min,
+            plane_max: bitmap.plane_max,
+        }));
+        self.pending_uploads.push(PendingUpload {
+            x,
+            y,
+            width: bitmap.width,
+            height: bitmap.height,
+            pixels: bitmap.pixels.clone(),
+        });
+        self.invalidated = true;
+
+        true
+    }
+
+    /// Forgets glyphs of the font and packs the remaining glyphs into an empty atlas,
+    /// so that the space of the forgotten glyphs can be used again.
+    fn remove_font(&mut self, font_id: usize) {
+        self.glyphs.retain(|&((key_font_id, _), _), _| key_font_id != font_id);
+        self.bitmaps.retain(|&((key_font_id, _), _), _| key_font_id != font_id);
+
+        self.packer = ShelfPacker::new(ATLAS_SIZE, ATLAS_SIZE, 1);
+        self.pending_uploads.clear();
+
+        // taller glyphs first, so that shelves are filled with glyphs of similar height
+        let mut keys: Vec<_> = self.bitmaps.keys().cloned().collect();
+        keys.sort_by(|a, b| self.bitmaps[b].height.cmp(&self.bitmaps[a].height));
+        for key in keys {
+            self.place(key);
+        }
+
+        self.invalidated = true;
+    }
+
+    fn glyph_vertices<'r>(&'r self, group: &'r GroupData) -> impl Iterator<Item = SdfTextVertex> + 'r {
+        let font_key = group.font_key;
+
+        group.items
+            .iter()
+            .filter_map(move |item| {
+                self.glyphs.get(&(font_key, item.glyph_id))
+                    .and_then(|g| *g)
+                    .map(|g| (g, item))
+            })
+            .flat_map(|(g, item)| {
+                let offset = na::Vector2::new(item.x_offset as f32, item.y_offset as f32);
+                let (p0, p1) = (g.plane_min + offset, g.plane_max + offset);
+                let corners = [
+                    (p0.x, p0.y, g.uv_min.x, g.uv_min.y),
+                    (p1.x, p0.y, g.uv_max.x, g.uv_min.y),
+                    (p1.x, p1.y, g.uv_max.x, g.uv_max.y),
+                    (p0.x, p0.y, g.uv_min.x, g.uv_min.y),
+                    (p1.x, p1.y, g.uv_max.x, g.uv_max.y),
+                    (p0.x, p1.y, g.uv_min.x, g.uv_max.y),
+                ];
+
+                // shadows are sampled away from the glyph and must not reach into neighbouring cells,
+                // bounds are half a texel inside so that filtering does not read atlas padding
+                let half_texel = 0.5 / ATLAS_SIZE as f32;
+                let uv_bounds = (
+                    g.uv_min.x + half_texel,
+                    g.uv_min.y + half_texel,
+                    g.uv_max.x - half_texel,
+                    g.uv_max.y - half_texel,
+                );
+
+                (0..6).map(move |i| {
+                    let (x, y, u, v) = corners[i];
+                    SdfTextVertex {
+                        pos: (x, y).into(),
+                        uv: (u, v).into(),
+                        uv_bounds: uv_bounds.into(),
+                    }
+                })
+            })
+    }
+}
+
+/// Text renderer that draws glyphs as textured quads sampling a signed distance field atlas.
+///
+/// Unlike tessellated glyphs in `Flatlander`, glyphs stay smooth under any scale and
+/// support outline, shadow and glow effects.
+pub struct SdfTextRenderer {
+    program: Program,
+    program_view_projection_location: Option<i32>,
+    program_model_location: Option<i32>,
+    program_atlas_location: Option<i32>,
+    program_distance_scale_location: Option<i32>,
+    program_text_color_location: Option<i32>,
+    program_outline_width_location: Option<i32>,
+    program_outline_color_location: Option<i32>,
+    program_shadow_offset_location: Option<i32>,
+    program_shadow_softness_location: Option<i32>,
+    program_shadow_color_location: Option<i32>,
+    program_glow_radius_location: Option<i32>,
+    program_glow_color_location: Option<i32>,
+    shared: Rc<RefCell<SharedSdfText>>,
+    atlas: Option<Texture2D>,
+    sampler: Option<Sampler>,
+    buffers: Option<Buffers>,
+    clipper: Clipper,
+    draw_enabled: bool,
+}
+
+impl SdfTextRenderer {
+    pub fn new(gl: &gl::Gl, res: &Resources) -> Result<SdfTextRenderer, failure::Error> {
+        let program = Program::from_res(gl, res, "shaders/render_gl/sdf_text")?;
+
+        Ok(SdfTextRenderer {
+            program_view_projection_location: program.get_uniform_location("ViewProjection"),
+            program_model_location: program.get_uniform_location("Model"),
+            program_atlas_location: program.get_uniform_location("Atlas"),
+            program_distance_scale_location: program.get_uniform_location("DistanceScale"),
+            program_text_color_location: program.get_uniform_location("TextColor"),
+            program_outline_width_location: program.get_uniform_location("OutlineWidth"),
+            program_outline_color_location: program.get_uniform_location("OutlineColor"),
+            program_shadow_offset_location: program.get_uniform_location("ShadowOffset"),
+            program_shadow_softness_location: program.get_uniform_location("ShadowSoftness"),
+            program_shadow_color_location: program.get_uniform_location("ShadowColor"),
+            program_glow_radius_location: program.get_uniform_location("GlowRadius"),
+            program_glow_color_location: program.get_uniform_location("GlowColor"),
+            program,
+            shared: Rc::new(RefCell::new(SharedSdfText::new())),
+            atlas: None,
+            sampler: None,
+            buffers: None,
+            clipper: Clipper::new(gl, res)?,
+            draw_enabled: true,
+        })
+    }
+
+    pub fn toggle(&mut self) {
+        self.draw_enabled = !self.draw_enabled;
+    }
+
+    pub fn has_glyph(&self, font_key: SdfFontKey, glyph_id: u32) -> bool {
+        self.shared.borrow().glyphs.contains_key(&(font_key, glyph_id))
+    }
+
+    /// Forgets glyphs of all instances of a font, so that they are generated again by `add_glyph`.
+    ///
+    /// Remaining glyphs are packed into the atlas again, so reloading a font does not use up atlas space.
+    pub fn remove_font(&self, font_id: usize) {
+        self.shared.borrow_mut().remove_font(font_id);
+    }
+
+    /// Generates the distance field for a glyph outline in font units and places it into the atlas.
+    ///
+    /// Returns `false` if the atlas is full, the glyph is then skipped when drawing.
+    pub fn add_glyph(&self, font_key: SdfFontKey, glyph_id: u32, units_per_em: u32, path: &Path) -> bool {
+        self.shared.borrow_mut().add_glyph((font_key, glyph_id), sdf::generate(path, units_per_em))
+    }
+
+    pub fn create_group(
+        &self,
+        font_key: SdfFontKey,
+        transform: &na::Projective3<f32>,
+        color: na::Vector4<u8>,
+        effects: SdfTextEffects,
+        items: Vec<SdfTextItem>,
+    ) -> SdfTextGroup {
+        let mut shared = self.shared.borrow_mut();
+        let slot = shared.groups.insert(GroupData {
+            font_key,
+            items,
+            transform: *transform,
+            color,
+            effects,
+            clip: None,
+            first_vertex: 0,
+            vertex_count: 0,
+        });
+        shared.invalidated = true;
+
+        SdfTextGroup {
+            slot,
+            shared: self.shared.clone(),
+        }
+    }
+
+    fn check_if_invalidated_and_reinitialize(&mut self, gl: &gl::Gl) {
+        let mut shared = self.shared.borrow_mut();
+
+        if !shared.invalidated {
+            return;
+        }
+
+        if self.atlas.is_none() {
+            self.atlas = Some(Texture2D::new_empty(gl, TextureFormat::R8, ATLAS_SIZE, ATLAS_SIZE));
+            self.sampler = Some(Sampler::new(gl, &SamplerOptions::linear()));
+        }
+
+        if let Some(ref atlas) = self.atlas {
+            for upload in shared.pending_uploads.drain(..) {
+                atlas.sub_image(upload.x, upload.y, upload.width, upload.height, &upload.pixels);
+            }
+        }
+
+        let mut ranges = Vec::with_capacity(shared.groups.len());
+        let mut vertices = Vec::new();
+        for (slot, group) in shared.groups.iter() {
+            let first_vertex = vertices.len() as i32;
+            vertices.extend(shared.glyph_vertices(group));
+            ranges.push((slot, first_vertex, vertices.len() as i32 - first_vertex));
+        }
+
+        for (slot, first_vertex, vertex_count) in ranges {
+            let group = &mut shared.groups[slot];
+            group.first_vertex = first_vertex;
+            group.vertex_count = vertex_count;
+        }
+
+        if self.buffers.is_none() {
+            self.buffers = Some(Buffers::new(gl, vertices.len()));
+        }
+
+        if let Some(ref mut buffers) = self.buffers {
+            buffers.upload_vertices(vertices.into_iter());
+        }
+
+        shared.invalidated = false;
+    }
+
+    pub fn render(&mut self, gl: &gl::Gl, target: &ColorBuffer, vp_matrix: &na::Matrix4<f32>) {
+        if !self.draw_enabled {
+            return;
+        }
+
+        self.check_if_invalidated_and_reinitialize(gl);
+
+        let (atlas, sampler, buffers) = match (&self.atlas, &self.sampler, &self.buffers) {
+            (Some(atlas), Some(sampler), Some(buffers)) => (atlas, sampler, buffers),
+            _ => return,
+        };
+
+        let shared = self.shared.borrow();
+        if shared.groups.is_empty() {
+            return;
+        }
+
+        self.program.set_used();
+        if let Some(loc) = self.program_view_projection_location {
+            self.program.set_uniform_matrix_4fv(loc, &vp_matrix);
+        }
+        if let Some(loc) = self.program_atlas_location {
+            self.program.set_uniform_1i(loc, 0);
+        }
+        if let Some(loc) = self.program_distance_scale_location {
+            // distance in ems covered by the full [0, 1] range of a texel
+            self.program.set_uniform_1f(loc, 2.0 * SDF_SPREAD / SDF_EM_SIZE);
+        }
+
+        atlas.bind(0);
+        sampler.bind(0);
+
+        let first_vertex = buffers.first_vertex();
+        buffers.quads_vao.bind();
+
+        unsafe {
+            target.set_default_blend_func(gl);
+            target.enable_blend(gl);
+            target.front_face_cw(gl);
+        }
+
+        let flip_y = na::Matrix4::<f32>::new_nonuniform_scaling(&na::Vector3::new(1.0, -1.0, 1.0));
+
+        for group in shared.groups.values().filter(|g| g.vertex_count > 0) {
+            if let Some(ref clip) = group.clip {
+                if !self.clipper.begin(vp_matrix, clip) {
+                    continue;
+                }
+                self.program.set_used();
+                buffers.quads_vao.bind();
+            }
+
+            if let Some(loc) = self.program_model_location {
+                let model = na::convert::<_, na::Matrix4<f32>>(group.transform) * flip_y;
+                self.program.set_uniform_matrix_4fv(loc, &model);
+            }
+            self.set_group_uniforms(group);
+
+            unsafe {
+                gl.DrawArrays(gl::TRIANGLES, first_vertex + group.first_vertex, group.vertex_count);
+            }
+
+            self.clipper.end();
+        }
+
+        unsafe {
+            target.front_face_ccw(gl);
+            target.disable_blend(gl);
+        }
+
+        buffers.quads_vao.unbind();
+        sampler.unbind(0);
+        atlas.unbind(0);
+
+        if let Some(ref mut buffers) = self.buffers {
+            buffers.fence();
+        }
+    }
+
+    fn set_group_uniforms(&self, group: &GroupData) {
+        let effects = &clamp_effects(&group.effects);
+        let color = na::Vector4::new(
+            group.color.x as f32 / 255.0,
+            group.color.y as f32 / 255.0,
+            group.color.z as f32 / 255.0,
+            group.color.w as f32 / 255.0,
+        );
+
+        if let Some(loc) = self.program_text_color_location {
+            self.program.set_uniform_4f(loc, &color);
+        }
+        if let Some(loc) = self.program_outline_width_location {
+            self.program.set_uniform_1f(loc, effects.outline_width);
+        }
+        if let Some(loc) = self.program_outline_color_location {
+            self.program.set_uniform_4f(loc, &effects.outline_color);
+        }
+        if let Some(loc) = self.program_shadow_offset_location {
+            // shadow is sampled in atlas space, where one em is SDF_EM_SIZE texels
+            let offset = effects.shadow_offset * (SDF_EM_SIZE / ATLAS_SIZE as f32);
+            self.program.set_uniform_2f(loc, &offset);
+        }
+        if let Some(loc) = self.program_shadow_softness_location {
+            self.program.set_uniform_1f(loc, effects.shadow_softness);
+        }
+        if let Some(loc) = self.program_shadow_color_location {
+            self.program.set_uniform_4f(loc, &effects.shadow_color);
+        }
+        if let Some(loc) = self.program_glow_radius_location {
+            self.program.set_uniform_1f(loc, effects.glow_radius);
+        }
+        if let Some(loc) = self.program_glow_color_location {
+            self.program.set_uniform_4f(loc, &effects.glow_color);
+        }
+    }
+}
+
+/// Limits effects to the distance stored in the atlas, see `SdfTextEffects`.
+fn clamp_effects(effects: &SdfTextEffects) -> SdfTextEffects {
+    let max_distance = (SDF_SPREAD - 1.0) / SDF_EM_SIZE;
+
+    let outline_width = effects.outline_width.max(0.0).min(max_distance);
+    let shadow_softness = effects.shadow_softness.max(0.0).min(max_distance - outline_width);
+
+    // the offset keeps its direction
+    let max_shadow_offset = max_distance - outline_width - shadow_softness;
+    let shadow_offset_length = effects.shadow_offset.norm();
+    let shadow_offset = if shadow_offset_length > max_shadow_offset {
+        effects.shadow_offset * (max_shadow_offset / shadow_offset_length)
+    } else {
+        effects.shadow_offset
+    };
+
+    SdfTextEffects {
+        outline_width,
+        shadow_offset,
+        shadow_softness,
+        glow_radius: effects.glow_radius.max(0.0).min(max_distance),
+        ..*effects
+    }
+}
+
+pub struct SdfTextGroup {
+    slot: GroupSlot,
+    shared: Rc<RefCell<SharedSdfText>>,
+}
+
+impl SdfTextGroup {
+    pub fn update_items<'p, I: Iterator<Item = &'p SdfTextItem>>(&self, items: I) {
+        let mut shared = self.shared.borrow_mut();
+        let group = &mut shared.groups[self.slot];
+        group.items.clear();
+        group.items.extend(items);
+        shared.invalidated = true;
+    }
+
+    pub fn update_transform(&self, transform: &na::Projective3<f32>) {
+        self.shared.borrow_mut().groups[self.slot].transform = *transform;
+    }
+
+    pub fn update_color(&self, color: na::Vector4<u8>) {
+        self.shared.borrow_mut().groups[self.slot].color = color;
+    }
+
+    pub fn update_effects(&self, effects: SdfTextEffects) {
+        self.shared.borrow_mut().groups[self.slot].effects = effects;
+    }
+
+    /// Limits drawing of the group to the clip, `None` draws it unclipped.
+    pub fn update_clip(&self, clip: Option<&Clip>) {
+        self.shared.borrow_mut().groups[self.slot].clip = clip.cloned();
+    }
+}
+
+impl Drop for SdfTextGroup {
+    fn drop(&mut self) {
+        let mut shared = self.shared.borrow_mut();
+        shared.groups.remove(self.slot);
+        shared.invalidated = true;
+    }
+}
+
+#[cfg(test)]
+mod sdf_text_tests {
+    use super::*;
+
+    const MAX_DISTANCE: f32 = (SDF_SPREAD - 1.0) / SDF_EM_SIZE;
+
+    fn assert_near(expected: f32, actual: f32) {
+        assert!((expected - actual).abs() < 0.00001, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn effects_within_spread_are_unchanged() {
+        let effects = SdfTextEffects {
+            outline_width: 0.02,
+            shadow_offset: na::Vector2::new(0.03, -0.04),
+            shadow_softness: 0.01,
+            glow_radius: 0.1,
+            ..SdfTextEffects::default()
+        };
+
+        let clamped = clamp_effects(&effects);
+
+        assert_near(0.02, clamped.outline_width);
+        assert_near(0.01, clamped.shadow_softness);
+        assert_near(0.03, clamped.shadow_offset.x);
+        assert_near(-0.04, clamped.shadow_offset.y);
+        assert_near(0.1, clamped.glow_radius);
+    }
+
+    #[test]
+    fn effects_are_clamped_to_spread() {
+        let effects = SdfTextEffects {
+            outline_width: 1.0,
+            shadow_softness: 1.0,
+            shadow_offset: na::Vector2::new(1.0, 1.0),
+            glow_radius: 1.0,
+            ..SdfTextEffects::default()
+        };
+
+        let clamped = clamp_effects(&effects);
+
+        assert_near(MAX_DISTANCE, clamped.outline_width);
+        assert_near(0.0, clamped.shadow_softness);
+        assert_near(0.0, clamped.shadow_offset.norm());
+        assert_near(MAX_DISTANCE, clamped.glow_radius);
+    }
+
+    #[test]
+    fn shadow_offset_keeps_direction_within_remaining_spread() {
+        let effects = SdfTextEffects {
+            outline_width: 0.02,
+            shadow_softness: 0.01,
+            shadow_offset: na::Vector2::new(3.0, -4.0),
+            ..SdfTextEffects::default()
+        };
+
+        let clamped = clamp_effects(&effects);
+
+        let length = MAX_DISTANCE - 0.03;
+        assert_near(length, clamped.shadow_offset.norm());
+        assert_near(length * 0.6, clamped.shadow_offset.x);
+        assert_near(length * -0.8, clamped.shadow_offset.y);
+    }
+
+    #[test]
+    fn negative_sizes_are_zero() {
+        let effects = SdfTextEffects {
+            outline_width: -1.0,
+            shadow_softness: -1.0,
+            glow_radius: -1.0,
+            ..SdfTextEffects::default()
+        };
+
+        let clamped = clamp_effects(&effects);
+
+        assert_near(0.0, clamped.outline_width);
+        assert_near(0.0, clamped.shadow_softness);
+        assert_near(0.0, clamped.glow_radius);
+    }
+
+    fn bitmap(size: u32) -> Option<sdf::SdfBitmap> {
+        Some(sdf::SdfBitmap {
+            width: size,
+            height: size,
+            pixels: vec![0; (size * size) as usize],
+            origin_x: 0.0,
+            origin_y: 0.0,
+            units_per_pixel: 1.0,
+        })
+    }
+
+    #[test]
+    fn glyph_does_not_fit_into_full_atlas() {
+        let mut shared = SharedSdfText::new();
+
+        assert!(shared.add_glyph(((1, 0), 1), bitmap(600)));
+        assert!(!shared.add_glyph(((2, 0), 1), bitmap(600)));
+        assert!(shared.glyphs[&((2, 0), 1)].is_none());
+    }
+
+    #[test]
+    fn removed_font_space_is_reused() {
+        let mut shared = SharedSdfText::new();
+        shared.add_glyph(((1, 0), 1), bitmap(600));
+
+        shared.remove_font(1);
+
+        assert!(shared.glyphs.is_empty());
+        assert!(shared.add_glyph(((2, 0), 1), bitmap(600)));
+    }
+
+    #[test]
+    fn remaining_glyphs_are_repacked_and_uploaded_again() {
+        let mut shared = SharedSdfText::new();
+        shared.add_glyph(((1, 0), 1), bitmap(600));
+        shared.add_glyph(((2, 0), 1), bitmap(300));
+        shared.add_glyph(((2, 0), 2), bitmap(20));
+        shared.pending_uploads.clear();
+
+        shared.remove_font(1);
+
+        let first = shared.glyphs[&((2, 0), 1)].unwrap();
+        assert_eq!(na::Vector2::new(0.0, 0.0), first.uv_min);
+        assert_eq!(2, shared.pending_uploads.len());
+        assert_eq!((0, 0, 300), (shared.pending_uploads[0].x, shared.pending_uploads[0].y, shared.pending_uploads[0].width));
+        assert_eq!(20, shared.pending_uploads[1].width);
+        assert!(shared.add_glyph(((3, 0), 1), bitmap(600)));
+    }
+
+    #[test]
+    fn glyph_without_outline_is_not_placed() {
+        let mut shared = SharedSdfText::new();
+
+        assert!(shared.add_glyph(((1, 0), 1), None));
+        assert!(shared.glyphs[&((1, 0), 1)].is_none());
+        assert!(shared.pending_uploads.is_empty());
+    }
+}
@@ -0,0 +1,224 @@
+use lyon_path::default::Path;
+use lyon_path::iterator::PathIterator;
+use lyon_path::math::Point;
+use lyon_path::FlattenedEvent;
+
+/// Pixels per em of glyphs rendered into the atlas.
+pub const SDF_EM_SIZE: f32 = 64.0;
+
+/// Distance in atlas pixels that maps to the full range of a distance field texel.
+///
+/// Effects like outline, glow and shadow can not reach further than this from the glyph edge.
+pub const SDF_SPREAD: f32 = 8.0;
+
+/// Single channel signed distance field of a glyph.
+///
+/// Texel values above 127 are inside the outline. Texel `(i, j)` is centered at
+/// `origin + (i + 0.5, j + 0.5) * units_per_pixel` in font units.
+pub struct SdfBitmap {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    pub origin_x: f32,
+    pub origin_y: f32,
+    pub units_per_pixel: f32,
+}
+
+struct Segment {
+    from: Point,
+    to: Point,
+}
+
+/// Generates a distance field from glyph outline in font units. Returns `None` for empty glyphs.
+pub fn generate(path: &Path, units_per_em: u32) -> Option<SdfBitmap> {
+    let pixels_per_unit = SDF_EM_SIZE / units_per_em as f32;
+    let units_per_pixel = 1.0 / pixels_per_unit;
+
+    // flatten with a quarter of atlas pixel precision, and convert to pixels
+    let mut segments = Vec::new();
+    let mut start = Point::new(0.0, 0.0);
+    let mut current = start;
+
+    for event in path.path_iter().flattened(units_per_pixel * 0.25) {
+        match event {
+            FlattenedEvent::MoveTo(p) => {
+                start = p;
+                current = p;
+            }
+            FlattenedEvent::LineTo(p) => {
+                segments.push(Segment { from: current, to: p });
+                current = p;
+            }
+            FlattenedEvent::Close => {
+                if current != start {
+                    segments.push(Segment { from: current, to: start });
+                }
+                current = start;
+            }
+        }
+    }
+
+    if segments.is_empty() {
+        return None;
+    }
+
+    let (mut min_x, mut min_y) = (::std::f32::MAX, ::std::f32::MAX);
+    let (mut max_x, mut max_y) = (::std::f32::MIN, ::std::f32::MIN);
+    for segment in segments.iter_mut() {
+        segment.from = Point::new(segment.from.x * pixels_per_unit, segment.from.y * pixels_per_unit);
+        segment.to = Point::new(segment.to.x * pixels_per_unit, segment.to.y * pixels_per_unit);
+
+        min_x = min_x.min(segment.from.x).min(segment.to.x);
+        min_y = min_y.min(segment.from.y).min(segment.to.y);
+        max_x = max_x.max(segment.from.x).max(segment.to.x);
+        max_y = max_y.max(segment.from.y).max(segment.to.y);
+    }
+
+    let origin_x = (min_x - SDF_SPREAD).floor();
+    let origin_y = (min_y - SDF_SPREAD).floor();
+    let width = (max_x + SDF_SPREAD - origin_x).ceil() as u32;
+    let height = (max_y + SDF_SPREAD - origin_y).ceil() as u32;
+
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+
+    for j in 0..height {
+        for i in 0..width {
+            let p = Point::new(origin_x + i as f32 + 0.5, origin_y + j as f32 + 0.5);
+
+            let mut min_distance_sq = ::std::f32::MAX;
+            let mut winding = 0;
+
+            for segment in &segments {
+                min_distance_sq = min_distance_sq.min(distance_sq_to_segment(p, segment));
+                winding += winding_contribution(p, segment);
+            }
+
+            let distance = min_distance_sq.sqrt();
+            let signed_distance = if winding != 0 { distance } else { -distance };
+            let value = 0.5 + signed_distance / (2.0 * SDF_SPREAD);
+
+            pixels.push((value.max(0.0).min(1.0) * 255.0).round() as u8);
+        }
+    }
+
+    Some(SdfBitmap {
+        width,
+        height,
+        pixels,
+        origin_x: origin_x * units_per_pixel,
+        origin_y: origin_y * units_per_pixel,
+        units_per_pixel,
+    })
+}
+
+fn distance_sq_to_segment(p: Point, segment: &Segment) -> f32 {
+    let d = segment.to - segment.from;
+    let length_sq = d.square_length();
+
+    let t = if length_sq > 0.0 {
+        ((p - segment.from).dot(d) / length_sq).max(0.0).min(1.0)
+    } else {
+        0.0
+    };
+
+    let closest = segment.from + d * t;
+    (p - closest).square_length()
+}
+
+/// Non-zero winding rule: +1 for upward crossings of a ray to the right, -1 for downward.
+fn winding_contribution(p: Point, segment: &Segment) -> i32 {
+    let (a, b) = (segment.from, segment.to);
+    let is_left = (b.x - a.x) * (p.y - a.y) - (p.x - a.x) * (b.y - a.y);
+
+    if a.y <= p.y {
+        if b.y > p.y && is_left > 0.0 {
+            return 1;
+        }
+    } else if b.y <= p.y && is_left < 0.0 {
+        return -1;
+    }
+
+    0
+}
+
+#[cfg(test)]
+mod sdf_tests {
+    use super::*;
+    use lyon_path::builder::FlatPathBuilder;
+    use lyon_path::math::point;
+
+    const UNITS_PER_EM: u32 = 1000;
+
+    /// Square contours in font units, `(x, y, size, clockwise)`.
+    fn squares(squares: &[(f32, f32, f32, bool)]) -> Path {
+        let mut builder = Path::builder();
+        for &(x, y, size, clockwise) in squares {
+            let mut corners = vec![point(x, y), point(x + size, y), point(x + size, y + size), point(x, y + size)];
+            if clockwise {
+                corners.reverse();
+            }
+
+            builder.move_to(corners[0]);
+            for &corner in corners[1..].iter() {
+                builder.line_to(corner);
+            }
+            builder.close();
+        }
+        builder.build()
+    }
+
+    fn texel(bitmap: &SdfBitmap, i: u32, j: u32) -> u8 {
+        bitmap.pixels[(j * bitmap.width + i) as usize]
+    }
+
+    #[test]
+    fn empty_path_has_no_bitmap() {
+        assert!(generate(&Path::builder().build(), UNITS_PER_EM).is_none());
+    }
+
+    #[test]
+    fn bitmap_covers_glyph_and_spread() {
+        // one em square is SDF_EM_SIZE pixels
+        let bitmap = generate(&squares(&[(0.0, 0.0, 1000.0, false)]), UNITS_PER_EM).unwrap();
+
+        let size = (SDF_EM_SIZE + 2.0 * SDF_SPREAD) as u32;
+        assert_eq!(size, bitmap.width);
+        assert_eq!(size, bitmap.height);
+        assert_eq!((size * size) as usize, bitmap.pixels.len());
+        assert!((bitmap.units_per_pixel - 1000.0 / SDF_EM_SIZE).abs() < 1e-4);
+        assert!((bitmap.origin_x + SDF_SPREAD * bitmap.units_per_pixel).abs() < 1e-3);
+        assert!((bitmap.origin_y + SDF_SPREAD * bitmap.units_per_pixel).abs() < 1e-3);
+    }
+
+    #[test]
+    fn distance_is_positive_inside_and_saturates_past_spread() {
+        let bitmap = generate(&squares(&[(0.0, 0.0, 1000.0, false)]), UNITS_PER_EM).unwrap();
+        let spread = SDF_SPREAD as u32;
+        let middle = bitmap.height / 2;
+
+        assert_eq!(255, texel(&bitmap, bitmap.width / 2, middle));
+        assert_eq!(0, texel(&bitmap, 0, 0));
+        // texel centers half a pixel inside and outside of the left edge
+        assert_eq!(135, texel(&bitmap, spread, middle));
+        assert_eq!(120, texel(&bitmap, spread - 1, middle));
+        assert!(texel(&bitmap, 1, middle) < texel(&bitmap, spread / 2, middle));
+    }
+
+    #[test]
+    fn contour_direction_does_not_matter() {
+        let counter_clockwise = generate(&squares(&[(0.0, 0.0, 500.0, false)]), UNITS_PER_EM).unwrap();
+        let clockwise = generate(&squares(&[(0.0, 0.0, 500.0, true)]), UNITS_PER_EM).unwrap();
+
+        assert_eq!(counter_clockwise.pixels, clockwise.pixels);
+    }
+
+    #[test]
+    fn counter_wound_contour_is_a_hole() {
+        let bitmap = generate(&squares(&[(0.0, 0.0, 1000.0, false), (250.0, 250.0, 500.0, true)]), UNITS_PER_EM).unwrap();
+        let center = bitmap.width / 2;
+        let ring = SDF_SPREAD as u32 + 8;
+
+        assert_eq!(0, texel(&bitmap, center, center));
+        assert!(texel(&bitmap, ring, center) > 128);
+    }
+}
@@ -134,6 +134,18 @@ impl Program {
         }
     }
 
+    pub fn set_uniform_1f(&self, location: i32, value: f32) {
+        unsafe {
+            self.gl.Uniform1f(location, value);
+        }
+    }
+
+    pub fn set_uniform_2f(&self, location: i32, value: &na::Vector2<f32>) {
+        unsafe {
+            self.gl.Uniform2f(location, value.x, value.y);
+        }
+    }
+
     pub fn set_uniform_3f(&self, location: i32, value: &na::Vector3<f32>) {
         unsafe {
             self.gl.Uniform3f(location, value.x, value.y, value.z);
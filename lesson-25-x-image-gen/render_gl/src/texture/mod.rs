@@ -0,0 +1,806 @@
+use gl;
+use image::{self, GenericImageView};
+use resources::{self, Resource, Resources};
+use std::os::raw;
+
+mod sampler;
+
+pub use self::sampler::{Filter, Sampler, SamplerOptions, Wrap};
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load resource {}", name)]
+    ResourceLoad {
+        name: String,
+        #[cause]
+        inner: resources::Error,
+    },
+    #[fail(display = "Failed to decode image {}", name)]
+    Decode {
+        name: String,
+        #[cause]
+        inner: image::ImageError,
+    },
+    #[fail(
+        display = "Image {} is {}x{}, expected {}x{}",
+        name, width, height, expected_width, expected_height
+    )]
+    SizeMismatch {
+        name: String,
+        width: u32,
+        height: u32,
+        expected_width: u32,
+        expected_height: u32,
+    },
+    #[fail(display = "Texture array needs at least one layer")]
+    NoLayers,
+}
+
+/// Pixel format of texture data.
+///
+/// sRGB formats are decoded to linear values when sampled, use them for color images.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextureFormat {
+    R8,
+    Rgb8,
+    Rgba8,
+    Srgb8,
+    Srgb8Alpha8,
+}
+
+impl TextureFormat {
+    pub fn internal_format(self) -> gl::types::GLenum {
+        match self {
+            TextureFormat::R8 => gl::R8,
+            TextureFormat::Rgb8 => gl::RGB8,
+            TextureFormat::Rgba8 => gl::RGBA8,
+            TextureFormat::Srgb8 => gl::SRGB8,
+            TextureFormat::Srgb8Alpha8 => gl::SRGB8_ALPHA8,
+        }
+    }
+
+    pub fn pixel_format(self) -> gl::types::GLenum {
+        match self {
+            TextureFormat::R8 => gl::RED,
+            TextureFormat::Rgb8 | TextureFormat::Srgb8 => gl::RGB,
+            TextureFormat::Rgba8 | TextureFormat::Srgb8Alpha8 => gl::RGBA,
+        }
+    }
+
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            TextureFormat::R8 => 1,
+            TextureFormat::Rgb8 | TextureFormat::Srgb8 => 3,
+            TextureFormat::Rgba8 | TextureFormat::Srgb8Alpha8 => 4,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct TextureLoadOptions {
+    pub format: TextureFormat,
+    pub gen_mipmaps: bool,
+}
+
+impl TextureLoadOptions {
+    pub fn rgba() -> TextureLoadOptions {
+        TextureLoadOptions {
+            format: TextureFormat::Rgba8,
+            gen_mipmaps: false,
+        }
+    }
+
+    pub fn srgba() -> TextureLoadOptions {
+        TextureLoadOptions {
+            format: TextureFormat::Srgb8Alpha8,
+            gen_mipmaps: false,
+        }
+    }
+
+    pub fn with_format(mut self, format: TextureFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_gen_mipmaps(mut self) -> Self {
+        self.gen_mipmaps = true;
+        self
+    }
+}
+
+/// Decoded image data, tightly packed in the requested format.
+struct ImageData {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+fn load_image(resource: &Resource, format: TextureFormat) -> Result<ImageData, Error> {
+    let bytes = resource.get().map_err(|e| Error::ResourceLoad {
+        name: resource.name(),
+        inner: e,
+    })?;
+
+    let img = image::load_from_memory(&bytes).map_err(|e| Error::Decode {
+        name: resource.name(),
+        inner: e,
+    })?;
+
+    let (width, height) = img.dimensions();
+    let pixels = match format {
+        TextureFormat::R8 => img.to_luma().into_raw(),
+        TextureFormat::Rgb8 | TextureFormat::Srgb8 => img.to_rgb().into_raw(),
+        TextureFormat::Rgba8 | TextureFormat::Srgb8Alpha8 => img.to_rgba().into_raw(),
+    };
+
+    Ok(ImageData {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn check_size(resource: &Resource, data: &ImageData, width: u32, height: u32) -> Result<(), Error> {
+    if data.width != width || data.height != height {
+        return Err(Error::SizeMismatch {
+            name: resource.name(),
+            width: data.width,
+            height: data.height,
+            expected_width: width,
+            expected_height: height,
+        });
+    }
+    Ok(())
+}
+
+fn check_region(format: TextureFormat, width: u32, height: u32, pixels: &[u8]) {
+    assert_eq!(
+        pixels.len(),
+        width as usize * height as usize * format.bytes_per_pixel(),
+        "pixel data does not match {}x{} region of {:?}",
+        width,
+        height,
+        format
+    );
+}
+
+/// Number of levels in a full mipmap chain, down to 1x1.
+fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+unsafe fn level_width(gl: &gl::Gl, target: gl::types::GLenum, level: u32) -> i32 {
+    // levels of a cube map are queried per face
+    let target = if target == gl::TEXTURE_CUBE_MAP {
+        gl::TEXTURE_CUBE_MAP_POSITIVE_X
+    } else {
+        target
+    };
+
+    let mut width = 0;
+    gl.GetTexLevelParameteriv(target, level as i32, gl::TEXTURE_WIDTH, &mut width);
+    width
+}
+
+unsafe fn generate_mipmaps(gl: &gl::Gl, target: gl::types::GLenum) {
+    // empty textures are created limited to the base level, mipmaps are only generated up to the max level
+    gl.TexParameteri(target, gl::TEXTURE_BASE_LEVEL, 0);
+    gl.TexParameteri(target, gl::TEXTURE_MAX_LEVEL, 1000);
+    gl.GenerateMipmap(target);
+}
+
+unsafe fn set_mip_levels(gl: &gl::Gl, target: gl::types::GLenum, gen_mipmaps: bool, width: u32, height: u32) {
+    if gen_mipmaps {
+        generate_mipmaps(gl, target);
+
+        debug_assert!(
+            {
+                let last = mip_level_count(width, height) - 1;
+                level_width(gl, target, last) > 0 && level_width(gl, target, last + 1) == 0
+            },
+            "expected {} mip levels for {}x{} texture",
+            mip_level_count(width, height),
+            width,
+            height
+        );
+    } else {
+        // without mipmaps texture is only complete if sampling is limited to the base level
+        gl.TexParameteri(target, gl::TEXTURE_BASE_LEVEL, 0);
+        gl.TexParameteri(target, gl::TEXTURE_MAX_LEVEL, 0);
+    }
+}
+
+pub struct Texture2D {
+    gl: gl::Gl,
+    obj: gl::types::GLuint,
+    format: TextureFormat,
+    gen_mipmaps: bool,
+    width: u32,
+    height: u32,
+    resource: Option<Resource>,
+}
+
+impl Texture2D {
+    pub fn from_res(gl: &gl::Gl, res: &Resources, name: &str, options: TextureLoadOptions) -> Result<Texture2D, Error> {
+        Texture2D::from_resource(gl, res.resource(name), options)
+    }
+
+    pub fn from_resource(gl: &gl::Gl, resource: Resource, options: TextureLoadOptions) -> Result<Texture2D, Error> {
+        let data = load_image(&resource, options.format)?;
+
+        let mut texture = Texture2D::create(gl, options.format, options.gen_mipmaps);
+        texture.upload(&data);
+        texture.resource = Some(resource);
+
+        Ok(texture)
+    }
+
+    /// Texture with undefined contents, to be filled with `sub_image`, for example a glyph atlas.
+    pub fn new_empty(gl: &gl::Gl, format: TextureFormat, width: u32, height: u32) -> Texture2D {
+        let mut texture = Texture2D::create(gl, format, false);
+
+        texture.upload(&ImageData {
+            width,
+            height,
+            pixels: Vec::new(),
+        });
+
+        texture
+    }
+
+    /// Texture object without storage, which is allocated by the first `upload`.
+    fn create(gl: &gl::Gl, format: TextureFormat, gen_mipmaps: bool) -> Texture2D {
+        let mut obj: gl::types::GLuint = 0;
+        unsafe {
+            gl.GenTextures(1, &mut obj);
+        }
+
+        Texture2D {
+            gl: gl.clone(),
+            obj,
+            format,
+            gen_mipmaps,
+            width: 0,
+            height: 0,
+            resource: None,
+        }
+    }
+
+    pub fn id(&self) -> gl::types::GLuint {
+        self.obj
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            self.gl.ActiveTexture(gl::TEXTURE0 + unit);
+            self.gl.BindTexture(gl::TEXTURE_2D, self.obj);
+        }
+    }
+
+    pub fn unbind(&self, unit: u32) {
+        unsafe {
+            self.gl.ActiveTexture(gl::TEXTURE0 + unit);
+            self.gl.BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    /// Replaces a rectangular region of the texture, `pixels` must be tightly packed.
+    ///
+    /// Only the base level is changed, call `generate_mipmaps` after the last region.
+    pub fn sub_image(&self, x: u32, y: u32, width: u32, height: u32, pixels: &[u8]) {
+        assert!(x + width <= self.width && y + height <= self.height, "sub image is out of texture bounds");
+        check_region(self.format, width, height, pixels);
+
+        let gl = &self.gl;
+        unsafe {
+            gl.BindTexture(gl::TEXTURE_2D, self.obj);
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl.TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                x as i32,
+                y as i32,
+                width as i32,
+                height as i32,
+                self.format.pixel_format(),
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const raw::c_void,
+            );
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            gl.BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    /// Generates mipmaps from the base level, for textures loaded with `gen_mipmaps`.
+    pub fn generate_mipmaps(&self) {
+        if !self.gen_mipmaps {
+            return;
+        }
+
+        unsafe {
+            self.gl.BindTexture(gl::TEXTURE_2D, self.obj);
+            generate_mipmaps(&self.gl, gl::TEXTURE_2D);
+            self.gl.BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    /// Reloads image data if the source resource has changed. Returns `true` if it was reloaded.
+    pub fn reload_if_modified(&mut self) -> Result<bool, Error> {
+        let data = match self.resource {
+            Some(ref resource) if resource.is_modified() => load_image(resource, self.format)?,
+            _ => return Ok(false),
+        };
+
+        self.upload(&data);
+        Ok(true)
+    }
+
+    fn upload(&mut self, data: &ImageData) {
+        let gl = &self.gl;
+        let pixels = if data.pixels.is_empty() {
+            ::std::ptr::null()
+        } else {
+            data.pixels.as_ptr() as *const raw::c_void
+        };
+
+        unsafe {
+            gl.BindTexture(gl::TEXTURE_2D, self.obj);
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl.TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                self.format.internal_format() as gl::types::GLint,
+                data.width as i32,
+                data.height as i32,
+                0,
+                self.format.pixel_format(),
+                gl::UNSIGNED_BYTE,
+                pixels,
+            );
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            set_mip_levels(gl, gl::TEXTURE_2D, self.gen_mipmaps, data.width, data.height);
+            gl.BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        self.width = data.width;
+        self.height = data.height;
+    }
+}
+
+impl Drop for Texture2D {
+    fn drop(&mut self) {
+        unsafe { self.gl.DeleteTextures(1, &mut self.obj) };
+    }
+}
+
+/// 2D texture array, all layers have the same size and format.
+pub struct TextureArray {
+    gl: gl::Gl,
+    obj: gl::types::GLuint,
+    format: TextureFormat,
+    gen_mipmaps: bool,
+    width: u32,
+    height: u32,
+    layers: u32,
+    resources: Vec<Resource>,
+}
+
+impl TextureArray {
+    /// Loads one layer per resource name. Every image must have the size of the first one.
+    pub fn from_res(gl: &gl::Gl, res: &Resources, names: &[&str], options: TextureLoadOptions) -> Result<TextureArray, Error> {
+        let resources = names.iter().map(|name| res.resource(*name)).collect::<Vec<_>>();
+        let layers = load_layers(&resources, options.format, None)?;
+
+        let (width, height) = match layers.first() {
+            Some(layer) => (layer.width, layer.height),
+            None => return Err(Error::NoLayers),
+        };
+
+        let mut texture = TextureArray::new_empty(gl, options.format, width, height, layers.len() as u32);
+        texture.gen_mipmaps = options.gen_mipmaps;
+        texture.upload_layers(&layers);
+        texture.resources = resources;
+
+        Ok(texture)
+    }
+
+    pub fn new_empty(gl: &gl::Gl, format: TextureFormat, width: u32, height: u32, layers: u32) -> TextureArray {
+        let mut obj: gl::types::GLuint = 0;
+        unsafe {
+            gl.GenTextures(1, &mut obj);
+            gl.BindTexture(gl::TEXTURE_2D_ARRAY, obj);
+            gl.TexImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                format.internal_format() as gl::types::GLint,
+                width as i32,
+                height as i32,
+                layers as i32,
+                0,
+                format.pixel_format(),
+                gl::UNSIGNED_BYTE,
+                ::std::ptr::null(),
+            );
+            set_mip_levels(gl, gl::TEXTURE_2D_ARRAY, false, width, height);
+            gl.BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+
+        TextureArray {
+            gl: gl.clone(),
+            obj,
+            format,
+            gen_mipmaps: false,
+            width,
+            height,
+            layers,
+            resources: Vec::new(),
+        }
+    }
+
+    pub fn id(&self) -> gl::types::GLuint {
+        self.obj
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            self.gl.ActiveTexture(gl::TEXTURE0 + unit);
+            self.gl.BindTexture(gl::TEXTURE_2D_ARRAY, self.obj);
+        }
+    }
+
+    pub fn unbind(&self, unit: u32) {
+        unsafe {
+            self.gl.ActiveTexture(gl::TEXTURE0 + unit);
+            self.gl.BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+    }
+
+    /// Replaces a rectangular region of a single layer, `pixels` must be tightly packed.
+    ///
+    /// Only the base level is changed, call `generate_mipmaps` after the last region.
+    pub fn sub_image(&self, layer: u32, x: u32, y: u32, width: u32, height: u32, pixels: &[u8]) {
+        assert!(layer < self.layers, "layer {} is out of {} texture array layers", layer, self.layers);
+        assert!(x + width <= self.width && y + height <= self.height, "sub image is out of texture bounds");
+        check_region(self.format, width, height, pixels);
+
+        unsafe {
+            self.gl.BindTexture(gl::TEXTURE_2D_ARRAY, self.obj);
+            self.sub_image_unchecked(layer, x, y, width, height, pixels);
+            self.gl.BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+    }
+
+    /// Generates mipmaps of all layers from the base level, for arrays loaded with `gen_mipmaps`.
+    pub fn generate_mipmaps(&self) {
+        if !self.gen_mipmaps {
+            return;
+        }
+
+        unsafe {
+            self.gl.BindTexture(gl::TEXTURE_2D_ARRAY, self.obj);
+            generate_mipmaps(&self.gl, gl::TEXTURE_2D_ARRAY);
+            self.gl.BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+    }
+
+    /// Reloads all layers if any of the source resources has changed.
+    pub fn reload_if_modified(&mut self) -> Result<bool, Error> {
+        if !self.resources.iter().any(|r| r.is_modified()) {
+            return Ok(false);
+        }
+
+        let layers = load_layers(&self.resources, self.format, Some((self.width, self.height)))?;
+        self.upload_layers(&layers);
+        Ok(true)
+    }
+
+    fn upload_layers(&self, layers: &[ImageData]) {
+        unsafe {
+            self.gl.BindTexture(gl::TEXTURE_2D_ARRAY, self.obj);
+            for (index, layer) in layers.iter().enumerate() {
+                self.sub_image_unchecked(index as u32, 0, 0, layer.width, layer.height, &layer.pixels);
+            }
+            set_mip_levels(&self.gl, gl::TEXTURE_2D_ARRAY, self.gen_mipmaps, self.width, self.height);
+            self.gl.BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+    }
+
+    unsafe fn sub_image_unchecked(&self, layer: u32, x: u32, y: u32, width: u32, height: u32, pixels: &[u8]) {
+        self.gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        self.gl.TexSubImage3D(
+            gl::TEXTURE_2D_ARRAY,
+            0,
+            x as i32,
+            y as i32,
+            layer as i32,
+            width as i32,
+            height as i32,
+            1,
+            self.format.pixel_format(),
+            gl::UNSIGNED_BYTE,
+            pixels.as_ptr() as *const raw::c_void,
+        );
+        self.gl.PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+    }
+}
+
+impl Drop for TextureArray {
+    fn drop(&mut self) {
+        unsafe { self.gl.DeleteTextures(1, &mut self.obj) };
+    }
+}
+
+/// Loads images that must all have the same size, the size of the first one if `size` is `None`.
+fn load_layers(resources: &[Resource], format: TextureFormat, size: Option<(u32, u32)>) -> Result<Vec<ImageData>, Error> {
+    let mut size = size;
+    let mut layers = Vec::with_capacity(resources.len());
+
+    for resource in resources {
+        let data = load_image(resource, format)?;
+        match size {
+            Some((width, height)) => check_size(resource, &data, width, height)?,
+            None => size = Some((data.width, data.height)),
+        }
+        layers.push(data);
+    }
+
+    Ok(layers)
+}
+
+/// Cube map with faces in `+X, -X, +Y, -Y, +Z, -Z` order.
+pub struct Cubemap {
+    gl: gl::Gl,
+    obj: gl::types::GLuint,
+    format: TextureFormat,
+    gen_mipmaps: bool,
+    size: u32,
+    resources: Vec<Resource>,
+}
+
+impl Cubemap {
+    pub fn from_res(gl: &gl::Gl, res: &Resources, faces: [&str; 6], options: TextureLoadOptions) -> Result<Cubemap, Error> {
+        let resources = faces.iter().map(|name| res.resource(*name)).collect::<Vec<_>>();
+        let faces = load_layers(&resources, options.format, None)?;
+
+        let first = &faces[0];
+        check_size(&resources[0], first, first.width, first.width)?;
+
+        let mut obj: gl::types::GLuint = 0;
+        unsafe {
+            gl.GenTextures(1, &mut obj);
+        }
+
+        let cubemap = Cubemap {
+            gl: gl.clone(),
+            obj,
+            format: options.format,
+            gen_mipmaps: options.gen_mipmaps,
+            size: first.width,
+            resources,
+        };
+
+        cubemap.upload_faces(&faces);
+        Ok(cubemap)
+    }
+
+    pub fn id(&self) -> gl::types::GLuint {
+        self.obj
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            self.gl.ActiveTexture(gl::TEXTURE0 + unit);
+            self.gl.BindTexture(gl::TEXTURE_CUBE_MAP, self.obj);
+        }
+    }
+
+    pub fn unbind(&self, unit: u32) {
+        unsafe {
+            self.gl.ActiveTexture(gl::TEXTURE0 + unit);
+            self.gl.BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+        }
+    }
+
+    /// Reloads all faces if any of the source resources has changed.
+    pub fn reload_if_modified(&mut self) -> Result<bool, Error> {
+        if !self.resources.iter().any(|r| r.is_modified()) {
+            return Ok(false);
+        }
+
+        let faces = load_layers(&self.resources, self.format, Some((self.size, self.size)))?;
+        self.upload_faces(&faces);
+        Ok(true)
+    }
+
+    fn upload_faces(&self, faces: &[ImageData]) {
+        let gl = &self.gl;
+
+        unsafe {
+            gl.BindTexture(gl::TEXTURE_CUBE_MAP, self.obj);
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            for (index, face) in faces.iter().enumerate() {
+                gl.TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + index as u32,
+                    0,
+                    self.format.internal_format() as gl::types::GLint,
+                    face.width as i32,
+                    face.height as i32,
+                    0,
+                    self.format.pixel_format(),
+                    gl::UNSIGNED_BYTE,
+                    face.pixels.as_ptr() as *const raw::c_void,
+                );
+            }
+            gl.PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            set_mip_levels(gl, gl::TEXTURE_CUBE_MAP, self.gen_mipmaps, self.size, self.size);
+            gl.BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+        }
+    }
+}
+
+impl Drop for Cubemap {
+    fn drop(&mut self) {
+        unsafe { self.gl.DeleteTextures(1, &mut self.obj) };
+    }
+}
+
+#[cfg(test)]
+mod texture_tests {
+    use super::*;
+
+    #[test]
+    fn mip_chain_goes_down_to_one_pixel() {
+        assert_eq!(1, mip_level_count(1, 1));
+        assert_eq!(2, mip_level_count(2, 1));
+        assert_eq!(9, mip_level_count(256, 256));
+        assert_eq!(10, mip_level_count(300, 512));
+        assert_eq!(11, mip_level_count(1024, 3));
+        assert_eq!(1, mip_level_count(0, 0));
+    }
+
+    fn png(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::png::PNGEncoder::new(&mut bytes)
+            .encode(rgba, width, height, image::ColorType::RGBA(8))
+            .unwrap();
+        bytes
+    }
+
+    fn resources_with(files: &[(&str, &[u8])]) -> Resources {
+        let backend = files.iter().fold(resources::backend::InMemory::new(), |backend, &(name, bytes)| {
+            backend.with(name, bytes)
+        });
+        Resources::new().loaded_from("test", 0, backend)
+    }
+
+    #[test]
+    fn bytes_per_pixel_match_pixel_format() {
+        let formats = [
+            TextureFormat::R8,
+            TextureFormat::Rgb8,
+            TextureFormat::Rgba8,
+            TextureFormat::Srgb8,
+            TextureFormat::Srgb8Alpha8,
+        ];
+
+        for &format in formats.iter() {
+            let channels = match format.pixel_format() {
+                gl::RED => 1,
+                gl::RGB => 3,
+                gl::RGBA => 4,
+                other => panic!("unexpected pixel format {}", other),
+            };
+            assert_eq!(channels, format.bytes_per_pixel(), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn srgb_formats_have_srgb_internal_format() {
+        assert_eq!(gl::SRGB8, TextureFormat::Srgb8.internal_format());
+        assert_eq!(gl::SRGB8_ALPHA8, TextureFormat::Srgb8Alpha8.internal_format());
+        assert_eq!(gl::RGBA8, TextureFormat::Rgba8.internal_format());
+    }
+
+    #[test]
+    fn image_is_converted_to_requested_format() {
+        let res = resources_with(&[("a.png", &png(2, 1, &[255, 0, 0, 255, 0, 0, 255, 128]))]);
+        let resource = res.resource("a.png");
+
+        let rgba = load_image(&resource, TextureFormat::Rgba8).unwrap();
+        assert_eq!((2, 1), (rgba.width, rgba.height));
+        assert_eq!(vec![255, 0, 0, 255, 0, 0, 255, 128], rgba.pixels);
+
+        let rgb = load_image(&resource, TextureFormat::Srgb8).unwrap();
+        assert_eq!(vec![255, 0, 0, 0, 0, 255], rgb.pixels);
+
+        let luma = load_image(&resource, TextureFormat::R8).unwrap();
+        assert_eq!(2, luma.pixels.len());
+    }
+
+    #[test]
+    fn missing_image_fails_to_load() {
+        let res = resources_with(&[]);
+
+        match load_image(&res.resource("missing.png"), TextureFormat::Rgba8) {
+            Err(Error::ResourceLoad { ref name, .. }) => assert_eq!("missing.png", name),
+            _ => panic!("expected resource load error"),
+        }
+    }
+
+    #[test]
+    fn invalid_image_fails_to_decode() {
+        let res = resources_with(&[("a.png", b"not an image")]);
+
+        match load_image(&res.resource("a.png"), TextureFormat::Rgba8) {
+            Err(Error::Decode { ref name, .. }) => assert_eq!("a.png", name),
+            _ => panic!("expected decode error"),
+        }
+    }
+
+    #[test]
+    fn layers_must_have_size_of_first_layer() {
+        let res = resources_with(&[
+            ("a.png", &png(2, 1, &[0; 8])),
+            ("b.png", &png(2, 1, &[0; 8])),
+            ("c.png", &png(1, 1, &[0; 4])),
+        ]);
+
+        let same = [res.resource("a.png"), res.resource("b.png")];
+        assert_eq!(2, load_layers(&same, TextureFormat::Rgba8, None).unwrap().len());
+
+        let different = [res.resource("a.png"), res.resource("c.png")];
+        match load_layers(&different, TextureFormat::Rgba8, None) {
+            Err(Error::SizeMismatch { width: 1, height: 1, expected_width: 2, expected_height: 1, .. }) => (),
+            _ => panic!("expected size mismatch"),
+        }
+    }
+
+    #[test]
+    fn layers_must_have_expected_size() {
+        let res = resources_with(&[("a.png", &png(2, 1, &[0; 8]))]);
+
+        match load_layers(&[res.resource("a.png")], TextureFormat::Rgba8, Some((4, 4))) {
+            Err(Error::SizeMismatch { width: 2, height: 1, expected_width: 4, expected_height: 4, .. }) => (),
+            _ => panic!("expected size mismatch"),
+        }
+    }
+
+    #[test]
+    fn region_matching_pixel_data_is_accepted() {
+        check_region(TextureFormat::Rgb8, 2, 3, &[0; 18]);
+    }
+
+    #[test]
+    #[should_panic(expected = "pixel data does not match 2x3 region")]
+    fn region_not_matching_pixel_data_is_rejected() {
+        check_region(TextureFormat::Rgba8, 2, 3, &[0; 18]);
+    }
+}
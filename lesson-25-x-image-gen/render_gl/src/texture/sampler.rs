@@ -0,0 +1,172 @@
+use gl;
+
+/// From `GL_EXT_texture_filter_anisotropic`, core only since OpenGL 4.6.
+const TEXTURE_MAX_ANISOTROPY: gl::types::GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: gl::types::GLenum = 0x84FF;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    ClampToBorder,
+}
+
+impl Wrap {
+    fn gl_value(self) -> gl::types::GLenum {
+        match self {
+            Wrap::Repeat => gl::REPEAT,
+            Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Wrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SamplerOptions {
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    /// Filter between mipmap levels, `None` to sample only the base level.
+    pub mipmap_filter: Option<Filter>,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub wrap_r: Wrap,
+    /// Requested anisotropy, clamped to the driver maximum. `1.0` disables it.
+    pub max_anisotropy: f32,
+}
+
+impl SamplerOptions {
+    pub fn linear() -> SamplerOptions {
+        SamplerOptions {
+            min_filter: Filter::Linear,
+            mag_filter: Filter::Linear,
+            mipmap_filter: None,
+            wrap_s: Wrap::ClampToEdge,
+            wrap_t: Wrap::ClampToEdge,
+            wrap_r: Wrap::ClampToEdge,
+            max_anisotropy: 1.0,
+        }
+    }
+
+    pub fn nearest() -> SamplerOptions {
+        SamplerOptions {
+            min_filter: Filter::Nearest,
+            mag_filter: Filter::Nearest,
+            ..SamplerOptions::linear()
+        }
+    }
+
+    /// Trilinear filtering, for textures with mipmaps.
+    pub fn trilinear() -> SamplerOptions {
+        SamplerOptions {
+            mipmap_filter: Some(Filter::Linear),
+            ..SamplerOptions::linear()
+        }
+    }
+
+    pub fn with_wrap(mut self, wrap: Wrap) -> Self {
+        self.wrap_s = wrap;
+        self.wrap_t = wrap;
+        self.wrap_r = wrap;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, max_anisotropy: f32) -> Self {
+        self.max_anisotropy = max_anisotropy;
+        self
+    }
+
+    fn min_filter_gl_value(&self) -> gl::types::GLenum {
+        match (self.min_filter, self.mipmap_filter) {
+            (Filter::Nearest, None) => gl::NEAREST,
+            (Filter::Linear, None) => gl::LINEAR,
+            (Filter::Nearest, Some(Filter::Nearest)) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Nearest, Some(Filter::Linear)) => gl::NEAREST_MIPMAP_LINEAR,
+            (Filter::Linear, Some(Filter::Nearest)) => gl::LINEAR_MIPMAP_NEAREST,
+            (Filter::Linear, Some(Filter::Linear)) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+}
+
+/// Sampler object, overrides filtering and wrapping of any texture bound to the same unit.
+pub struct Sampler {
+    gl: gl::Gl,
+    obj: gl::types::GLuint,
+}
+
+impl Sampler {
+    pub fn new(gl: &gl::Gl, options: &SamplerOptions) -> Sampler {
+        let mut obj: gl::types::GLuint = 0;
+        unsafe {
+            gl.GenSamplers(1, &mut obj);
+        }
+
+        let sampler = Sampler {
+            gl: gl.clone(),
+            obj,
+        };
+
+        sampler.set_options(options);
+        sampler
+    }
+
+    pub fn set_options(&self, options: &SamplerOptions) {
+        let gl = &self.gl;
+        let mag_filter = match options.mag_filter {
+            Filter::Nearest => gl::NEAREST,
+            Filter::Linear => gl::LINEAR,
+        };
+
+        unsafe {
+            gl.SamplerParameteri(self.obj, gl::TEXTURE_MIN_FILTER, options.min_filter_gl_value() as i32);
+            gl.SamplerParameteri(self.obj, gl::TEXTURE_MAG_FILTER, mag_filter as i32);
+            gl.SamplerParameteri(self.obj, gl::TEXTURE_WRAP_S, options.wrap_s.gl_value() as i32);
+            gl.SamplerParameteri(self.obj, gl::TEXTURE_WRAP_T, options.wrap_t.gl_value() as i32);
+            gl.SamplerParameteri(self.obj, gl::TEXTURE_WRAP_R, options.wrap_r.gl_value() as i32);
+
+            if options.max_anisotropy > 1.0 {
+                let max_supported = max_anisotropy(gl);
+                if max_supported > 1.0 {
+                    let value = options.max_anisotropy.min(max_supported);
+                    gl.SamplerParameterf(self.obj, TEXTURE_MAX_ANISOTROPY, value);
+                }
+            }
+        }
+    }
+
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            self.gl.BindSampler(unit, self.obj);
+        }
+    }
+
+    pub fn unbind(&self, unit: u32) {
+        unsafe {
+            self.gl.BindSampler(unit, 0);
+        }
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.DeleteSamplers(1, &mut self.obj);
+        }
+    }
+}
+
+/// Returns 1.0 if anisotropic filtering is not supported.
+unsafe fn max_anisotropy(gl: &gl::Gl) -> f32 {
+    let mut value: f32 = 1.0;
+    gl.GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut value);
+    // unsupported enum leaves an error behind, do not let it leak into the next check
+    gl.GetError();
+    value
+}
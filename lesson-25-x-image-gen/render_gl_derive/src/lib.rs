@@ -1,26 +1,40 @@
-#![recursion_limit = "128"]
-
 extern crate proc_macro;
+extern crate proc_macro2;
 extern crate syn;
 #[macro_use]
 extern crate quote;
 
-#[proc_macro_derive(VertexAttribPointers, attributes(location, divisor))]
+use proc_macro2::TokenStream;
+use syn::spanned::Spanned;
+
+#[proc_macro_derive(VertexAttribPointers, attributes(location, divisor, normalized, skip))]
 pub fn vertex_attrib_pointers_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let s = input.to_string();
-    let ast = syn::parse_derive_input(&s).unwrap();
-    let gen = generate_impl(&ast);
-    gen.parse().unwrap()
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
+    match generate_impl(&ast) {
+        Ok(gen) => gen.into(),
+        Err(e) => to_compile_errors(e).into(),
+    }
 }
 
-fn generate_impl(ast: &syn::DeriveInput) -> quote::Tokens {
+/// Emits one spanned `compile_error!` per collected error.
+///
+/// `syn::Error::to_compile_error` refers to `::core`, which does not resolve in 2015 edition crates.
+fn to_compile_errors(error: syn::Error) -> TokenStream {
+    let errors = error.into_iter().map(|e| {
+        let message = e.to_string();
+        quote_spanned! { e.span() => compile_error!(#message); }
+    });
+
+    quote! { #(#errors)* }
+}
+
+fn generate_impl(ast: &syn::DeriveInput) -> syn::Result<TokenStream> {
     let ident = &ast.ident;
-    let generics = &ast.generics;
-    let where_clause = &ast.generics.where_clause;
-    let fields_vertex_attrib_pointer = generate_vertex_attrib_pointer_calls(&ast.body);
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let fields_vertex_attrib_pointer = generate_vertex_attrib_pointer_calls(ast)?;
 
-    quote!{
-        impl #ident #generics #where_clause {
+    Ok(quote! {
+        impl #impl_generics #ident #ty_generics #where_clause {
             #[allow(unused_variables)]
             pub fn vertex_attrib_pointers(gl: &::gl::Gl) {
                 let stride = ::std::mem::size_of::<Self>();
@@ -29,93 +43,175 @@ fn generate_impl(ast: &syn::DeriveInput) -> quote::Tokens {
                 #(#fields_vertex_attrib_pointer)*
             }
         }
+    })
+}
+
+fn generate_vertex_attrib_pointer_calls(ast: &syn::DeriveInput) -> syn::Result<Vec<TokenStream>> {
+    let fields = match ast.data {
+        syn::Data::Enum(ref e) => {
+            return Err(syn::Error::new(
+                e.enum_token.span(),
+                "VertexAttribPointers can not be implemented for enums",
+            ))
+        }
+        syn::Data::Union(ref u) => {
+            return Err(syn::Error::new(
+                u.union_token.span(),
+                "VertexAttribPointers can not be implemented for unions",
+            ))
+        }
+        syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Unit, .. }) => {
+            return Err(syn::Error::new_spanned(
+                &ast.ident,
+                "VertexAttribPointers can not be implemented for unit structs",
+            ))
+        }
+        syn::Data::Struct(syn::DataStruct { ref fields, .. }) => fields,
+    };
+
+    // collect all field errors so that every bad attribute is reported at once
+    let mut calls = Vec::with_capacity(fields.len());
+    let mut error: Option<syn::Error> = None;
+
+    for (index, field) in fields.iter().enumerate() {
+        match generate_field_vertex_attrib_pointer_call(index, field) {
+            Ok(call) => calls.push(call),
+            Err(e) => match error {
+                None => error = Some(e),
+                Some(ref mut error) => error.combine(e),
+            },
+        }
+    }
+
+    match error {
+        None => Ok(calls),
+        Some(e) => Err(e),
     }
 }
 
-fn generate_vertex_attrib_pointer_calls(body: &syn::Body) -> Vec<quote::Tokens> {
-    match body {
-        &syn::Body::Enum(_) => panic!("VertexAttribPointers can not be implemented for enums"),
-        &syn::Body::Struct(syn::VariantData::Unit) => {
-            panic!("VertexAttribPointers can not be implemented for Unit structs")
+/// Attribute values found on a single field.
+struct FieldAttrs {
+    location: Option<u32>,
+    divisor: Option<u32>,
+    normalized: bool,
+    skip: Option<syn::Path>,
+}
+
+fn parse_field_attrs(field: &syn::Field) -> syn::Result<FieldAttrs> {
+    let mut attrs = FieldAttrs {
+        location: None,
+        divisor: None,
+        normalized: false,
+        skip: None,
+    };
+
+    for attr in field.attrs.iter() {
+        if attr.path().is_ident("location") {
+            if attrs.location.is_some() {
+                return Err(syn::Error::new_spanned(attr.path(), "duplicate location attribute"));
+            }
+            attrs.location = Some(parse_int_value(attr, "location")?);
+        } else if attr.path().is_ident("divisor") {
+            if attrs.divisor.is_some() {
+                return Err(syn::Error::new_spanned(attr.path(), "duplicate divisor attribute"));
+            }
+            attrs.divisor = Some(parse_int_value(attr, "divisor")?);
+        } else if attr.path().is_ident("normalized") {
+            attr.meta.require_path_only()?;
+            attrs.normalized = true;
+        } else if attr.path().is_ident("skip") {
+            attr.meta.require_path_only()?;
+            attrs.skip = Some(attr.path().clone());
         }
-        &syn::Body::Struct(syn::VariantData::Tuple(_)) => {
-            panic!("VertexAttribPointers can not be implemented for Tuple structs")
+    }
+
+    Ok(attrs)
+}
+
+fn parse_int_value(attr: &syn::Attribute, name: &str) -> syn::Result<u32> {
+    let name_value = match attr.meta {
+        syn::Meta::NameValue(ref nv) => nv,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &attr.meta,
+                format!("expected {} attribute in the form #[{} = 0]", name, name),
+            ))
         }
-        &syn::Body::Struct(syn::VariantData::Struct(ref s)) => s
-            .iter()
-            .map(generate_struct_field_vertex_attrib_pointer_call)
-            .collect(),
+    };
+
+    match name_value.value {
+        syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(ref i), .. }) => i.base10_parse::<u32>(),
+        ref other => Err(syn::Error::new_spanned(
+            other,
+            format!("{} attribute value must be an integer literal", name),
+        )),
     }
 }
 
-fn generate_struct_field_vertex_attrib_pointer_call(field: &syn::Field) -> quote::Tokens {
+fn generate_field_vertex_attrib_pointer_call(index: usize, field: &syn::Field) -> syn::Result<TokenStream> {
+    let field_ty = &field.ty;
+    let attrs = parse_field_attrs(field)?;
+
     let field_name = match field.ident {
         Some(ref i) => format!("{}", i),
-        None => String::from(""),
+        None => format!("{}", index),
     };
-    let field_ty = &field.ty;
 
-    if let Some(location_attr) = field
-        .attrs
-        .iter()
-        .filter(|a| a.value.name() == "location")
-        .next()
-    {
-        let location_value: usize = match location_attr.value {
-            syn::MetaItem::NameValue(_, syn::Lit::Str(ref s, _)) => {
-                s.parse().unwrap_or_else(|_| {
-                    panic!(
-                        "Field {} location attribute value must contain an integer",
-                        field_name
-                    )
-                })
-            }
-            _ => panic!(
-                "Field {} location attribute value must be a string literal",
-                field_name
-            ),
-        };
-
-        let divisor_call = match field
-            .attrs
-            .iter()
-            .filter(|a| a.value.name() == "divisor")
-            .next()
-        {
-            Some(attr) => {
-                let divisor_value: u32 = match attr.value {
-                    syn::MetaItem::NameValue(_, syn::Lit::Str(ref s, _)) => {
-                        s.parse().unwrap_or_else(|_| {
-                            panic!(
-                                "Field {} divisor attribute value must contain an integer",
-                                field_name
-                            )
-                        })
-                    }
-                    _ => panic!(
-                        "Field {} divisor attribute value must be a string literal",
-                        field_name
-                    ),
-                };
-
-                quote! {
-                    gl.VertexAttribDivisor(#location_value as u32, #divisor_value);
-                }
-            }
-            None => quote!{},
-        };
-
-        quote! {
-            let location = #location_value;
-            unsafe {
-                #field_ty::vertex_attrib_pointer(gl, stride, location, offset);
-                #divisor_call
-            }
-            let offset = offset + ::std::mem::size_of::<#field_ty>();
+    let skip_offset = quote! {
+        let offset = offset + ::std::mem::size_of::<#field_ty>();
+    };
+
+    if let Some(skip) = attrs.skip {
+        if attrs.location.is_some() || attrs.divisor.is_some() || attrs.normalized {
+            return Err(syn::Error::new_spanned(
+                skip,
+                format!("field {} is marked with skip and can not have location, divisor or normalized attributes", field_name),
+            ));
+        }
+
+        return Ok(skip_offset);
+    }
+
+    let location_value = match attrs.location {
+        Some(location) => location as usize,
+        None => {
+            return Err(syn::Error::new(
+                field_span(field),
+                format!("field {} is missing a location attribute, add #[location = N] or #[skip]", field_name),
+            ))
+        }
+    };
+
+    let divisor_call = match attrs.divisor {
+        Some(divisor_value) => quote! {
+            gl.VertexAttribDivisor(#location_value as u32, #divisor_value);
+        },
+        None => quote! {},
+    };
+
+    let pointer_call = if attrs.normalized {
+        quote_spanned! { field_ty.span() =>
+            <#field_ty>::vertex_attrib_pointer_normalized(gl, stride, location, offset);
         }
     } else {
-        quote! {
-            let offset = offset + ::std::mem::size_of::<#field_ty>();
+        quote_spanned! { field_ty.span() =>
+            <#field_ty>::vertex_attrib_pointer(gl, stride, location, offset);
+        }
+    };
+
+    Ok(quote! {
+        let location = #location_value;
+        unsafe {
+            #pointer_call
+            #divisor_call
         }
+        #skip_offset
+    })
+}
+
+fn field_span(field: &syn::Field) -> proc_macro2::Span {
+    match field.ident {
+        Some(ref i) => i.span(),
+        None => field.ty.span(),
     }
 }
@@ -0,0 +1,12 @@
+#[macro_use]
+extern crate lesson_25_x_render_gl_derive;
+extern crate gl;
+
+#[derive(VertexAttribPointers)]
+struct Vertex {
+    #[location = 0]
+    #[skip]
+    pos: f32,
+}
+
+fn main() {}
@@ -0,0 +1,44 @@
+#[macro_use]
+extern crate lesson_25_x_render_gl_derive;
+extern crate gl;
+
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+struct f32_f32 {
+    d0: f32,
+    d1: f32,
+}
+
+impl f32_f32 {
+    unsafe fn vertex_attrib_pointer(_gl: &gl::Gl, _stride: usize, _location: usize, _offset: usize) {}
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+struct u8_u8 {
+    d0: u8,
+    d1: u8,
+}
+
+impl u8_u8 {
+    unsafe fn vertex_attrib_pointer_normalized(_gl: &gl::Gl, _stride: usize, _location: usize, _offset: usize) {}
+}
+
+#[derive(VertexAttribPointers, Copy, Clone)]
+#[repr(C, packed)]
+struct Vertex {
+    #[location = 0]
+    pos: f32_f32,
+    #[location = 1]
+    #[divisor = 1]
+    #[normalized]
+    uv: u8_u8,
+    #[skip]
+    _padding: u8_u8,
+}
+
+fn main() {
+    let _ = Vertex::vertex_attrib_pointers;
+}
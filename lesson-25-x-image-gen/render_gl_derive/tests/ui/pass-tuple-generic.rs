@@ -0,0 +1,35 @@
+#[macro_use]
+extern crate lesson_25_x_render_gl_derive;
+extern crate gl;
+
+use std::marker::PhantomData;
+
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+struct f32_f32 {
+    d0: f32,
+    d1: f32,
+}
+
+impl f32_f32 {
+    unsafe fn vertex_attrib_pointer(_gl: &gl::Gl, _stride: usize, _location: usize, _offset: usize) {}
+}
+
+#[derive(VertexAttribPointers)]
+#[repr(C, packed)]
+struct Pair(#[location = 0] f32_f32, #[location = 1] f32_f32);
+
+#[derive(VertexAttribPointers)]
+#[repr(C)]
+struct Tagged<T> where T: Copy {
+    #[location = 0]
+    pos: f32_f32,
+    #[skip]
+    _tag: PhantomData<T>,
+}
+
+fn main() {
+    let _ = Pair::vertex_attrib_pointers;
+    let _ = Tagged::<u32>::vertex_attrib_pointers;
+}
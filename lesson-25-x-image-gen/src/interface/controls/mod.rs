@@ -1,2 +1,4 @@
 pub mod presentation;
-pub mod rust_fest;
\ No newline at end of file
+pub mod profiler_legend;
+pub mod rust_fest;
+pub mod text_input;
\ No newline at end of file
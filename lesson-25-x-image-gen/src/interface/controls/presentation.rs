@@ -49,12 +49,14 @@ impl Element for CombinedSlide {
 struct InterpolationTarget {
     rotation: na::UnitQuaternion<f32>,
     position: na::Vector3<f32>,
+    opacity: f32,
     t: f32,
 }
 
 struct ElementInfo {
     rotation: na::UnitQuaternion<f32>,
     position: na::Vector3<f32>,
+    opacity: f32,
     target: Option<InterpolationTarget>,
 }
 
@@ -63,6 +65,7 @@ impl ElementInfo {
         ElementInfo {
             rotation: na::UnitQuaternion::<f32>::identity(),
             position: [0.0, 0.0, 0.0].into(),
+            opacity: 1.0,
             target: None,
         }
     }
@@ -81,22 +84,37 @@ impl ElementInfo {
         }
     }
 
+    pub fn opacity(&self) -> f32 {
+        if let Some(ref target) = self.target {
+            let alpha = smootherstep(0.0, 1.0, target.t);
+            self.opacity + (target.opacity - self.opacity) * alpha
+        } else {
+            self.opacity
+        }
+    }
+
+    /// Slides in from the source position while fading in.
     pub fn transition_from(&mut self, source_pos: &na::Vector3<f32>) {
         self.target = Some(InterpolationTarget {
             rotation: na::UnitQuaternion::<f32>::identity(),
             position: [0.0, 0.0, 0.0].into(),
+            opacity: 1.0,
             t: 0.0,
         });
         self.position = *source_pos;
+        self.opacity = 0.0;
     }
 
+    /// Slides out to the target position while fading out.
     pub fn transition_to(&mut self, target_pos: &na::Vector3<f32>) {
         self.target = Some(InterpolationTarget {
             rotation: na::UnitQuaternion::<f32>::identity(),
             position: *target_pos,
+            opacity: 0.0,
             t: 0.0,
         });
         self.position = [0.0, 0.0, 0.0].into();
+        self.opacity = 1.0;
     }
 
     pub fn is_transitioning(&self) -> bool {
@@ -134,6 +152,38 @@ impl Presentation {
         self.element_info.push(ElementInfo::new());
         self
     }
+
+    fn next_slide(&mut self, base: &mut Base) {
+        if self.slide_index < self.num_elements - 1 {
+            let previous_index = self.slide_index;
+            self.slide_index += 1;
+
+            self.element_info[self.slide_index].transition_from(
+                &[self.last_size.w as f32, 0.0, 0.0].into()
+            );
+            self.element_info[previous_index].transition_to(
+                &[-self.last_size.w as f32, 0.0, 0.0].into()
+            );
+            base.enable_update(true);
+            base.invalidate_size();
+        }
+    }
+
+    fn previous_slide(&mut self, base: &mut Base) {
+        if self.slide_index > 0 {
+            let previous_index = self.slide_index;
+            self.slide_index -= 1;
+
+            self.element_info[self.slide_index].transition_from(
+                &[-self.last_size.w as f32, 0.0, 0.0].into()
+            );
+            self.element_info[previous_index].transition_to(
+                &[self.last_size.w as f32, 0.0, 0.0].into()
+            );
+            base.enable_update(true);
+            base.invalidate_size();
+        }
+    }
 }
 
 impl Element for Presentation {
@@ -141,7 +191,8 @@ impl Element for Presentation {
         for slide in self.slides.drain(..) {
             base.add_boxed(slide);
         }
-        base.enable_actions(true);
+        base.enable_input(true);
+        base.focus();
     }
 
     fn resize(&mut self, base: &mut Base) {
@@ -180,6 +231,7 @@ impl Element for Presentation {
                     }
 
                     child.element_transform(&self.element_info[i].transform());
+                    child.element_opacity(self.element_info[i].opacity());
                 });
 
                 resolved_child_size
@@ -211,10 +263,12 @@ impl Element for Presentation {
                 if end {
                     self.element_info[i].position = self.element_info[i].target.as_mut().unwrap().position;
                     self.element_info[i].rotation = self.element_info[i].target.as_mut().unwrap().rotation;
+                    self.element_info[i].opacity = self.element_info[i].target.as_mut().unwrap().opacity;
                     self.element_info[i].target = None;
                 }
 
                 child.element_transform(&self.element_info[i].transform());
+                child.element_opacity(self.element_info[i].opacity());
 
                 someone_updating = true;
             }
@@ -225,39 +279,27 @@ impl Element for Presentation {
         }
     }
 
-    fn action(&mut self, base: &mut Base, action: UiAction) {
-        match action {
-            UiAction::NextSlide => {
-                if self.slide_index < self.num_elements - 1 {
-                    let previous_index = self.slide_index;
-                    self.slide_index += 1;
-
-                    self.element_info[self.slide_index].transition_from(
-                        &[self.last_size.w as f32, 0.0, 0.0].into()
-                    );
-                    self.element_info[previous_index].transition_to(
-                        &[-self.last_size.w as f32, 0.0, 0.0].into()
-                    );
-                    base.enable_update(true);
-                    base.invalidate_size();
-                }
-            },
-            UiAction::PreviousSlide => {
-                if self.slide_index > 0 {
-                    let previous_index = self.slide_index;
-                    self.slide_index -= 1;
-
-                    self.element_info[self.slide_index].transition_from(
-                        &[-self.last_size.w as f32, 0.0, 0.0].into()
-                    );
-                    self.element_info[previous_index].transition_to(
-                        &[self.last_size.w as f32, 0.0, 0.0].into()
-                    );
-                    base.enable_update(true);
-                    base.invalidate_size();
-                }
-            },
+    /// Clicks on slides that are not handled by their contents bring the focus back to the
+    /// presentation.
+    fn pointer_down(&mut self, base: &mut Base, event: &PointerEvent) -> bool {
+        if event.phase != EventPhase::Capture {
+            base.focus();
         }
+        false
+    }
+
+    fn key(&mut self, base: &mut Base, event: &KeyEvent) -> bool {
+        if event.phase == EventPhase::Capture {
+            return false;
+        }
+
+        match event.key {
+            Key::Right | Key::PageDown => self.next_slide(base),
+            Key::Left | Key::PageUp => self.previous_slide(base),
+            _ => return false,
+        }
+
+        true
     }
 }
 
@@ -269,7 +311,7 @@ pub enum Align {
 
 pub struct TextSlide {
     single_line: Option<primitives::Text>,
-    multi_lines: Vec<primitives::Text>,
+    multi_lines: Vec<primitives::RichTextLine>,
 
     text_string: String,
     text_size: f32,
@@ -281,6 +323,7 @@ pub struct TextSlide {
     _monospaced: bool,
 
     _word_wrap: bool,
+    _vertical: bool,
 
     _color: na::Vector4<u8>,
 
@@ -304,6 +347,7 @@ impl TextSlide {
             _monospaced: false,
 
             _word_wrap: true,
+            _vertical: false,
 
             _color: [0, 0, 0, 255].into(),
 
@@ -342,6 +386,12 @@ impl TextSlide {
         self
     }
 
+    /// Lays out lines as columns from top to bottom, starting from the right, for CJK text.
+    pub fn vertical(mut self, value: bool) -> TextSlide {
+        self._vertical = value;
+        self
+    }
+
     pub fn color(mut self, color: na::Vector4<u8>) -> TextSlide {
         self._color = color;
         self
@@ -353,6 +403,58 @@ impl TextSlide {
         self
     }
 
+    fn span_style(&self) -> SpanStyle {
+        SpanStyle::new()
+            .bold(self._bold)
+            .italic(self._italic)
+            .monospaced(self._monospaced)
+            .color(self._color)
+    }
+
+    fn shaping(&self) -> ShapingOptions {
+        ShapingOptions::new().vertical(self._vertical)
+    }
+
+    fn rich_text(&self, range: ::std::ops::Range<usize>) -> RichText {
+        match self.highlighted_lines {
+            None => RichText::new().span(&self.text_string[range], self.span_style()),
+            Some(ref tokens) => highlighted_rich_text(&self.text_string, tokens, range, self.span_style()),
+        }
+    }
+
+    /// Places every line in a vertical column, columns go from right to left and are
+    /// centered in the box.
+    fn layout_columns(&mut self, base: &mut Base, w: i32, h: i32, column_width: f32) {
+        let mut ranges = Vec::new();
+        let mut offset = 0;
+        for line in self.text_string.split('\n') {
+            ranges.push(offset..offset + line.len());
+            offset += line.len() + 1;
+        }
+
+        self.multi_lines.clear();
+
+        let mut max_column_height: f32 = 0.0;
+        for range in ranges {
+            let mut text = base.primitives().rich_text(&self.rich_text(range));
+            text.set_size(self.text_size);
+            text.set_shaping(self.shaping());
+            if let Some(m) = text.measure() {
+                max_column_height = max_column_height.max(m.height);
+            }
+            self.multi_lines.push(text);
+        }
+
+        let columns_width = column_width * self.multi_lines.len() as f32;
+        let mut x = w as f32 / 2.0 + columns_width / 2.0 - column_width / 2.0;
+        let top = h as f32 / 2.0 - max_column_height / 2.0;
+
+        for column in self.multi_lines.iter_mut() {
+            column.set_position(x, top);
+            x -= column_width;
+        }
+    }
+
     fn update_highlighted_lines(&mut self) {
         match self._highlighter {
             Some(ref lang) => {
@@ -382,10 +484,47 @@ impl TextSlide {
     }
 }
 
+/// Spans of highlighted tokens that overlap the byte `range` of `text`, line breaks are left out.
+fn highlighted_rich_text(text: &str, tokens: &[(syntect::highlighting::Style, usize)], range: ::std::ops::Range<usize>, style: SpanStyle) -> RichText {
+    use syntect::highlighting::FontStyle;
+
+    let mut rich_text = RichText::new();
+    let mut token_start = 0;
+
+    for &(token_style, token_len) in tokens {
+        let token_end = token_start + token_len;
+        let start = token_start.max(range.start);
+        let end = token_end.min(range.end);
+        token_start = token_end;
+
+        if start >= end {
+            continue;
+        }
+
+        let color = token_style.foreground;
+        rich_text.push(
+            text[start..end].trim_end_matches('\n'),
+            style
+                .color([color.r, color.g, color.b, color.a].into())
+                .bold(style.bold || token_style.font_style.contains(FontStyle::BOLD))
+                .italic(style.italic || token_style.font_style.contains(FontStyle::ITALIC)),
+        );
+
+        if token_start >= range.end {
+            break;
+        }
+    }
+
+    rich_text
+}
+
 impl Element for TextSlide {
     fn inflate(&mut self, base: &mut Base) {
+        base.set_clip(true);
+
         let mut text = base.primitives().text(self.text_string.clone(), self._bold, self._italic, self._monospaced, self._color).expect("failed to create text");
         text.set_size(self.text_size);
+        text.set_shaping(self.shaping());
         self.single_line = Some(text);
     }
 
@@ -404,7 +543,11 @@ impl Element for TextSlide {
                         };
 
                         line.set_hidden(false);
-                        line.set_position(0.0, m.ascent + m.line_gap / 2.0);
+                        if self._vertical {
+                            line.set_position(m.width / 2.0, 0.0);
+                        } else {
+                            line.set_position(0.0, m.ascent + m.line_gap / 2.0);
+                        }
 
                         m
                     }
@@ -420,298 +563,50 @@ impl Element for TextSlide {
                 })
             },
             BoxSize::Fixed { w, h, .. } => {
-                let (metrics, mut positions) = match self.single_line.as_mut() {
+                let (metrics, glyphs) = match self.single_line.as_mut() {
                     None => return base.layout_empty(),
                     Some(line) => {
                         line.set_hidden(true);
                         match line.measurement().measure() {
                             None => return base.layout_empty(),
-                            Some(m) => (m, line.measurement().glyph_positions())
+                            Some(m) => (m, line.measurement().glyph_positions().collect::<Vec<_>>())
                         }
                     }
                 };
 
-                enum Separator {
-                    Space,
-                    NewLine,
-                }
-
-                #[derive(Debug, Copy, Clone)]
-                enum Line {
-                    Empty(Segment),
-                    WordWrap(Segment),
-                    ParagraphBreak(Segment),
-                }
-
-                #[derive(Debug, Copy, Clone)]
-                struct Segment {
-                    offset: u32,
-                    len: u32,
-                    width: f32,
+                if self._vertical {
+                    self.layout_columns(base, w, h, metrics.width);
+                    return base.resolve_size(Some(ResolvedSize { w, h }));
                 }
 
-                fn is_separator(text: &str, word_wrap: bool, m: &primitives::GlyphMeasurement) -> Option<Separator> {
-                    let s: &str = &text[m.byte_offset as usize..(m.byte_offset + m.len) as usize];
-                    if s.chars().any(|c| c == '\n') {
-                        Some(Separator::NewLine)
-                    } else if word_wrap && s.chars().all(|c| c.is_whitespace()) {
-                        Some(Separator::Space)
-                    } else {
-                        None
-                    }
+                let mut style = ParagraphStyle::new();
+                if self._word_wrap {
+                    style = style.max_width(w as f32);
                 }
-
-                let max_line_w = w as f32;
-
-                let mut lines = Vec::new();
-
-                let mut leading_space = None;
-                let mut current_line = None;
-                let mut candidate_word_whitespace = None;
-                let mut candidate_word = None;
-
-                while let Some(g) = positions.next() {
-                    match (current_line, candidate_word_whitespace, candidate_word) {
-                        (None, None, None) => match is_separator(&self.text_string, self._word_wrap, &g) {
-                            Some(Separator::NewLine) => {
-                                lines.push(Line::Empty(Segment {
-                                    offset: g.byte_offset,
-                                    len: g.len,
-                                    width: g.x_advance,
-                                }));
-                                leading_space = None;
-                            },
-                            Some(Separator::Space) => match leading_space {
-                                None => leading_space = Some(Segment {
-                                    offset: g.byte_offset,
-                                    len: g.len,
-                                    width: g.x_advance,
-                                }),
-                                Some(_) => {
-                                    let leading_space = leading_space.as_mut().unwrap();
-
-                                    leading_space.len += g.len;
-                                    leading_space.width += g.x_advance;
-                                }
-                            },
-                            None => match leading_space {
-                                None => current_line = Some(Segment {
-                                    offset: g.byte_offset,
-                                    len: g.len,
-                                    width: g.x_advance,
-                                }),
-                                Some(r_leading_space) => {
-                                    current_line = Some(Segment {
-                                        offset: r_leading_space.offset,
-                                        len: r_leading_space.len + g.len,
-                                        width: r_leading_space.width + g.x_advance,
-                                    });
-                                    leading_space = None;
-                                }
-                            }
-                        }
-                        (Some(r_current_line), None, None) => match is_separator(&self.text_string, self._word_wrap, &g) {
-                            Some(Separator::NewLine) => {
-                                lines.push(Line::ParagraphBreak(r_current_line));
-                                current_line = None;
-                            },
-                            Some(Separator::Space) => candidate_word_whitespace = Some(Segment {
-                                offset: g.byte_offset,
-                                len: g.len,
-                                width: g.x_advance,
-                            }),
-                            None => {
-                                let current_line = current_line.as_mut().unwrap();
-
-                                current_line.len += g.len;
-                                current_line.width += g.x_advance;
-                            }
-                        }
-                        (Some(r_current_line), Some(_), None) => match is_separator(&self.text_string, self._word_wrap, &g) {
-                            Some(Separator::NewLine) => {
-                                lines.push(Line::ParagraphBreak(r_current_line));
-                                current_line = None;
-                                candidate_word_whitespace = None;
-                            },
-                            Some(Separator::Space) => {
-                                let candidate_word_whitespace = candidate_word_whitespace.as_mut().unwrap();
-
-                                candidate_word_whitespace.len += g.len;
-                                candidate_word_whitespace.width += g.x_advance;
-                            },
-                            None => candidate_word = Some(Segment {
-                                offset: g.byte_offset,
-                                len: g.len,
-                                width: g.x_advance,
-                            })
-                        }
-                        (Some(r_current_line), Some(r_candidate_word_whitespace), Some(r_candidate_word)) => match is_separator(&self.text_string, self._word_wrap, &g) {
-                            Some(Separator::NewLine) => {
-                                let line_width_with_candidate_word = r_current_line.width + r_candidate_word_whitespace.width + r_candidate_word.width;
-                                if line_width_with_candidate_word <= max_line_w {
-                                    lines.push(Line::ParagraphBreak(Segment {
-                                        width: line_width_with_candidate_word,
-                                        offset: r_current_line.offset,
-                                        len: r_current_line.len + r_candidate_word_whitespace.len + r_candidate_word.len,
-                                    }));
-                                } else {
-                                    lines.push(Line::WordWrap(r_current_line));
-                                    lines.push(Line::ParagraphBreak(r_candidate_word));
-                                }
-                                current_line = None;
-                                candidate_word_whitespace = None;
-                                candidate_word = None;
-                            },
-                            Some(Separator::Space) => {
-                                let line_width_with_candidate_word = r_current_line.width + r_candidate_word_whitespace.width + r_candidate_word.width;
-                                if line_width_with_candidate_word <= max_line_w {
-                                    {
-                                        let current_line = current_line.as_mut().unwrap();
-                                        current_line.len += r_candidate_word_whitespace.len + r_candidate_word.len;
-                                        current_line.width += r_candidate_word_whitespace.width + r_candidate_word.width;
-                                    }
-
-                                    candidate_word_whitespace = Some(Segment {
-                                        offset: g.byte_offset,
-                                        len: g.len,
-                                        width: g.x_advance,
-                                    });
-                                    candidate_word = None;
-                                } else {
-                                    lines.push(Line::WordWrap(r_current_line));
-                                    current_line = candidate_word;
-                                    candidate_word = None;
-                                    candidate_word_whitespace = Some(Segment {
-                                        offset: g.byte_offset,
-                                        len: g.len,
-                                        width: g.x_advance,
-                                    });
-                                }
-                            },
-                            None => {
-                                let candidate_word = candidate_word.as_mut().unwrap();
-
-                                candidate_word.len += g.len;
-                                candidate_word.width += g.x_advance;
-                            }
-                        }
-                        _ => unreachable!("invalid state"),
-                    }
-                }
-
-                match (current_line, candidate_word_whitespace, candidate_word) {
-                    (None, None, None) => lines.push(Line::Empty(Segment {
-                        offset: self.text_string.len() as u32,
-                        len: 0,
-                        width: 0.0,
-                    })),
-                    (Some(r_current_line), None, None) => {
-                        lines.push(Line::ParagraphBreak(r_current_line))
-                    },
-                    (Some(r_current_line), Some(_), None) => {
-                        lines.push(Line::ParagraphBreak(r_current_line))
-                    },
-                    (Some(r_current_line), Some(r_candidate_word_whitespace), Some(r_candidate_word)) => {
-                        let line_width_with_candidate_word = r_current_line.width + r_candidate_word_whitespace.width + r_candidate_word.width;
-                        if line_width_with_candidate_word <= max_line_w {
-                            lines.push(Line::ParagraphBreak(Segment {
-                                width: line_width_with_candidate_word,
-                                offset: r_current_line.offset,
-                                len: r_current_line.len + r_candidate_word_whitespace.len + r_candidate_word.len,
-                            }));
-                        } else {
-                            lines.push(Line::WordWrap(r_current_line));
-                            lines.push(Line::ParagraphBreak(r_candidate_word));
-                        }
-                    },
-                    _ => unreachable!("invalid state"),
+                match self.align {
+                    Align::Center => style = style.align(TextAlign::Center),
+                    // right-to-left paragraphs start at the right
+                    Align::Left if text::is_rtl(&self.text_string, Direction::Auto) => style = style.align(TextAlign::Right),
+                    Align::Left => (),
                 }
 
+                let paragraph = Paragraph::layout(&self.text_string, &glyphs, &metrics, &style);
+                let (text_width, text_height) = paragraph.size();
 
-                let mut max_text_width: f32 = lines.iter().map(|l| match l {
-                    Line::Empty(_) => 0.0,
-                    Line::ParagraphBreak(s) => s.width,
-                    Line::WordWrap(s) => s.width,
-                }).max_by(|x, y| if x > y {
-                    ::std::cmp::Ordering::Greater
-                } else {
-                    ::std::cmp::Ordering::Less
-                }).unwrap_or(0.0);
-                let max_text_height = lines.len() as f32 * metrics.height;
-
-                let left_offset = max_line_w / 2.0 - max_text_width / 2.0;
+                // lines are aligned within the paragraph, center the paragraph as a block
+                let block_x = paragraph.lines().iter().map(|l| l.x).fold(w as f32, f32::min);
+                let left_offset = (w as f32 - text_width) / 2.0 - block_x;
+                let top_offset = h as f32 / 2.0 - text_height / 2.0;
 
                 self.multi_lines.clear();
 
-                let mut highlight_index = self.highlighted_lines.as_ref().map(|_| 0);
-                let mut highlight_index_byte = 0;
-
-                let mut top: f32 = h as f32 / 2.0 - max_text_height / 2.0 + metrics.descent;
-                for line in lines.iter() {
-                    let (_new_line, do_render, s) = match line {
-                        Line::WordWrap(s) => (false, true, s),
-                        Line::ParagraphBreak(s) => (true, true, s),
-                        Line::Empty(s) => (true, false, s),
-                    };
-
-                    let (mut x, y) = if self.align == Align::Center {
-                        (left_offset + max_text_width / 2.0 - s.width / 2.0, top + metrics.height)
-                    } else {
-                        (left_offset, top + metrics.height)
-                    };
-
-                    match highlight_index {
-                        None => {
-                            if do_render {
-                                let mut text = base.primitives()
-                                    .text(self.text_string[s.offset as usize..(s.offset + s.len) as usize].to_string(),
-                                          self._bold, self._italic, self._monospaced, self._color)
-                                    .unwrap();
-                                text.set_position(x, y);
-                                text.set_size(self.text_size);
-                                self.multi_lines.push(text);
-                            }
-                        },
-                        Some(mut ix) => {
-                            let mut mismatch = s.offset as i32 - highlight_index_byte as i32;
-
-                            while mismatch > 0 {
-                                let (_h_item, h_len) = self.highlighted_lines.as_ref().unwrap()[ix];
-                                highlight_index_byte += h_len;
-                                ix += 1;
-                                mismatch = s.offset as i32 - highlight_index_byte as i32;
-                            }
-
-                            if do_render {
-                                while (highlight_index_byte as u32) < s.offset + s.len {
-                                    let (h_item, h_len) = self.highlighted_lines.as_ref().unwrap()[ix];
-
-                                    let mut text = &self.text_string[highlight_index_byte as usize..(highlight_index_byte as usize + h_len)];
-
-                                    while text.ends_with("\n") {
-                                        text = &text[..text.len() - 1];
-                                    }
-
-                                    let mut text = base.primitives()
-                                        .text(text,
-                                              self._bold, self._italic, self._monospaced, [h_item.foreground.r, h_item.foreground.g, h_item.foreground.b, h_item.foreground.a].into())
-                                        .unwrap();
-                                    text.set_position(x, y);
-                                    text.set_size(self.text_size);
-                                    x += text.measurement().measure().unwrap().width;
-
-                                    self.multi_lines.push(text);
-
-                                    highlight_index_byte += h_len;
-                                    ix += 1;
-                                }
-                            }
-
-                            highlight_index = Some(ix);
-                        }
+                for line in paragraph.lines() {
+                    for segment in line.segments.iter() {
+                        let mut text = base.primitives().rich_text(&self.rich_text(segment.range.clone()));
+                        text.set_size(self.text_size);
+                        text.set_position(left_offset + segment.x, top_offset + line.baseline);
+                        self.multi_lines.push(text);
                     }
-
-                    top += metrics.height;
                 }
 
                 Some(ResolvedSize {
@@ -0,0 +1,138 @@
+use ui::*;
+use crate::na;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// One line of the profiler legend, drawn next to a swatch of the scope color.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LegendRow {
+    pub label: String,
+    pub color: na::Vector4<u8>,
+}
+
+/// Legend contents shared between the interface and the legend element.
+#[derive(Default)]
+pub struct LegendState {
+    rows: Vec<LegendRow>,
+    visible: bool,
+    version: usize,
+}
+
+impl LegendState {
+    pub fn set_rows(&mut self, rows: Vec<LegendRow>) {
+        if self.rows == rows {
+            return;
+        }
+
+        self.rows = rows;
+        self.version += 1;
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.version += 1;
+    }
+}
+
+struct Row {
+    text: primitives::Text,
+    swatch: Shape,
+}
+
+pub struct ProfilerLegend {
+    state: Rc<RefCell<LegendState>>,
+    version: Option<usize>,
+    visible: bool,
+    rows: Vec<Row>,
+}
+
+impl ProfilerLegend {
+    pub fn new(state: Rc<RefCell<LegendState>>) -> ProfilerLegend {
+        ProfilerLegend {
+            state,
+            version: None,
+            visible: false,
+            rows: Vec::new(),
+        }
+    }
+
+    fn rebuild_rows(&mut self, base: &mut Base) {
+        let state = self.state.borrow();
+
+        self.visible = state.visible;
+        self.version = Some(state.version);
+        self.rows.clear();
+
+        if !state.visible {
+            return;
+        }
+
+        let swatch_size = 10.0 * base.scale();
+
+        for row in state.rows.iter() {
+            let mut text = match base.primitives().text(row.label.clone(), false, false, true, [0, 0, 0, 255].into()) {
+                Some(text) => text,
+                None => continue,
+            };
+            text.set_size(14.0);
+
+            let swatch = base.primitives().rect(0.0, 0.0, swatch_size, swatch_size, ShapeStyle::fill(Paint::Color(row.color)));
+
+            self.rows.push(Row { text, swatch });
+        }
+    }
+}
+
+impl Element for ProfilerLegend {
+    fn inflate(&mut self, base: &mut Base) {
+        base.enable_update(true);
+        self.rebuild_rows(base);
+    }
+
+    fn resize(&mut self, base: &mut Base) {
+        if !self.visible || self.rows.is_empty() {
+            return base.layout_empty();
+        }
+
+        let margin = 6.0 * base.scale();
+        let swatch_size = 10.0 * base.scale();
+        let swatch_gap = 6.0 * base.scale();
+
+        let mut y = margin;
+        let mut text_width: f32 = 0.0;
+
+        for row in self.rows.iter_mut() {
+            let m = match row.text.measurement().measure() {
+                None => {
+                    row.text.set_hidden(true);
+                    row.swatch.set_hidden(true);
+                    continue;
+                }
+                Some(m) => m,
+            };
+
+            row.swatch.set_hidden(false);
+            row.swatch.set_position(margin, y + (m.height - swatch_size) / 2.0);
+
+            row.text.set_hidden(false);
+            row.text.set_position(margin + swatch_size + swatch_gap, y + m.ascent + m.line_gap / 2.0);
+
+            y += m.height;
+            text_width = text_width.max(m.width);
+        }
+
+        base.resolve_size(Some(ResolvedSize {
+            w: (margin * 2.0 + swatch_size + swatch_gap + text_width).ceil() as i32,
+            h: (y + margin).ceil() as i32,
+        }));
+    }
+
+    fn update(&mut self, base: &mut Base, _delta: f32) {
+        if self.version == Some(self.state.borrow().version) {
+            return;
+        }
+
+        self.rebuild_rows(base);
+        base.invalidate_size();
+    }
+}
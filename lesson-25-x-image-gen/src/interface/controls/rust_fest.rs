@@ -1,6 +1,7 @@
 use ui::*;
 use crate::na;
 use super::presentation::*;
+use super::text_input::TextInput;
 
 pub struct RustFest {
 
@@ -44,7 +45,7 @@ impl Element for RustFest {
                                         .size(25.0)
                                 )
                                 .with(
-                                    TextSlide::new("B to toggle element borders, P to toggle the profiler")
+                                    TextSlide::new("B to toggle element borders, P to toggle the profiler, X to export its trace")
                                         .bold(true)
                                         .centered()
                                         .size(25.0)
@@ -61,6 +62,10 @@ impl Element for RustFest {
                                         .centered()
                                         .size(25.0)
                                 )
+                                .with(
+                                    TextInput::new("Click to edit, Escape to leave the field")
+                                        .size(25.0)
+                                )
                         )
                 )
                 .with_slide(
@@ -727,6 +732,10 @@ let (sender, receiver) = channel();
                                 .size(90.0)
                         )
                 )
+                // Svg resources are drawn as tessellated shapes, scaled to fit the slide.
+                .with_slide(
+                    SvgImage::new("test.svg")
+                )
                 .with_slide(
                     CreditsSlide::new()
                 )
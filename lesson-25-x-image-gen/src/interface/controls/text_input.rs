@@ -0,0 +1,455 @@
+use ui::*;
+use ui::primitives::{GlyphMeasurement, TextMeasurement};
+use ui::text::{self, CaretLayout, TextEditor};
+
+const PADDING: f32 = 6.0;
+const CARET_WIDTH: f32 = 2.0;
+const BLINK_PERIOD: f32 = 1.0;
+
+/// Single line text field.
+///
+/// Takes the focus when clicked, and gives it back to the closest ancestor with input
+/// enabled when Escape is pressed. While focused, it consumes all keyboard input.
+pub struct TextInput {
+    editor: TextEditor,
+    _size: f32,
+    _width: f32,
+    _direction: Direction,
+
+    focused: bool,
+    dragging: bool,
+    /// Text being composed by an input method, and the cursor within it.
+    composition: Option<(String, usize)>,
+    blink_time: f32,
+
+    /// Text shown by the `text` primitive, with composition inserted at the caret.
+    shown: Option<String>,
+    text: Option<primitives::Text>,
+    layout: Option<CaretLayout>,
+    /// Line metrics of the font, used even if the text is empty.
+    line: Option<TextMeasurement>,
+
+    frame: Option<Shape>,
+    caret: Option<Shape>,
+    highlights: Vec<Shape>,
+
+    /// Field position and size in the element, in pixels.
+    field: (f32, f32, f32, f32),
+    scroll: f32,
+}
+
+impl TextInput {
+    pub fn new<P: ToString>(text: P) -> TextInput {
+        TextInput {
+            editor: TextEditor::new(text),
+            _size: 20.0,
+            _width: 300.0,
+            _direction: Direction::Auto,
+
+            focused: false,
+            dragging: false,
+            composition: None,
+            blink_time: 0.0,
+
+            shown: None,
+            text: None,
+            layout: None,
+            line: None,
+
+            frame: None,
+            caret: None,
+            highlights: Vec::new(),
+
+            field: (0.0, 0.0, 0.0, 0.0),
+            scroll: 0.0,
+        }
+    }
+
+    pub fn size(mut self, size: f32) -> Self {
+        self._size = size;
+        self
+    }
+
+    /// Width of the field when it is sized automatically.
+    pub fn width(mut self, width: f32) -> Self {
+        self._width = width;
+        self
+    }
+
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self._direction = direction;
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        self.editor.text()
+    }
+
+    /// Shown text and caret offset in it.
+    fn display_text(&self) -> (String, usize) {
+        let caret = self.editor.caret();
+
+        match self.composition {
+            None => (self.editor.text().to_string(), caret),
+            Some((ref composition, cursor)) => {
+                let mut text = self.editor.text().to_string();
+                text.insert_str(caret, composition);
+                (text, caret + cursor)
+            }
+        }
+    }
+
+    fn line_metrics(&mut self) -> (f32, f32) {
+        match self.line.as_mut().and_then(|line| line.measure()) {
+            Some(m) => (m.ascent + m.line_gap / 2.0, m.height),
+            None => (self._size, self._size * 1.2),
+        }
+    }
+
+    fn rebuild_text(&mut self, base: &mut Base, shown: String) {
+        self.text = None;
+        self.layout = None;
+
+        let mut text = match base.primitives().text(shown.clone(), false, false, false, [20, 20, 20, 255].into()) {
+            Some(text) => text,
+            None => return,
+        };
+        text.set_size(self._size);
+        text.set_shaping(ShapingOptions::new().direction(self._direction));
+        self.shown = Some(shown.clone());
+
+        let glyphs: Vec<GlyphMeasurement> = text.measurement().glyph_positions().collect();
+        self.layout = Some(CaretLayout::new(&shown, &glyphs, self._direction));
+        self.text = Some(text);
+    }
+
+    /// Shapes the text again if it changed, and places the text, caret and selection.
+    fn refresh(&mut self, base: &mut Base) {
+        let (shown, shown_caret) = self.display_text();
+        if self.shown.as_ref() != Some(&shown) {
+            self.rebuild_text(base, shown.clone());
+        }
+
+        let scale = base.scale();
+        let padding = PADDING * scale;
+        let (field_x, field_y, field_w, field_h) = self.field;
+        let (ascent, line_height) = self.line_metrics();
+
+        let inner_w = (field_w - padding * 2.0).max(0.0);
+        let (content_w, caret_x) = match self.layout {
+            Some(ref layout) => (layout.width(), layout.caret_x(shown_caret)),
+            None => (0.0, 0.0),
+        };
+
+        // keep the caret visible, right-to-left text is aligned to the right if it fits
+        let align = if content_w <= inner_w {
+            self.scroll = 0.0;
+            if text::is_rtl(&shown, self._direction) { inner_w - content_w } else { 0.0 }
+        } else {
+            self.scroll = self.scroll
+                .max(caret_x - inner_w)
+                .min(caret_x)
+                .max(0.0)
+                .min(content_w - inner_w);
+            0.0
+        };
+
+        let text_x = field_x + padding + align - self.scroll;
+        let text_y = field_y + (field_h - line_height) / 2.0;
+
+        if let Some(ref mut text) = self.text {
+            text.set_hidden(false);
+            text.set_position(text_x, text_y + ascent);
+        }
+
+        let frame_color: [u8; 4] = if self.focused { [40, 110, 220, 255] } else { [160, 160, 160, 255] };
+        self.frame = Some(base.primitives().rect(
+            field_x, field_y, field_w, field_h,
+            ShapeStyle::stroke(Paint::Color(frame_color.into()), scale)
+        ));
+
+        let caret_w = CARET_WIDTH * scale;
+        let mut caret = base.primitives().rect(
+            0.0, 0.0, caret_w, line_height,
+            ShapeStyle::fill(Paint::Color([20, 20, 20, 255].into()))
+        );
+        caret.set_position(text_x + caret_x - caret_w / 2.0, text_y);
+        caret.set_hidden(!self.is_caret_visible());
+        self.caret = Some(caret);
+
+        self.highlights.clear();
+
+        let layout = match self.layout {
+            Some(ref layout) => layout,
+            None => return,
+        };
+
+        match self.composition {
+            Some((ref composition, _)) => {
+                let start = self.editor.caret();
+                let underline_h = scale.max(1.0);
+                for (left, right) in layout.selection_spans(start..start + composition.len()) {
+                    let mut underline = base.primitives().rect(
+                        0.0, 0.0, right - left, underline_h,
+                        ShapeStyle::fill(Paint::Color([20, 20, 20, 255].into()))
+                    );
+                    underline.set_position(text_x + left, text_y + ascent + underline_h * 2.0);
+                    self.highlights.push(underline);
+                }
+            },
+            None => {
+                let color: [u8; 4] = if self.focused { [170, 200, 250, 255] } else { [210, 210, 210, 255] };
+                for (left, right) in layout.selection_spans(self.editor.selection()) {
+                    let mut highlight = base.primitives().rect(
+                        0.0, 0.0, right - left, line_height,
+                        ShapeStyle::fill(Paint::Color(color.into()))
+                    );
+                    highlight.set_position(text_x + left, text_y);
+                    self.highlights.push(highlight);
+                }
+            },
+        }
+    }
+
+    fn is_caret_visible(&self) -> bool {
+        self.focused && self.blink_time % BLINK_PERIOD < BLINK_PERIOD / 2.0
+    }
+
+    fn is_in_field(&self, x: f32, y: f32) -> bool {
+        let (field_x, field_y, field_w, field_h) = self.field;
+        x >= field_x && x < field_x + field_w && y >= field_y && y < field_y + field_h
+    }
+
+    /// Byte offset in the edited text under the pointer.
+    fn hit_test(&self, base: &Base, x: f32) -> usize {
+        let padding = PADDING * base.scale();
+        let (field_x, _, field_w, _) = self.field;
+
+        let layout = match self.layout {
+            Some(ref layout) => layout,
+            None => return 0,
+        };
+
+        let inner_w = (field_w - padding * 2.0).max(0.0);
+        let align = match self.shown {
+            Some(ref shown) if layout.width() <= inner_w && text::is_rtl(shown, self._direction) => inner_w - layout.width(),
+            _ => 0.0,
+        };
+
+        layout.hit_test(x - (field_x + padding + align - self.scroll))
+            .min(self.editor.text().len())
+    }
+
+    /// Restarts the caret blinking and shows the change.
+    fn changed(&mut self, base: &mut Base) {
+        self.blink_time = 0.0;
+        self.refresh(base);
+    }
+
+    fn copy(&self, base: &Base) {
+        if !self.editor.has_selection() {
+            return;
+        }
+
+        if let Some(clipboard) = base.clipboard() {
+            clipboard.set_text(self.editor.selected_text());
+        }
+    }
+
+    fn handle_key(&mut self, base: &mut Base, key: Key, modifiers: Modifiers) {
+        let is_rtl = text::is_rtl(self.editor.text(), self._direction);
+        let caret = self.editor.caret();
+        let shift = modifiers.shift;
+
+        match key {
+            Key::Left | Key::Right => {
+                let to_right = key == Key::Right;
+                let selection = self.editor.selection();
+
+                let offset = if self.editor.has_selection() && !shift {
+                    // collapse the selection to its side in the direction of movement
+                    if to_right != is_rtl { selection.end } else { selection.start }
+                } else if modifiers.ctrl {
+                    if to_right != is_rtl {
+                        text::next_word(self.editor.text(), caret)
+                    } else {
+                        text::previous_word(self.editor.text(), caret)
+                    }
+                } else {
+                    text::move_caret_visually(self.editor.text(), self._direction, caret, to_right)
+                };
+
+                self.editor.move_caret(offset, shift);
+            },
+            Key::Home | Key::Up | Key::PageUp => self.editor.move_caret(0, shift),
+            Key::End | Key::Down | Key::PageDown => {
+                let end = self.editor.text().len();
+                self.editor.move_caret(end, shift);
+            },
+            Key::Backspace => self.editor.delete_backward(modifiers.ctrl),
+            Key::Delete => self.editor.delete_forward(modifiers.ctrl),
+            Key::Escape => base.blur(),
+            Key::A if modifiers.ctrl => self.editor.select_all(),
+            Key::C if modifiers.ctrl => self.copy(base),
+            Key::X if modifiers.ctrl => {
+                self.copy(base);
+                self.editor.cut();
+            },
+            Key::V if modifiers.ctrl => {
+                let pasted = base.clipboard().and_then(|clipboard| clipboard.text());
+                if let Some(pasted) = pasted {
+                    self.editor.insert(&single_line(&pasted));
+                }
+            },
+            Key::Z if modifiers.ctrl && shift => { self.editor.redo(); },
+            Key::Z if modifiers.ctrl => { self.editor.undo(); },
+            Key::Y if modifiers.ctrl => { self.editor.redo(); },
+            _ => (),
+        }
+    }
+}
+
+/// Text with line breaks replaced by spaces and other control characters removed.
+fn single_line(text: &str) -> String {
+    text.chars()
+        .filter_map(|c| match c {
+            '\n' | '\r' | '\t' => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+impl Element for TextInput {
+    fn inflate(&mut self, base: &mut Base) {
+        base.enable_input(true);
+        base.set_clip(true);
+
+        self.line = base.primitives()
+            .text("Ag", false, false, false, [0, 0, 0, 0].into())
+            .map(|text| text.into_measurement());
+        if let Some(ref mut line) = self.line {
+            line.set_size(self._size);
+        }
+    }
+
+    fn resize(&mut self, base: &mut Base) {
+        let scale = base.scale();
+        let (_, line_height) = self.line_metrics();
+        let field_h = (line_height + PADDING * scale * 2.0).ceil();
+
+        let (w, h) = match base.box_size() {
+            BoxSize::Hidden => {
+                base.blur();
+                return base.layout_empty();
+            },
+            BoxSize::Auto => ((self._width * scale).ceil(), field_h),
+            BoxSize::Fixed { w, h } => (w as f32, h as f32),
+        };
+
+        // the field fills the width and is centered vertically
+        self.field = (0.0, ((h - field_h) / 2.0).max(0.0).floor(), w, field_h.min(h));
+        self.refresh(base);
+
+        base.resolve_size(Some(ResolvedSize { w: w as i32, h: h as i32 }));
+    }
+
+    fn update(&mut self, _base: &mut Base, delta: f32) {
+        let was_visible = self.is_caret_visible();
+        self.blink_time += delta;
+        let visible = self.is_caret_visible();
+
+        if visible != was_visible {
+            if let Some(ref mut caret) = self.caret {
+                caret.set_hidden(!visible);
+            }
+        }
+    }
+
+    fn pointer_down(&mut self, base: &mut Base, event: &PointerEvent) -> bool {
+        if !self.is_in_field(event.x, event.y) {
+            return false;
+        }
+
+        base.focus();
+
+        let offset = self.hit_test(base, event.x);
+        match event.clicks {
+            2 => self.editor.select_word(offset),
+            c if c >= 3 => self.editor.select_all(),
+            _ => {
+                self.editor.move_caret(offset, event.modifiers.shift);
+                self.dragging = true;
+            },
+        }
+
+        self.changed(base);
+        true
+    }
+
+    fn pointer_move(&mut self, base: &mut Base, event: &PointerEvent) -> bool {
+        if !self.dragging {
+            return false;
+        }
+
+        let offset = self.hit_test(base, event.x);
+        if offset != self.editor.caret() {
+            self.editor.move_caret(offset, true);
+            self.changed(base);
+        }
+
+        true
+    }
+
+    fn pointer_up(&mut self, _base: &mut Base, _event: &PointerEvent) -> bool {
+        let was_dragging = self.dragging;
+        self.dragging = false;
+        was_dragging
+    }
+
+    fn focus(&mut self, base: &mut Base, focused: bool) {
+        self.focused = focused;
+        if !focused {
+            self.dragging = false;
+            self.composition = None;
+        }
+
+        base.enable_update(focused);
+        self.changed(base);
+    }
+
+    fn key(&mut self, base: &mut Base, event: &KeyEvent) -> bool {
+        if !self.focused {
+            return false;
+        }
+
+        self.handle_key(base, event.key, event.modifiers);
+        self.changed(base);
+        true
+    }
+
+    fn text(&mut self, base: &mut Base, event: &TextEvent) -> bool {
+        if !self.focused {
+            return false;
+        }
+
+        let text = single_line(&event.text);
+        match event.composition_cursor {
+            Some(cursor) => {
+                self.composition = if text.is_empty() {
+                    None
+                } else {
+                    let cursor = cursor.min(text.len());
+                    Some((text, cursor))
+                };
+            },
+            None => {
+                self.composition = None;
+                self.editor.insert(&text);
+            },
+        }
+
+        self.changed(base);
+        true
+    }
+}
@@ -3,55 +3,128 @@ use gl;
 use crate::na;
 use crate::render_gl::data;
 use crate::render_gl::ColorBuffer;
+use crate::render_gl::FrameProfiler;
 use crate::render_gl::{DebugLines, RectMarker};
-use crate::render_gl::{Flatlander, Alphabet, FlatlanderVertex, FlatlandGroup, FlatlandItem};
+use crate::render_gl::{Flatlander, Alphabet, FlatlanderVertex, FlatlandGroup, FlatlandItem, IndexWidth};
+use crate::render_gl::{SdfTextRenderer, SdfTextGroup, SdfTextItem, SdfTextEffects, SdfFontKey};
+use crate::render_gl::{Gradient, FlatlanderPaint};
+use crate::render_gl::Clip;
 use resources;
+use std::cell::RefCell;
 use std::collections;
+use std::rc::Rc;
 use metrohash::MetroHashSet;
 use metrohash::MetroHashMap;
 use ui::*;
+use ui::fonts::FamilyName;
 
 pub mod controls;
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 enum ControlId {
     Node(Ix),
-    Text(usize),
+    /// Glyphs of a text buffer drawn with one font: buffer id and font id.
+    Text(usize, usize),
+    Shape(usize),
 }
 
 struct ControlInfo {
     _id: ControlId,
+    /// Node that clips this control: the parent for nodes, the owning node for primitives.
+    owner: Option<Ix>,
+    clip: Option<Clip>,
+    /// Product of the opacities of the owner and its ancestors.
+    opacity: f32,
     size: Option<(i32, i32)>,
     absolute_transform: Option<na::Projective3<f32>>,
     marker: Option<RectMarker>,
     flatland_group_data: Option<(Alphabet, Vec<FlatlandItem>)>,
     flatland_group: Option<FlatlandGroup>,
+    sdf_group_data: Option<(SdfFontKey, SdfTextEffects, Vec<SdfTextItem>)>,
+    sdf_group: Option<SdfTextGroup>,
+    _gradients: Vec<Gradient>,
     color: Option<na::Vector4<u8>>,
+    /// Scale from glyph units to units of the absolute transform, for fallback fonts
+    /// with a different number of units per em than the text buffer font.
+    glyph_scale: f32,
 }
 
 impl ControlInfo {
     pub fn new(id: ControlId) -> ControlInfo {
         ControlInfo {
             _id: id,
+            owner: None,
+            clip: None,
+            opacity: 1.0,
             size: None,
             absolute_transform: None,
             marker: None,
             flatland_group_data: None,
             flatland_group: None,
+            sdf_group_data: None,
+            sdf_group: None,
+            _gradients: Vec::new(),
             color: None,
+            glyph_scale: 1.0,
         }
     }
 
     pub fn new_flatlander(id: ControlId, alphabet: Alphabet, items: Vec<FlatlandItem>) -> ControlInfo {
         ControlInfo {
-            _id: id,
-            size: None,
-            absolute_transform: None,
-            marker: None,
             flatland_group_data: Some((alphabet, items)),
-            flatland_group: None,
-            color: None,
+            ..ControlInfo::new(id)
+        }
+    }
+
+    pub fn new_sdf(id: ControlId, font_key: SdfFontKey, effects: SdfTextEffects, items: Vec<SdfTextItem>) -> ControlInfo {
+        ControlInfo {
+            sdf_group_data: Some((font_key, effects, items)),
+            ..ControlInfo::new(id)
+        }
+    }
+
+    pub fn new_shape(id: ControlId, alphabet: Alphabet, gradients: Vec<Gradient>) -> ControlInfo {
+        let items = vec![FlatlandItem {
+            alphabet_entry_index: 0,
+            x_offset: 0,
+            y_offset: 0,
+        }];
+
+        ControlInfo {
+            flatland_group_data: Some((alphabet, items)),
+            _gradients: gradients,
+            // shape colors are in the vertices
+            color: Some([255, 255, 255, 255].into()),
+            ..ControlInfo::new(id)
+        }
+    }
+
+    pub fn with_owner(mut self, owner: Option<Ix>) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    pub fn with_glyph_scale(mut self, glyph_scale: f32) -> Self {
+        self.glyph_scale = glyph_scale;
+        self
+    }
+
+    /// Returns true if the clip changed.
+    pub fn update_clip(&mut self, clip: Option<Clip>) -> bool {
+        if self.clip == clip {
+            return false;
+        }
+        self.clip = clip;
+        true
+    }
+
+    /// Returns true if the opacity changed.
+    pub fn update_opacity(&mut self, opacity: f32) -> bool {
+        if self.opacity == opacity {
+            return false;
         }
+        self.opacity = opacity;
+        true
     }
 
     pub fn update_size(&mut self, size: Option<(i32, i32)>) {
@@ -59,21 +132,26 @@ impl ControlInfo {
     }
 
     pub fn update_transform(&mut self, absolute_transform: Option<na::Projective3<f32>>) {
-        self.absolute_transform = absolute_transform;
+        self.absolute_transform = match absolute_transform {
+            Some(t) if self.glyph_scale != 1.0 => Some(t * na::convert::<_, na::Projective3<f32>>(na::Similarity3::from_scaling(self.glyph_scale))),
+            t => t,
+        };
     }
 
     pub fn update_color(&mut self, color: Option<na::Vector4<u8>>) {
         self.color = color;
     }
 
-    pub fn flush_updates(&mut self, debug_lines: &DebugLines) {
+    pub fn flush_updates(&mut self, debug_lines: &DebugLines, sdf_text: &SdfTextRenderer) {
         match (self.marker.is_some(), self.size, self.absolute_transform) {
             (false, Some(wh), Some(t)) => {
-                self.marker = Some(debug_lines.rect_marker(
+                let marker = debug_lines.rect_marker(
                     t * na::Translation3::new(1.0, 0.0, 0.0),
                     na::Vector2::new((wh.0 - 1) as f32, (wh.1 - 1) as f32),
                     na::Vector4::new(0.0, 0.5, 1.0, 1.0),
-                ))
+                );
+                marker.update_clip(self.clip.as_ref());
+                self.marker = Some(marker);
             }
             (true, Some(wh), Some(t)) => {
                 let marker = self.marker.as_mut().unwrap();
@@ -82,6 +160,7 @@ impl ControlInfo {
                     na::Vector4::new(0.0, 0.5, 1.0, 1.0),
                 );
                 marker.update_transform(t * na::Translation3::new(1.0, 0.0, 0.0));
+                marker.update_clip(self.clip.as_ref());
             }
             (false, _, _) => {}
             (true, _, _) => self.marker = None,
@@ -89,42 +168,154 @@ impl ControlInfo {
 
         match (self.flatland_group.is_some(), &self.flatland_group_data, self.absolute_transform, self.color) {
             (false, &Some((ref alphabet, ref items)), Some(t), Some(color)) => {
-                self.flatland_group = Some(
-                    FlatlandGroup::new(&t, color, alphabet.clone(), items.clone())
-                );
+                let g = FlatlandGroup::new(&t, color, alphabet.clone(), items.clone());
+                g.update_clip(self.clip.as_ref());
+                g.update_opacity(self.opacity);
+                self.flatland_group = Some(g);
             }
             (true, Some((ref _alphabet, ref items)), Some(t), Some(color)) => {
                 let g = self.flatland_group.as_mut().unwrap();
                 g.update_items(items.iter());
                 g.update_transform(&t);
                 g.update_color(color);
+                g.update_clip(self.clip.as_ref());
+                g.update_opacity(self.opacity);
             },
             (false, _, _, _) => {},
             (true, _, _, _) => {
                 self.flatland_group = None
             },
         }
+
+        match (self.sdf_group.is_some(), &self.sdf_group_data, self.absolute_transform, self.color) {
+            (false, &Some((font_key, effects, ref items)), Some(t), Some(color)) => {
+                let g = sdf_text.create_group(font_key, &t, faded(color, self.opacity), effects, items.clone());
+                g.update_clip(self.clip.as_ref());
+                self.sdf_group = Some(g);
+            }
+            (true, Some((_, _, ref items)), Some(t), Some(color)) => {
+                let g = self.sdf_group.as_mut().unwrap();
+                g.update_items(items.iter());
+                g.update_transform(&t);
+                g.update_color(faded(color, self.opacity));
+                g.update_clip(self.clip.as_ref());
+            },
+            (false, _, _, _) => {},
+            (true, _, _, _) => {
+                self.sdf_group = None
+            },
+        }
+    }
+}
+
+/// Fonts shipped with the slides, so that they look the same on machines without these fonts.
+const BUNDLED_FONTS: &[&str] = &[
+    "fonts/DejaVuSans.ttf",
+    "fonts/DejaVuSans-Bold.ttf",
+    "fonts/DejaVuSansMono.ttf",
+    "fonts/DejaVuSansMono-Bold.ttf",
+];
+
+/// Loads bundled fonts and makes generic families resolve to them.
+///
+/// Missing fonts are not fatal, system fonts are used instead.
+fn load_bundled_fonts(fonts: &Fonts) {
+    for path in BUNDLED_FONTS {
+        if let Err(e) = fonts.load_from_resource(path) {
+            warn!("{}, falling back to system fonts", e);
+        }
     }
+
+    fonts.set_family_alias(FamilyName::SansSerif, vec![FamilyName::Title("DejaVu Sans".into()), FamilyName::SansSerif]);
+    fonts.set_family_alias(FamilyName::Monospace, vec![FamilyName::Title("DejaVu Sans Mono".into()), FamilyName::Monospace]);
+
+    fonts.set_fallback_chain(FamilyName::SansSerif, fallback_families());
+    fonts.set_fallback_chain(FamilyName::Monospace, fallback_families());
+}
+
+/// System fonts for scripts and emoji the bundled fonts do not cover, missing ones are skipped.
+fn fallback_families() -> Vec<FamilyName> {
+    [
+        "DejaVu Sans",
+        "Noto Sans",
+        "Noto Sans Arabic",
+        "Noto Sans Hebrew",
+        "Noto Sans Devanagari",
+        "Noto Sans Thai",
+        "Noto Sans CJK SC",
+        "Noto Sans CJK JP",
+        "Noto Sans CJK KR",
+        "Apple SD Gothic Neo",
+        "PingFang SC",
+        "Hiragino Sans",
+        "Microsoft YaHei",
+        "Segoe UI",
+        "Noto Color Emoji",
+        "Apple Color Emoji",
+        "Segoe UI Emoji",
+        "Segoe UI Symbol",
+    ].iter()
+        .map(|name| FamilyName::Title(name.to_string()))
+        .collect()
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 enum AlphabetKey {
-    Font(usize),
+    /// Font id, on-screen size bucket, see `size_bucket`, and variable font instance,
+    /// see `ShapingOptions::instance_key`.
+    Font(usize, u32, u64),
+}
+
+/// Maximum allowed distance in pixels between the glyph outline and its tessellation.
+const TESSELLATION_TOLERANCE_PX: f32 = 0.25;
+
+/// Rounds on-screen pixels per em up to a power of two, so that glyphs tessellated
+/// for one size can be reused for similar sizes.
+fn size_bucket(pixels_per_em: f32) -> u32 {
+    let pixels_per_em = pixels_per_em.max(1.0).min(4096.0).ceil() as u32;
+    pixels_per_em.next_power_of_two()
+}
+
+/// Tessellation tolerance in font units for glyphs in a size bucket.
+fn tessellation_tolerance(units_per_em: u32, size_bucket: u32) -> f32 {
+    TESSELLATION_TOLERANCE_PX * units_per_em as f32 / size_bucket as f32
+}
+
+/// Tessellated shape shared by all shapes with the same content hash.
+struct CachedShape {
+    alphabet: Alphabet,
+    gradients: Vec<Gradient>,
+    users: usize,
 }
 
 pub struct Interface {
     tree: Tree,
     fonts: Fonts,
     fill: Leaf<controls::rust_fest::RustFest>,
+    legend: Leaf<controls::profiler_legend::ProfilerLegend>,
+    legend_state: Rc<RefCell<controls::profiler_legend::LegendState>>,
+    window_scale: f32,
     events: Events,
     controls: MetroHashMap<ControlId, ControlInfo>,
+    /// Fonts of text controls of every text buffer.
+    text_fonts: MetroHashMap<usize, Vec<usize>>,
     event_read_buffer: Vec<Effect>,
     flush_updates_set: MetroHashSet<ControlId>,
 
     debug_lines: DebugLines,
     flatlander: Flatlander,
+    sdf_text: SdfTextRenderer,
 
     alphabets: MetroHashMap<AlphabetKey, Alphabet>,
+    shape_cache: MetroHashMap<u64, CachedShape>,
+    shape_hashes: MetroHashMap<usize, u64>,
+
+    node_parents: MetroHashMap<Ix, Option<Ix>>,
+    clipping_nodes: MetroHashSet<Ix>,
+    /// Opacities of nodes relative to their parents, missing nodes are fully opaque.
+    node_opacities: MetroHashMap<Ix, f32>,
+    /// Maps pointer positions to the ui, from the last render.
+    inverse_view_projection: Option<na::Matrix4<f32>>,
 }
 
 impl Interface {
@@ -136,28 +327,47 @@ impl Interface {
     ) -> Result<Interface, failure::Error> {
         let tree = Tree::new(resources);
         let fonts = tree.fonts();
+        load_bundled_fonts(&fonts);
 
         let events = tree.events();
         let fill = tree.create_root(controls::rust_fest::RustFest::new(), window_scale);
 
         fill.resize(size, window_scale);
 
+        let legend_state = Rc::new(RefCell::new(controls::profiler_legend::LegendState::default()));
+        let legend = tree.create_root(controls::profiler_legend::ProfilerLegend::new(legend_state.clone()), window_scale);
+
+        legend.resize(BoxSize::Auto, window_scale);
+
         Ok(Interface {
             tree,
             fonts,
             fill,
+            legend,
+            legend_state,
+            window_scale,
             events,
             controls: MetroHashMap::default(),
+            text_fonts: MetroHashMap::default(),
             event_read_buffer: Vec::new(),
             flush_updates_set: MetroHashSet::default(),
             debug_lines: DebugLines::new(gl, resources)?,
             flatlander: Flatlander::new(gl, resources)?,
+            sdf_text: SdfTextRenderer::new(gl, resources)?,
             alphabets: MetroHashMap::default(),
+            shape_cache: MetroHashMap::default(),
+            shape_hashes: MetroHashMap::default(),
+            node_parents: MetroHashMap::default(),
+            clipping_nodes: MetroHashSet::default(),
+            node_opacities: MetroHashMap::default(),
+            inverse_view_projection: None,
         })
     }
 
     pub fn resize(&mut self, size: BoxSize, window_scale: f32) {
+        self.window_scale = window_scale;
         self.fill.resize(size, window_scale);
+        self.legend.resize(BoxSize::Auto, window_scale);
     }
 
     fn process_events(&mut self) {
@@ -165,16 +375,20 @@ impl Interface {
         self.flush_updates_set.clear();
 
         let mut glyph_buffer = Vec::new();
+        let mut inherited_invalidated = false;
 
         for event in self.event_read_buffer.drain(..) {
             match event {
-                Effect::Add { id, .. } => {
-                    self.controls.insert(ControlId::Node(id), ControlInfo::new(ControlId::Node(id)));
+                Effect::Add { id, parent_id } => {
+                    self.controls.insert(ControlId::Node(id), ControlInfo::new(ControlId::Node(id)).with_owner(parent_id));
+                    self.node_parents.insert(id, parent_id);
                     self.flush_updates_set.insert(ControlId::Node(id));
+                    inherited_invalidated = true;
                 }
                 Effect::Resize { id, size } => {
                     self.controls.get_mut(&ControlId::Node(id)).map(|c| c.update_size(size));
                     self.flush_updates_set.insert(ControlId::Node(id));
+                    inherited_invalidated |= self.clipping_nodes.contains(&id);
                 }
                 Effect::Transform {
                     id,
@@ -184,135 +398,660 @@ impl Interface {
                         .get_mut(&ControlId::Node(id))
                         .map(|c| c.update_transform(absolute_transform));
                     self.flush_updates_set.insert(ControlId::Node(id));
+                    inherited_invalidated |= self.clipping_nodes.contains(&id);
                 }
                 Effect::Remove { id } => {
                     self.controls
                         .remove(&ControlId::Node(id))
                         .expect("process_events: self.controls.remove(&id)");
+                    self.node_parents.remove(&id);
+                    self.clipping_nodes.remove(&id);
+                    self.node_opacities.remove(&id);
+                    inherited_invalidated = true;
                 }
-                Effect::TextAdd { buffer } => {
-
-                    let alphabet = match self.alphabets.entry(AlphabetKey::Font(buffer._font_id)) {
-                        collections::hash_map::Entry::Occupied(e) => e.into_mut(),
-                        collections::hash_map::Entry::Vacant(mut e) => e.insert(self.flatlander.create_alphabet()),
+                Effect::Clip { id, enabled } => {
+                    let changed = if enabled {
+                        self.clipping_nodes.insert(id)
+                    } else {
+                        self.clipping_nodes.remove(&id)
                     };
-
+                    inherited_invalidated |= changed;
+                }
+                Effect::Opacity { id, opacity } => {
+                    self.node_opacities.insert(id, opacity);
+                    inherited_invalidated = true;
+                }
+                Effect::TextAdd { node_id, buffer, renderer } => {
                     let buffer = self.fonts.buffer_from_id(buffer._id).expect("buffer missing: self.fonts.buffer_from_id(buffer.id)");
+                    let buffer_units_per_em = buffer.font().metrics().units_per_em;
+                    let pixels_per_unit = buffer.transform()
+                        .map(|t| (t * na::Vector3::new(1.0, 0.0, 0.0)).norm())
+                        .unwrap_or(1.0);
+                    let size_bucket = size_bucket(pixels_per_unit * buffer_units_per_em as f32);
+                    let shaping = buffer.shaping();
+                    let instance = shaping.instance_key();
+
+                    glyph_buffer.clear();
+                    buffer.glyphs(&mut glyph_buffer);
 
                     use lyon_path::default::Path;
 
                     let mut builder = Path::builder();
-                    glyph_buffer.clear();
-                    buffer.glyphs(&mut glyph_buffer);
+                    let mut font_ids = Vec::new();
 
-                    let mut flatland_group_items = Vec::new();
+                    for (font_id, glyphs) in glyphs_by_font(&glyph_buffer) {
+                        let font = self.fonts.font_from_id(font_id).expect("font missing: self.fonts.font_from_id(font_id)");
+                        let units_per_em = font.metrics().units_per_em;
+                        // glyph positions are in buffer font units
+                        let to_font_units = units_per_em as f32 / buffer_units_per_em as f32;
+                        let font_offset = |v: i32| (v as f32 * to_font_units).round() as i32;
 
-                    let mut x = 0;
-                    let mut y = 0;
+                        let control_id = ControlId::Text(buffer.id(), font_id);
 
-                    for glyph in glyph_buffer.iter() {
-                        let ix = ensure_glyph_is_in_alphabet_and_return_index(&mut builder, alphabet, buffer.font(), glyph.id);
-                        flatland_group_items.push(FlatlandItem {
-                            alphabet_entry_index: ix,
-                            x_offset: x,
-                            y_offset: y,
-                        });
+                        let control = match renderer {
+                            TextRenderer::Sdf(style) => {
+                                let mut sdf_group_items = Vec::with_capacity(glyphs.len());
 
-                        x += glyph.x_advance + glyph.x_offset;
-                        y += glyph.y_advance + glyph.y_offset;
+                                for &(glyph_id, x, y) in glyphs.iter() {
+                                    ensure_glyph_is_in_sdf_atlas(&mut builder, &self.sdf_text, &font, (font_id, instance), &shaping.variations, units_per_em, glyph_id);
+                                    sdf_group_items.push(SdfTextItem {
+                                        glyph_id,
+                                        x_offset: font_offset(x),
+                                        y_offset: font_offset(y),
+                                    });
+                                }
+
+                                ControlInfo::new_sdf(control_id, (font_id, instance), sdf_effects(&style), sdf_group_items)
+                            }
+                            TextRenderer::Tessellated => {
+                                let tolerance = tessellation_tolerance(units_per_em, size_bucket);
+
+                                let alphabet = match self.alphabets.entry(AlphabetKey::Font(font_id, size_bucket, instance)) {
+                                    collections::hash_map::Entry::Occupied(e) => e.into_mut(),
+                                    collections::hash_map::Entry::Vacant(e) => e.insert(self.flatlander.create_alphabet()),
+                                };
+
+                                let mut flatland_group_items = Vec::with_capacity(glyphs.len());
+
+                                for &(glyph_id, x, y) in glyphs.iter() {
+                                    let ix = ensure_glyph_is_in_alphabet_and_return_index(&mut builder, alphabet, &font, &shaping.variations, glyph_id, tolerance);
+                                    flatland_group_items.push(FlatlandItem {
+                                        alphabet_entry_index: ix,
+                                        x_offset: font_offset(x),
+                                        y_offset: font_offset(y),
+                                    });
+                                }
+
+                                ControlInfo::new_flatlander(control_id, alphabet.clone(), flatland_group_items)
+                            }
+                        };
+
+                        self.controls.insert(control_id, control
+                            .with_owner(Some(node_id))
+                            .with_glyph_scale(1.0 / to_font_units));
+                        self.flush_updates_set.insert(control_id);
+                        font_ids.push(font_id);
                     }
 
-                    self.controls.insert(ControlId::Text(buffer.id()), ControlInfo::new_flatlander(
-                        ControlId::Text(buffer.id()), alphabet.clone(), flatland_group_items
-                    ));
-                    self.flush_updates_set.insert(ControlId::Text(buffer.id()));
+                    self.text_fonts.insert(buffer.id(), font_ids);
+                    inherited_invalidated = true;
                 }
                 Effect::TextUpdate { buffer_id, absolute_transform, color } => {
-                    self.controls
-                        .get_mut(&ControlId::Text(buffer_id))
-                        .map(|c| {
-                            c.update_transform(absolute_transform);
-                            c.update_color(Some(color));
-                        });
-                    self.flush_updates_set.insert(ControlId::Text(buffer_id));
+                    if let Some(font_ids) = self.text_fonts.get(&buffer_id) {
+                        for &font_id in font_ids.iter() {
+                            let control_id = ControlId::Text(buffer_id, font_id);
+                            self.controls
+                                .get_mut(&control_id)
+                                .map(|c| {
+                                    c.update_transform(absolute_transform);
+                                    c.update_color(Some(color));
+                                });
+                            self.flush_updates_set.insert(control_id);
+                        }
+                    }
                 }
                 Effect::TextRemove { buffer_id } => {
-                    if let None = self.controls.remove(&ControlId::Text(buffer_id)) {
-                        warn!("tried to remove nonexisting flatland group {}", buffer_id);
+                    match self.text_fonts.remove(&buffer_id) {
+                        Some(font_ids) => for font_id in font_ids {
+                            self.controls.remove(&ControlId::Text(buffer_id, font_id));
+                        },
+                        None => warn!("tried to remove nonexisting text {}", buffer_id),
+                    }
+                }
+                Effect::FontReload { font_id } => {
+                    self.alphabets.retain(|key, _| match *key {
+                        AlphabetKey::Font(key_font_id, _, _) => key_font_id != font_id,
+                    });
+                    self.sdf_text.remove_font(font_id);
+                }
+                Effect::ShapeAdd { node_id, shape } => {
+                    if let Some(previous_hash) = self.shape_hashes.remove(&shape.id()) {
+                        release_cached_shape(&mut self.shape_cache, previous_hash);
+                    }
+
+                    let flatlander = &self.flatlander;
+                    let cached = self.shape_cache.entry(shape.content_hash()).or_insert_with(|| {
+                        let (alphabet, gradients) = tessellate_shape(flatlander, &shape);
+                        CachedShape { alphabet, gradients, users: 0 }
+                    });
+                    cached.users += 1;
+                    self.shape_hashes.insert(shape.id(), shape.content_hash());
+
+                    self.controls.insert(ControlId::Shape(shape.id()), ControlInfo::new_shape(
+                        ControlId::Shape(shape.id()), cached.alphabet.clone(), cached.gradients.clone()
+                    ).with_owner(Some(node_id)));
+                    inherited_invalidated = true;
+                    self.flush_updates_set.insert(ControlId::Shape(shape.id()));
+                }
+                Effect::ShapeUpdate { shape_id, absolute_transform } => {
+                    self.controls
+                        .get_mut(&ControlId::Shape(shape_id))
+                        .map(|c| c.update_transform(absolute_transform));
+                    self.flush_updates_set.insert(ControlId::Shape(shape_id));
+                }
+                Effect::ShapeRemove { shape_id } => {
+                    if let None = self.controls.remove(&ControlId::Shape(shape_id)) {
+                        warn!("tried to remove nonexisting shape {}", shape_id);
+                    }
+                    if let Some(hash) = self.shape_hashes.remove(&shape_id) {
+                        release_cached_shape(&mut self.shape_cache, hash);
                     }
                 }
             }
         }
 
+        if inherited_invalidated {
+            self.update_inherited();
+        }
+
         for id in &self.flush_updates_set {
             let debug_lines = &self.debug_lines;
+            let sdf_text = &self.sdf_text;
             self.controls
                 .get_mut(id)
-                .map(|c| c.flush_updates(debug_lines));
+                .map(|c| c.flush_updates(debug_lines, sdf_text));
+        }
+    }
+
+    /// Recalculates clips and opacities of all controls, changed controls are flushed.
+    fn update_inherited(&mut self) {
+        let mut node_clips = MetroHashMap::default();
+        let mut opacities = MetroHashMap::default();
+        let ids: Vec<ControlId> = self.controls.keys().cloned().collect();
+
+        for id in ids {
+            let (clip, opacity) = match self.controls[&id].owner {
+                Some(owner) => (
+                    node_clip(owner, &self.node_parents, &self.clipping_nodes, &self.controls, &mut node_clips),
+                    node_opacity(owner, &self.node_parents, &self.node_opacities, &mut opacities),
+                ),
+                None => (None, 1.0),
+            };
+
+            let changed = self.controls.get_mut(&id)
+                .map(|c| {
+                    let clip_changed = c.update_clip(clip);
+                    c.update_opacity(opacity) || clip_changed
+                })
+                .unwrap_or(false);
+            if changed {
+                self.flush_updates_set.insert(id);
+            }
         }
     }
 
     pub fn update(&mut self, delta: f32) {
         self.tree.update(delta);
+        // a root does not reflow itself after its size is invalidated
+        self.legend.resize(BoxSize::Auto, self.window_scale);
         self.process_events()
     }
 
-    pub fn send_action(&mut self, action: UiAction) {
-        self.tree.send_action(action);
+    pub fn set_clipboard<C: Clipboard + 'static>(&mut self, clipboard: C) {
+        self.tree.set_clipboard(clipboard);
+    }
+
+    /// Ray through the pointer at `x` and `y` in normalized device coordinates of the last render.
+    fn pointer_ray(&self, x: f32, y: f32) -> Option<PointerRay> {
+        self.inverse_view_projection
+            .and_then(|inverse| PointerRay::unproject(&inverse, x, y))
     }
 
-    pub fn mouse_move(&mut self, _x: i32, _y: i32) {}
+    /// Pointer position is in normalized device coordinates, returns true if an element
+    /// handled the event.
+    pub fn mouse_move(&mut self, x: f32, y: f32, modifiers: Modifiers) -> bool {
+        match self.pointer_ray(x, y) {
+            Some(ray) => self.tree.pointer_move(&ray, modifiers),
+            None => false,
+        }
+    }
+
+    pub fn mouse_down(&mut self, x: f32, y: f32, clicks: u8, modifiers: Modifiers) -> bool {
+        match self.pointer_ray(x, y) {
+            Some(ray) => self.tree.pointer_down(&ray, clicks, modifiers),
+            None => false,
+        }
+    }
+
+    pub fn mouse_up(&mut self, x: f32, y: f32, modifiers: Modifiers) -> bool {
+        match self.pointer_ray(x, y) {
+            Some(ray) => self.tree.pointer_up(&ray, modifiers),
+            None => false,
+        }
+    }
+
+    /// Returns true if the focused element or one of its ancestors consumed the key.
+    pub fn key_down(&mut self, key: Key, modifiers: Modifiers) -> bool {
+        self.tree.key(key, modifiers)
+    }
 
-    pub fn mouse_down(&mut self, _x: i32, _y: i32) {}
+    pub fn text_input(&mut self, text: &str) -> bool {
+        self.tree.text(text)
+    }
 
-    pub fn mouse_up(&mut self, _x: i32, _y: i32) {}
+    /// Input method composition, `start` is the cursor position in characters.
+    pub fn text_editing(&mut self, text: &str, start: usize) -> bool {
+        let cursor = text.char_indices()
+            .nth(start)
+            .map(|(i, _)| i)
+            .unwrap_or(text.len());
+        self.tree.composition(text, cursor)
+    }
 
-    pub fn render(&mut self, gl: &gl::Gl, target: &ColorBuffer, vp_matrix: &na::Matrix4<f32>) {
+    pub fn render(&mut self, gl: &gl::Gl, target: &ColorBuffer, vp_matrix: &na::Matrix4<f32>, profiler: &FrameProfiler) {
         let flipped_matrix = vp_matrix * na::Matrix4::new_nonuniform_scaling(&[1.0, -1.0, 1.0].into());
+        self.inverse_view_projection = flipped_matrix.try_inverse();
 
-        self.debug_lines.render(gl, target, &flipped_matrix);
-        self.flatlander.render(gl, target, &flipped_matrix);
+        {
+            let _scope = profiler.gpu_scope("gpu.debug_lines");
+            self.debug_lines.render(gl, target, &flipped_matrix);
+        }
+        {
+            let _scope = profiler.gpu_scope("gpu.flatland");
+            self.flatlander.render(gl, target, &flipped_matrix);
+        }
+        {
+            let _scope = profiler.gpu_scope("gpu.sdf_text");
+            self.sdf_text.render(gl, target, &flipped_matrix);
+        }
     }
 
     pub fn toggle_wireframe(&mut self) {
         self.flatlander.toggle_wireframe()
     }
 
+    /// Switches the anti-aliasing fringe off and back on, edges are aliased without it.
+    pub fn toggle_fringe(&mut self) {
+        let fringe_width = if self.flatlander.fringe_width() > 0.0 { 0.0 } else { 1.0 };
+        self.flatlander.set_fringe_width(fringe_width)
+    }
+
     pub fn toggle_bounds(&mut self) {
         self.debug_lines.toggle()
     }
+
+    pub fn toggle_profiler_legend(&mut self) {
+        self.legend_state.borrow_mut().toggle()
+    }
+
+    /// Replaces the profiler legend lines, shown while the legend is toggled on.
+    pub fn set_profiler_legend(&mut self, rows: Vec<controls::profiler_legend::LegendRow>) {
+        self.legend_state.borrow_mut().set_rows(rows)
+    }
+
+    /// Bytes of geometry uploaded to the GPU since the last call.
+    pub fn take_uploaded_bytes(&mut self) -> usize {
+        self.flatlander.take_uploaded_bytes()
+    }
 }
 
-fn ensure_glyph_is_in_alphabet_and_return_index(builder: &mut lyon_path::default::Builder, alphabet: &mut Alphabet, font: &Font, glyph_id: u32) -> usize {
+/// Groups glyphs by font, in order of first use, with positions in buffer font units.
+///
+/// Glyphs are in visual order, advances move the pen and offsets only shift their glyph.
+fn glyphs_by_font(glyphs: &[GlyphPosition]) -> Vec<(usize, Vec<(u32, i32, i32)>)> {
+    let mut groups: Vec<(usize, Vec<(u32, i32, i32)>)> = Vec::new();
+
+    let mut x = 0;
+    let mut y = 0;
+
+    for glyph in glyphs.iter() {
+        let index = match groups.iter().position(|&(font_id, _)| font_id == glyph.font_id) {
+            Some(index) => index,
+            None => {
+                groups.push((glyph.font_id, Vec::new()));
+                groups.len() - 1
+            }
+        };
+        groups[index].1.push((glyph.id, x + glyph.x_offset, y + glyph.y_offset));
+
+        x += glyph.x_advance;
+        y += glyph.y_advance;
+    }
+
+    groups
+}
+
+fn ensure_glyph_is_in_alphabet_and_return_index(builder: &mut lyon_path::default::Builder, alphabet: &mut Alphabet, font: &Font, variations: &[FontVariation], glyph_id: u32, tolerance: f32) -> usize {
     if let Some(index) = alphabet.get_entry_index(glyph_id) {
         return index;
     }
 
-    trace!("tessellate glyph {} from {:?} font", glyph_id, font.full_name());
+    trace!("tessellate glyph {} from {:?} font with tolerance {}", glyph_id, font.full_name(), tolerance);
 
     use lyon_path::builder::{FlatPathBuilder};
     use lyon_tessellation::{BuffersBuilder, FillOptions, FillTessellator, FillVertex, VertexBuffers};
+    use lyon_tessellation::{StrokeOptions, StrokeTessellator, StrokeVertex};
 
-    font.outline(glyph_id, ui::HintingOptions::None, builder).expect("outline failed");
+    font.outline_variation(glyph_id, variations, ui::HintingOptions::None, builder).expect("outline failed");
     let path = builder.build_and_reset();
 
-    // Will contain the result of the tessellation.
-    let mut geometry: VertexBuffers<FlatlanderVertex, u16> = VertexBuffers::new();
-    let mut tessellator = FillTessellator::new();
+    // Will contain the result of the tessellation. Indices that do not fit the index
+    // width of the alphabet are split into several parts by the alphabet.
+    let mut geometry: VertexBuffers<FlatlanderVertex, u32> = VertexBuffers::new();
+
+    let result = FillTessellator::new().tessellate_path(
+        path.path_iter(),
+        &FillOptions::default().with_tolerance(tolerance),
+        &mut BuffersBuilder::new(&mut geometry, |vertex: FillVertex| {
+            FlatlanderVertex {
+                pos: data::f32_f32::from((vertex.position.x, vertex.position.y)),
+                normal: data::f16_f16::from((0.0, 0.0)),
+                color: (255, 255, 255, 255).into(),
+                paint: FlatlanderPaint::Solid.vertex_paint(0.0, 0.0),
+            }
+        }),
+    );
+
+    match result {
+        Ok(_) => {
+            let (fill_vertices, fill_indices) = (geometry.vertices.len(), geometry.indices.len());
+
+            // Zero width stroke along the outline becomes the anti-aliasing fringe:
+            // the shader extrudes its vertices along the normal in screen space.
+            let result = StrokeTessellator::new().tessellate_path(
+                path.path_iter(),
+                &StrokeOptions::tolerance(tolerance).dont_apply_line_width(),
+                &mut BuffersBuilder::new(&mut geometry, |vertex: StrokeVertex| {
+                    FlatlanderVertex {
+                        pos: data::f32_f32::from((vertex.position.x, vertex.position.y)),
+                        normal: data::f16_f16::from((vertex.normal.x, vertex.normal.y)),
+                        color: (255, 255, 255, 255).into(),
+                        paint: FlatlanderPaint::Solid.vertex_paint(0.0, 0.0),
+                    }
+                }),
+            );
+
+            if let Err(e) = result {
+                warn!("failed to tessellate fringe of glyph {} from {:?} font: {:?}", glyph_id, font.full_name(), e);
+                geometry.vertices.truncate(fill_vertices);
+                geometry.indices.truncate(fill_indices);
+            }
+        },
+        Err(e) => {
+            // the empty entry keeps the glyph from being tessellated again
+            warn!("failed to tessellate glyph {} from {:?} font: {:?}", glyph_id, font.full_name(), e);
+            geometry = VertexBuffers::new();
+        },
+    }
+
+    alphabet.add_entry(glyph_id, geometry.vertices, geometry.indices)
+}
+
+/// Clip of everything inside the node, including the node box itself if the node clips.
+fn node_clip(
+    id: Ix,
+    node_parents: &MetroHashMap<Ix, Option<Ix>>,
+    clipping_nodes: &MetroHashSet<Ix>,
+    controls: &MetroHashMap<ControlId, ControlInfo>,
+    node_clips: &mut MetroHashMap<Ix, Option<Clip>>,
+) -> Option<Clip> {
+    if let Some(clip) = node_clips.get(&id) {
+        return clip.clone();
+    }
+
+    let parent_clip = match node_parents.get(&id) {
+        Some(&Some(parent_id)) => node_clip(parent_id, node_parents, clipping_nodes, controls, node_clips),
+        _ => None,
+    };
+
+    let node_box = if clipping_nodes.contains(&id) {
+        controls.get(&ControlId::Node(id)).and_then(|c| match (c.size, c.absolute_transform) {
+            (Some((w, h)), Some(t)) => Some((t, na::Vector2::new(w as f32, h as f32))),
+            _ => None,
+        })
+    } else {
+        None
+    };
+
+    let clip = match (parent_clip, node_box) {
+        (Some(parent_clip), Some((t, size))) => Some(parent_clip.nested(&t, size)),
+        (None, Some((t, size))) => Some(Clip::new(&t, size)),
+        (parent_clip, None) => parent_clip,
+    };
+
+    node_clips.insert(id, clip.clone());
+    clip
+}
+
+/// Opacity of everything inside the node, including the node itself.
+fn node_opacity(
+    id: Ix,
+    node_parents: &MetroHashMap<Ix, Option<Ix>>,
+    node_opacities: &MetroHashMap<Ix, f32>,
+    opacities: &mut MetroHashMap<Ix, f32>,
+) -> f32 {
+    if let Some(opacity) = opacities.get(&id) {
+        return *opacity;
+    }
+
+    let parent_opacity = match node_parents.get(&id) {
+        Some(&Some(parent_id)) => node_opacity(parent_id, node_parents, node_opacities, opacities),
+        _ => 1.0,
+    };
+
+    let opacity = parent_opacity * node_opacities.get(&id).cloned().unwrap_or(1.0);
+
+    opacities.insert(id, opacity);
+    opacity
+}
+
+/// Color with the alpha multiplied by the opacity.
+fn faded(color: na::Vector4<u8>, opacity: f32) -> na::Vector4<u8> {
+    let opacity = na::clamp(opacity, 0.0, 1.0);
+    na::Vector4::new(color.x, color.y, color.z, (color.w as f32 * opacity).round() as u8)
+}
+
+/// Drops the cached tessellation when the last shape using it is removed.
+fn release_cached_shape(shape_cache: &mut MetroHashMap<u64, CachedShape>, hash: u64) {
+    let unused = match shape_cache.get_mut(&hash) {
+        Some(cached) => {
+            cached.users -= 1;
+            cached.users == 0
+        },
+        None => false,
+    };
+    if unused {
+        shape_cache.remove(&hash);
+    }
+}
+
+/// Maximum allowed distance between the shape outline and its tessellation, in shape units.
+const SHAPE_TESSELLATION_TOLERANCE: f32 = 0.1;
 
-    {
-        // Compute the tessellation.
-        tessellator.tessellate_path(
+/// Tessellates shape fill and stroke into a single entry of a new alphabet.
+///
+/// Returned gradients must live as long as the alphabet.
+fn tessellate_shape(flatlander: &Flatlander, shape: &shapes::ShapeData) -> (Alphabet, Vec<Gradient>) {
+    use lyon_tessellation::{BuffersBuilder, FillOptions, FillTessellator, FillVertex, VertexBuffers};
+    use lyon_tessellation::{Side, StrokeOptions, StrokeTessellator, StrokeVertex};
+    use lyon_tessellation::math::vector;
+
+    let path = shape.path();
+    let style = shape.style();
+    let tolerance = SHAPE_TESSELLATION_TOLERANCE;
+
+    let fill_gradient = style.fill.as_ref().and_then(|paint| create_gradient(flatlander, paint));
+    let stroke_gradient = style.stroke.as_ref().and_then(|(paint, _)| create_gradient(flatlander, paint));
+
+    // Flatland group transform flips y for glyph outlines, but shapes are in ui
+    // coordinates, so their vertices are flipped in advance.
+    let mut geometry: VertexBuffers<FlatlanderVertex, u32> = VertexBuffers::new();
+
+    if let Some(ref paint) = style.fill {
+        let (color, paint) = flatlander_paint(paint, fill_gradient.as_ref());
+
+        let result = FillTessellator::new().tessellate_path(
             path.path_iter(),
-            &FillOptions::default().with_tolerance(10.0),
+            &FillOptions::default().with_tolerance(tolerance),
             &mut BuffersBuilder::new(&mut geometry, |vertex: FillVertex| {
                 FlatlanderVertex {
-                    pos: data::f16_f16::from((vertex.position.x, vertex.position.y)),
-                    normal: data::f16_f16::from((vertex.normal.x, vertex.normal.y)),
+                    pos: data::f32_f32::from((vertex.position.x, -vertex.position.y)),
+                    normal: data::f16_f16::from((0.0, 0.0)),
+                    color: (color.x, color.y, color.z, color.w).into(),
+                    paint: paint.vertex_paint(vertex.position.x, vertex.position.y),
+                }
+            }),
+        );
+
+        if let Err(e) = result {
+            warn!("failed to tessellate fill of shape {}: {:?}", shape.id(), e);
+        }
+
+        // anti-aliasing fringe, same as for glyphs
+        let result = StrokeTessellator::new().tessellate_path(
+            path.path_iter(),
+            &StrokeOptions::tolerance(tolerance).dont_apply_line_width(),
+            &mut BuffersBuilder::new(&mut geometry, |vertex: StrokeVertex| {
+                FlatlanderVertex {
+                    pos: data::f32_f32::from((vertex.position.x, -vertex.position.y)),
+                    normal: data::f16_f16::from((vertex.normal.x, -vertex.normal.y)),
+                    color: (color.x, color.y, color.z, color.w).into(),
+                    paint: paint.vertex_paint(vertex.position.x, vertex.position.y),
                 }
             }),
-        ).unwrap();
+        );
+
+        if let Err(e) = result {
+            warn!("failed to tessellate fill fringe of shape {}: {:?}", shape.id(), e);
+        }
     }
 
-    alphabet.add_entry(glyph_id, geometry.vertices, geometry.indices)
+    if let Some((ref paint, stroke)) = style.stroke {
+        let (color, paint) = flatlander_paint(paint, stroke_gradient.as_ref());
+        let half_width = stroke.width / 2.0;
+
+        // The stroke is tessellated with zero width, vertices are moved out along their
+        // normals here. Body vertices are moved to their own side of the stroke. For the
+        // fringe of each edge, the vertices of both sides are moved to that edge, which
+        // gives a zero width stroke along the edge that the shader extrudes like the
+        // fringe of a fill. Ends of butt capped open strokes get no fringe.
+        let edges = [None, Some(Side::Left), Some(Side::Right)];
+
+        for &edge in edges.iter() {
+            let result = StrokeTessellator::new().tessellate_path(
+                path.path_iter(),
+                &StrokeOptions::tolerance(tolerance)
+                    .with_line_width(stroke.width)
+                    .with_line_cap(stroke.cap)
+                    .with_line_join(stroke.join)
+                    .dont_apply_line_width(),
+                &mut BuffersBuilder::new(&mut geometry, |vertex: StrokeVertex| {
+                    let (offset, normal) = match edge {
+                        None => (vertex.normal, vector(0.0, 0.0)),
+                        Some(side) if side == vertex.side => (vertex.normal, vertex.normal),
+                        Some(_) => (-vertex.normal, vertex.normal),
+                    };
+                    let position = vertex.position + offset * half_width;
+
+                    FlatlanderVertex {
+                        pos: data::f32_f32::from((position.x, -position.y)),
+                        normal: data::f16_f16::from((normal.x, -normal.y)),
+                        color: (color.x, color.y, color.z, color.w).into(),
+                        paint: paint.vertex_paint(position.x, position.y),
+                    }
+                }),
+            );
+
+            if let Err(e) = result {
+                warn!("failed to tessellate stroke of shape {}: {:?}", shape.id(), e);
+                break;
+            }
+        }
+    }
+
+    // large shapes would be split into several draw commands with 16 bit indices
+    let index_width = if geometry.vertices.len() > IndexWidth::U16.max_vertices() {
+        IndexWidth::U32
+    } else {
+        IndexWidth::U16
+    };
+
+    let alphabet = flatlander.create_alphabet_with_index_width(index_width);
+    alphabet.add_entry(0, geometry.vertices, geometry.indices);
+
+    (alphabet, fill_gradient.into_iter().chain(stroke_gradient).collect())
+}
+
+fn create_gradient(flatlander: &Flatlander, paint: &Paint) -> Option<Gradient> {
+    let stops = match *paint {
+        Paint::Color(_) => return None,
+        Paint::LinearGradient { ref stops, .. } => stops,
+        Paint::RadialGradient { ref stops, .. } => stops,
+    };
+
+    flatlander.create_gradient(
+        &stops.iter()
+            .map(|s| crate::render_gl::GradientStop { offset: s.offset, color: s.color })
+            .collect::<Vec<_>>()
+    )
+}
+
+fn flatlander_paint<'a>(paint: &Paint, gradient: Option<&'a Gradient>) -> (na::Vector4<u8>, FlatlanderPaint<'a>) {
+    let white = [255, 255, 255, 255].into();
+
+    match (paint, gradient) {
+        (&Paint::Color(color), _) => (color, FlatlanderPaint::Solid),
+        (&Paint::LinearGradient { start, end, .. }, Some(gradient)) => {
+            (white, FlatlanderPaint::LinearGradient { gradient, start, end })
+        }
+        (&Paint::RadialGradient { center, radius, .. }, Some(gradient)) => {
+            (white, FlatlanderPaint::RadialGradient { gradient, center, radius })
+        }
+        // gradient could not be created, fall back to its first color
+        (&Paint::LinearGradient { ref stops, .. }, None) | (&Paint::RadialGradient { ref stops, .. }, None) => {
+            (stops.first().map(|s| s.color).unwrap_or(white), FlatlanderPaint::Solid)
+        }
+    }
+}
+
+fn ensure_glyph_is_in_sdf_atlas(builder: &mut lyon_path::default::Builder, sdf_text: &SdfTextRenderer, font: &Font, font_key: SdfFontKey, variations: &[FontVariation], units_per_em: u32, glyph_id: u32) {
+    if sdf_text.has_glyph(font_key, glyph_id) {
+        return;
+    }
+
+    trace!("generate distance field for glyph {} from {:?} font", glyph_id, font.full_name());
+
+    use lyon_path::builder::{FlatPathBuilder};
+
+    font.outline_variation(glyph_id, variations, ui::HintingOptions::None, builder).expect("outline failed");
+    let path = builder.build_and_reset();
+
+    sdf_text.add_glyph(font_key, glyph_id, units_per_em, &path);
+}
+
+fn sdf_effects(style: &SdfTextStyle) -> SdfTextEffects {
+    fn color(c: na::Vector4<u8>) -> na::Vector4<f32> {
+        na::Vector4::new(c.x as f32 / 255.0, c.y as f32 / 255.0, c.z as f32 / 255.0, c.w as f32 / 255.0)
+    }
+
+    SdfTextEffects {
+        outline_width: style.outline_width,
+        outline_color: color(style.outline_color),
+        // glyph outlines have y going up, the ui has y going down
+        shadow_offset: na::Vector2::new(style.shadow_offset.x, -style.shadow_offset.y),
+        shadow_softness: style.shadow_softness,
+        shadow_color: color(style.shadow_color),
+        glow_radius: style.glow_radius,
+        glow_color: color(style.glow_color),
+    }
 }
\ No newline at end of file
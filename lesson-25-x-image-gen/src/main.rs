@@ -48,6 +48,15 @@ fn run() -> Result<(), failure::Error> {
         "core",
         0,
         resources::backend::FileSystem::from_rel_path(env!("CARGO_MANIFEST_DIR"), "core")
+            .with_write()
+            .with_watch(),
+    );
+
+    // captured data is written outside of watched resources, so that it does not trigger reloads
+    let captures = resources::Resources::new().loaded_from(
+        "captures",
+        0,
+        resources::backend::FileSystem::from_rel_path(env!("CARGO_MANIFEST_DIR"), "captures")
             .with_write(),
     );
 
@@ -59,8 +68,7 @@ fn run() -> Result<(), failure::Error> {
     gl_attr.set_context_version(4, 1);
     gl_attr.set_accelerated_visual(true);
     gl_attr.set_double_buffer(true);
-    gl_attr.set_multisample_buffers(1);
-    gl_attr.set_multisample_samples(16);
+    gl_attr.set_stencil_size(8);
 
     let mut window_size = render::WindowSize {
 //        width: 1800,
@@ -116,11 +124,18 @@ fn run() -> Result<(), failure::Error> {
     allocation_profiler.toggle();
     let mut gl_call_profiler = render_gl::EventCountProfiler::new(&gl, &resources, 1, 20)?;
     gl_call_profiler.toggle();
+    let mut upload_profiler = render_gl::EventCountProfiler::new(&gl, &resources, 1, 40)?;
+    upload_profiler.toggle();
+
+    let mut backdrop = render_gl::Backdrop::new(&gl, &resources, "images/backdrop.png")?;
 
     let mut viewport =
         render_gl::Viewport::for_window(window_size.highdpi_width, window_size.highdpi_height);
     let color_buffer = render_gl::ColorBuffer::new();
 
+    // scene is drawn offscreen, so that it can be sampled, for example by slide transitions
+    let mut scene = render_gl::Framebuffer::new_color_depth(&gl, viewport.w, viewport.h, 1)?;
+
     // set up shared state for window
 
     let mut camera = camera::TargetCamera::new(
@@ -135,7 +150,6 @@ fn run() -> Result<(), failure::Error> {
 
     viewport.set_used(&gl);
     color_buffer.set_clear_color(&gl, na::Vector3::new(1.0, 1.0, 1.0));
-    color_buffer.enable_multisample(&gl);
 
     let mut iface_auto_size = false;
     let mut iface = Interface::new(
@@ -148,12 +162,14 @@ fn run() -> Result<(), failure::Error> {
         scale * scale_modifier
     )?;
     iface.toggle_bounds();
+    iface.set_clipboard(system::input::keyboard::SdlClipboard::new(video_subsystem.clipboard()));
 
     let mut perspective_view = false;
 
     // main loop
 
     let mut time = Instant::now();
+    let mut legend_time = Instant::now();
 
     let mut event_pump = sdl.event_pump().map_err(err_msg)?;
     'main: loop {
@@ -163,6 +179,9 @@ fn run() -> Result<(), failure::Error> {
         frame_profiler.begin();
         allocation_profiler.begin();
         gl_call_profiler.begin();
+        upload_profiler.begin();
+
+        let events_scope = frame_profiler.scope("events");
 
         for event in event_pump.poll_iter() {
             if system::input::window::handle_default_window_events(
@@ -176,10 +195,45 @@ fn run() -> Result<(), failure::Error> {
             {
                 break 'main;
             }
-            system::input::camera::handle_camera_events(&event, &mut camera);
 
             use sdl2::event::Event;
             use sdl2::keyboard::Scancode;
+            use sdl2::mouse::MouseButton;
+            use crate::system::input::keyboard::{to_ui_key, to_ui_modifiers};
+
+            // pointer position in normalized device coordinates
+            let ndc = |x: i32, y: i32| (
+                2.0 * x as f32 / window_size.width as f32 - 1.0,
+                1.0 - 2.0 * y as f32 / window_size.height as f32,
+            );
+            let modifiers = to_ui_modifiers(sdl.keyboard().mod_state());
+
+            let handled_by_iface = match event {
+                Event::KeyDown { keycode: Some(keycode), keymod, .. } => {
+                    iface.key_down(to_ui_key(keycode), to_ui_modifiers(keymod))
+                }
+                Event::TextInput { ref text, .. } => iface.text_input(text),
+                Event::TextEditing { ref text, start, .. } => iface.text_editing(text, start.max(0) as usize),
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, clicks, x, y, .. } => {
+                    let (x, y) = ndc(x, y);
+                    iface.mouse_down(x, y, clicks, modifiers)
+                }
+                Event::MouseMotion { x, y, .. } => {
+                    let (x, y) = ndc(x, y);
+                    iface.mouse_move(x, y, modifiers)
+                }
+                Event::MouseButtonUp { mouse_btn: MouseButton::Left, x, y, .. } => {
+                    let (x, y) = ndc(x, y);
+                    iface.mouse_up(x, y, modifiers)
+                }
+                _ => false,
+            };
+
+            if handled_by_iface {
+                continue;
+            }
+
+            system::input::camera::handle_camera_events(&event, &mut camera);
 
             let iface_resize = match event {
                 Event::Window {
@@ -187,6 +241,9 @@ fn run() -> Result<(), failure::Error> {
                     ..
                 } => {
                     scale = window_size.highdpi_width as f32 / window_size.width as f32;
+                    if let Err(e) = scene.resize_to_viewport(&viewport) {
+                        error!("failed to resize scene framebuffer: {}", e);
+                    }
                     true
                 }
                 Event::KeyDown {
@@ -203,6 +260,13 @@ fn run() -> Result<(), failure::Error> {
                     iface.toggle_wireframe();
                     false
                 }
+                Event::KeyDown {
+                    scancode: Some(Scancode::G),
+                    ..
+                } => {
+                    iface.toggle_fringe();
+                    false
+                }
                 Event::KeyDown {
                     scancode: Some(Scancode::C),
                     ..
@@ -232,26 +296,31 @@ fn run() -> Result<(), failure::Error> {
                     true
                 }
                 Event::KeyDown {
-                    scancode: Some(Scancode::Left),
+                    scancode: Some(Scancode::K),
                     ..
                 } => {
-                    iface.send_action(ui::UiAction::PreviousSlide);
+                    backdrop.toggle();
                     false
                 }
                 Event::KeyDown {
-                    scancode: Some(Scancode::Right),
+                    scancode: Some(Scancode::P),
                     ..
                 } => {
-                    iface.send_action(ui::UiAction::NextSlide);
+                    frame_profiler.toggle();
+                    allocation_profiler.toggle();
+                    gl_call_profiler.toggle();
+                    upload_profiler.toggle();
+                    iface.toggle_profiler_legend();
                     false
                 }
                 Event::KeyDown {
-                    scancode: Some(Scancode::P),
+                    scancode: Some(Scancode::X),
                     ..
                 } => {
-                    frame_profiler.toggle();
-                    allocation_profiler.toggle();
-                    gl_call_profiler.toggle();
+                    match frame_profiler.export_chrome_trace(&captures, "frame_trace.json") {
+                        Ok(()) => info!("exported frame trace to captures/frame_trace.json"),
+                        Err(e) => error!("failed to export frame trace: {}", e),
+                    }
                     false
                 }
                 Event::KeyDown {
@@ -280,15 +349,30 @@ fn run() -> Result<(), failure::Error> {
             }
         }
 
-        frame_profiler.push(render::color_black());
+        drop(events_scope);
+
+        let update_scope = frame_profiler.scope("update");
 
         let delta = time.elapsed().as_fractional_secs() as f32;
         time = Instant::now();
 
         camera.update(delta);
-        iface.update(delta);
 
-        frame_profiler.push(render::color_blue());
+        {
+            let _scope = frame_profiler.scope("ui.update");
+            let resource_changes = resources.new_changes();
+            iface.update(delta);
+            if let Some(sync_point) = resource_changes {
+                backdrop.reload_if_modified();
+                resources.notify_changes_synced(sync_point);
+            }
+        }
+
+        drop(update_scope);
+
+        let render_scope = frame_profiler.scope("render");
+
+        scene.bind_as_target();
 
         unsafe {
             gl.Enable(gl::CULL_FACE);
@@ -298,6 +382,8 @@ fn run() -> Result<(), failure::Error> {
 
         color_buffer.clear(&gl);
 
+        backdrop.render();
+
         let ui_matrix = if !perspective_view {
             let left = 0;
             let top = 0;
@@ -316,9 +402,23 @@ fn run() -> Result<(), failure::Error> {
             camera.get_vp_matrix()
         };
 
-        iface.render(&gl, &color_buffer, &ui_matrix);
+        {
+            let _scope = frame_profiler.scope("ui.render");
+            let _gpu_scope = frame_profiler.gpu_scope("gpu.ui");
+            iface.render(&gl, &color_buffer, &ui_matrix, &frame_profiler);
+        }
+
+        scene.unbind();
+        viewport.set_used(&gl);
+        scene.blit_to_default(viewport.w, viewport.h);
 
-        frame_profiler.push(render::color_red());
+        unsafe {
+            gl.Clear(gl::DEPTH_BUFFER_BIT);
+        }
+
+        drop(render_scope);
+
+        let profilers_scope = frame_profiler.scope("profilers");
 
         let left = 0;
         let top = window_size.highdpi_height;
@@ -334,6 +434,8 @@ fn run() -> Result<(), failure::Error> {
             10.0,
         );
 
+        let profilers_gpu_scope = frame_profiler.gpu_scope("gpu.profilers");
+
         frame_profiler.render(
             &gl,
             &color_buffer,
@@ -343,8 +445,36 @@ fn run() -> Result<(), failure::Error> {
         );
         allocation_profiler.render(&gl, &color_buffer, &ui_matrix, window_size.highdpi_width);
         gl_call_profiler.render(&gl, &color_buffer, &ui_matrix, window_size.highdpi_width);
+        upload_profiler.render(&gl, &color_buffer, &ui_matrix, window_size.highdpi_width);
+
+        drop(profilers_gpu_scope);
+
+        if legend_time.elapsed() >= Duration::from_millis(500) {
+            legend_time = Instant::now();
+            iface.set_profiler_legend(
+                frame_profiler.scope_stats().iter()
+                    .chain(frame_profiler.gpu_scope_stats().iter())
+                    .map(|s| interface::controls::profiler_legend::LegendRow {
+                        label: format!(
+                            "{:indent$}{:<width$} min {:>6.2} avg {:>6.2} max {:>6.2} ms",
+                            "", s.name, s.min_ms, s.avg_ms, s.max_ms,
+                            indent = s.depth * 2,
+                            width = 18 - (s.depth * 2).min(18),
+                        ),
+                        color: [
+                            (s.color.x * 255.0) as u8,
+                            (s.color.y * 255.0) as u8,
+                            (s.color.z * 255.0) as u8,
+                            255,
+                        ].into(),
+                    })
+                    .collect()
+            );
+        }
+
+        drop(profilers_scope);
 
-        frame_profiler.push(render::color_green());
+        let wait_scope = frame_profiler.scope("wait");
 
         while time.elapsed() < Duration::from_millis(12) {
             ::std::thread::yield_now()
@@ -371,13 +501,22 @@ fn run() -> Result<(), failure::Error> {
             gl_call_profiler.push(gl_call_c, render::color_light_blue());
         }
 
+        // kilobytes, rounded up so that small uploads are still visible
+        let uploaded_kb = (iface.take_uploaded_bytes() + 1023) / 1024;
+        if uploaded_kb > 0 {
+            upload_profiler.push(uploaded_kb, render::color_yellow());
+        }
+
         while time.elapsed() < Duration::from_millis(24) {
             ::std::thread::yield_now()
         }
 
-        window.gl_swap_window();
+        drop(wait_scope);
 
-        frame_profiler.push(render::color_orange());
+        {
+            let _scope = frame_profiler.scope("swap");
+            window.gl_swap_window();
+        }
     }
 
     Ok(())
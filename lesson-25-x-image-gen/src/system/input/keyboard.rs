@@ -0,0 +1,62 @@
+use sdl2::clipboard::ClipboardUtil;
+use sdl2::keyboard::{self, Keycode, Mod};
+use ui;
+
+pub fn to_ui_key(keycode: Keycode) -> ui::Key {
+    match keycode {
+        Keycode::Left => ui::Key::Left,
+        Keycode::Right => ui::Key::Right,
+        Keycode::Up => ui::Key::Up,
+        Keycode::Down => ui::Key::Down,
+        Keycode::Home => ui::Key::Home,
+        Keycode::End => ui::Key::End,
+        Keycode::PageUp => ui::Key::PageUp,
+        Keycode::PageDown => ui::Key::PageDown,
+        Keycode::Backspace => ui::Key::Backspace,
+        Keycode::Delete => ui::Key::Delete,
+        Keycode::Return | Keycode::KpEnter => ui::Key::Enter,
+        Keycode::Escape => ui::Key::Escape,
+        Keycode::Tab => ui::Key::Tab,
+        Keycode::A => ui::Key::A,
+        Keycode::C => ui::Key::C,
+        Keycode::V => ui::Key::V,
+        Keycode::X => ui::Key::X,
+        Keycode::Y => ui::Key::Y,
+        Keycode::Z => ui::Key::Z,
+        _ => ui::Key::Other,
+    }
+}
+
+pub fn to_ui_modifiers(keymod: Mod) -> ui::Modifiers {
+    ui::Modifiers::new()
+        .shift(keymod.intersects(keyboard::LSHIFTMOD | keyboard::RSHIFTMOD))
+        .ctrl(keymod.intersects(keyboard::LCTRLMOD | keyboard::RCTRLMOD | keyboard::LGUIMOD | keyboard::RGUIMOD))
+        .alt(keymod.intersects(keyboard::LALTMOD | keyboard::RALTMOD))
+}
+
+/// System clipboard for the ui.
+pub struct SdlClipboard {
+    clipboard: ClipboardUtil,
+}
+
+impl SdlClipboard {
+    pub fn new(clipboard: ClipboardUtil) -> SdlClipboard {
+        SdlClipboard { clipboard }
+    }
+}
+
+impl ui::Clipboard for SdlClipboard {
+    fn text(&self) -> Option<String> {
+        if !self.clipboard.has_clipboard_text() {
+            return None;
+        }
+
+        self.clipboard.clipboard_text().ok()
+    }
+
+    fn set_text(&self, text: &str) {
+        if let Err(e) = self.clipboard.set_clipboard_text(text) {
+            error!("failed to set clipboard text: {}", e);
+        }
+    }
+}
@@ -1,2 +1,3 @@
 pub mod camera;
+pub mod keyboard;
 pub mod window;
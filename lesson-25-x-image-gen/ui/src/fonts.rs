@@ -8,6 +8,189 @@ pub use font_kit::error::GlyphLoadingError;
 pub use font_kit::metrics::Metrics;
 pub use self::shared::GlyphPosition;
 use lyon_path::builder::PathBuilder;
+use resources::Resources;
+use crate::text::Direction;
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load font resource {}", name)]
+    ResourceLoad { name: String, #[cause] inner: resources::Error },
+    #[fail(display = "Failed to load font {}: {}", name, message)]
+    Load { name: String, message: String },
+}
+
+/// How glyphs of a text buffer are drawn.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextRenderer {
+    /// Glyph outlines are tessellated into triangles.
+    Tessellated,
+    /// Glyphs are drawn from a signed distance field atlas, which stays sharp when scaled
+    /// and supports outline, shadow and glow.
+    Sdf(SdfTextStyle),
+}
+
+impl Default for TextRenderer {
+    fn default() -> Self {
+        TextRenderer::Tessellated
+    }
+}
+
+/// How text of a buffer is split into runs and shaped.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShapingOptions {
+    /// Base direction of paragraphs, runs in the other direction are reordered.
+    pub direction: Direction,
+    /// Places glyphs from top to bottom, for CJK text. Bidirectional reordering is skipped.
+    pub vertical: bool,
+    /// BCP 47 language tag, like "ja" or "sr", for language specific glyph forms.
+    pub language: Option<String>,
+    /// OpenType features turned on or off for the whole text, in addition to the defaults.
+    pub features: Vec<FontFeature>,
+    /// Axis values of variable fonts, axes a font does not have are ignored.
+    ///
+    /// Glyph outlines and advances follow the variations, but harfbuzz_rs 0.3 can not set
+    /// variations on its fonts: glyph substitutions and positioning such as kerning are
+    /// those of the default instance.
+    pub variations: Vec<FontVariation>,
+}
+
+impl ShapingOptions {
+    pub fn new() -> ShapingOptions {
+        ShapingOptions::default()
+    }
+
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn vertical(mut self, vertical: bool) -> Self {
+        self.vertical = vertical;
+        self
+    }
+
+    pub fn language<P: ToString>(mut self, language: P) -> Self {
+        self.language = Some(language.to_string());
+        self
+    }
+
+    /// Sets an OpenType feature, like `feature("liga", 0)` or `feature("ss01", 1)`.
+    pub fn feature(mut self, tag: &str, value: u32) -> Self {
+        let feature = FontFeature::new(tag, value);
+        self.features.retain(|f| f.tag != feature.tag);
+        self.features.push(feature);
+        self
+    }
+
+    /// Sets a variable font axis, like `variation("wght", 650.0)`.
+    pub fn variation(mut self, tag: &str, value: f32) -> Self {
+        let variation = FontVariation::new(tag, value);
+        self.variations.retain(|v| v.tag != variation.tag);
+        self.variations.push(variation);
+        self
+    }
+
+    /// Identifies glyph outlines of the variations, 0 for default instances.
+    pub fn instance_key(&self) -> u64 {
+        use std::hash::Hasher;
+        use metrohash::MetroHash64;
+
+        if self.variations.is_empty() {
+            return 0;
+        }
+
+        let mut variations = self.variations.clone();
+        variations.sort_by_key(|v| v.tag);
+
+        let mut hasher = MetroHash64::new();
+        for variation in variations {
+            hasher.write(&variation.tag);
+            hasher.write_u32(variation.value.to_bits());
+        }
+        hasher.finish().max(1)
+    }
+}
+
+/// OpenType feature with a value, 0 turns it off, 1 turns it on, higher values pick alternates.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FontFeature {
+    pub tag: [u8; 4],
+    pub value: u32,
+}
+
+impl FontFeature {
+    pub fn new(tag: &str, value: u32) -> FontFeature {
+        FontFeature { tag: tag_bytes(tag), value }
+    }
+}
+
+/// Value of a variable font axis, like weight "wght", width "wdth" or optical size "opsz".
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FontVariation {
+    pub tag: [u8; 4],
+    pub value: f32,
+}
+
+impl FontVariation {
+    pub fn new(tag: &str, value: f32) -> FontVariation {
+        FontVariation { tag: tag_bytes(tag), value }
+    }
+}
+
+/// OpenType tag of up to 4 characters, padded with spaces.
+fn tag_bytes(tag: &str) -> [u8; 4] {
+    let mut bytes = [b' '; 4];
+    for (byte, c) in bytes.iter_mut().zip(tag.bytes()) {
+        *byte = c;
+    }
+    bytes
+}
+
+/// Effects of the `TextRenderer::Sdf` renderer. Sizes are in ems.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SdfTextStyle {
+    pub outline_width: f32,
+    pub outline_color: na::Vector4<u8>,
+    /// Shadow offset, `y` goes down.
+    pub shadow_offset: na::Vector2<f32>,
+    pub shadow_softness: f32,
+    pub shadow_color: na::Vector4<u8>,
+    pub glow_radius: f32,
+    pub glow_color: na::Vector4<u8>,
+}
+
+impl SdfTextStyle {
+    pub fn new() -> SdfTextStyle {
+        SdfTextStyle {
+            outline_width: 0.0,
+            outline_color: [0, 0, 0, 0].into(),
+            shadow_offset: na::zero(),
+            shadow_softness: 0.0,
+            shadow_color: [0, 0, 0, 0].into(),
+            glow_radius: 0.0,
+            glow_color: [0, 0, 0, 0].into(),
+        }
+    }
+
+    pub fn with_outline(mut self, width: f32, color: na::Vector4<u8>) -> Self {
+        self.outline_width = width;
+        self.outline_color = color;
+        self
+    }
+
+    pub fn with_shadow(mut self, x: f32, y: f32, softness: f32, color: na::Vector4<u8>) -> Self {
+        self.shadow_offset = na::Vector2::new(x, y);
+        self.shadow_softness = softness;
+        self.shadow_color = color;
+        self
+    }
+
+    pub fn with_glow(mut self, radius: f32, color: na::Vector4<u8>) -> Self {
+        self.glow_radius = radius;
+        self.glow_color = color;
+        self
+    }
+}
 
 #[derive(Clone)]
 pub struct Fonts {
@@ -15,18 +198,67 @@ pub struct Fonts {
 }
 
 impl Fonts {
-    pub fn new() -> Fonts {
+    pub fn new(resources: &Resources) -> Fonts {
         Fonts {
-            container: Rc::new(RefCell::new(shared::FontsContainer::new())),
+            container: Rc::new(RefCell::new(shared::FontsContainer::new(resources))),
         }
     }
 
+    /// Loads a font file from resources and registers its family, so that `find_best_match`
+    /// prefers it over system fonts with the same family name.
+    ///
+    /// The font is reloaded by `reload_modified` when the resource changes.
+    pub fn load_from_resource(&self, path: &str) -> Result<Font, Error> {
+        let id = self.container.borrow_mut().load_from_resource(path)?;
+
+        Ok(Font {
+            id,
+            fallbacks: Vec::new(),
+            container: self.container.clone(),
+        })
+    }
+
+    /// Loads a font from bytes in memory and registers its family, see `load_from_resource`.
+    pub fn load_from_memory(&self, name: &str, bytes: Vec<u8>, font_index: u32) -> Result<Font, Error> {
+        let id = self.container.borrow_mut().load_from_memory(name, bytes, font_index)?;
+
+        Ok(Font {
+            id,
+            fallbacks: Vec::new(),
+            container: self.container.clone(),
+        })
+    }
+
+    /// Makes `find_best_match` try `substitutes` in order when it looks up the family `name`,
+    /// before falling back to system fonts of that name.
+    pub fn set_family_alias(&self, name: FamilyName, substitutes: Vec<FamilyName>) {
+        self.container.borrow_mut().set_family_alias(&name, substitutes);
+    }
+
+    /// Sets families used for characters missing in fonts found for the family `name`.
+    ///
+    /// Fallback fonts are matched with the same properties as the font itself, so bold
+    /// text falls back to bold fonts. Families that can not be found are skipped.
+    pub fn set_fallback_chain(&self, name: FamilyName, fallbacks: Vec<FamilyName>) {
+        self.container.borrow_mut().set_fallback_chain(&name, fallbacks);
+    }
+
+    /// Reloads fonts whose resources changed and returns their ids.
+    ///
+    /// Font ids stay the same, text buffers of reloaded fonts are shaped again.
+    pub fn reload_modified(&self) -> Vec<usize> {
+        self.container.borrow_mut().reload_modified()
+    }
+
+    /// Finds the font for the first available family, together with its fallback fonts,
+    /// see `set_fallback_chain`.
     pub fn find_best_match(&self, family_names: &[FamilyName], properties: &Properties) -> Option<Font> {
         let mut shared = self.container.borrow_mut();
 
         shared.find_best_match(family_names, properties)
-            .map(|id| Font {
+            .map(|(id, fallbacks)| Font {
                 id,
+                fallbacks,
                 container: self.container.clone(),
             })
     }
@@ -37,6 +269,7 @@ impl Fonts {
         Some(Font {
             container: self.container.clone(),
             id: shared.get_and_inc_font(id)?,
+            fallbacks: Vec::new(),
         })
     }
 
@@ -49,6 +282,7 @@ impl Fonts {
             _font: Font {
                 container: self.container.clone(),
                 id: shared.get_and_inc_font(font_id)?,
+                fallbacks: Vec::new(),
             },
             _id: buffer_id,
         })
@@ -59,6 +293,8 @@ impl Fonts {
 
 pub struct Font {
     id: usize,
+    /// Fonts used for characters this font does not have, in order.
+    fallbacks: Vec<usize>,
     container: Rc<RefCell<shared::FontsContainer>>,
 }
 
@@ -86,6 +322,27 @@ impl Font {
             .fk_font.outline(glyph_id, hinting, path_builder)
     }
 
+    /// Outline of the glyph at variable font axis values.
+    ///
+    /// Fonts without variations, and variable fonts with CFF2 outlines, are drawn with
+    /// their default instance.
+    pub fn outline_variation<B>(&self, glyph_id: u32, variations: &[FontVariation], hinting: HintingOptions, path_builder: &mut B)
+                                -> Result<(), GlyphLoadingError>
+        where B: PathBuilder {
+        let shared = self.container.borrow();
+        let data = shared.get(self.id)
+            .expect("outline_variation: loaded font should exist");
+
+        if let Some(ref variable) = data.variable {
+            let coords = variable.normalized_coords(variations);
+            if coords.iter().any(|&c| c != 0.0) && variable.outline(glyph_id, &coords, path_builder).is_some() {
+                return Ok(());
+            }
+        }
+
+        data.fk_font.outline(glyph_id, hinting, path_builder)
+    }
+
     pub fn metrics(&self) -> Metrics {
         let shared = self.container.borrow();
         shared.get(self.id)
@@ -93,6 +350,14 @@ impl Font {
             .metrics
     }
 
+    /// Changes every time the font is reloaded, so that cached measurements can be refreshed.
+    pub fn generation(&self) -> usize {
+        let shared = self.container.borrow();
+        shared.get(self.id)
+            .expect("generation: loaded font should exist")
+            .generation
+    }
+
     pub fn create_buffer<P: ToString>(&self, text: P, transform: Option<na::Projective3<f32>>, color: na::Vector4<u8>) -> Buffer {
         Buffer::new(self.clone(), text, transform, color)
     }
@@ -102,8 +367,12 @@ impl Clone for Font {
     fn clone(&self) -> Self {
         let mut shared = self.container.borrow_mut();
         shared.inc_font(self.id);
+        for &fallback_id in self.fallbacks.iter() {
+            shared.inc_font(fallback_id);
+        }
         Font {
             id: self.id,
+            fallbacks: self.fallbacks.clone(),
             container: self.container.clone(),
         }
     }
@@ -113,6 +382,9 @@ impl Drop for Font {
     fn drop(&mut self) {
         let mut shared = self.container.borrow_mut();
         shared.dec_font(self.id);
+        for &fallback_id in self.fallbacks.iter() {
+            shared.dec_font(fallback_id);
+        }
     }
 }
 
@@ -125,7 +397,7 @@ impl Buffer {
     fn new<P: ToString>(font: Font, text: P, transform: Option<na::Projective3<f32>>, color: na::Vector4<u8>) -> Buffer {
         let id = {
             let mut shared = font.container.borrow_mut();
-            shared.create_buffer(font.id, text, transform, color)
+            shared.create_buffer(font.id, &font.fallbacks, text, transform, color)
         };
 
         Buffer {
@@ -154,6 +426,12 @@ impl Buffer {
         self._id
     }
 
+    /// True if the font or one of its fallback fonts draws glyphs of this buffer.
+    pub fn uses_font(&self, font_id: usize) -> bool {
+        let shared = self._font.container.borrow();
+        shared.buffer_uses_font(self._id, font_id)
+    }
+
     pub fn absolute_transform(&self, parent_absolute_transform: &na::Projective3<f32>) -> Option<na::Projective3<f32>> {
         let shared = self._font.container.borrow();
         shared.get_buffer_transform(self._id).map(|bt| parent_absolute_transform * bt)
@@ -173,23 +451,37 @@ impl Buffer {
         self._font.container.borrow_mut().set_buffer_transform(self._id, transform);
     }
 
+    pub fn renderer(&self) -> TextRenderer {
+        let shared = self._font.container.borrow();
+        shared.get_buffer_renderer(self._id)
+    }
+
+    pub fn set_renderer(&self, renderer: TextRenderer) {
+        self._font.container.borrow_mut().set_buffer_renderer(self._id, renderer);
+    }
+
     pub fn measure(&self, glyphs: &mut Vec<GlyphPosition>) -> Option<Measurement> {
         self._font.container.borrow().measure(self._id, glyphs)
     }
+
+    pub fn shaping(&self) -> ShapingOptions {
+        let shared = self._font.container.borrow();
+        shared.get_buffer_shaping(self._id)
+    }
+
+    /// Shapes the text again with different options.
+    pub fn set_shaping(&self, shaping: ShapingOptions) {
+        self._font.container.borrow_mut().set_buffer_shaping(self._id, shaping);
+    }
 }
 
 impl Clone for Buffer {
     fn clone(&self) -> Self {
-        let mut shared = self._font.container.borrow_mut();
-        shared.inc_buffer(self._id);
-        shared.inc_font(self._font.id);
+        self._font.container.borrow_mut().inc_buffer(self._id);
 
         Buffer {
             _id: self._id,
-            _font: Font {
-                id: self._font.id,
-                container: self._font.container.clone(),
-            },
+            _font: self._font.clone(),
         }
     }
 }
@@ -217,6 +509,8 @@ impl BufferRef {
     }
 }
 
+/// Metrics of a buffer. For vertical buffers, `width` is the width of the column
+/// and `height` is the length of the text along it.
 #[derive(Copy, Clone, Debug)]
 pub struct Measurement {
     pub ascent: f32,
@@ -232,9 +526,11 @@ pub mod shared {
     use crate::na;
     use harfbuzz_rs as hb;
 
+    use std::sync::Arc;
     use slab::Slab;
     use metrohash::MetroHashMap;
     use sha1::{Digest, Sha1};
+    use resources::{Resource, Resources};
 
     use font_kit::source::SystemSource;
     use font_kit::family_name::FamilyName;
@@ -243,12 +539,19 @@ pub mod shared {
     use font_kit::metrics::Metrics;
     use font_kit::font::Font as FontkitFont;
     use byteorder::{LittleEndian, WriteBytesExt};
-    use super::Measurement;
+    use font_kit::matching;
+    use super::{Error, Measurement, ShapingOptions, TextRenderer};
+    use crate::text;
+    use crate::variable_font::VariableFont;
 
     #[derive(Debug, Copy, Clone)]
     pub struct GlyphPosition {
         pub id: u32,
+        /// Font of the glyph, the buffer font or one of its fallback fonts.
+        pub font_id: usize,
+        /// Start of the glyph cluster in the buffer text, in bytes.
         pub cluster: u32,
+        /// Byte range of graphemes drawn by the glyph cluster, a ligature covers several.
         pub byte_offset: u32,
         pub len: u32,
         pub x_advance: i32,
@@ -265,82 +568,182 @@ pub mod shared {
     pub struct BufferData {
         text: String,
         graphemes: Vec<GraphemeInfo>,
+        /// Shaped glyphs in visual order, with advances and offsets in units of the buffer font.
+        glyphs: Vec<GlyphPosition>,
+        shaping: ShapingOptions,
         transform: Option<na::Projective3<f32>>,
-        buffer: Option<hb::GlyphBuffer>,
         font_id: usize,
+        fallback_font_ids: Vec<usize>,
         count: usize,
         color: na::Vector4<u8>,
+        renderer: TextRenderer,
     }
 
     impl BufferData {
-        fn new<P: ToString>(font_id: usize, font_data: &FontData, text: P, transform: Option<na::Projective3<f32>>, color: na::Vector4<u8>) -> BufferData {
-            let text = text.to_string();
-            let unicode_buffer = hb::UnicodeBuffer::new().add_str(&text);
-
-            let buffer = Some({
-                let font = &font_data.hb_font;
-
-                hb::shape(&font, unicode_buffer, &[])
-            });
-
-            use unicode_segmentation::UnicodeSegmentation;
-            let graphemes = text.grapheme_indices(true)
-                .map(|(pos, s)| GraphemeInfo { start_byte: pos as u32, len: s.len() as u32 })
-                .collect::<Vec<_>>();
-
-            if graphemes.len() != buffer.as_ref().unwrap().len() {
-                panic!("unicode segmentation mismatch for {:?}", text);
-            }
-
-            BufferData {
-                text,
-                graphemes,
+        fn new<P: ToString>(font_id: usize, fallback_font_ids: &[usize], fonts: &MetroHashMap<usize, FontData>, text: P, transform: Option<na::Projective3<f32>>, color: na::Vector4<u8>) -> BufferData {
+            let mut buffer = BufferData {
+                text: text.to_string(),
+                graphemes: Vec::new(),
+                glyphs: Vec::new(),
+                shaping: ShapingOptions::default(),
                 transform,
-                buffer,
                 font_id,
+                fallback_font_ids: fallback_font_ids.to_vec(),
                 count: 1,
                 color,
-            }
+                renderer: TextRenderer::default(),
+            };
+
+            buffer.shape(fonts);
+
+            buffer
         }
 
-        pub fn replace(&mut self, font_data: &FontData, text: &str) {
+        pub fn replace(&mut self, fonts: &MetroHashMap<usize, FontData>, text: &str) {
             self.text.clear();
             self.text.push_str(text);
-            self.shape(font_data)
+            self.shape(fonts)
         }
 
-        pub fn shape(&mut self, font_data: &FontData) {
-            let font = &font_data.hb_font;
+        pub fn set_shaping(&mut self, fonts: &MetroHashMap<usize, FontData>, shaping: ShapingOptions) {
+            if self.shaping == shaping {
+                return;
+            }
 
-            let mut unicode_buffer = ::std::mem::replace(&mut self.buffer, None).unwrap().clear();
-            unicode_buffer = unicode_buffer.add_str(&self.text);
+            self.shaping = shaping;
+            self.shape(fonts)
+        }
 
-            ::std::mem::replace(&mut self.buffer, Some(hb::shape(&font, unicode_buffer, &[])));
+        pub fn uses_font(&self, font_id: usize) -> bool {
+            self.font_id == font_id || self.fallback_font_ids.contains(&font_id)
         }
 
-        fn measure(&self, output: &mut Vec<GlyphPosition>) -> Option<(i32, i32)> {
-            let buffer_data = self.buffer.as_ref().expect("expected glyph buffer to always contain glyph output");
+        fn chain_font_id(&self, font_index: usize) -> usize {
+            match font_index {
+                0 => self.font_id,
+                i => self.fallback_font_ids[i - 1],
+            }
+        }
 
-            let positions = buffer_data.get_glyph_positions();
-            let infos = buffer_data.get_glyph_infos();
+        /// Splits text into runs by direction, script and font coverage, and shapes each run with its font.
+        ///
+        /// Runs are placed in visual order: paragraphs are reordered with the bidirectional
+        /// algorithm, and right-to-left runs are shaped right-to-left.
+        pub fn shape(&mut self, fonts: &MetroHashMap<usize, FontData>) {
+            use unicode_segmentation::UnicodeSegmentation;
 
-            let mut last_glyph_pos = None;
+            self.graphemes.clear();
+            self.graphemes.extend(
+                self.text.grapheme_indices(true)
+                    .map(|(pos, s)| GraphemeInfo { start_byte: pos as u32, len: s.len() as u32 })
+            );
+
+            let bidi_runs = if self.shaping.vertical {
+                vec![text::BidiRun { range: 0..self.text.len(), level: 0 }]
+            } else {
+                text::visual_runs(&self.text, self.shaping.direction)
+            };
 
-            for (position, (info, grapheme)) in positions.iter().zip(infos.iter().zip(self.graphemes.iter())) {
-                output.push(GlyphPosition {
-                    id: info.codepoint,
-                    cluster: info.cluster,
-                    byte_offset: grapheme.start_byte,
-                    len: grapheme.len,
-                    x_advance: position.x_advance,
-                    y_advance: position.y_advance,
-                    x_offset: position.x_offset,
-                    y_offset: position.y_offset,
+            let mut runs = Vec::new();
+            for bidi_run in bidi_runs {
+                let buffer: &BufferData = self;
+                let mut level_runs = text::itemize(&self.text[bidi_run.range.clone()], 1 + self.fallback_font_ids.len(), |font_index, c| {
+                    fonts.get(&buffer.chain_font_id(font_index))
+                        .map(|f| f.has_glyph(c))
+                        .unwrap_or(false)
                 });
 
+                for run in level_runs.iter_mut() {
+                    run.range = bidi_run.range.start + run.range.start..bidi_run.range.start + run.range.end;
+                }
+                if bidi_run.is_rtl() {
+                    level_runs.reverse();
+                }
+
+                runs.extend(level_runs.into_iter().map(|run| (run, bidi_run.is_rtl())));
+            }
+
+            let vertical = self.shaping.vertical;
+            let direction = |rtl: bool| match (vertical, rtl) {
+                (true, _) => hb::Direction::Ttb,
+                (false, true) => hb::Direction::Rtl,
+                (false, false) => hb::Direction::Ltr,
+            };
+            let language = self.shaping.language.as_ref()
+                .and_then(|l| l.parse::<hb::Language>().ok());
+            let features = self.shaping.features.iter()
+                .map(|f| hb::Feature::new(hb::Tag::new(f.tag[0] as char, f.tag[1] as char, f.tag[2] as char, f.tag[3] as char), f.value, 0..))
+                .collect::<Vec<_>>();
+
+            let units_per_em = fonts[&self.font_id].metrics.units_per_em;
+
+            let mut glyphs = Vec::with_capacity(self.text.len());
+            let mut unicode_buffer = hb::UnicodeBuffer::new();
+
+            for (run, rtl) in runs {
+                let run_font_id = self.chain_font_id(run.font_index);
+                let font_data = &fonts[&run_font_id];
+                let scale = units_per_em as f32 / font_data.metrics.units_per_em as f32;
+                let to_buffer_units = |v: i32| (v as f32 * scale).round() as i32;
+
+                // glyphs are shaped with the default instance, as harfbuzz_rs 0.3 has no way to pass
+                // variations to harfbuzz, so advances are corrected with deltas of the variable font
+                let variation = match font_data.variable {
+                    Some(ref variable) if !vertical => Some((variable, variable.normalized_coords(&self.shaping.variations)))
+                        .filter(|&(_, ref coords)| coords.iter().any(|&c| c != 0.0)),
+                    _ => None,
+                };
+
+                unicode_buffer = unicode_buffer
+                    .add_str(&self.text[run.range.clone()])
+                    .set_direction(direction(rtl));
+                if !run.script.is_neutral() {
+                    unicode_buffer = unicode_buffer.set_script(script_tag(run.script));
+                }
+                if let Some(language) = language {
+                    unicode_buffer = unicode_buffer.set_language(language);
+                }
+
+                let glyph_buffer = hb::shape(&font_data.hb_font, unicode_buffer, &features);
+
+                let first_glyph = glyphs.len();
+
+                for (position, info) in glyph_buffer.get_glyph_positions().iter().zip(glyph_buffer.get_glyph_infos().iter()) {
+                    let advance_delta = variation.as_ref()
+                        .map(|&(variable, ref coords)| variable.advance_delta(info.codepoint, coords).round() as i32)
+                        .unwrap_or(0);
+
+                    glyphs.push(GlyphPosition {
+                        id: info.codepoint,
+                        font_id: run_font_id,
+                        cluster: run.range.start as u32 + info.cluster,
+                        byte_offset: 0,
+                        len: 0,
+                        x_advance: to_buffer_units(position.x_advance + advance_delta),
+                        y_advance: to_buffer_units(position.y_advance),
+                        x_offset: to_buffer_units(position.x_offset),
+                        y_offset: to_buffer_units(position.y_offset),
+                    });
+                }
+
+                assign_grapheme_ranges(&mut glyphs[first_glyph..], run.range.end as u32, &self.graphemes);
+
+                unicode_buffer = glyph_buffer.clear();
+            }
+
+            self.glyphs = glyphs;
+        }
+
+        /// Pen position after the last glyph, offsets only move single glyphs.
+        fn measure(&self, output: &mut Vec<GlyphPosition>) -> Option<(i32, i32)> {
+            let mut last_glyph_pos = None;
+
+            for glyph in self.glyphs.iter() {
+                output.push(*glyph);
+
                 last_glyph_pos = match last_glyph_pos {
-                    None => Some((position.x_offset + position.x_advance, position.y_offset + position.y_advance)),
-                    Some((x, y)) => Some((position.x_offset + position.x_advance + x, position.y_offset + position.y_advance + y)),
+                    None => Some((glyph.x_advance, glyph.y_advance)),
+                    Some((x, y)) => Some((glyph.x_advance + x, glyph.y_advance + y)),
                 };
             }
 
@@ -348,45 +751,141 @@ pub mod shared {
         }
     }
 
+    /// Sets byte ranges of glyphs to graphemes of their clusters.
+    ///
+    /// A cluster ends where the next cluster of the run starts. It may contain several
+    /// graphemes (ligatures), and a grapheme may be drawn with several glyphs (combining marks).
+    fn assign_grapheme_ranges(glyphs: &mut [GlyphPosition], run_end: u32, graphemes: &[GraphemeInfo]) {
+        let mut cluster_starts = glyphs.iter().map(|g| g.cluster).collect::<Vec<_>>();
+        cluster_starts.sort();
+        cluster_starts.dedup();
+
+        for glyph in glyphs.iter_mut() {
+            let cluster_end = match cluster_starts.binary_search(&glyph.cluster) {
+                Ok(i) if i + 1 < cluster_starts.len() => cluster_starts[i + 1],
+                _ => run_end,
+            };
+
+            let start = grapheme_at(graphemes, glyph.cluster)
+                .map(|g| g.start_byte)
+                .unwrap_or(glyph.cluster);
+            let end = grapheme_at(graphemes, cluster_end.max(glyph.cluster + 1) - 1)
+                .map(|g| g.start_byte + g.len)
+                .unwrap_or(cluster_end);
+
+            glyph.byte_offset = start;
+            glyph.len = end.max(start) - start;
+        }
+    }
+
+    fn script_tag(script: text::Script) -> hb::Tag {
+        let code = script.iso15924().as_bytes();
+        hb::Tag::new(code[0] as char, code[1] as char, code[2] as char, code[3] as char)
+    }
+
+    fn grapheme_at(graphemes: &[GraphemeInfo], byte: u32) -> Option<&GraphemeInfo> {
+        match graphemes.binary_search_by_key(&byte, |g| g.start_byte) {
+            Ok(i) => graphemes.get(i),
+            Err(0) => None,
+            Err(i) => graphemes.get(i - 1),
+        }
+    }
+
     pub struct FontData {
         pub fk_font: FontkitFont,
         pub hb_font: hb::Owned<hb::Font<'static>>,
+        /// Variation tables of variable TrueType fonts.
+        pub variable: Option<VariableFont>,
         pub metrics: Metrics,
         pub count: usize,
+        pub generation: usize,
+    }
+
+    impl FontData {
+        pub fn has_glyph(&self, c: char) -> bool {
+            self.fk_font.glyph_for_char(c)
+                .map(|glyph_id| glyph_id != 0)
+                .unwrap_or(false)
+        }
+    }
+
+    struct RegisteredFace {
+        font_id: usize,
+        properties: Properties,
+    }
+
+    struct ResourceFont {
+        resource: Resource,
+        font_id: usize,
     }
 
     pub struct FontsContainer {
         system_source: SystemSource,
+        resources: Resources,
 
         fonts: Slab<[u8; 20]>,
         fonts_fingerprint_id: MetroHashMap<[u8; 20], usize>,
         fonts_id_prop: MetroHashMap<usize, FontData>,
 
+        /// Faces loaded from resources or memory by family key, see `family_key`.
+        /// Every registered face holds a reference to its font.
+        families: MetroHashMap<String, Vec<RegisteredFace>>,
+        /// Families tried for a family key before system fonts.
+        aliases: MetroHashMap<String, Vec<FamilyName>>,
+        /// Fallback families for fonts found by a family key.
+        fallback_chains: MetroHashMap<String, Vec<FamilyName>>,
+        /// Fonts and fallbacks found by `find_best_match` for a family, see `match_key`.
+        /// Entries hold no references to their fonts, they are forgotten when one of the
+        /// fonts is deleted or when registered fonts, aliases or fallback chains change.
+        resolved_matches: MetroHashMap<MatchKey, Option<(usize, Vec<usize>)>>,
+        resource_fonts: Vec<ResourceFont>,
+        last_generation: usize,
+
         buffers: Slab<BufferData>,
     }
 
     impl FontsContainer {
-        pub fn new() -> FontsContainer {
+        pub fn new(resources: &Resources) -> FontsContainer {
+            let mut aliases = MetroHashMap::default();
+            aliases.insert(
+                family_key(&FamilyName::Monospace),
+                vec![FamilyName::Title("Menlo".into()), FamilyName::Monospace]
+            );
+
             FontsContainer {
                 system_source: SystemSource::new(),
+                resources: resources.clone(),
 
                 fonts: Slab::new(),
                 fonts_fingerprint_id: MetroHashMap::default(),
                 fonts_id_prop: MetroHashMap::default(),
 
+                families: MetroHashMap::default(),
+                aliases,
+                fallback_chains: MetroHashMap::default(),
+                resolved_matches: MetroHashMap::default(),
+                resource_fonts: Vec::new(),
+                last_generation: 0,
+
                 buffers: Slab::new(),
             }
         }
 
-        pub fn create_buffer<P: ToString>(&mut self, font_id: usize, text: P, transform: Option<na::Projective3<f32>>, color: na::Vector4<u8>) -> usize {
-            let buffer = {
-                let font_data = self.get(font_id).expect("FontsContainer::create_buffer - self.get(font_id)");
-                BufferData::new(font_id, font_data, text, transform, color)
-            };
+        pub fn create_buffer<P: ToString>(&mut self, font_id: usize, fallback_font_ids: &[usize], text: P, transform: Option<na::Projective3<f32>>, color: na::Vector4<u8>) -> usize {
+            let buffer = BufferData::new(font_id, fallback_font_ids, &self.fonts_id_prop, text, transform, color);
+
+            // the buffer keeps its fallback fonts loaded, the buffer font is kept by `Buffer`
+            for &fallback_id in fallback_font_ids {
+                self.inc_font(fallback_id);
+            }
 
             self.buffers.insert(buffer)
         }
 
+        pub fn buffer_uses_font(&self, buffer_id: usize, font_id: usize) -> bool {
+            self.buffers[buffer_id].uses_font(font_id)
+        }
+
         pub fn buffer_glyphs(&self, buffer_id: usize, output: &mut Vec<GlyphPosition>) {
             self.buffers.get(buffer_id).expect("buffer_glyph_ids: self.buffers.get(buffer_id)")
                 .measure(output);
@@ -396,14 +895,21 @@ pub mod shared {
             let buffer = self.buffers.get(buffer_id).expect("get_buffer_size: self.buffers.get(buffer_id)");
             let font = self.fonts_id_prop.get(&buffer.font_id).expect("get_buffer_size: self.fonts_id_prop.get(&buffer.font_id)");
             if let Some(last_glyph_pos) = buffer.measure(glyphs) {
+                let line_height = font.metrics.line_gap + font.metrics.ascent - font.metrics.descent;
+                let (width, height) = if buffer.shaping.vertical {
+                    (line_height, -last_glyph_pos.1 as f32)
+                } else {
+                    (last_glyph_pos.0 as f32, line_height)
+                };
+
                 Some(Measurement {
                     ascent: font.metrics.ascent,
                     descent: font.metrics.descent,
-                    width: last_glyph_pos.0 as f32,
+                    width,
                     cap_height: font.metrics.cap_height,
                     x_height: font.metrics.x_height,
                     line_gap: font.metrics.line_gap,
-                    height: font.metrics.line_gap + font.metrics.ascent - font.metrics.descent,
+                    height,
                 })
             } else {
                 None
@@ -422,6 +928,22 @@ pub mod shared {
             self.buffers[buffer_id].transform = transform;
         }
 
+        pub fn get_buffer_renderer(&self, buffer_id: usize) -> TextRenderer {
+            self.buffers[buffer_id].renderer
+        }
+
+        pub fn set_buffer_renderer(&mut self, buffer_id: usize, renderer: TextRenderer) {
+            self.buffers[buffer_id].renderer = renderer;
+        }
+
+        pub fn get_buffer_shaping(&self, buffer_id: usize) -> ShapingOptions {
+            self.buffers[buffer_id].shaping.clone()
+        }
+
+        pub fn set_buffer_shaping(&mut self, buffer_id: usize, shaping: ShapingOptions) {
+            self.buffers[buffer_id].set_shaping(&self.fonts_id_prop, shaping);
+        }
+
         pub fn get_and_inc_buffer(&mut self, id: usize) -> Option<(usize, usize)> {
             let buffer_data = self.buffers.get_mut(id)?;
             buffer_data.count += 1;
@@ -446,7 +968,11 @@ pub mod shared {
         }
 
         pub fn delete_buffer(&mut self, id: usize) {
-            self.buffers.remove(id);
+            let buffer = self.buffers.remove(id);
+
+            for &fallback_id in buffer.fallback_font_ids.iter() {
+                self.dec_font(fallback_id);
+            }
         }
 
         pub fn inc_font(&mut self, id: usize) {
@@ -472,70 +998,237 @@ pub mod shared {
             }
         }
 
-        pub fn find_best_match(&mut self, family_names: &[FamilyName], properties: &Properties) -> Option<usize> {
-            let font_handle = match self.system_source.select_best_match(family_names, properties) {
+        /// Returns the font id and ids of its fallback fonts, all with an added reference.
+        pub fn find_best_match(&mut self, family_names: &[FamilyName], properties: &Properties) -> Option<(usize, Vec<usize>)> {
+            for family_name in family_names {
+                if let Some(found) = self.find_family_with_fallbacks(family_name, properties) {
+                    return Some(found);
+                }
+            }
+
+            None
+        }
+
+        /// Finds the family and its fallbacks, all with an added reference.
+        ///
+        /// Searching system fonts is slow, so the found ids are kept in `resolved_matches`.
+        fn find_family_with_fallbacks(&mut self, family_name: &FamilyName, properties: &Properties) -> Option<(usize, Vec<usize>)> {
+            let key = match_key(family_name, properties);
+
+            if let Some(resolved) = self.resolved_matches.get(&key).cloned() {
+                if let Some((id, ref fallbacks)) = resolved {
+                    self.inc_font(id);
+                    for &fallback_id in fallbacks {
+                        self.inc_font(fallback_id);
+                    }
+                }
+                return resolved;
+            }
+
+            let resolved = self.find_family_or_alias_match(family_name, properties)
+                .map(|id| (id, self.find_fallbacks(id, family_name, properties)));
+            self.resolved_matches.insert(key, resolved.clone());
+
+            resolved
+        }
+
+        fn find_family_or_alias_match(&mut self, family_name: &FamilyName, properties: &Properties) -> Option<usize> {
+            match self.aliases.get(&family_key(family_name)).cloned() {
+                Some(substitutes) => substitutes.iter()
+                    .filter_map(|substitute| self.find_family_match(substitute, properties))
+                    .next(),
+                None => self.find_family_match(family_name, properties),
+            }
+        }
+
+        fn find_fallbacks(&mut self, font_id: usize, family_name: &FamilyName, properties: &Properties) -> Vec<usize> {
+            let chain = match self.fallback_chains.get(&family_key(family_name)) {
+                Some(chain) => chain.clone(),
+                None => return Vec::new(),
+            };
+
+            let mut fallbacks: Vec<usize> = Vec::with_capacity(chain.len());
+
+            for fallback_name in chain.iter() {
+                if let Some(fallback_id) = self.find_family_or_alias_match(fallback_name, properties) {
+                    if fallback_id == font_id || fallbacks.contains(&fallback_id) {
+                        self.dec_font(fallback_id);
+                    } else {
+                        fallbacks.push(fallback_id);
+                    }
+                }
+            }
+
+            fallbacks
+        }
+
+        pub fn set_fallback_chain(&mut self, name: &FamilyName, fallbacks: Vec<FamilyName>) {
+            self.fallback_chains.insert(family_key(name), fallbacks);
+            self.resolved_matches.clear();
+        }
+
+        /// Finds the family in registered fonts first, and then in system fonts.
+        fn find_family_match(&mut self, family_name: &FamilyName, properties: &Properties) -> Option<usize> {
+            let registered = self.families.get(&family_key(family_name))
+                .and_then(|faces| {
+                    let candidates = faces.iter().map(|f| f.properties).collect::<Vec<_>>();
+                    matching::find_best_match(&candidates, properties)
+                        .ok()
+                        .map(|index| faces[index].font_id)
+                });
+
+            if let Some(id) = registered {
+                self.inc_font(id);
+                return Some(id);
+            }
+
+            let font_handle = match self.system_source.select_best_match(&[family_name.clone()], properties) {
                 Ok(handle) => handle,
                 Err(_) => return None,
             };
 
+            match self.load_handle(font_handle, &format!("{:?}", family_name)) {
+                Ok(id) => Some(id),
+                Err(e) => {
+                    error!("{}", e);
+                    None
+                }
+            }
+        }
+
+        pub fn load_from_resource(&mut self, path: &str) -> Result<usize, Error> {
+            let resource = self.resources.resource(path);
+            let bytes = resource.get()
+                .map_err(|e| Error::ResourceLoad { name: path.into(), inner: e })?;
+
+            let id = self.load_from_memory(path, bytes, 0)?;
+
+            if !self.resource_fonts.iter().any(|f| f.font_id == id) {
+                self.resource_fonts.push(ResourceFont { resource, font_id: id });
+            }
+
+            Ok(id)
+        }
+
+        pub fn load_from_memory(&mut self, name: &str, bytes: Vec<u8>, font_index: u32) -> Result<usize, Error> {
+            let id = self.load_handle(Handle::Memory { bytes: Arc::new(bytes), font_index }, name)?;
+            self.register(id);
+            Ok(id)
+        }
+
+        pub fn set_family_alias(&mut self, name: &FamilyName, substitutes: Vec<FamilyName>) {
+            self.aliases.insert(family_key(name), substitutes);
+            self.resolved_matches.clear();
+        }
+
+        /// Returns the id of an already loaded font with the same fingerprint, or loads it.
+        fn load_handle(&mut self, font_handle: Handle, name: &str) -> Result<usize, Error> {
             let fingerprint = generate_fingerprint(&font_handle);
 
-            let mut id = self.fonts_fingerprint_id.get(&fingerprint).map(|v| *v);
+            if let Some(id) = self.fonts_fingerprint_id.get(&fingerprint).map(|v| *v) {
+                self.inc_font(id);
+                return Ok(id);
+            }
+
+            let generation = self.next_generation();
+            let data = load_font_data(&font_handle, name, generation)?;
+
+            let id = self.fonts.insert(fingerprint.clone());
+            self.fonts_fingerprint_id.insert(fingerprint, id);
+            self.fonts_id_prop.insert(id, data);
+
+            Ok(id)
+        }
+
+        /// Adds the font to its family in the registry, the registry keeps the font loaded.
+        fn register(&mut self, font_id: usize) {
+            let (key, properties) = {
+                let fk_font = &self.fonts_id_prop[&font_id].fk_font;
+                (family_key(&FamilyName::Title(fk_font.family_name())), fk_font.properties())
+            };
+
+            let registered = {
+                let faces = self.families.entry(key).or_insert_with(Vec::new);
+                if faces.iter().any(|f| f.font_id == font_id) {
+                    true
+                } else {
+                    faces.push(RegisteredFace { font_id, properties });
+                    false
+                }
+            };
 
-            match id {
-                None => {
-                    match font_handle.load() {
+            if !registered {
+                self.inc_font(font_id);
+                self.resolved_matches.clear();
+            }
+        }
+
+        pub fn reload_modified(&mut self) -> Vec<usize> {
+            let mut reloaded = Vec::new();
+
+            for index in 0..self.resource_fonts.len() {
+                let (font_id, name, bytes) = {
+                    let resource_font = &self.resource_fonts[index];
+                    if !resource_font.resource.is_modified() {
+                        continue;
+                    }
+
+                    let name = resource_font.resource.name().to_string();
+                    match resource_font.resource.get() {
+                        Ok(bytes) => (resource_font.font_id, name, bytes),
                         Err(e) => {
-                            error!("failed to load font: {:?}", e);
-                            return None;
+                            error!("failed to reload font {}: {}", name, e);
+                            continue;
                         }
-                        Ok(fk_font) => {
-                            let face = match font_handle {
-                                Handle::Path { path, font_index } => {
-                                    match hb::Face::from_file(&path, font_index) {
-                                        Err(e) => {
-                                            error!("failed to load font face from {:?} - {:?}: {:?}", path, font_index, e);
-                                            return None;
-                                        }
-                                        Ok(f) => f,
-                                    }
-                                }
-                                Handle::Memory { .. } => unimplemented!("can not load fonts from memory"),
-                            };
-
-                            let mut hb_font = hb::Font::new(face);
-
-                            use harfbuzz_rs::rusttype::SetRustTypeFuncs;
-                            if let Err(e) = hb_font.set_rusttype_funcs() {
-                                error!("failed to set up rusttype: {:?}", e);
-                                return None;
-                            }
-
-                            let new_id = self.fonts.insert(fingerprint.clone());
-                            id = Some(new_id);
-
-                            debug!("load font {:?}", fk_font.full_name());
-
-                            let metrics = fk_font.metrics();
-
-                            let data = FontData {
-                                fk_font,
-                                hb_font,
-                                count: 1,
-                                metrics,
-                            };
-
-                            self.fonts_fingerprint_id.insert(fingerprint, new_id);
-                            self.fonts_id_prop.insert(new_id, data);
-                        }
-                    };
+                    }
+                };
+
+                match self.reload_font(font_id, &name, bytes) {
+                    Ok(()) => {
+                        info!("reloaded font {}", name);
+                        reloaded.push(font_id);
+                    },
+                    Err(e) => error!("{}", e),
                 }
-                Some(id) => {
-                    self.inc_font(id);
+            }
+
+            reloaded
+        }
+
+        /// Replaces font data while keeping the font id, and shapes its buffers again.
+        fn reload_font(&mut self, font_id: usize, name: &str, bytes: Vec<u8>) -> Result<(), Error> {
+            let font_handle = Handle::Memory { bytes: Arc::new(bytes), font_index: 0 };
+
+            let generation = self.next_generation();
+            let mut data = load_font_data(&font_handle, name, generation)?;
+            data.count = self.fonts_id_prop[&font_id].count;
+
+            let properties = data.fk_font.properties();
+            for faces in self.families.values_mut() {
+                for face in faces.iter_mut().filter(|f| f.font_id == font_id) {
+                    face.properties = properties;
                 }
             }
+            self.resolved_matches.clear();
+
+            let fingerprint = generate_fingerprint(&font_handle);
+            let previous_fingerprint = ::std::mem::replace(&mut self.fonts[font_id], fingerprint.clone());
+            self.fonts_fingerprint_id.remove(&previous_fingerprint);
+            self.fonts_fingerprint_id.insert(fingerprint, font_id);
+            self.fonts_id_prop.insert(font_id, data);
+
+            for (_, buffer) in self.buffers.iter_mut() {
+                if buffer.uses_font(font_id) {
+                    buffer.shape(&self.fonts_id_prop);
+                }
+            }
+
+            Ok(())
+        }
 
-            return id;
+        fn next_generation(&mut self) -> usize {
+            self.last_generation += 1;
+            self.last_generation
         }
 
         pub fn delete_font(&mut self, id: usize) {
@@ -544,6 +1237,7 @@ pub mod shared {
             self.fonts_id_prop.remove(&id);
             let fingerprint = self.fonts.remove(id);
             self.fonts_fingerprint_id.remove(&fingerprint);
+            forget_matches_with_font(&mut self.resolved_matches, id);
         }
 
         pub fn get(&self, id: usize) -> Option<&FontData> {
@@ -551,6 +1245,80 @@ pub mod shared {
         }
     }
 
+    fn load_font_data(font_handle: &Handle, name: &str, generation: usize) -> Result<FontData, Error> {
+        let load_error = |message: String| Error::Load { name: name.into(), message };
+
+        let fk_font = font_handle.load()
+            .map_err(|e| load_error(format!("{:?}", e)))?;
+
+        let face = match *font_handle {
+            Handle::Path { ref path, font_index } => hb::Face::from_file(path, font_index)
+                .map_err(|e| load_error(format!("failed to load font face from {:?} - {:?}: {:?}", path, font_index, e)))?,
+            Handle::Memory { ref bytes, font_index } => hb::Face::new(hb::Blob::with_bytes_owned(bytes.clone(), font_bytes), font_index),
+        };
+
+        let variable = match *font_handle {
+            Handle::Path { ref path, font_index } => ::std::fs::read(path).ok()
+                .and_then(|bytes| VariableFont::parse(Arc::new(bytes), font_index)),
+            Handle::Memory { ref bytes, font_index } => VariableFont::parse(bytes.clone(), font_index),
+        };
+
+        let mut hb_font = hb::Font::new(face);
+
+        use harfbuzz_rs::rusttype::SetRustTypeFuncs;
+        hb_font.set_rusttype_funcs()
+            .map_err(|e| load_error(format!("failed to set up rusttype: {:?}", e)))?;
+
+        debug!("load font {:?}", fk_font.full_name());
+
+        let metrics = fk_font.metrics();
+
+        Ok(FontData {
+            fk_font,
+            hb_font,
+            variable,
+            count: 1,
+            metrics,
+            generation,
+        })
+    }
+
+    fn font_bytes(bytes: &Arc<Vec<u8>>) -> &[u8] {
+        &bytes[..]
+    }
+
+    /// Registry key of a family, generic families can not collide with family titles.
+    fn family_key(family_name: &FamilyName) -> String {
+        match *family_name {
+            FamilyName::Title(ref title) => title.to_lowercase(),
+            FamilyName::Serif => "<serif>".into(),
+            FamilyName::SansSerif => "<sans-serif>".into(),
+            FamilyName::Monospace => "<monospace>".into(),
+            FamilyName::Cursive => "<cursive>".into(),
+            FamilyName::Fantasy => "<fantasy>".into(),
+        }
+    }
+
+    /// Family key with style, weight and stretch, float properties are compared by bits.
+    type MatchKey = (String, u8, u32, u32);
+
+    fn match_key(family_name: &FamilyName, properties: &Properties) -> MatchKey {
+        (
+            family_key(family_name),
+            properties.style as u8,
+            properties.weight.0.to_bits(),
+            properties.stretch.0.to_bits(),
+        )
+    }
+
+    /// Removes matches that found the font or use it as a fallback, the font id can be reused.
+    fn forget_matches_with_font(matches: &mut MetroHashMap<MatchKey, Option<(usize, Vec<usize>)>>, font_id: usize) {
+        matches.retain(|_, resolved| match *resolved {
+            Some((id, ref fallbacks)) => id != font_id && !fallbacks.contains(&font_id),
+            None => true,
+        });
+    }
+
     fn generate_fingerprint(handle: &Handle) -> [u8; 20] {
         let generic_array = match *handle {
             Handle::Path { ref path, font_index } => {
@@ -589,4 +1357,152 @@ pub mod shared {
 
         output
     }
+
+    #[cfg(test)]
+    mod shared_tests {
+        use super::*;
+        use font_kit::properties::{Style, Weight};
+        use unicode_segmentation::UnicodeSegmentation;
+
+        fn graphemes(text: &str) -> Vec<GraphemeInfo> {
+            text.grapheme_indices(true)
+                .map(|(pos, s)| GraphemeInfo { start_byte: pos as u32, len: s.len() as u32 })
+                .collect()
+        }
+
+        fn glyphs(clusters: &[u32]) -> Vec<GlyphPosition> {
+            clusters.iter()
+                .map(|&cluster| GlyphPosition {
+                    id: 1,
+                    font_id: 0,
+                    cluster,
+                    byte_offset: 0,
+                    len: 0,
+                    x_advance: 100,
+                    y_advance: 0,
+                    x_offset: 0,
+                    y_offset: 0,
+                })
+                .collect()
+        }
+
+        fn ranges(glyphs: &[GlyphPosition]) -> Vec<(u32, u32)> {
+            glyphs.iter().map(|g| (g.byte_offset, g.byte_offset + g.len)).collect()
+        }
+
+        /// Glyph ranges, each distinct range once, must cover `0..len` without gaps or overlaps.
+        fn assert_covers(glyphs: &[GlyphPosition], len: u32) {
+            let mut ranges = ranges(glyphs);
+            ranges.sort();
+            ranges.dedup();
+
+            let mut end = 0;
+            for (start, range_end) in ranges {
+                assert_eq!(end, start);
+                end = range_end;
+            }
+            assert_eq!(len, end);
+        }
+
+        #[test]
+        fn glyph_per_grapheme() {
+            let text = "ab\u{e9}";
+            let mut glyphs = glyphs(&[0, 1, 2]);
+
+            assign_grapheme_ranges(&mut glyphs, text.len() as u32, &graphemes(text));
+            assert_eq!(vec![(0, 1), (1, 2), (2, 4)], ranges(&glyphs));
+        }
+
+        #[test]
+        fn ligature_covers_its_graphemes() {
+            // "ffi" drawn with one glyph
+            let text = "ffi x";
+            let mut glyphs = glyphs(&[0, 3, 4]);
+
+            assign_grapheme_ranges(&mut glyphs, text.len() as u32, &graphemes(text));
+            assert_eq!(vec![(0, 3), (3, 4), (4, 5)], ranges(&glyphs));
+            assert_covers(&glyphs, text.len() as u32);
+        }
+
+        #[test]
+        fn every_combining_mark_glyph_covers_whole_grapheme() {
+            let text = "e\u{301}\u{302}a";
+
+            // marks merged into the base cluster
+            let mut merged = glyphs(&[0, 0, 0, 5]);
+            assign_grapheme_ranges(&mut merged, text.len() as u32, &graphemes(text));
+            assert_eq!(vec![(0, 5), (0, 5), (0, 5), (5, 6)], ranges(&merged));
+
+            // marks in clusters of their own characters
+            let mut separate = glyphs(&[0, 1, 3, 5]);
+            assign_grapheme_ranges(&mut separate, text.len() as u32, &graphemes(text));
+            assert_eq!(vec![(0, 5), (0, 5), (0, 5), (5, 6)], ranges(&separate));
+        }
+
+        #[test]
+        fn right_to_left_clusters_are_in_descending_order() {
+            // last two letters drawn with one glyph
+            let text = "\u{5d0}\u{5d1}\u{5d2}\u{5d3}";
+            let mut glyphs = glyphs(&[4, 2, 0]);
+
+            assign_grapheme_ranges(&mut glyphs, text.len() as u32, &graphemes(text));
+            assert_eq!(vec![(4, 8), (2, 4), (0, 2)], ranges(&glyphs));
+            assert_covers(&glyphs, text.len() as u32);
+        }
+
+        #[test]
+        fn last_cluster_ends_at_run_end() {
+            // run of "bc" in "abcd"
+            let text = "abcd";
+            let mut glyphs = glyphs(&[1, 2]);
+
+            assign_grapheme_ranges(&mut glyphs, 3, &graphemes(text));
+            assert_eq!(vec![(1, 2), (2, 3)], ranges(&glyphs));
+        }
+
+        #[test]
+        fn match_key_ignores_family_title_case() {
+            let properties = Properties::new();
+
+            assert_eq!(
+                match_key(&FamilyName::Title("Noto Sans".into()), &properties),
+                match_key(&FamilyName::Title("noto sans".into()), &properties)
+            );
+            assert!(
+                match_key(&FamilyName::Title("Monospace".into()), &properties)
+                    != match_key(&FamilyName::Monospace, &properties)
+            );
+        }
+
+        #[test]
+        fn match_key_differs_by_properties() {
+            let family = FamilyName::SansSerif;
+            let mut italic = Properties::new();
+            italic.style = Style::Italic;
+            let mut bold = Properties::new();
+            bold.weight = Weight::BOLD;
+
+            let normal_key = match_key(&family, &Properties::new());
+            assert!(normal_key != match_key(&family, &italic));
+            assert!(normal_key != match_key(&family, &bold));
+            assert_eq!(normal_key, match_key(&family, &Properties::new()));
+        }
+
+        #[test]
+        fn deleted_font_is_forgotten_in_matches() {
+            let mut matches = MetroHashMap::default();
+            let properties = Properties::new();
+            matches.insert(match_key(&FamilyName::Serif, &properties), Some((1, vec![2, 3])));
+            matches.insert(match_key(&FamilyName::SansSerif, &properties), Some((2, vec![])));
+            matches.insert(match_key(&FamilyName::Monospace, &properties), Some((4, vec![])));
+            matches.insert(match_key(&FamilyName::Cursive, &properties), None);
+
+            forget_matches_with_font(&mut matches, 2);
+
+            assert!(!matches.contains_key(&match_key(&FamilyName::Serif, &properties)));
+            assert!(!matches.contains_key(&match_key(&FamilyName::SansSerif, &properties)));
+            assert!(matches.contains_key(&match_key(&FamilyName::Monospace, &properties)));
+            assert!(matches.contains_key(&match_key(&FamilyName::Cursive, &properties)));
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,207 @@
+use crate::na;
+
+/// Keys the ui reacts to, other keys are delivered as `Key::Other`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Key {
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Backspace,
+    Delete,
+    Enter,
+    Escape,
+    Tab,
+    A,
+    C,
+    V,
+    X,
+    Y,
+    Z,
+    Other,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Modifiers {
+    pub shift: bool,
+    /// Control, or Command on macOS.
+    pub ctrl: bool,
+    pub alt: bool,
+}
+
+impl Modifiers {
+    pub fn new() -> Modifiers {
+        Modifiers::default()
+    }
+
+    pub fn shift(mut self, value: bool) -> Self {
+        self.shift = value;
+        self
+    }
+
+    pub fn ctrl(mut self, value: bool) -> Self {
+        self.ctrl = value;
+        self
+    }
+
+    pub fn alt(mut self, value: bool) -> Self {
+        self.alt = value;
+        self
+    }
+}
+
+/// Stage of event delivery along the path from the root to the target element.
+///
+/// Ancestors of the target see the event in the `Capture` phase before the target,
+/// and in the `Bubble` phase after it. Delivery stops at the first element that
+/// handles the event.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum EventPhase {
+    Capture,
+    Target,
+    Bubble,
+}
+
+/// Pointer position is in pixels, in coordinates of the element receiving the event.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PointerEvent {
+    pub x: f32,
+    pub y: f32,
+    /// Number of quick consecutive clicks, only set for `pointer_down`.
+    pub clicks: u8,
+    pub modifiers: Modifiers,
+    pub phase: EventPhase,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub modifiers: Modifiers,
+    pub phase: EventPhase,
+}
+
+/// Text typed or committed by an input method, or text being composed by an input method.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextEvent {
+    pub text: String,
+    /// Byte offset of the cursor in text being composed, `None` for committed text.
+    ///
+    /// The composed text is replaced by the next composition or the committed text,
+    /// empty composed text ends the composition.
+    pub composition_cursor: Option<usize>,
+    pub phase: EventPhase,
+}
+
+/// Ray from the viewer through the pointer, in root coordinates.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PointerRay {
+    origin: na::Point3<f32>,
+    direction: na::Vector3<f32>,
+}
+
+impl PointerRay {
+    /// Ray through a point in normalized device coordinates, from the near to the far plane.
+    ///
+    /// `inverse_view_projection` maps normalized device coordinates to root coordinates.
+    pub fn unproject(inverse_view_projection: &na::Matrix4<f32>, x: f32, y: f32) -> Option<PointerRay> {
+        let unproject = |z: f32| {
+            let p = inverse_view_projection * na::Vector4::new(x, y, z, 1.0);
+            if p.w.abs() < ::std::f32::EPSILON {
+                None
+            } else {
+                Some(na::Point3::new(p.x / p.w, p.y / p.w, p.z / p.w))
+            }
+        };
+
+        let near = unproject(-1.0)?;
+        let far = unproject(1.0)?;
+        let direction = far - near;
+        let length = direction.norm();
+        if length < ::std::f32::EPSILON {
+            return None;
+        }
+
+        Some(PointerRay {
+            origin: near,
+            direction: direction / length,
+        })
+    }
+
+    /// Intersection with the `z = 0` plane of an element with the absolute `transform`.
+    ///
+    /// Returns the position on the plane, and the distance from the ray origin.
+    /// Planes behind the ray origin are not hit.
+    pub fn intersect(&self, transform: &na::Projective3<f32>) -> Option<(f32, f32, f32)> {
+        let inverse = transform.inverse();
+        let origin = inverse * self.origin;
+        let direction = inverse * (self.origin + self.direction) - origin;
+
+        if direction.z.abs() < ::std::f32::EPSILON {
+            return None;
+        }
+
+        let s = -origin.z / direction.z;
+        if s < 0.0 {
+            return None;
+        }
+
+        let local = origin + direction * s;
+        let hit = transform * na::Point3::new(local.x, local.y, 0.0);
+
+        Some((local.x, local.y, (hit - self.origin).dot(&self.direction)))
+    }
+}
+
+/// System clipboard, set with `Tree::set_clipboard`.
+pub trait Clipboard {
+    fn text(&self) -> Option<String>;
+    fn set_text(&self, text: &str);
+}
+
+#[cfg(test)]
+mod input_tests {
+    use super::*;
+
+    fn ray(origin: (f32, f32, f32), direction: (f32, f32, f32)) -> PointerRay {
+        PointerRay {
+            origin: na::Point3::new(origin.0, origin.1, origin.2),
+            direction: na::Vector3::new(direction.0, direction.1, direction.2).normalize(),
+        }
+    }
+
+    #[test]
+    fn plane_in_front_is_hit() {
+        let transform = na::Projective3::identity();
+
+        let hit = ray((1.0, 2.0, 5.0), (0.0, 0.0, -1.0)).intersect(&transform);
+
+        assert_eq!(Some((1.0, 2.0, 5.0)), hit);
+    }
+
+    #[test]
+    fn translated_plane_is_hit_in_local_coordinates() {
+        let transform = na::convert(na::Translation3::new(10.0, 0.0, 2.0));
+
+        let hit = ray((11.0, 3.0, 5.0), (0.0, 0.0, -1.0)).intersect(&transform);
+
+        assert_eq!(Some((1.0, 3.0, 3.0)), hit);
+    }
+
+    #[test]
+    fn plane_behind_origin_is_not_hit() {
+        let transform = na::Projective3::identity();
+
+        assert_eq!(None, ray((0.0, 0.0, 5.0), (0.0, 0.0, 1.0)).intersect(&transform));
+    }
+
+    #[test]
+    fn parallel_plane_is_not_hit() {
+        let transform = na::Projective3::identity();
+
+        assert_eq!(None, ray((0.0, 0.0, 5.0), (1.0, 0.0, 0.0)).intersect(&transform));
+    }
+}
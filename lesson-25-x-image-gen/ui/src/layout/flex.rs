@@ -0,0 +1,597 @@
+use std::ops::Range;
+use crate::{FlowDirection, ResolvedSize};
+use super::placement::{Align, Edges, Placement, clamp};
+
+/// Distribution of free space between children along the flow direction.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Justify {
+    Start,
+    End,
+    Center,
+    /// Free space goes between children, none before the first and after the last one.
+    SpaceBetween,
+    /// Every child gets equal space on both of its sides.
+    SpaceAround,
+    /// Equal space before, between and after children.
+    SpaceEvenly,
+}
+
+impl Justify {
+    /// Space before the first child and space between children.
+    fn distribute(&self, free: f32, count: usize) -> (f32, f32) {
+        let count = count as f32;
+        match *self {
+            Justify::Start => (0.0, 0.0),
+            Justify::End => (free, 0.0),
+            Justify::Center => (free / 2.0, 0.0),
+            Justify::SpaceBetween if free > 0.0 && count > 1.0 => (0.0, free / (count - 1.0)),
+            Justify::SpaceBetween => (0.0, 0.0),
+            Justify::SpaceAround if free > 0.0 => (free / count / 2.0, free / count),
+            Justify::SpaceEvenly if free > 0.0 => (free / (count + 1.0), free / (count + 1.0)),
+            // overflowing children are centered
+            Justify::SpaceAround | Justify::SpaceEvenly => (free / 2.0, 0.0),
+        }
+    }
+}
+
+/// Layout of children with `Base::layout_flex`, similar to the CSS flexible box layout.
+#[derive(Copy, Clone, Debug)]
+pub struct FlexStyle {
+    /// Direction children are placed in, vertical by default.
+    pub direction: FlowDirection,
+    /// Moves children that do not fit to a new line, instead of shrinking them.
+    ///
+    /// Lines are stretched to fill the box across the direction.
+    pub wrap: bool,
+    pub justify: Justify,
+    /// Alignment of children across the direction, inside their line.
+    pub align: Align,
+    pub padding: Edges,
+    /// Space between children in a line.
+    pub gap: i32,
+    /// Space between lines.
+    pub line_gap: i32,
+}
+
+impl FlexStyle {
+    pub fn new() -> FlexStyle {
+        FlexStyle {
+            direction: FlowDirection::Vertical,
+            wrap: false,
+            justify: Justify::Start,
+            align: Align::Stretch,
+            padding: Edges::new(),
+            gap: 0,
+            line_gap: 0,
+        }
+    }
+
+    pub fn direction(mut self, direction: FlowDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn justify(mut self, justify: Justify) -> Self {
+        self.justify = justify;
+        self
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn padding(mut self, padding: Edges) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn gap(mut self, gap: i32) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    pub fn line_gap(mut self, line_gap: i32) -> Self {
+        self.line_gap = line_gap;
+        self
+    }
+}
+
+impl Default for FlexStyle {
+    fn default() -> Self {
+        FlexStyle::new()
+    }
+}
+
+/// Flex properties of a child, set with `Base::set_flex_item`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FlexItem {
+    /// Share of the free space in the line the child grows by.
+    pub grow: f32,
+    /// Share of the missing space in the line the child shrinks by, weighted by its basis.
+    pub shrink: f32,
+    /// Size in the flow direction before growing or shrinking, `None` uses the measured size.
+    pub basis: Option<i32>,
+    pub margin: Edges,
+    /// Overrides `FlexStyle::align` for this child.
+    pub align: Option<Align>,
+    pub min_width: i32,
+    pub min_height: i32,
+    pub max_width: Option<i32>,
+    pub max_height: Option<i32>,
+}
+
+impl FlexItem {
+    pub fn new() -> FlexItem {
+        FlexItem {
+            grow: 0.0,
+            shrink: 1.0,
+            basis: None,
+            margin: Edges::new(),
+            align: None,
+            min_width: 0,
+            min_height: 0,
+            max_width: None,
+            max_height: None,
+        }
+    }
+
+    pub fn grow(mut self, grow: f32) -> Self {
+        self.grow = grow;
+        self
+    }
+
+    pub fn shrink(mut self, shrink: f32) -> Self {
+        self.shrink = shrink;
+        self
+    }
+
+    pub fn basis(mut self, basis: i32) -> Self {
+        self.basis = Some(basis);
+        self
+    }
+
+    pub fn margin(mut self, margin: Edges) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = Some(align);
+        self
+    }
+
+    pub fn min_size(mut self, w: i32, h: i32) -> Self {
+        self.min_width = w;
+        self.min_height = h;
+        self
+    }
+
+    pub fn max_width(mut self, w: i32) -> Self {
+        self.max_width = Some(w);
+        self
+    }
+
+    pub fn max_height(mut self, h: i32) -> Self {
+        self.max_height = Some(h);
+        self
+    }
+
+    /// Minimum and maximum sizes in the `flow` direction, and across it.
+    fn limits(&self, flow: FlowDirection) -> ((i32, Option<i32>), (i32, Option<i32>)) {
+        let horizontal = (self.min_width, self.max_width);
+        let vertical = (self.min_height, self.max_height);
+        match flow {
+            FlowDirection::Vertical => (vertical, horizontal),
+            FlowDirection::Horizontal => (horizontal, vertical),
+        }
+    }
+}
+
+impl Default for FlexItem {
+    fn default() -> Self {
+        FlexItem::new()
+    }
+}
+
+struct FlexEntry {
+    index: usize,
+    item: FlexItem,
+    /// Size in the flow direction before growing or shrinking.
+    base: f32,
+    /// Hypothetical size in the flow direction, the final size after resolving flexible lengths.
+    main: f32,
+    main_limits: (i32, Option<i32>),
+    cross: f32,
+    cross_limits: (i32, Option<i32>),
+    margin_main: f32,
+    margin_cross: f32,
+    frozen: bool,
+    violation: f32,
+}
+
+/// Places children measured with `BoxSize::Auto` in a box of `size`, or in the smallest box
+/// that fits them if `size` is `None`. The measured size is `None` for children that do not
+/// have one, they are hidden unless they have a basis or grow.
+///
+/// Returns the resolved size of the box, and placements of children, `None` for hidden ones.
+pub fn place_flex(
+    style: &FlexStyle,
+    size: Option<ResolvedSize>,
+    items: &[(FlexItem, Option<ResolvedSize>)],
+) -> (Option<ResolvedSize>, Vec<Option<Placement>>) {
+    let flow = style.direction;
+    let (padding_main_start, padding_main_end) = style.padding.par(flow);
+    let (padding_cross_start, padding_cross_end) = style.padding.ort(flow);
+    let gap = style.gap as f32;
+    let line_gap = style.line_gap as f32;
+
+    // inner size across and along the flow direction
+    let inner = size.map(|size| {
+        let (cross, main) = size.to_flow(flow);
+        (
+            (cross - padding_cross_start - padding_cross_end).max(0) as f32,
+            (main - padding_main_start - padding_main_end).max(0) as f32,
+        )
+    });
+
+    let mut entries: Vec<FlexEntry> = items
+        .iter()
+        .enumerate()
+        .filter(|&(_, &(item, measured))| measured.is_some() || item.basis.is_some() || item.grow > 0.0)
+        .map(|(index, &(item, measured))| {
+            let (cross, main) = measured.unwrap_or_else(ResolvedSize::zero).to_flow(flow);
+            let (main_limits, cross_limits) = item.limits(flow);
+            let (margin_main_start, margin_main_end) = item.margin.par(flow);
+            let (margin_cross_start, margin_cross_end) = item.margin.ort(flow);
+            let base = item.basis.unwrap_or(main) as f32;
+
+            FlexEntry {
+                index,
+                item,
+                base,
+                main: clamp(base, main_limits.0, main_limits.1),
+                main_limits,
+                cross: clamp(cross as f32, cross_limits.0, cross_limits.1),
+                cross_limits,
+                margin_main: (margin_main_start + margin_main_end) as f32,
+                margin_cross: (margin_cross_start + margin_cross_end) as f32,
+                frozen: false,
+                violation: 0.0,
+            }
+        })
+        .collect();
+
+    let lines = collect_lines(&entries, style.wrap, gap, inner.map(|(_, main)| main));
+
+    if let Some((_, inner_main)) = inner {
+        for line in &lines {
+            let available = inner_main - gap * (line.len() - 1) as f32;
+            resolve_flexible_lengths(&mut entries[line.clone()], available);
+        }
+    }
+
+    let line_mains: Vec<f32> = lines
+        .iter()
+        .map(|line| {
+            entries[line.clone()].iter().map(|e| e.main + e.margin_main).sum::<f32>()
+                + gap * (line.len() - 1) as f32
+        })
+        .collect();
+
+    let mut line_crosses: Vec<f32> = lines
+        .iter()
+        .map(|line| entries[line.clone()].iter().map(|e| e.cross + e.margin_cross).fold(0.0, f32::max))
+        .collect();
+
+    if let Some((inner_cross, _)) = inner {
+        if !style.wrap {
+            line_crosses = vec![inner_cross; line_crosses.len()];
+        } else if !line_crosses.is_empty() {
+            let used = line_crosses.iter().sum::<f32>() + line_gap * (line_crosses.len() - 1) as f32;
+            if used < inner_cross {
+                let extra = (inner_cross - used) / line_crosses.len() as f32;
+                for line_cross in &mut line_crosses {
+                    *line_cross += extra;
+                }
+            }
+        }
+    }
+
+    let mut placements = vec![None; items.len()];
+    let mut cross_offset = padding_cross_start as f32;
+
+    for ((line, &line_main), &line_cross) in lines.iter().zip(&line_mains).zip(&line_crosses) {
+        let free = inner.map(|(_, main)| main - line_main).unwrap_or(0.0);
+        let (start, spacing) = style.justify.distribute(free, line.len());
+        let mut main_offset = padding_main_start as f32 + start;
+
+        for entry in &entries[line.clone()] {
+            let (margin_main_start, margin_main_end) = entry.item.margin.par(flow);
+            let (margin_cross_start, _) = entry.item.margin.ort(flow);
+            let align = entry.item.align.unwrap_or(style.align);
+
+            let (offset, cross) = align.place(line_cross - entry.margin_cross, entry.cross);
+            let cross = match align {
+                Align::Stretch => clamp(cross, entry.cross_limits.0, entry.cross_limits.1),
+                _ => cross,
+            };
+
+            main_offset += margin_main_start as f32;
+            placements[entry.index] = Some(Placement::from_flow(
+                flow,
+                cross_offset + margin_cross_start as f32 + offset,
+                main_offset,
+                cross,
+                entry.main,
+            ));
+            main_offset += entry.main + margin_main_end as f32 + gap + spacing;
+        }
+
+        cross_offset += line_cross + line_gap;
+    }
+
+    let resolved_size = match size {
+        Some(size) => Some(size),
+        None if entries.is_empty() => None,
+        None => {
+            let main = line_mains.iter().cloned().fold(0.0, f32::max);
+            let cross = line_crosses.iter().sum::<f32>() + line_gap * (line_crosses.len() - 1) as f32;
+            Some(ResolvedSize::from_flow(
+                flow,
+                cross.ceil() as i32 + padding_cross_start + padding_cross_end,
+                main.ceil() as i32 + padding_main_start + padding_main_end,
+            ))
+        }
+    };
+
+    (resolved_size, placements)
+}
+
+/// Splits entries into lines that fit into `available` space, or a single line if not wrapping.
+fn collect_lines(entries: &[FlexEntry], wrap: bool, gap: f32, available: Option<f32>) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut line_main = 0.0;
+
+    for (i, entry) in entries.iter().enumerate() {
+        let outer = entry.main + entry.margin_main;
+        if i > start {
+            let fits = match available {
+                Some(available) if wrap => line_main + gap + outer <= available,
+                _ => true,
+            };
+            if fits {
+                line_main += gap + outer;
+                continue;
+            }
+            lines.push(start..i);
+            start = i;
+        }
+        line_main = outer;
+    }
+
+    if start < entries.len() {
+        lines.push(start..entries.len());
+    }
+
+    lines
+}
+
+/// Grows or shrinks entries of a line so that they fill the `available` space, freezing entries
+/// that reach their minimum or maximum size until the rest can take the remaining space.
+fn resolve_flexible_lengths(entries: &mut [FlexEntry], available: f32) {
+    let margins: f32 = entries.iter().map(|e| e.margin_main).sum();
+    let hypothetical: f32 = entries.iter().map(|e| e.main).sum();
+    let growing = hypothetical + margins < available;
+
+    for entry in entries.iter_mut() {
+        let factor = if growing { entry.item.grow } else { entry.item.shrink };
+        entry.frozen = factor <= 0.0
+            || (growing && entry.base > entry.main)
+            || (!growing && entry.base < entry.main);
+    }
+
+    while entries.iter().any(|e| !e.frozen) {
+        let used: f32 = margins + entries.iter()
+            .map(|e| if e.frozen { e.main } else { e.base })
+            .sum::<f32>();
+        let free = available - used;
+
+        let weight = |e: &FlexEntry| if growing { e.item.grow } else { e.item.shrink * e.base };
+        let total: f32 = entries.iter().filter(|e| !e.frozen).map(&weight).sum();
+
+        let mut violation = 0.0;
+        for entry in entries.iter_mut().filter(|e| !e.frozen) {
+            let target = if total > 0.0 {
+                entry.base + free * weight(entry) / total
+            } else {
+                entry.base
+            };
+            let size = clamp(target, entry.main_limits.0, entry.main_limits.1).max(0.0);
+            entry.violation = size - target;
+            entry.main = size;
+            violation += entry.violation;
+        }
+
+        for entry in entries.iter_mut().filter(|e| !e.frozen) {
+            entry.frozen = if violation.abs() < 0.001 {
+                true
+            } else if violation > 0.0 {
+                entry.violation > 0.0
+            } else {
+                entry.violation < 0.0
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod flex_tests {
+    use crate::*;
+    use crate::layout::test_tree::*;
+
+    fn row() -> FlexStyle {
+        FlexStyle::new().direction(FlowDirection::Horizontal)
+    }
+
+    #[test]
+    fn grow_distributes_free_space() {
+        let root = Flex::new(row(), vec![
+            item(FlexItem::new().grow(1.0), Block::new(50, 20)),
+            item(FlexItem::new().grow(2.0), Block::new(50, 20)),
+            item(FlexItem::new(), Block::new(50, 20)),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 300, 100)),
+            Some((0, 0, 100, 100)),
+            Some((100, 0, 150, 100)),
+            Some((250, 0, 50, 100)),
+        ], layout(root, BoxSize::Fixed { w: 300, h: 100 }));
+    }
+
+    #[test]
+    fn shrink_is_weighted_by_basis() {
+        let root = Flex::new(row(), vec![
+            item(FlexItem::new().basis(100), Block::new(10, 10)),
+            item(FlexItem::new().basis(200), Block::new(10, 10)),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 200, 50)),
+            Some((0, 0, 67, 50)),
+            Some((67, 0, 133, 50)),
+        ], layout(root, BoxSize::Fixed { w: 200, h: 50 }));
+    }
+
+    #[test]
+    fn max_size_freezes_growing_child() {
+        let root = Flex::new(row(), vec![
+            item(FlexItem::new().grow(1.0).max_width(50), Block::new(0, 10)),
+            item(FlexItem::new().grow(1.0), Block::new(0, 10)),
+            item(FlexItem::new().grow(1.0), Block::new(0, 10)),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 300, 10)),
+            Some((0, 0, 50, 10)),
+            Some((50, 0, 125, 10)),
+            Some((175, 0, 125, 10)),
+        ], layout(root, BoxSize::Fixed { w: 300, h: 10 }));
+    }
+
+    #[test]
+    fn min_size_freezes_shrinking_child() {
+        let root = Flex::new(row(), vec![
+            item(FlexItem::new().basis(100).min_size(80, 0), Block::new(10, 10)),
+            item(FlexItem::new().basis(100), Block::new(10, 10)),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 100, 10)),
+            Some((0, 0, 80, 10)),
+            Some((80, 0, 20, 10)),
+        ], layout(root, BoxSize::Fixed { w: 100, h: 10 }));
+    }
+
+    #[test]
+    fn justify_and_align_children() {
+        let style = FlexStyle::new()
+            .justify(Justify::SpaceBetween)
+            .align(Align::Center);
+        let root = Flex::new(style, vec![
+            item(FlexItem::new(), Block::new(40, 20)),
+            item(FlexItem::new().align(Align::End), Block::new(40, 20)),
+            item(FlexItem::new().align(Align::Stretch), Block::new(40, 20)),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 100, 200)),
+            Some((30, 0, 40, 20)),
+            Some((60, 90, 40, 20)),
+            Some((0, 180, 100, 20)),
+        ], layout(root, BoxSize::Fixed { w: 100, h: 200 }));
+    }
+
+    #[test]
+    fn padding_gap_and_margins_offset_children() {
+        let style = row()
+            .padding(Edges::all(10))
+            .gap(5)
+            .align(Align::Start);
+        let root = Flex::new(style, vec![
+            item(FlexItem::new().margin(Edges::new().left(5).top(10)), Block::new(20, 20)),
+            item(FlexItem::new(), Block::new(20, 20)),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 200, 100)),
+            Some((15, 20, 20, 20)),
+            Some((40, 10, 20, 20)),
+        ], layout(root, BoxSize::Fixed { w: 200, h: 100 }));
+    }
+
+    #[test]
+    fn wrap_moves_children_to_stretched_lines() {
+        let style = row()
+            .wrap(true)
+            .gap(10)
+            .line_gap(10)
+            .align(Align::Start);
+        let root = Flex::new(style, vec![
+            item(FlexItem::new(), Block::new(40, 20)),
+            item(FlexItem::new(), Block::new(40, 20)),
+            item(FlexItem::new(), Block::new(40, 20)),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 100, 100)),
+            Some((0, 0, 40, 20)),
+            Some((50, 0, 40, 20)),
+            Some((0, 55, 40, 20)),
+        ], layout(root, BoxSize::Fixed { w: 100, h: 100 }));
+    }
+
+    #[test]
+    fn auto_size_fits_children() {
+        let style = FlexStyle::new()
+            .padding(Edges::all(5))
+            .gap(2);
+        let root = Flex::new(style, vec![
+            item(FlexItem::new(), Block::new(30, 10)),
+            item(FlexItem::new(), Block::new(50, 20)),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 60, 42)),
+            Some((5, 5, 50, 10)),
+            Some((5, 17, 50, 20)),
+        ], layout(root, BoxSize::Auto));
+    }
+
+    #[test]
+    fn nested_boxes_are_placed_in_root_coordinates() {
+        let column = Flex::new(FlexStyle::new(), vec![
+            item(FlexItem::new().grow(1.0), Block::new(10, 10)),
+            item(FlexItem::new().grow(1.0), Block::new(10, 10)),
+        ]);
+        let root = Flex::new(row().padding(Edges::all(10)), vec![
+            item(FlexItem::new().grow(1.0), column),
+            item(FlexItem::new(), Empty),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 220, 120)),
+            Some((10, 10, 200, 100)),
+            Some((10, 10, 200, 50)),
+            Some((10, 60, 200, 50)),
+            None,
+        ], layout(root, BoxSize::Fixed { w: 220, h: 120 }));
+    }
+}
@@ -0,0 +1,451 @@
+use crate::ResolvedSize;
+use super::placement::{Align, Edges, Placement};
+
+/// Size of a grid column or row.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Track {
+    Fixed(i32),
+    /// Share of the space left after other tracks, sized like `Auto` tracks in auto sized grids.
+    Fraction(f32),
+    /// Fits the largest child in the track. Auto tracks share the space left in grids
+    /// without fraction tracks.
+    Auto,
+}
+
+/// Layout of children in cells of a table with `Base::layout_grid`.
+///
+/// Rows that are not listed in `rows` are added as needed, and sized as `Track::Auto`.
+#[derive(Clone, Debug)]
+pub struct GridStyle {
+    pub columns: Vec<Track>,
+    pub rows: Vec<Track>,
+    pub column_gap: i32,
+    pub row_gap: i32,
+    pub padding: Edges,
+}
+
+impl GridStyle {
+    pub fn new() -> GridStyle {
+        GridStyle {
+            columns: Vec::new(),
+            rows: Vec::new(),
+            column_gap: 0,
+            row_gap: 0,
+            padding: Edges::new(),
+        }
+    }
+
+    pub fn column(mut self, track: Track) -> Self {
+        self.columns.push(track);
+        self
+    }
+
+    pub fn row(mut self, track: Track) -> Self {
+        self.rows.push(track);
+        self
+    }
+
+    pub fn gap(mut self, column_gap: i32, row_gap: i32) -> Self {
+        self.column_gap = column_gap;
+        self.row_gap = row_gap;
+        self
+    }
+
+    pub fn padding(mut self, padding: Edges) -> Self {
+        self.padding = padding;
+        self
+    }
+}
+
+impl Default for GridStyle {
+    fn default() -> Self {
+        GridStyle::new()
+    }
+}
+
+/// Cell of a child in the grid, set with `Base::set_grid_item`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GridItem {
+    /// Column and row, `None` places the child in the next free cell, row by row.
+    pub cell: Option<(usize, usize)>,
+    pub column_span: usize,
+    pub row_span: usize,
+    pub horizontal: Align,
+    pub vertical: Align,
+}
+
+impl GridItem {
+    pub fn new() -> GridItem {
+        GridItem {
+            cell: None,
+            column_span: 1,
+            row_span: 1,
+            horizontal: Align::Stretch,
+            vertical: Align::Stretch,
+        }
+    }
+
+    pub fn cell(mut self, column: usize, row: usize) -> Self {
+        self.cell = Some((column, row));
+        self
+    }
+
+    pub fn span(mut self, columns: usize, rows: usize) -> Self {
+        self.column_span = columns;
+        self.row_span = rows;
+        self
+    }
+
+    pub fn align(mut self, horizontal: Align, vertical: Align) -> Self {
+        self.horizontal = horizontal;
+        self.vertical = vertical;
+        self
+    }
+}
+
+impl Default for GridItem {
+    fn default() -> Self {
+        GridItem::new()
+    }
+}
+
+struct GridEntry {
+    index: usize,
+    item: GridItem,
+    column: usize,
+    row: usize,
+    column_span: usize,
+    row_span: usize,
+    size: ResolvedSize,
+}
+
+/// Places children measured with `BoxSize::Auto` in grid cells, in a box of `size`, or in the
+/// smallest box that fits them if `size` is `None`. Children without a measured size are hidden
+/// unless they stretch in both directions.
+///
+/// Returns the resolved size of the box, and placements of children, `None` for hidden ones.
+pub fn place_grid(
+    style: &GridStyle,
+    size: Option<ResolvedSize>,
+    items: &[(GridItem, Option<ResolvedSize>)],
+) -> (Option<ResolvedSize>, Vec<Option<Placement>>) {
+    let padding = style.padding;
+    let column_count = style.columns.len().max(1);
+    let mut occupied: Vec<Vec<bool>> = Vec::new();
+    let mut cursor = 0;
+
+    let mut entries = Vec::new();
+    for (index, &(item, measured)) in items.iter().enumerate() {
+        let stretched = item.horizontal == Align::Stretch && item.vertical == Align::Stretch;
+        if measured.is_none() && !stretched {
+            continue;
+        }
+
+        let column_span = item.column_span.max(1).min(column_count);
+        let row_span = item.row_span.max(1);
+
+        let (column, row) = match item.cell {
+            Some((column, row)) => (column.min(column_count - column_span), row),
+            None => {
+                let mut position = cursor;
+                loop {
+                    let (column, row) = (position % column_count, position / column_count);
+                    if column + column_span <= column_count
+                        && is_free(&occupied, column, row, column_span, row_span) {
+                        cursor = position + column_span;
+                        break (column, row);
+                    }
+                    position += 1;
+                }
+            }
+        };
+
+        while occupied.len() < row + row_span {
+            occupied.push(vec![false; column_count]);
+        }
+        for cells in &mut occupied[row..row + row_span] {
+            for cell in &mut cells[column..column + column_span] {
+                *cell = true;
+            }
+        }
+
+        entries.push(GridEntry {
+            index,
+            item,
+            column,
+            row,
+            column_span,
+            row_span,
+            size: measured.unwrap_or_else(ResolvedSize::zero),
+        });
+    }
+
+    let mut columns = style.columns.clone();
+    if columns.is_empty() {
+        columns.push(Track::Auto);
+    }
+    let mut rows = style.rows.clone();
+    while rows.len() < occupied.len() {
+        rows.push(Track::Auto);
+    }
+
+    let column_gap = style.column_gap as f32;
+    let row_gap = style.row_gap as f32;
+
+    let column_sizes = size_tracks(
+        &columns,
+        size.map(|s| (s.w - padding.horizontal()) as f32),
+        column_gap,
+        entries.iter().map(|e| (e.column, e.column_span, e.size.w as f32)),
+    );
+    let row_sizes = size_tracks(
+        &rows,
+        size.map(|s| (s.h - padding.vertical()) as f32),
+        row_gap,
+        entries.iter().map(|e| (e.row, e.row_span, e.size.h as f32)),
+    );
+
+    let column_offsets = track_offsets(&column_sizes, padding.left as f32, column_gap);
+    let row_offsets = track_offsets(&row_sizes, padding.top as f32, row_gap);
+
+    let mut placements = vec![None; items.len()];
+    for entry in &entries {
+        let cell_width = span_size(&column_sizes, entry.column, entry.column_span, column_gap);
+        let cell_height = span_size(&row_sizes, entry.row, entry.row_span, row_gap);
+
+        let (x, w) = entry.item.horizontal.place(cell_width, entry.size.w as f32);
+        let (y, h) = entry.item.vertical.place(cell_height, entry.size.h as f32);
+
+        placements[entry.index] = Some(Placement::round(
+            column_offsets[entry.column] + x,
+            row_offsets[entry.row] + y,
+            w,
+            h,
+        ));
+    }
+
+    let resolved_size = match size {
+        Some(size) => Some(size),
+        None if entries.is_empty() => None,
+        None => Some(ResolvedSize {
+            w: span_size(&column_sizes, 0, column_sizes.len(), column_gap).ceil() as i32 + padding.horizontal(),
+            h: span_size(&row_sizes, 0, row_sizes.len(), row_gap).ceil() as i32 + padding.vertical(),
+        }),
+    };
+
+    (resolved_size, placements)
+}
+
+fn is_free(occupied: &[Vec<bool>], column: usize, row: usize, column_span: usize, row_span: usize) -> bool {
+    occupied
+        .iter()
+        .skip(row)
+        .take(row_span)
+        .all(|cells| cells[column..column + column_span].iter().all(|&cell| !cell))
+}
+
+/// Sizes tracks to fit children spanning them, and fraction or auto tracks to fill the `available` space.
+///
+/// Children are given as their first track, number of spanned tracks and size.
+fn size_tracks<I>(tracks: &[Track], available: Option<f32>, gap: f32, children: I) -> Vec<f32>
+where
+    I: Iterator<Item = (usize, usize, f32)>,
+{
+    // fraction tracks fit children like auto tracks if there is no space to fill
+    let fits_content = |track: &Track| match *track {
+        Track::Fixed(_) => false,
+        Track::Fraction(_) => available.is_none(),
+        Track::Auto => true,
+    };
+
+    let mut sizes: Vec<f32> = tracks
+        .iter()
+        .map(|track| match *track {
+            Track::Fixed(size) => size as f32,
+            _ => 0.0,
+        })
+        .collect();
+
+    // children spanning fewer tracks are fitted first, wider ones only add the missing space
+    let mut children: Vec<(usize, usize, f32)> = children.collect();
+    children.sort_by_key(|&(_, span, _)| span);
+
+    for (start, span, size) in children {
+        let range = start..start + span;
+        let fitting = tracks[range.clone()].iter().filter(|t| fits_content(t)).count();
+        if fitting == 0 {
+            continue;
+        }
+
+        let current = sizes[range.clone()].iter().sum::<f32>() + gap * (span - 1) as f32;
+        if size > current {
+            let extra = (size - current) / fitting as f32;
+            for (track, track_size) in tracks[range.clone()].iter().zip(&mut sizes[range]) {
+                if fits_content(track) {
+                    *track_size += extra;
+                }
+            }
+        }
+    }
+
+    let fractions: f32 = tracks
+        .iter()
+        .map(|track| match *track {
+            Track::Fraction(fraction) => fraction.max(0.0),
+            _ => 0.0,
+        })
+        .sum();
+
+    if fractions > 0.0 {
+        // space of a single fraction, fitting the largest content if not filling
+        let unit = match available {
+            Some(available) => {
+                let used = sizes.iter().sum::<f32>() + gap * (tracks.len() - 1) as f32;
+                (available - used).max(0.0) / fractions
+            }
+            None => tracks
+                .iter()
+                .zip(&sizes)
+                .map(|(track, &size)| match *track {
+                    Track::Fraction(fraction) if fraction > 0.0 => size / fraction,
+                    _ => 0.0,
+                })
+                .fold(0.0, f32::max),
+        };
+
+        for (track, size) in tracks.iter().zip(&mut sizes) {
+            if let Track::Fraction(fraction) = *track {
+                *size = unit * fraction.max(0.0);
+            }
+        }
+    } else if let Some(available) = available {
+        let auto_count = tracks.iter().filter(|&&track| track == Track::Auto).count();
+        let used = sizes.iter().sum::<f32>() + gap * tracks.len().saturating_sub(1) as f32;
+        if auto_count > 0 && used < available {
+            let extra = (available - used) / auto_count as f32;
+            for (track, size) in tracks.iter().zip(&mut sizes) {
+                if *track == Track::Auto {
+                    *size += extra;
+                }
+            }
+        }
+    }
+
+    sizes
+}
+
+fn track_offsets(sizes: &[f32], start: f32, gap: f32) -> Vec<f32> {
+    let mut offset = start;
+    sizes
+        .iter()
+        .map(|size| {
+            let track_offset = offset;
+            offset += size + gap;
+            track_offset
+        })
+        .collect()
+}
+
+fn span_size(sizes: &[f32], start: usize, span: usize, gap: f32) -> f32 {
+    if span == 0 {
+        return 0.0;
+    }
+    sizes[start..start + span].iter().sum::<f32>() + gap * (span - 1) as f32
+}
+
+#[cfg(test)]
+mod grid_tests {
+    use crate::*;
+    use crate::layout::test_tree::*;
+
+    #[test]
+    fn fraction_tracks_share_space_left_by_fixed_and_auto_tracks() {
+        let style = GridStyle::new()
+            .column(Track::Fixed(50))
+            .column(Track::Auto)
+            .column(Track::Fraction(1.0))
+            .column(Track::Fraction(2.0))
+            .row(Track::Fraction(1.0))
+            .gap(10, 0);
+        let root = Grid::new(style, vec![
+            item(GridItem::new(), Block::new(10, 10)),
+            item(GridItem::new(), Block::new(30, 10)),
+            item(GridItem::new(), Block::new(10, 10)),
+            item(GridItem::new(), Block::new(10, 10)),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 320, 100)),
+            Some((0, 0, 50, 100)),
+            Some((60, 0, 30, 100)),
+            Some((100, 0, 70, 100)),
+            Some((180, 0, 140, 100)),
+        ], layout(root, BoxSize::Fixed { w: 320, h: 100 }));
+    }
+
+    #[test]
+    fn children_flow_into_free_cells_around_spans() {
+        let style = GridStyle::new()
+            .column(Track::Fixed(20))
+            .column(Track::Fixed(20))
+            .column(Track::Fixed(20));
+        let root = Grid::new(style, vec![
+            item(GridItem::new().span(2, 2), Block::new(10, 30)),
+            item(GridItem::new(), Block::new(10, 10)),
+            item(GridItem::new(), Block::new(10, 10)),
+            item(GridItem::new(), Block::new(10, 10)),
+            item(GridItem::new().cell(0, 3), Block::new(10, 10)),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 60, 50)),
+            Some((0, 0, 40, 30)),
+            Some((40, 0, 20, 15)),
+            Some((40, 15, 20, 15)),
+            Some((0, 30, 20, 10)),
+            Some((0, 40, 20, 10)),
+        ], layout(root, BoxSize::Auto));
+    }
+
+    #[test]
+    fn children_are_aligned_in_their_cells() {
+        let style = GridStyle::new()
+            .column(Track::Fraction(1.0))
+            .column(Track::Fraction(1.0))
+            .padding(Edges::all(10));
+        let root = Grid::new(style, vec![
+            item(GridItem::new().align(Align::Center, Align::End), Block::new(20, 10)),
+            item(GridItem::new().align(Align::End, Align::Start), Block::new(20, 10)),
+            item(GridItem::new().span(2, 1).align(Align::Stretch, Align::Center), Block::new(20, 10)),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 120, 60)),
+            Some((25, 20, 20, 10)),
+            Some((90, 10, 20, 10)),
+            Some((10, 35, 100, 10)),
+        ], layout(root, BoxSize::Fixed { w: 120, h: 60 }));
+    }
+
+    #[test]
+    fn auto_sized_grid_fits_fraction_tracks_to_content() {
+        let style = GridStyle::new()
+            .column(Track::Fraction(1.0))
+            .column(Track::Fraction(3.0))
+            .gap(4, 2);
+        let root = Grid::new(style, vec![
+            item(GridItem::new(), Block::new(20, 10)),
+            item(GridItem::new(), Block::new(30, 10)),
+            item(GridItem::new(), Block::new(10, 20)),
+            item(GridItem::new(), Empty),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 84, 32)),
+            Some((0, 0, 20, 10)),
+            Some((24, 0, 60, 10)),
+            Some((0, 12, 20, 20)),
+            None,
+        ], layout(root, BoxSize::Auto));
+    }
+}
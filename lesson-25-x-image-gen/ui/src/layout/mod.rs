@@ -0,0 +1,14 @@
+//! Placement of children inside a box, that does not depend on the tree, see `Base::layout_flex`,
+//! `Base::layout_grid` and `Base::layout_stack`.
+
+mod placement;
+mod flex;
+mod grid;
+mod stack;
+#[cfg(test)]
+mod test_tree;
+
+pub use self::placement::{Edges, Align, Placement};
+pub use self::flex::{FlexStyle, FlexItem, Justify, place_flex};
+pub use self::grid::{GridStyle, GridItem, Track, place_grid};
+pub use self::stack::{StackItem, place_stack};
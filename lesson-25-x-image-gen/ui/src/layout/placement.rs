@@ -0,0 +1,125 @@
+use crate::{FlowDirection, ResolvedSize};
+
+/// Space on the four sides of a box, in pixels.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Edges {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Edges {
+    pub fn new() -> Edges {
+        Edges::default()
+    }
+
+    pub fn all(value: i32) -> Edges {
+        Edges { left: value, top: value, right: value, bottom: value }
+    }
+
+    pub fn left(mut self, value: i32) -> Self {
+        self.left = value;
+        self
+    }
+
+    pub fn top(mut self, value: i32) -> Self {
+        self.top = value;
+        self
+    }
+
+    pub fn right(mut self, value: i32) -> Self {
+        self.right = value;
+        self
+    }
+
+    pub fn bottom(mut self, value: i32) -> Self {
+        self.bottom = value;
+        self
+    }
+
+    pub fn horizontal(&self) -> i32 {
+        self.left + self.right
+    }
+
+    pub fn vertical(&self) -> i32 {
+        self.top + self.bottom
+    }
+
+    /// Edges at the start and the end of the `flow` axis.
+    pub fn par(&self, flow: FlowDirection) -> (i32, i32) {
+        match flow {
+            FlowDirection::Vertical => (self.top, self.bottom),
+            FlowDirection::Horizontal => (self.left, self.right),
+        }
+    }
+
+    /// Edges at the start and the end of the axis orthogonal to `flow`.
+    pub fn ort(&self, flow: FlowDirection) -> (i32, i32) {
+        match flow {
+            FlowDirection::Vertical => (self.left, self.right),
+            FlowDirection::Horizontal => (self.top, self.bottom),
+        }
+    }
+}
+
+/// Alignment of a child inside the space given to it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Align {
+    Start,
+    End,
+    Center,
+    /// Fills the space.
+    Stretch,
+}
+
+impl Align {
+    /// Offset and size of a box with the preferred `size` in the space of `available` size.
+    pub(crate) fn place(&self, available: f32, size: f32) -> (f32, f32) {
+        match *self {
+            Align::Start => (0.0, size),
+            Align::End => (available - size, size),
+            Align::Center => ((available - size) / 2.0, size),
+            Align::Stretch => (0.0, available),
+        }
+    }
+}
+
+/// Position of a child relative to its parent, and the size it is resized to.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Placement {
+    pub offset: ResolvedSize,
+    pub size: ResolvedSize,
+}
+
+impl Placement {
+    /// Rounds edges of the box instead of its size, so that adjacent boxes stay adjacent.
+    pub(crate) fn round(x: f32, y: f32, w: f32, h: f32) -> Placement {
+        let left = x.round() as i32;
+        let top = y.round() as i32;
+
+        Placement {
+            offset: ResolvedSize { w: left, h: top },
+            size: ResolvedSize {
+                w: ((x + w).round() as i32 - left).max(0),
+                h: ((y + h).round() as i32 - top).max(0),
+            },
+        }
+    }
+
+    pub(crate) fn from_flow(flow: FlowDirection, ort: f32, par: f32, ort_size: f32, par_size: f32) -> Placement {
+        match flow {
+            FlowDirection::Vertical => Placement::round(ort, par, ort_size, par_size),
+            FlowDirection::Horizontal => Placement::round(par, ort, par_size, ort_size),
+        }
+    }
+}
+
+/// Limits `value` to `max`, and then to `min`, so that `min` wins if they conflict.
+pub(crate) fn clamp(value: f32, min: i32, max: Option<i32>) -> f32 {
+    let value = match max {
+        Some(max) => value.min(max as f32),
+        None => value,
+    };
+    value.max(min as f32)
+}
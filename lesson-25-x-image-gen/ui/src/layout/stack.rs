@@ -0,0 +1,172 @@
+use crate::ResolvedSize;
+use super::placement::{Align, Edges, Placement};
+
+/// Position of a child on top of its siblings, set with `Base::set_stack_item`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct StackItem {
+    /// Edge of the box the child is anchored to, or `Stretch` to fill the box horizontally.
+    pub horizontal: Align,
+    pub vertical: Align,
+    /// Moves the child from its anchored position, without changing the size of the box.
+    pub offset_x: i32,
+    pub offset_y: i32,
+    /// Space between the child and the edges of the box.
+    pub margin: Edges,
+}
+
+impl StackItem {
+    pub fn new() -> StackItem {
+        StackItem {
+            horizontal: Align::Start,
+            vertical: Align::Start,
+            offset_x: 0,
+            offset_y: 0,
+            margin: Edges::new(),
+        }
+    }
+
+    pub fn anchor(mut self, horizontal: Align, vertical: Align) -> Self {
+        self.horizontal = horizontal;
+        self.vertical = vertical;
+        self
+    }
+
+    pub fn offset(mut self, x: i32, y: i32) -> Self {
+        self.offset_x = x;
+        self.offset_y = y;
+        self
+    }
+
+    pub fn margin(mut self, margin: Edges) -> Self {
+        self.margin = margin;
+        self
+    }
+}
+
+impl Default for StackItem {
+    fn default() -> Self {
+        StackItem::new()
+    }
+}
+
+/// Places children measured with `BoxSize::Auto` over each other, in a box of `size`, or in the
+/// smallest box that fits them if `size` is `None`. Children without a measured size are hidden
+/// unless they stretch in both directions.
+///
+/// Returns the resolved size of the box, and placements of children, `None` for hidden ones.
+pub fn place_stack(
+    padding: Edges,
+    size: Option<ResolvedSize>,
+    items: &[(StackItem, Option<ResolvedSize>)],
+) -> (Option<ResolvedSize>, Vec<Option<Placement>>) {
+    let visible = |&(item, measured): &(StackItem, Option<ResolvedSize>)| {
+        measured.is_some() || (item.horizontal == Align::Stretch && item.vertical == Align::Stretch)
+    };
+
+    let inner = match size {
+        Some(size) => ResolvedSize {
+            w: (size.w - padding.horizontal()).max(0),
+            h: (size.h - padding.vertical()).max(0),
+        },
+        None => items
+            .iter()
+            .filter(|entry| visible(entry))
+            .fold(ResolvedSize::zero(), |inner, &(item, measured)| {
+                let measured = measured.unwrap_or_else(ResolvedSize::zero);
+                ResolvedSize {
+                    w: inner.w.max(measured.w + item.margin.horizontal()),
+                    h: inner.h.max(measured.h + item.margin.vertical()),
+                }
+            }),
+    };
+
+    let placements = items
+        .iter()
+        .map(|entry| {
+            if !visible(entry) {
+                return None;
+            }
+
+            let (item, measured) = *entry;
+            let measured = measured.unwrap_or_else(ResolvedSize::zero);
+            let (x, w) = item.horizontal.place((inner.w - item.margin.horizontal()) as f32, measured.w as f32);
+            let (y, h) = item.vertical.place((inner.h - item.margin.vertical()) as f32, measured.h as f32);
+
+            Some(Placement::round(
+                (padding.left + item.margin.left + item.offset_x) as f32 + x,
+                (padding.top + item.margin.top + item.offset_y) as f32 + y,
+                w,
+                h,
+            ))
+        })
+        .collect();
+
+    let resolved_size = match size {
+        Some(size) => Some(size),
+        None if !items.iter().any(&visible) => None,
+        None => Some(ResolvedSize {
+            w: inner.w + padding.horizontal(),
+            h: inner.h + padding.vertical(),
+        }),
+    };
+
+    (resolved_size, placements)
+}
+
+#[cfg(test)]
+mod stack_tests {
+    use crate::*;
+    use crate::layout::test_tree::*;
+
+    #[test]
+    fn children_overlap_at_their_anchors() {
+        let root = Stack::new(Edges::all(10), vec![
+            item(StackItem::new().anchor(Align::Stretch, Align::Stretch), Block::new(0, 0)),
+            item(StackItem::new().anchor(Align::Center, Align::End).offset(0, -5), Block::new(40, 20)),
+            item(StackItem::new().anchor(Align::End, Align::Start).margin(Edges::new().right(5)), Block::new(10, 10)),
+            item(StackItem::new(), Empty),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 200, 100)),
+            Some((10, 10, 180, 80)),
+            Some((80, 65, 40, 20)),
+            Some((175, 10, 10, 10)),
+            None,
+        ], layout(root, BoxSize::Fixed { w: 200, h: 100 }));
+    }
+
+    #[test]
+    fn auto_sized_stack_fits_largest_child() {
+        let root = Stack::new(Edges::all(2), vec![
+            item(StackItem::new().anchor(Align::Stretch, Align::Stretch), Block::new(10, 10)),
+            item(StackItem::new().anchor(Align::Center, Align::Center).margin(Edges::all(4)), Block::new(30, 10)),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 42, 22)),
+            Some((2, 2, 38, 18)),
+            Some((6, 6, 30, 10)),
+        ], layout(root, BoxSize::Auto));
+    }
+
+    #[test]
+    fn stack_and_flex_nest_in_root_coordinates() {
+        let overlay = Stack::new(Edges::new(), vec![
+            item(StackItem::new().anchor(Align::Stretch, Align::Stretch), Block::new(10, 10)),
+            item(StackItem::new().anchor(Align::Start, Align::End).offset(5, 0), Block::new(20, 10)),
+        ]);
+        let root = Flex::new(FlexStyle::new().padding(Edges::all(10)), vec![
+            item(FlexItem::new(), Block::new(10, 30)),
+            item(FlexItem::new().grow(1.0), overlay),
+        ]);
+
+        assert_eq!(vec![
+            Some((0, 0, 100, 100)),
+            Some((10, 10, 80, 30)),
+            Some((10, 40, 80, 50)),
+            Some((10, 40, 80, 50)),
+            Some((15, 80, 20, 10)),
+        ], layout(root, BoxSize::Fixed { w: 100, h: 100 }));
+    }
+}
@@ -0,0 +1,166 @@
+//! Trees of elements with known sizes for layout tests, built without fonts or GL.
+//!
+//! Rectangles of nodes are collected from the effects the tree sends to the renderer.
+
+use std::collections::BTreeMap;
+use resources::Resources;
+use crate::*;
+
+/// Rectangle of a node in root coordinates, `(x, y, w, h)`.
+pub type Rect = (i32, i32, i32, i32);
+
+/// Measures as `w` by `h` with `BoxSize::Auto`, and fills the size it is given otherwise.
+pub struct Block {
+    w: i32,
+    h: i32,
+}
+
+impl Block {
+    pub fn new(w: i32, h: i32) -> Block {
+        Block { w, h }
+    }
+}
+
+impl Element for Block {
+    fn resize(&mut self, base: &mut Base) {
+        let size = match base.box_size() {
+            BoxSize::Hidden => None,
+            BoxSize::Auto => Some(ResolvedSize { w: self.w, h: self.h }),
+            BoxSize::Fixed { w, h } => Some(ResolvedSize { w, h }),
+        };
+        base.resolve_size(size);
+    }
+}
+
+/// Never has a size.
+pub struct Empty;
+
+impl Element for Empty {
+    fn resize(&mut self, base: &mut Base) {
+        base.resolve_size(None);
+    }
+}
+
+pub struct Flex {
+    style: FlexStyle,
+    children: Vec<(FlexItem, Box<Element>)>,
+}
+
+impl Flex {
+    pub fn new(style: FlexStyle, children: Vec<(FlexItem, Box<Element>)>) -> Flex {
+        Flex { style, children }
+    }
+}
+
+impl Element for Flex {
+    fn inflate(&mut self, base: &mut Base) {
+        for (item, element) in self.children.drain(..) {
+            let id = base.add_boxed(element);
+            base.set_flex_item(id, item);
+        }
+    }
+
+    fn resize(&mut self, base: &mut Base) {
+        base.layout_flex(&self.style);
+    }
+}
+
+pub struct Grid {
+    style: GridStyle,
+    children: Vec<(GridItem, Box<Element>)>,
+}
+
+impl Grid {
+    pub fn new(style: GridStyle, children: Vec<(GridItem, Box<Element>)>) -> Grid {
+        Grid { style, children }
+    }
+}
+
+impl Element for Grid {
+    fn inflate(&mut self, base: &mut Base) {
+        for (item, element) in self.children.drain(..) {
+            let id = base.add_boxed(element);
+            base.set_grid_item(id, item);
+        }
+    }
+
+    fn resize(&mut self, base: &mut Base) {
+        base.layout_grid(&self.style);
+    }
+}
+
+pub struct Stack {
+    padding: Edges,
+    children: Vec<(StackItem, Box<Element>)>,
+}
+
+impl Stack {
+    pub fn new(padding: Edges, children: Vec<(StackItem, Box<Element>)>) -> Stack {
+        Stack { padding, children }
+    }
+}
+
+impl Element for Stack {
+    fn inflate(&mut self, base: &mut Base) {
+        for (item, element) in self.children.drain(..) {
+            let id = base.add_boxed(element);
+            base.set_stack_item(id, item);
+        }
+    }
+
+    fn resize(&mut self, base: &mut Base) {
+        base.layout_stack(self.padding);
+    }
+}
+
+/// Pairs layout properties of a child with the child element.
+pub fn item<T, E: Element + 'static>(item: T, element: E) -> (T, Box<Element>) {
+    (item, Box::new(element) as Box<Element>)
+}
+
+/// Lays out the `root` in a box of `size`.
+///
+/// Returns rectangles of all nodes in the order they were added, the root first,
+/// `None` for hidden nodes.
+pub fn layout<E: Element + 'static>(root: E, size: BoxSize) -> Vec<Option<Rect>> {
+    let tree = Tree::new(&Resources::new());
+    let events = tree.events();
+    let root = tree.create_root(root, 1.0);
+    root.resize(size, 1.0);
+
+    let mut effects = Vec::new();
+    events.drain_into(&mut effects);
+
+    let mut nodes = BTreeMap::new();
+    for effect in effects {
+        match effect {
+            Effect::Add { id, .. } => {
+                nodes.insert(id, (None, None));
+            }
+            Effect::Remove { id } => {
+                nodes.remove(&id);
+            }
+            Effect::Transform { id, absolute_transform } => {
+                if let Some(node) = nodes.get_mut(&id) {
+                    node.0 = absolute_transform;
+                }
+            }
+            Effect::Resize { id, size } => {
+                if let Some(node) = nodes.get_mut(&id) {
+                    node.1 = size;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    nodes.values()
+        .map(|&(transform, size)| match (transform, size) {
+            (Some(transform), Some((w, h))) => {
+                let origin = transform * na::Point3::origin();
+                Some((origin.x.round() as i32, origin.y.round() as i32, w, h))
+            }
+            _ => None,
+        })
+        .collect()
+}
@@ -14,18 +14,30 @@ extern crate lyon_geom;
 extern crate unicode_segmentation;
 extern crate resources;
 extern crate usvg;
-extern crate failure;
+#[macro_use] extern crate failure;
 extern crate lyon_tessellation;
+extern crate xi_unicode;
+extern crate unicode_bidi;
 
 mod tree;
+pub mod input;
 pub mod primitives;
 mod queues;
 pub mod fonts;
+pub mod shapes;
 pub mod svg;
-
-pub use crate::primitives::Primitives;
+pub mod text;
+pub mod layout;
+mod variable_font;
+
+pub use crate::primitives::{Primitives, Shape, RichTextLine};
+pub use crate::shapes::{Paint, GradientStop, ShapeStyle, StrokeStyle, ShapeRef};
+pub use crate::svg::SvgImage;
+pub use crate::text::{RichText, SpanStyle, Paragraph, ParagraphStyle, TextAlign, Direction};
+pub use crate::layout::{Edges, Align, Placement, FlexStyle, FlexItem, Justify, GridStyle, GridItem, Track, StackItem};
 pub use crate::tree::{Base, Events, LastResolvedSize, Leaf, Tree};
-pub use crate::fonts::{Fonts, Font, BufferRef, GlyphPosition, HintingOptions};
+pub use crate::input::{Clipboard, EventPhase, Key, KeyEvent, Modifiers, PointerEvent, PointerRay, TextEvent};
+pub use crate::fonts::{Fonts, Font, Error as FontError, BufferRef, GlyphPosition, HintingOptions, TextRenderer, SdfTextStyle, ShapingOptions, FontFeature, FontVariation};
 
 fn approx_equal(a: f32, b: f32, decimal_places: u8) -> bool {
     let factor = 10.0f32.powi(decimal_places as i32);
@@ -101,8 +113,20 @@ pub enum Effect {
         id: Ix,
         absolute_transform: Option<na::Projective3<f32>>,
     },
+    /// Enables or disables clipping to the node box, see `Base::set_clip`.
+    Clip {
+        id: Ix,
+        enabled: bool,
+    },
+    /// Opacity of the node relative to its parent, multiplied into all its descendants.
+    Opacity {
+        id: Ix,
+        opacity: f32,
+    },
     TextAdd {
+        node_id: Ix,
         buffer: fonts::BufferRef,
+        renderer: fonts::TextRenderer,
     },
     TextUpdate {
         buffer_id: usize,
@@ -112,13 +136,22 @@ pub enum Effect {
     TextRemove {
         buffer_id: usize,
     },
-}
-
-/// This is a hack - the ui does not yet have input events
-#[derive(Copy, Clone, Debug)]
-pub enum UiAction {
-    NextSlide,
-    PreviousSlide,
+    /// The font was reloaded, glyphs cached for it are outdated. Text that uses the font
+    /// is removed and added again after this.
+    FontReload {
+        font_id: usize,
+    },
+    ShapeAdd {
+        node_id: Ix,
+        shape: shapes::ShapeRef,
+    },
+    ShapeUpdate {
+        shape_id: usize,
+        absolute_transform: Option<na::Projective3<f32>>,
+    },
+    ShapeRemove {
+        shape_id: usize,
+    },
 }
 
 pub trait Element {
@@ -129,7 +162,22 @@ pub trait Element {
         base.layout_vertical(margin, item_gap)
     }
     fn update(&mut self, _base: &mut Base, _delta: f32) {}
-    fn action(&mut self, _base: &mut Base, _action: UiAction) {}
+
+    // Input is delivered to elements after `Base::enable_input`. Pointer events go along the
+    // path to the element under the pointer, key and text events along the path to the focused
+    // element. Handlers return `true` if they handled the event, which stops its delivery.
+
+    /// Handling the event captures the pointer, the element then receives all pointer events
+    /// until `pointer_up`.
+    fn pointer_down(&mut self, _base: &mut Base, _event: &PointerEvent) -> bool { false }
+    fn pointer_move(&mut self, _base: &mut Base, _event: &PointerEvent) -> bool { false }
+    fn pointer_up(&mut self, _base: &mut Base, _event: &PointerEvent) -> bool { false }
+    /// The pointer entered or left the element or one of its descendants.
+    fn hover(&mut self, _base: &mut Base, _hovered: bool) {}
+    /// The element gained or lost focus, see `Base::focus`.
+    fn focus(&mut self, _base: &mut Base, _focused: bool) {}
+    fn key(&mut self, _base: &mut Base, _event: &KeyEvent) -> bool { false }
+    fn text(&mut self, _base: &mut Base, _event: &TextEvent) -> bool { false }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
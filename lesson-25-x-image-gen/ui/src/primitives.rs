@@ -3,6 +3,8 @@ use std::rc::Rc;
 
 use crate::*;
 use crate::fonts::*;
+use crate::shapes::*;
+use crate::text::RichText;
 use resources::{Resources};
 
 pub use self::shared::ModificationLogEntry;
@@ -25,6 +27,8 @@ pub struct TextMeasurement {
     font_scale: f32,
 
     metrics: Option<Measurement>,
+    /// Font generation the metrics were measured with, see `Font::generation`.
+    generation: usize,
     glyph_positions: Vec<GlyphPosition>,
 
     shared: Rc<RefCell<shared::InnerPrimitives>>,
@@ -35,10 +39,15 @@ impl TextMeasurement {
     fn update_metrics(&mut self) {
         self.glyph_positions.clear();
         self.metrics = self.buffer.measure(&mut self.glyph_positions);
+        self.generation = self.buffer.font().generation();
+    }
+
+    fn is_outdated(&self) -> bool {
+        self.metrics.is_none() || self.generation != self.buffer.font().generation()
     }
 
     pub fn measure(&mut self) -> Option<Measurement> {
-        if self.metrics.is_none() {
+        if self.is_outdated() {
             self.update_metrics();
         }
 
@@ -61,7 +70,7 @@ impl TextMeasurement {
     }
 
     pub fn glyph_positions<'r>(&'r mut self) -> impl Iterator<Item=GlyphMeasurement> + 'r {
-        if self.metrics.is_none() {
+        if self.is_outdated() {
             self.update_metrics();
         }
 
@@ -138,6 +147,31 @@ impl Text {
         self.update_transform();
     }
 
+    /// Switches between tessellated and distance field glyphs.
+    pub fn set_renderer(&mut self, renderer: TextRenderer) {
+        if self.measurement.buffer.renderer() == renderer {
+            return;
+        }
+
+        self.measurement.buffer.set_renderer(renderer);
+        self.measurement.shared.borrow_mut().recreate_text_buffer(self.slot);
+        self.update_transform();
+    }
+
+    /// Shapes the text again, for example as right-to-left or vertical text.
+    ///
+    /// Vertical text starts at the position and goes down, centered on it horizontally.
+    pub fn set_shaping(&mut self, shaping: ShapingOptions) {
+        if self.measurement.buffer.shaping() == shaping {
+            return;
+        }
+
+        self.measurement.buffer.set_shaping(shaping);
+        self.measurement.metrics = None;
+        self.measurement.shared.borrow_mut().recreate_text_buffer(self.slot);
+        self.update_transform();
+    }
+
     fn update_transform(&self) {
         if self.hidden {
             let mut shared = self.measurement.shared.borrow_mut();
@@ -170,6 +204,208 @@ impl Drop for Text {
     }
 }
 
+struct RichTextSpan {
+    text: Text,
+    /// Byte offset of the span in the rich text.
+    offset: usize,
+    /// Size of this span, `None` if it follows the size of the line.
+    size: Option<f32>,
+}
+
+/// Spans of rich text placed one after another on a shared baseline.
+///
+/// Every span is a separate text buffer shaped with its own font and size.
+pub struct RichTextLine {
+    spans: Vec<RichTextSpan>,
+
+    position: na::Vector3<f32>,
+    vertical: bool,
+}
+
+impl RichTextLine {
+    /// Sets the size of spans without their own size.
+    pub fn set_size(&mut self, size: f32) {
+        for span in self.spans.iter_mut().filter(|s| s.size.is_none()) {
+            span.text.set_size(size);
+        }
+        self.update_layout();
+    }
+
+    pub fn set_transform(&mut self, transform: &na::Projective3<f32>) {
+        for span in self.spans.iter_mut() {
+            span.text.set_transform(transform);
+        }
+    }
+
+    pub fn set_hidden(&mut self, value: bool) {
+        for span in self.spans.iter_mut() {
+            span.text.set_hidden(value);
+        }
+    }
+
+    /// Moves the start of the baseline to `x`, `y`.
+    pub fn set_position(&mut self, x: f32, y: f32) {
+        self.position = [x, y, 0.0].into();
+        self.update_layout();
+    }
+
+    pub fn set_position_3d(&mut self, x: f32, y: f32, z: f32) {
+        self.position = [x, y, z].into();
+        self.update_layout();
+    }
+
+    pub fn set_renderer(&mut self, renderer: TextRenderer) {
+        for span in self.spans.iter_mut() {
+            span.text.set_renderer(renderer);
+        }
+    }
+
+    /// Shapes all spans again, vertical spans are placed one below another.
+    pub fn set_shaping(&mut self, shaping: ShapingOptions) {
+        self.vertical = shaping.vertical;
+        for span in self.spans.iter_mut() {
+            span.text.set_shaping(shaping.clone());
+        }
+        self.update_layout();
+    }
+
+    /// Measures all spans together: the width is the sum of span widths, vertical metrics
+    /// are the largest of all spans. Vertical lines sum heights instead.
+    pub fn measure(&mut self) -> Option<Measurement> {
+        let vertical = self.vertical;
+        let mut line: Option<Measurement> = None;
+
+        for span in self.spans.iter_mut() {
+            let m = match span.text.measurement().measure() {
+                Some(m) => m,
+                None => continue,
+            };
+
+            line = Some(match line {
+                None => m,
+                Some(l) => Measurement {
+                    ascent: l.ascent.max(m.ascent),
+                    descent: l.descent.min(m.descent),
+                    width: if vertical { l.width.max(m.width) } else { l.width + m.width },
+                    cap_height: l.cap_height.max(m.cap_height),
+                    x_height: l.x_height.max(m.x_height),
+                    line_gap: l.line_gap.max(m.line_gap),
+                    height: if vertical { l.height + m.height } else { 0.0 },
+                },
+            });
+        }
+
+        if vertical {
+            return line;
+        }
+
+        line.map(|l| Measurement {
+            height: l.line_gap + l.ascent - l.descent,
+            ..l
+        })
+    }
+
+    /// Glyphs of all spans, with byte offsets in the whole rich text.
+    pub fn glyph_positions(&mut self, output: &mut Vec<GlyphMeasurement>) {
+        for span in self.spans.iter_mut() {
+            let offset = span.offset as u32;
+            output.extend(
+                span.text.measurement().glyph_positions()
+                    .map(|g| GlyphMeasurement {
+                        cluster: g.cluster + offset,
+                        byte_offset: g.byte_offset + offset,
+                        ..g
+                    })
+            );
+        }
+    }
+
+    fn update_layout(&mut self) {
+        let mut x = self.position.x;
+        let mut y = self.position.y;
+
+        for span in self.spans.iter_mut() {
+            let m = span.text.measurement().measure();
+            span.text.set_position_3d(x, y, self.position.z);
+            if self.vertical {
+                y += m.map(|m| m.height).unwrap_or(0.0);
+            } else {
+                x += m.map(|m| m.width).unwrap_or(0.0);
+            }
+        }
+    }
+}
+
+pub struct Shape {
+    shape: ShapeRef,
+
+    position: na::Vector3<f32>,
+    transform: na::Projective3<f32>,
+    slot: shared::PrimitiveSlot,
+    hidden: bool,
+
+    shared: Rc<RefCell<shared::InnerPrimitives>>,
+}
+
+impl Shape {
+    pub fn id(&self) -> usize {
+        self.shape.id()
+    }
+
+    pub fn style(&self) -> &ShapeStyle {
+        self.shape.style()
+    }
+
+    pub fn set_transform(&mut self, transform: &na::Projective3<f32>) {
+        self.transform = transform.clone();
+        self.update_transform();
+    }
+
+    pub fn set_hidden(&mut self, value: bool) {
+        self.hidden = value;
+        self.update_transform();
+    }
+
+    pub fn set_position(&mut self, x: f32, y: f32) {
+        self.position = [x, y, 0.0].into();
+        self.update_transform();
+    }
+
+    pub fn set_position_3d(&mut self, x: f32, y: f32, z: f32) {
+        self.position = [x, y, z].into();
+        self.update_transform();
+    }
+
+    /// Replaces fill and stroke, the shape is tessellated again.
+    pub fn set_style(&mut self, style: ShapeStyle) {
+        if *self.shape.style() == style {
+            return;
+        }
+
+        self.shape = Rc::new(self.shape.with_style(style));
+        self.shared.borrow_mut().replace_shape(self.slot, self.shape.clone());
+        self.update_transform();
+    }
+
+    fn update_transform(&self) {
+        let mut shared = self.shared.borrow_mut();
+        if self.hidden {
+            shared.set_shape_transform(self.slot, None);
+        } else {
+            shared.set_shape_transform(self.slot, Some(
+                self.transform
+                    * na::convert::<_, na::Projective3<_>>(na::Translation3::new(self.position.x, self.position.y, self.position.z))
+            ));
+        }
+    }
+}
+
+impl Drop for Shape {
+    fn drop(&mut self) {
+        self.shared.borrow_mut().delete_primitive(self.slot);
+    }
+}
+
 #[derive(Clone)]
 pub struct Primitives {
     fonts: Fonts,
@@ -186,6 +422,10 @@ impl Primitives {
         }
     }
 
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
+
     pub fn text<P: ToString>(&mut self, text: P, bold: bool, italic: bool, monospaced: bool, color: na::Vector4<u8>) -> Option<Text> {
         let text = text.to_string();
         let mut properties = Properties::new();
@@ -197,7 +437,7 @@ impl Primitives {
         }
 
         let font = if monospaced {
-            self.fonts.find_best_match(&[FamilyName::Title("Menlo".into()), FamilyName::Monospace], &properties)
+            self.fonts.find_best_match(&[FamilyName::Monospace], &properties)
         } else {
             self.fonts.find_best_match(&[FamilyName::SansSerif], &properties)
         };
@@ -223,6 +463,7 @@ impl Primitives {
             return Some(Text {
                 measurement: TextMeasurement {
                     metrics: None,
+                    generation: 0,
                     glyph_positions: Vec::with_capacity(text_len),
 
                     size,
@@ -244,6 +485,72 @@ impl Primitives {
 
         None
     }
+
+    /// Creates a text buffer for every span, spans without a font are skipped.
+    pub fn rich_text(&mut self, text: &RichText) -> RichTextLine {
+        let mut spans = Vec::with_capacity(text.spans().len());
+
+        for (offset, span) in text.spans_with_offsets() {
+            let style = span.style;
+            if let Some(mut span_text) = self.text(span.text.clone(), style.bold, style.italic, style.monospaced, style.color) {
+                if let Some(size) = style.size {
+                    span_text.set_size(size);
+                }
+
+                spans.push(RichTextSpan {
+                    text: span_text,
+                    offset,
+                    size: style.size,
+                });
+            }
+        }
+
+        let mut line = RichTextLine {
+            spans,
+            position: na::zero(),
+            vertical: false,
+        };
+        line.update_layout();
+        line
+    }
+
+    /// Shape from an arbitrary path, coordinates are in the same units as the element size.
+    pub fn shape(&mut self, path: lyon_path::default::Path, style: ShapeStyle) -> Shape {
+        let shape = Rc::new(ShapeData::new(path, style));
+        let slot = self.shared.borrow_mut().create_shape(shape.clone());
+
+        let shape = Shape {
+            shape,
+            position: na::zero(),
+            transform: na::Projective3::<f32>::identity(),
+            slot,
+            hidden: false,
+            shared: self.shared.clone(),
+        };
+
+        shape.update_transform();
+        shape
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, style: ShapeStyle) -> Shape {
+        self.shape(rect_path(x, y, w, h), style)
+    }
+
+    pub fn rounded_rect(&mut self, x: f32, y: f32, w: f32, h: f32, radius: f32, style: ShapeStyle) -> Shape {
+        self.shape(rounded_rect_path(x, y, w, h, radius), style)
+    }
+
+    pub fn circle(&mut self, cx: f32, cy: f32, radius: f32, style: ShapeStyle) -> Shape {
+        self.shape(circle_path(cx, cy, radius), style)
+    }
+
+    pub fn polyline(&mut self, points: &[na::Point2<f32>], closed: bool, style: ShapeStyle) -> Shape {
+        self.shape(polyline_path(points, closed), style)
+    }
+
+    pub fn arrow(&mut self, from: na::Point2<f32>, to: na::Point2<f32>, head_size: f32, style: ShapeStyle) -> Shape {
+        self.shape(arrow_path(from, to, head_size), style)
+    }
 }
 
 pub mod shared {
@@ -251,6 +558,7 @@ pub mod shared {
     use slotmap;
 
     use crate::fonts::*;
+    use crate::shapes::ShapeRef;
 
     new_key_type! { pub struct PrimitiveSlot; }
 
@@ -265,10 +573,10 @@ pub mod shared {
 
     impl PrimitiveSlotData {
         pub fn update_buffer(&mut self, window_scale: f32) {
+            let transform = self.outer_transform.map(|transform| PrimitiveSlotData::calc_transform(&transform, window_scale));
             match self.kind {
-                PrimitiveKind::TextBuffer(ref mut b) => b.set_transform(
-                    self.outer_transform.map(|transform| PrimitiveSlotData::calc_transform(&transform, window_scale))
-                ),
+                PrimitiveKind::TextBuffer(ref mut b) => b.set_transform(transform),
+                PrimitiveKind::Shape { transform: ref mut t, .. } => *t = transform,
             }
         }
 
@@ -281,11 +589,26 @@ pub mod shared {
 
     pub enum PrimitiveKind {
         TextBuffer(Buffer),
+        Shape {
+            shape: ShapeRef,
+            transform: Option<na::Projective3<f32>>,
+        },
+    }
+
+    impl PrimitiveKind {
+        fn removed_log_entry(&self) -> ModificationLogEntry {
+            match *self {
+                PrimitiveKind::TextBuffer(ref b) => ModificationLogEntry::Removed { buffer_id: b.id() },
+                PrimitiveKind::Shape { ref shape, .. } => ModificationLogEntry::ShapeRemoved { shape_id: shape.id() },
+            }
+        }
     }
 
     pub enum ModificationLogEntry {
         Added { buffer: Buffer },
         Removed { buffer_id: usize },
+        ShapeAdded { shape: ShapeRef },
+        ShapeRemoved { shape_id: usize },
     }
 
     pub struct InnerPrimitives {
@@ -355,19 +678,82 @@ pub mod shared {
             (slot, buffer)
         }
 
+        pub fn create_shape(&mut self, shape: ShapeRef) -> PrimitiveSlot {
+            let data = PrimitiveSlotData {
+                invalidated: true,
+                kind: PrimitiveKind::Shape {
+                    shape: shape.clone(),
+                    transform: None,
+                },
+                outer_transform: None,
+            };
+
+            let slot = self.primitive_slots.insert(PrimitiveSlotKeyData {});
+            self.modification_log.push(ModificationLogEntry::ShapeAdded { shape });
+
+            self.primitive_data.insert(slot, data);
+            self.invalidated = true;
+
+            slot
+        }
+
+        pub fn set_shape_transform(&mut self, slot: PrimitiveSlot, transform: Option<na::Projective3<f32>>) {
+            if let Some(data) = self.primitive_data.get_mut(slot) {
+                self.invalidated = true;
+                data.invalidated = true;
+                data.outer_transform = transform;
+                data.update_buffer(self.window_scale);
+            }
+        }
+
+        /// Sends the new shape data to the renderer, which replaces the old one with the same id.
+        pub fn replace_shape(&mut self, slot: PrimitiveSlot, new_shape: ShapeRef) {
+            if let Some(data) = self.primitive_data.get_mut(slot) {
+                self.invalidated = true;
+                data.invalidated = true;
+                if let PrimitiveKind::Shape { ref mut shape, .. } = data.kind {
+                    self.modification_log.push(ModificationLogEntry::ShapeRemoved { shape_id: shape.id() });
+                    self.modification_log.push(ModificationLogEntry::ShapeAdded { shape: new_shape.clone() });
+                    *shape = new_shape;
+                }
+            }
+        }
+
         pub fn delete_primitive(&mut self, slot: PrimitiveSlot) {
             if let Some(data) = self.primitive_data.remove(slot) {
                 self.invalidated = true;
-                match data.kind {
-                    PrimitiveKind::TextBuffer(b) => {
-                        self.modification_log.push(ModificationLogEntry::Removed { buffer_id: b.id() });
-                    }
-                };
+                self.modification_log.push(data.kind.removed_log_entry());
             }
 
             self.primitive_slots.remove(slot);
         }
 
+        /// Sends the buffer to the renderer again, for changes that require a different renderer.
+        pub fn recreate_text_buffer(&mut self, slot: PrimitiveSlot) {
+            if let Some(data) = self.primitive_data.get_mut(slot) {
+                self.invalidated = true;
+                data.invalidated = true;
+                if let PrimitiveKind::TextBuffer(ref b) = data.kind {
+                    self.modification_log.push(ModificationLogEntry::Removed { buffer_id: b.id() });
+                    self.modification_log.push(ModificationLogEntry::Added { buffer: b.clone() });
+                }
+            }
+        }
+
+        /// Sends text buffers of the given fonts to the renderer again, after the fonts were reloaded.
+        pub fn recreate_font_text_buffers(&mut self, font_ids: &[usize]) {
+            for (_, data) in self.primitive_data.iter_mut() {
+                if let PrimitiveKind::TextBuffer(ref b) = data.kind {
+                    if font_ids.iter().any(|&font_id| b.uses_font(font_id)) {
+                        self.invalidated = true;
+                        data.invalidated = true;
+                        self.modification_log.push(ModificationLogEntry::Removed { buffer_id: b.id() });
+                        self.modification_log.push(ModificationLogEntry::Added { buffer: b.clone() });
+                    }
+                }
+            }
+        }
+
         pub fn invalidate_text_buffer(&mut self, slot: PrimitiveSlot) {
             if let Some(data) = self.primitive_data.get_mut(slot) {
                 self.invalidated = true;
@@ -379,30 +765,24 @@ pub mod shared {
             self.modification_log.drain(..)
         }
 
-        pub(crate) fn buffers_keep_invalidated<'r>(&'r mut self) -> impl Iterator<Item=&'r Buffer> + 'r {
+        pub(crate) fn primitives_keep_invalidated<'r>(&'r mut self) -> impl Iterator<Item=&'r PrimitiveKind> + 'r {
             self.primitive_data
                 .iter_mut()
-                .filter_map(|(_, v)| {
-                    match v.kind {
-                        PrimitiveKind::TextBuffer(ref b) => Some(b),
-                    }
-                })
+                .map(|(_, v)| &v.kind)
         }
 
-        pub(crate) fn buffers<'r>(&'r mut self) -> impl Iterator<Item=&'r Buffer> + 'r {
+        pub(crate) fn primitives<'r>(&'r mut self) -> impl Iterator<Item=&'r PrimitiveKind> + 'r {
             self.invalidated = false;
 
             self.primitive_data
                 .iter_mut()
-                .filter_map(|(_, v)| {
+                .map(|(_, v)| {
                     v.invalidated = false;
-                    match v.kind {
-                        PrimitiveKind::TextBuffer(ref b) => Some(b),
-                    }
+                    &v.kind
                 })
         }
 
-        pub(crate) fn only_invalidated_buffers<'r>(&'r mut self) -> impl Iterator<Item=&'r Buffer> + 'r {
+        pub(crate) fn only_invalidated_primitives<'r>(&'r mut self) -> impl Iterator<Item=&'r PrimitiveKind> + 'r {
             self.invalidated = false;
 
             self.primitive_data
@@ -410,9 +790,7 @@ pub mod shared {
                 .filter_map(|(_, v)| {
                     if v.invalidated {
                         v.invalidated = false;
-                        match v.kind {
-                            PrimitiveKind::TextBuffer(ref b) => Some(b),
-                        }
+                        Some(&v.kind)
                     } else {
                         None
                     }
@@ -0,0 +1,337 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::na;
+use lyon_path::default::Path;
+use lyon_path::builder::{FlatPathBuilder, PathBuilder};
+use lyon_path::math::{point, Point};
+use lyon_path::PathEvent;
+use metrohash::MetroHash64;
+
+pub use lyon_tessellation::{LineCap, LineJoin};
+
+/// Shape ids are unique across all `Primitives`, like text buffer ids.
+static NEXT_SHAPE_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Control point distance for a cubic bezier approximating a quarter circle.
+const KAPPA: f32 = 0.552_284_8;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GradientStop {
+    /// Position along the gradient in `[0, 1]` range.
+    pub offset: f32,
+    pub color: na::Vector4<u8>,
+}
+
+impl GradientStop {
+    pub fn new(offset: f32, color: na::Vector4<u8>) -> GradientStop {
+        GradientStop { offset, color }
+    }
+}
+
+/// Fill or stroke paint. Gradient coordinates are in the same space as the shape path.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Paint {
+    Color(na::Vector4<u8>),
+    LinearGradient {
+        start: na::Point2<f32>,
+        end: na::Point2<f32>,
+        stops: Vec<GradientStop>,
+    },
+    RadialGradient {
+        center: na::Point2<f32>,
+        radius: f32,
+        stops: Vec<GradientStop>,
+    },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StrokeStyle {
+    pub width: f32,
+    pub cap: LineCap,
+    pub join: LineJoin,
+}
+
+impl StrokeStyle {
+    pub fn new(width: f32) -> StrokeStyle {
+        StrokeStyle {
+            width,
+            cap: LineCap::Butt,
+            join: LineJoin::Miter,
+        }
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShapeStyle {
+    pub fill: Option<Paint>,
+    pub stroke: Option<(Paint, StrokeStyle)>,
+}
+
+impl ShapeStyle {
+    pub fn fill(paint: Paint) -> ShapeStyle {
+        ShapeStyle {
+            fill: Some(paint),
+            stroke: None,
+        }
+    }
+
+    pub fn stroke(paint: Paint, width: f32) -> ShapeStyle {
+        ShapeStyle {
+            fill: None,
+            stroke: Some((paint, StrokeStyle::new(width))),
+        }
+    }
+
+    pub fn with_fill(mut self, paint: Paint) -> Self {
+        self.fill = Some(paint);
+        self
+    }
+
+    pub fn with_stroke(mut self, paint: Paint, stroke: StrokeStyle) -> Self {
+        self.stroke = Some((paint, stroke));
+        self
+    }
+}
+
+/// Path and style of a shape, shared with the renderer through `Effect::ShapeAdd`.
+pub struct ShapeData {
+    id: usize,
+    content_hash: u64,
+    path: Path,
+    style: ShapeStyle,
+}
+
+pub type ShapeRef = Rc<ShapeData>;
+
+impl ShapeData {
+    pub(crate) fn new(path: Path, style: ShapeStyle) -> ShapeData {
+        ShapeData {
+            id: NEXT_SHAPE_ID.fetch_add(1, Ordering::Relaxed),
+            content_hash: content_hash(&path, &style),
+            path,
+            style,
+        }
+    }
+
+    /// Same shape with a different style, keeps the id.
+    pub(crate) fn with_style(&self, style: ShapeStyle) -> ShapeData {
+        ShapeData {
+            id: self.id,
+            content_hash: content_hash(&self.path, &style),
+            path: self.path.clone(),
+            style,
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Equal for shapes with the same path and style, so their tessellation can be shared.
+    pub fn content_hash(&self) -> u64 {
+        self.content_hash
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn style(&self) -> &ShapeStyle {
+        &self.style
+    }
+}
+
+impl fmt::Debug for ShapeData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ShapeData")
+            .field("id", &self.id)
+            .field("content_hash", &self.content_hash)
+            .field("style", &self.style)
+            .finish()
+    }
+}
+
+fn content_hash(path: &Path, style: &ShapeStyle) -> u64 {
+    let mut hasher = MetroHash64::default();
+
+    for event in path.iter() {
+        mem::discriminant(&event).hash(&mut hasher);
+        match event {
+            PathEvent::MoveTo(to) | PathEvent::LineTo(to) => hash_point(&mut hasher, to),
+            PathEvent::QuadraticTo(ctrl, to) => {
+                hash_point(&mut hasher, ctrl);
+                hash_point(&mut hasher, to);
+            },
+            PathEvent::CubicTo(ctrl1, ctrl2, to) => {
+                hash_point(&mut hasher, ctrl1);
+                hash_point(&mut hasher, ctrl2);
+                hash_point(&mut hasher, to);
+            },
+            PathEvent::Arc(center, radii, sweep_angle, x_rotation) => {
+                hash_point(&mut hasher, center);
+                hash_f32(&mut hasher, radii.x);
+                hash_f32(&mut hasher, radii.y);
+                hash_f32(&mut hasher, sweep_angle.radians);
+                hash_f32(&mut hasher, x_rotation.radians);
+            },
+            PathEvent::Close => (),
+        }
+    }
+
+    mem::discriminant(&style.fill).hash(&mut hasher);
+    if let Some(ref paint) = style.fill {
+        hash_paint(&mut hasher, paint);
+    }
+
+    mem::discriminant(&style.stroke).hash(&mut hasher);
+    if let Some((ref paint, ref stroke)) = style.stroke {
+        hash_paint(&mut hasher, paint);
+        hash_f32(&mut hasher, stroke.width);
+        hasher.write_u8(stroke.cap as u8);
+        hasher.write_u8(stroke.join as u8);
+    }
+
+    hasher.finish()
+}
+
+fn hash_paint(hasher: &mut MetroHash64, paint: &Paint) {
+    mem::discriminant(paint).hash(hasher);
+    match *paint {
+        Paint::Color(color) => hash_color(hasher, color),
+        Paint::LinearGradient { start, end, ref stops } => {
+            hash_f32(hasher, start.x);
+            hash_f32(hasher, start.y);
+            hash_f32(hasher, end.x);
+            hash_f32(hasher, end.y);
+            hash_stops(hasher, stops);
+        },
+        Paint::RadialGradient { center, radius, ref stops } => {
+            hash_f32(hasher, center.x);
+            hash_f32(hasher, center.y);
+            hash_f32(hasher, radius);
+            hash_stops(hasher, stops);
+        },
+    }
+}
+
+fn hash_stops(hasher: &mut MetroHash64, stops: &[GradientStop]) {
+    hasher.write_usize(stops.len());
+    for stop in stops {
+        hash_f32(hasher, stop.offset);
+        hash_color(hasher, stop.color);
+    }
+}
+
+fn hash_color(hasher: &mut MetroHash64, color: na::Vector4<u8>) {
+    hasher.write(&[color.x, color.y, color.z, color.w]);
+}
+
+fn hash_point(hasher: &mut MetroHash64, p: Point) {
+    hash_f32(hasher, p.x);
+    hash_f32(hasher, p.y);
+}
+
+/// Hashes the exact bits, `0.0` and `-0.0` only lose the sharing of their tessellation.
+fn hash_f32(hasher: &mut MetroHash64, value: f32) {
+    hasher.write_u32(value.to_bits());
+}
+
+pub fn rect_path(x: f32, y: f32, w: f32, h: f32) -> Path {
+    let mut builder = Path::builder();
+    builder.move_to(point(x, y));
+    builder.line_to(point(x + w, y));
+    builder.line_to(point(x + w, y + h));
+    builder.line_to(point(x, y + h));
+    builder.close();
+    builder.build()
+}
+
+pub fn rounded_rect_path(x: f32, y: f32, w: f32, h: f32, radius: f32) -> Path {
+    let r = radius.max(0.0).min(w / 2.0).min(h / 2.0);
+    if r <= 0.0 {
+        return rect_path(x, y, w, h);
+    }
+
+    let k = r * (1.0 - KAPPA);
+    let (right, bottom) = (x + w, y + h);
+
+    let mut builder = Path::builder();
+    builder.move_to(point(x + r, y));
+    builder.line_to(point(right - r, y));
+    builder.cubic_bezier_to(point(right - k, y), point(right, y + k), point(right, y + r));
+    builder.line_to(point(right, bottom - r));
+    builder.cubic_bezier_to(point(right, bottom - k), point(right - k, bottom), point(right - r, bottom));
+    builder.line_to(point(x + r, bottom));
+    builder.cubic_bezier_to(point(x + k, bottom), point(x, bottom - k), point(x, bottom - r));
+    builder.line_to(point(x, y + r));
+    builder.cubic_bezier_to(point(x, y + k), point(x + k, y), point(x + r, y));
+    builder.close();
+    builder.build()
+}
+
+pub fn circle_path(cx: f32, cy: f32, radius: f32) -> Path {
+    let (r, k) = (radius, radius * KAPPA);
+
+    let mut builder = Path::builder();
+    builder.move_to(point(cx + r, cy));
+    builder.cubic_bezier_to(point(cx + r, cy + k), point(cx + k, cy + r), point(cx, cy + r));
+    builder.cubic_bezier_to(point(cx - k, cy + r), point(cx - r, cy + k), point(cx - r, cy));
+    builder.cubic_bezier_to(point(cx - r, cy - k), point(cx - k, cy - r), point(cx, cy - r));
+    builder.cubic_bezier_to(point(cx + k, cy - r), point(cx + r, cy - k), point(cx + r, cy));
+    builder.close();
+    builder.build()
+}
+
+pub fn polyline_path(points: &[na::Point2<f32>], closed: bool) -> Path {
+    let mut builder = Path::builder();
+
+    let mut points = points.iter();
+    if let Some(first) = points.next() {
+        builder.move_to(point(first.x, first.y));
+        for p in points {
+            builder.line_to(point(p.x, p.y));
+        }
+        if closed {
+            builder.close();
+        }
+    }
+
+    builder.build()
+}
+
+/// Line from `from` to `to` with an open arrow head at `to`, meant to be stroked.
+pub fn arrow_path(from: na::Point2<f32>, to: na::Point2<f32>, head_size: f32) -> Path {
+    let direction = to - from;
+    let length = direction.norm();
+
+    let mut builder = Path::builder();
+    builder.move_to(point(from.x, from.y));
+    builder.line_to(point(to.x, to.y));
+
+    if length > 0.0 {
+        let back = direction / length * head_size;
+        let side = na::Vector2::new(-back.y, back.x) * 0.5;
+        let left = to - back + side;
+        let right = to - back - side;
+
+        builder.move_to(point(left.x, left.y));
+        builder.line_to(point(to.x, to.y));
+        builder.line_to(point(right.x, right.y));
+    }
+
+    builder.build()
+}
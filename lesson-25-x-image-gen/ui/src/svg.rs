@@ -1,11 +1,21 @@
 use std::iter;
 use std::slice;
 
+use crate::na;
 use lyon_geom::math::Point;
 use lyon_path::PathEvent;
 use lyon_path::iterator::PathIter;
+use lyon_path::default::Path as LyonPath;
+use lyon_path::builder::{FlatPathBuilder, PathBuilder};
+use lyon_path::math::point as lyon_point;
+use lyon_tessellation::{self as tessellation, StrokeOptions};
+use resources::Resources;
+
+use usvg::{self, Color, Paint, Path, PathSegment, Stroke};
 
-use usvg::{Path, PathSegment};
+use crate::{Base, BoxSize, Element, ResolvedSize};
+use crate::primitives::Shape;
+use crate::shapes::{self, GradientStop, ShapeStyle, StrokeStyle};
 
 fn point(x: f64, y: f64) -> Point {
     Point::new(x as f32, y as f32)
@@ -43,30 +53,448 @@ pub fn convert_path(p: &Path) -> PathConv {
     PathConv(p.segments.iter())
 }
 
-
-use usvg::{self, Color, Paint, Stroke};
-use lyon_tessellation::{self as tessellation, StrokeOptions};
-
 pub fn convert_stroke(s: &Stroke, fallback_color: &Color) -> (Color, StrokeOptions) {
     let color = match s.paint {
         Paint::Color(c) => c,
         _ => *fallback_color,
     };
-    let linecap = match s.linecap {
+
+    let opt = StrokeOptions::tolerance(0.01)
+        .with_line_width(s.width as f32)
+        .with_line_cap(line_cap(s.linecap))
+        .with_line_join(line_join(s.linejoin));
+
+    (color, opt)
+}
+
+fn line_cap(cap: usvg::LineCap) -> tessellation::LineCap {
+    match cap {
         usvg::LineCap::Butt => tessellation::LineCap::Butt,
         usvg::LineCap::Square => tessellation::LineCap::Square,
         usvg::LineCap::Round => tessellation::LineCap::Round,
-    };
-    let linejoin = match s.linejoin {
+    }
+}
+
+fn line_join(join: usvg::LineJoin) -> tessellation::LineJoin {
+    match join {
         usvg::LineJoin::Miter => tessellation::LineJoin::Miter,
         usvg::LineJoin::Bevel => tessellation::LineJoin::Bevel,
         usvg::LineJoin::Round => tessellation::LineJoin::Round,
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load svg resource {}", name)]
+    ResourceLoad { name: String, #[cause] inner: resources::Error },
+    #[fail(display = "Failed to parse svg resource {}: {}", name, message)]
+    Parse { name: String, message: String },
+}
+
+/// Shape of a parsed document, with group transforms and opacity already applied.
+#[derive(Debug, Clone)]
+pub struct SvgShape {
+    pub path: LyonPath,
+    pub style: ShapeStyle,
+}
+
+/// Svg tree flattened into shapes, in `0..width, 0..height` coordinates.
+#[derive(Debug, Clone)]
+pub struct SvgDocument {
+    pub width: f32,
+    pub height: f32,
+    pub shapes: Vec<SvgShape>,
+}
+
+impl SvgDocument {
+    pub fn from_resource(res: &Resources, name: &str) -> Result<SvgDocument, Error> {
+        let data = res.resource(name).get()
+            .map_err(|e| Error::ResourceLoad { name: name.into(), inner: e })?;
+        SvgDocument::from_data(name, &data)
+    }
+
+    pub fn from_data(name: &str, data: &[u8]) -> Result<SvgDocument, Error> {
+        let text = ::std::str::from_utf8(data)
+            .map_err(|e| Error::Parse { name: name.into(), message: e.to_string() })?;
+        let tree = usvg::Tree::from_str(text, &usvg::Options::default())
+            .map_err(|e| Error::Parse { name: name.into(), message: format!("{:?}", e) })?;
+
+        let svg = tree.svg_node();
+        let (width, height) = (svg.size.width as f32, svg.size.height as f32);
+        let view_box = svg.view_box.rect;
+
+        let view_box_transform = if view_box.width > 0.0 && view_box.height > 0.0 {
+            na::Matrix3::new(
+                width / view_box.width as f32, 0.0, -view_box.x as f32 * width / view_box.width as f32,
+                0.0, height / view_box.height as f32, -view_box.y as f32 * height / view_box.height as f32,
+                0.0, 0.0, 1.0,
+            )
+        } else {
+            na::Matrix3::identity()
+        };
+
+        let mut shapes = Vec::new();
+        for child in tree.root().children() {
+            collect_shapes(&tree, &child, &view_box_transform, 1.0, &mut shapes);
+        }
+
+        Ok(SvgDocument {
+            width,
+            height,
+            shapes,
+        })
+    }
+}
+
+fn collect_shapes(tree: &usvg::Tree, node: &usvg::Node, parent_transform: &na::Matrix3<f32>, opacity: f32, output: &mut Vec<SvgShape>) {
+    match *node.borrow() {
+        usvg::NodeKind::Group(ref group) => {
+            let transform = parent_transform * to_matrix(&group.transform);
+            let opacity = opacity * group.opacity.value() as f32;
+            for child in node.children() {
+                collect_shapes(tree, &child, &transform, opacity, output);
+            }
+        },
+        usvg::NodeKind::Path(ref path) => {
+            let transform = parent_transform * to_matrix(&path.transform);
+            let lyon_path = transformed_path(&path.segments, &transform);
+            let bbox = path_bbox(&path.segments);
+
+            let fill = path.fill.as_ref().and_then(|fill| convert_paint(
+                tree, &fill.paint, opacity * fill.opacity.value() as f32, &transform, bbox,
+            ));
+            let stroke = path.stroke.as_ref().and_then(|stroke| {
+                let paint = convert_paint(
+                    tree, &stroke.paint, opacity * stroke.opacity.value() as f32, &transform, bbox,
+                )?;
+                let style = StrokeStyle::new(stroke.width as f32 * transform_scale(&transform))
+                    .with_cap(line_cap(stroke.linecap))
+                    .with_join(line_join(stroke.linejoin));
+                Some((paint, style))
+            });
+
+            if fill.is_none() && stroke.is_none() {
+                return;
+            }
+
+            output.push(SvgShape {
+                path: lyon_path,
+                style: ShapeStyle { fill, stroke },
+            });
+        },
+        // text is converted to paths by usvg, images, clip paths and masks are not supported
+        _ => (),
+    }
+}
+
+fn to_matrix(t: &usvg::Transform) -> na::Matrix3<f32> {
+    na::Matrix3::new(
+        t.a as f32, t.c as f32, t.e as f32,
+        t.b as f32, t.d as f32, t.f as f32,
+        0.0, 0.0, 1.0,
+    )
+}
+
+fn transform_point(transform: &na::Matrix3<f32>, x: f64, y: f64) -> na::Point2<f32> {
+    let p = transform * na::Vector3::new(x as f32, y as f32, 1.0);
+    na::Point2::new(p.x, p.y)
+}
+
+/// Uniform scale factor of the transform, used for stroke widths and gradient radii.
+fn transform_scale(transform: &na::Matrix3<f32>) -> f32 {
+    let determinant = transform[(0, 0)] * transform[(1, 1)] - transform[(0, 1)] * transform[(1, 0)];
+    determinant.abs().sqrt()
+}
+
+fn transformed_path(segments: &[PathSegment], transform: &na::Matrix3<f32>) -> LyonPath {
+    let p = |x, y| {
+        let p = transform_point(transform, x, y);
+        lyon_point(p.x, p.y)
     };
 
-    let opt = StrokeOptions::tolerance(0.01)
-        .with_line_width(s.width as f32)
-        .with_line_cap(linecap)
-        .with_line_join(linejoin);
+    let mut builder = LyonPath::builder();
+    for segment in segments {
+        match *segment {
+            PathSegment::MoveTo { x, y } => builder.move_to(p(x, y)),
+            PathSegment::LineTo { x, y } => builder.line_to(p(x, y)),
+            PathSegment::CurveTo { x1, y1, x2, y2, x, y } => builder.cubic_bezier_to(p(x1, y1), p(x2, y2), p(x, y)),
+            PathSegment::ClosePath => builder.close(),
+        }
+    }
+    builder.build()
+}
 
-    (color, opt)
-}
\ No newline at end of file
+/// Bounding box of path control points, `(x, y, w, h)` in path coordinates.
+fn path_bbox(segments: &[PathSegment]) -> Option<(f32, f32, f32, f32)> {
+    let mut min = na::Vector2::new(::std::f32::MAX, ::std::f32::MAX);
+    let mut max = na::Vector2::new(::std::f32::MIN, ::std::f32::MIN);
+
+    let mut add = |x: f64, y: f64| {
+        min.x = min.x.min(x as f32);
+        min.y = min.y.min(y as f32);
+        max.x = max.x.max(x as f32);
+        max.y = max.y.max(y as f32);
+    };
+
+    for segment in segments {
+        match *segment {
+            PathSegment::MoveTo { x, y } | PathSegment::LineTo { x, y } => add(x, y),
+            PathSegment::CurveTo { x1, y1, x2, y2, x, y } => {
+                add(x1, y1);
+                add(x2, y2);
+                add(x, y);
+            },
+            PathSegment::ClosePath => (),
+        }
+    }
+
+    if min.x > max.x || min.y > max.y {
+        None
+    } else {
+        Some((min.x, min.y, max.x - min.x, max.y - min.y))
+    }
+}
+
+fn convert_paint(tree: &usvg::Tree, paint: &Paint, opacity: f32, transform: &na::Matrix3<f32>, bbox: Option<(f32, f32, f32, f32)>) -> Option<shapes::Paint> {
+    let id = match *paint {
+        Paint::Color(c) => return Some(shapes::Paint::Color(color(c, opacity))),
+        Paint::Link(ref id) => id,
+    };
+
+    let node = tree.defs_by_id(id)?;
+    let kind = node.borrow();
+
+    let gradient_transform = |base: &usvg::BaseGradient| -> Option<na::Matrix3<f32>> {
+        let units = match base.units {
+            usvg::Units::UserSpaceOnUse => na::Matrix3::identity(),
+            usvg::Units::ObjectBoundingBox => {
+                let (x, y, w, h) = bbox?;
+                na::Matrix3::new(
+                    w, 0.0, x,
+                    0.0, h, y,
+                    0.0, 0.0, 1.0,
+                )
+            },
+        };
+        Some(transform * units * to_matrix(&base.transform))
+    };
+
+    match *kind {
+        usvg::NodeKind::LinearGradient(ref gradient) => {
+            let t = gradient_transform(&gradient.base)?;
+            Some(shapes::Paint::LinearGradient {
+                start: transform_point(&t, gradient.x1, gradient.y1),
+                end: transform_point(&t, gradient.x2, gradient.y2),
+                stops: gradient_stops(&gradient.base, opacity),
+            })
+        },
+        usvg::NodeKind::RadialGradient(ref gradient) => {
+            let t = gradient_transform(&gradient.base)?;
+            Some(shapes::Paint::RadialGradient {
+                center: transform_point(&t, gradient.cx, gradient.cy),
+                radius: gradient.r as f32 * transform_scale(&t),
+                stops: gradient_stops(&gradient.base, opacity),
+            })
+        },
+        // patterns are not supported
+        _ => None,
+    }
+}
+
+fn gradient_stops(base: &usvg::BaseGradient, opacity: f32) -> Vec<GradientStop> {
+    base.stops.iter()
+        .map(|stop| GradientStop::new(
+            stop.offset.value() as f32,
+            color(stop.color, opacity * stop.opacity.value() as f32),
+        ))
+        .collect()
+}
+
+fn color(c: Color, opacity: f32) -> na::Vector4<u8> {
+    [c.red, c.green, c.blue, (opacity.max(0.0).min(1.0) * 255.0).round() as u8].into()
+}
+
+/// Element that draws an svg resource, scaled to fit the available size while keeping aspect ratio.
+pub struct SvgImage {
+    resource_name: String,
+    document: Option<SvgDocument>,
+    shapes: Vec<Shape>,
+}
+
+impl SvgImage {
+    pub fn new(resource_name: &str) -> SvgImage {
+        SvgImage {
+            resource_name: resource_name.into(),
+            document: None,
+            shapes: Vec::new(),
+        }
+    }
+
+    fn place_shapes(&mut self, offset_x: f32, offset_y: f32, scale: f32) {
+        let transform = na::convert::<_, na::Projective3<_>>(na::Translation3::new(offset_x, offset_y, 0.0))
+            * na::convert::<_, na::Projective3<_>>(na::Similarity3::new(na::zero(), na::zero(), scale));
+
+        for shape in self.shapes.iter_mut() {
+            shape.set_transform(&transform);
+            shape.set_hidden(false);
+        }
+    }
+}
+
+impl Element for SvgImage {
+    fn inflate(&mut self, base: &mut Base) {
+        let document = match SvgDocument::from_resource(base.primitives().resources(), &self.resource_name) {
+            Ok(document) => document,
+            Err(e) => {
+                error!("failed to load svg image: {:?}", e);
+                return;
+            }
+        };
+
+        self.shapes = document.shapes.iter()
+            .map(|shape| base.primitives().shape(shape.path.clone(), shape.style.clone()))
+            .collect();
+        self.document = Some(document);
+    }
+
+    fn resize(&mut self, base: &mut Base) {
+        let (width, height) = match self.document {
+            Some(ref document) if document.width > 0.0 && document.height > 0.0 => (document.width, document.height),
+            _ => return base.layout_empty(),
+        };
+
+        let window_scale = base.scale();
+
+        match base.box_size() {
+            BoxSize::Hidden => {
+                for shape in self.shapes.iter_mut() {
+                    shape.set_hidden(true);
+                }
+                base.layout_empty()
+            },
+            BoxSize::Auto => {
+                self.place_shapes(0.0, 0.0, 1.0);
+                base.resolve_size(Some(ResolvedSize {
+                    w: (width * window_scale).round() as i32,
+                    h: (height * window_scale).round() as i32,
+                }));
+            },
+            BoxSize::Fixed { w, h } => {
+                // box size is in pixels, but shape coordinates are multiplied by window
+                // scale when rendered, so the placement is in unscaled units
+                let fit = (w as f32 / width).min(h as f32 / height);
+                let offset_x = (w as f32 - width * fit) / 2.0;
+                let offset_y = (h as f32 - height * fit) / 2.0;
+
+                self.place_shapes(offset_x / window_scale, offset_y / window_scale, fit / window_scale);
+                base.resolve_size(Some(ResolvedSize { w, h }));
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod svg_tests {
+    use super::*;
+
+    fn parse(body: &str) -> SvgDocument {
+        let data = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" viewBox="0 0 20 10">{}</svg>"#,
+            body
+        );
+        SvgDocument::from_data("test.svg", data.as_bytes()).unwrap()
+    }
+
+    /// Distinct end points of path segments, sorted.
+    fn points(path: &LyonPath) -> Vec<(f32, f32)> {
+        let mut points: Vec<(f32, f32)> = path.iter()
+            .filter_map(|event| match event {
+                PathEvent::MoveTo(p) | PathEvent::LineTo(p) => Some((p.x, p.y)),
+                PathEvent::QuadraticTo(_, p) | PathEvent::CubicTo(_, _, p) => Some((p.x, p.y)),
+                _ => None,
+            })
+            .collect();
+        points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        points.dedup();
+        points
+    }
+
+    fn rect_points(x: f32, y: f32, w: f32, h: f32) -> Vec<(f32, f32)> {
+        vec![(x, y), (x, y + h), (x + w, y), (x + w, y + h)]
+    }
+
+    #[test]
+    fn view_box_is_scaled_to_document_size() {
+        let document = parse(r##"<rect x="1" y="2" width="3" height="4" fill="#ff0000"/>"##);
+
+        assert_eq!(200.0, document.width);
+        assert_eq!(100.0, document.height);
+        assert_eq!(1, document.shapes.len());
+        assert_eq!(rect_points(10.0, 20.0, 30.0, 40.0), points(&document.shapes[0].path));
+    }
+
+    #[test]
+    fn view_box_origin_is_moved_to_zero() {
+        let data = r##"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10" viewBox="5 5 20 10">
+            <rect x="5" y="5" width="2" height="2"/>
+        </svg>"##;
+        let document = SvgDocument::from_data("test.svg", data.as_bytes()).unwrap();
+
+        assert_eq!(rect_points(0.0, 0.0, 2.0, 2.0), points(&document.shapes[0].path));
+    }
+
+    #[test]
+    fn fill_color_and_opacity() {
+        let document = parse(r##"<rect width="1" height="1" fill="#0080ff" fill-opacity="0.5"/>"##);
+
+        let style = &document.shapes[0].style;
+        assert_eq!(Some(shapes::Paint::Color([0, 128, 255, 128].into())), style.fill);
+        assert_eq!(None, style.stroke);
+    }
+
+    #[test]
+    fn stroke_width_is_scaled_with_view_box() {
+        let document = parse(r##"<rect width="1" height="1" fill="none" stroke="#00ff00"
+            stroke-width="0.5" stroke-linecap="round" stroke-linejoin="bevel"/>"##);
+
+        let style = &document.shapes[0].style;
+        assert_eq!(None, style.fill);
+
+        let expected_stroke = StrokeStyle::new(5.0)
+            .with_cap(tessellation::LineCap::Round)
+            .with_join(tessellation::LineJoin::Bevel);
+        assert_eq!(Some((shapes::Paint::Color([0, 255, 0, 255].into()), expected_stroke)), style.stroke);
+    }
+
+    #[test]
+    fn group_and_path_transforms_are_applied() {
+        let document = parse(r#"<g transform="translate(1, 2)">
+            <rect width="1" height="1" transform="scale(2)"/>
+        </g>"#);
+
+        assert_eq!(rect_points(10.0, 20.0, 20.0, 20.0), points(&document.shapes[0].path));
+    }
+
+    #[test]
+    fn group_opacity_multiplies_paint_opacity() {
+        let document = parse(r##"<g opacity="0.5"><rect width="1" height="1" fill="#000000"/></g>"##);
+
+        assert_eq!(Some(shapes::Paint::Color([0, 0, 0, 128].into())), document.shapes[0].style.fill);
+    }
+
+    #[test]
+    fn shapes_without_paint_are_skipped() {
+        let document = parse(r##"<rect width="1" height="1" fill="none"/><rect width="2" height="2"/>"##);
+
+        assert_eq!(1, document.shapes.len());
+        assert_eq!(rect_points(0.0, 0.0, 20.0, 20.0), points(&document.shapes[0].path));
+    }
+
+    #[test]
+    fn invalid_data_is_parse_error() {
+        match SvgDocument::from_data("broken.svg", b"<svg") {
+            Err(Error::Parse { ref name, .. }) => assert_eq!("broken.svg", name),
+            other => panic!("unexpected result {:?}", other.map(|d| d.shapes.len())),
+        }
+    }
+}
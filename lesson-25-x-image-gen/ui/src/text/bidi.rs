@@ -0,0 +1,240 @@
+use std::ops::Range;
+use unicode_bidi::{BidiInfo, Level};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Base direction of paragraphs.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Direction {
+    /// Taken from the first strong character of every paragraph, left to right if there is none.
+    Auto,
+    LeftToRight,
+    RightToLeft,
+}
+
+impl Default for Direction {
+    fn default() -> Self {
+        Direction::Auto
+    }
+}
+
+/// Text of the same embedding level, see Unicode Standard Annex #9.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BidiRun {
+    pub range: Range<usize>,
+    pub level: u8,
+}
+
+impl BidiRun {
+    pub fn is_rtl(&self) -> bool {
+        self.level % 2 == 1
+    }
+}
+
+/// Splits text into runs of the same direction, in visual order from left to right.
+///
+/// Every paragraph is reordered as a single line, paragraphs follow each other.
+pub fn visual_runs(text: &str, direction: Direction) -> Vec<BidiRun> {
+    let default_level = match direction {
+        Direction::Auto => None,
+        Direction::LeftToRight => Some(Level::ltr()),
+        Direction::RightToLeft => Some(Level::rtl()),
+    };
+
+    let paragraphs = BidiInfo::new(text, default_level).paragraphs;
+    let mut runs = Vec::new();
+
+    // lines are reordered one paragraph at a time, as reordering expects lines to start
+    // at the beginning of the analyzed text
+    for paragraph in paragraphs {
+        let info = BidiInfo::new(&text[paragraph.range.clone()], default_level);
+        let line = info.paragraphs.first();
+        if let Some(line) = line {
+            let (levels, level_runs) = info.visual_runs(line, line.range.clone());
+            for range in level_runs {
+                let level = levels[range.start].number();
+                runs.push(BidiRun {
+                    range: paragraph.range.start + range.start..paragraph.range.start + range.end,
+                    level,
+                });
+            }
+        }
+    }
+
+    runs
+}
+
+/// Resolved direction of the first paragraph.
+pub fn is_rtl(text: &str, direction: Direction) -> bool {
+    match direction {
+        Direction::LeftToRight => false,
+        Direction::RightToLeft => true,
+        Direction::Auto => BidiInfo::new(text, None).paragraphs.first()
+            .map(|p| p.level.is_rtl())
+            .unwrap_or(false),
+    }
+}
+
+/// Moves the caret at the byte `offset` by one grapheme to the left or to the right on screen.
+///
+/// In right-to-left text the caret moves backwards in the text when moving to the right.
+/// Where runs of different direction meet, the caret belongs to the run with the lower
+/// embedding level, usually the surrounding paragraph. The start and the end of the text
+/// are at the edges of the line where the paragraph direction starts and ends.
+pub fn move_caret_visually(text: &str, direction: Direction, offset: usize, to_right: bool) -> usize {
+    let paragraph_rtl = is_rtl(text, direction);
+
+    // graphemes from left to right, with their embedding levels
+    let mut graphemes: Vec<(Range<usize>, u8)> = Vec::new();
+
+    for run in visual_runs(text, direction) {
+        let first = graphemes.len();
+        graphemes.extend(
+            text[run.range.clone()].grapheme_indices(true)
+                .map(|(start, g)| (run.range.start + start..run.range.start + start + g.len(), run.level))
+        );
+        if run.is_rtl() {
+            graphemes[first..].reverse();
+        }
+    }
+
+    // byte offset of the caret at the left edge of every grapheme and at the end
+    let edge_offset = |edge: usize| -> usize {
+        if edge == 0 {
+            return if paragraph_rtl { text.len() } else { 0 };
+        }
+        if edge == graphemes.len() {
+            return if paragraph_rtl { 0 } else { text.len() };
+        }
+
+        let left = edge.checked_sub(1).and_then(|e| graphemes.get(e)).map(|&(ref range, level)| {
+            (if level % 2 == 1 { range.start } else { range.end }, level)
+        });
+        let right = graphemes.get(edge).map(|&(ref range, level)| {
+            (if level % 2 == 1 { range.end } else { range.start }, level)
+        });
+
+        match (left, right) {
+            (Some((left_offset, left_level)), Some((_, right_level))) if left_level < right_level => left_offset,
+            (_, Some((right_offset, _))) => right_offset,
+            (Some((left_offset, _)), None) => left_offset,
+            (None, None) => 0,
+        }
+    };
+
+    // offsets inside a grapheme, or at a grapheme boundary that another edge owns, are placed
+    // at the edge of their grapheme
+    let edge = match (0..=graphemes.len()).find(|&e| edge_offset(e) == offset) {
+        Some(edge) => edge,
+        None => {
+            let inside = graphemes.iter().position(|(range, _)| range.start <= offset && offset < range.end);
+            let after = graphemes.iter().position(|(range, _)| range.end == offset);
+            let (index, at_end) = match (inside, after) {
+                (Some(index), _) => (index, false),
+                (None, Some(index)) => (index, true),
+                (None, None) => return offset,
+            };
+            let rtl = graphemes[index].1 % 2 == 1;
+            if at_end != rtl { index + 1 } else { index }
+        }
+    };
+
+    match (to_right, edge) {
+        (true, e) if e < graphemes.len() => edge_offset(e + 1),
+        (false, e) if e > 0 => edge_offset(e - 1),
+        _ => offset,
+    }
+}
+
+#[cfg(test)]
+mod bidi_tests {
+    use super::*;
+
+    fn runs(text: &str, direction: Direction) -> Vec<(Range<usize>, u8)> {
+        visual_runs(text, direction).into_iter().map(|r| (r.range, r.level)).collect()
+    }
+
+    /// Offsets visited by moving the caret from `offset` until it stops.
+    fn walk(text: &str, direction: Direction, offset: usize, to_right: bool) -> Vec<usize> {
+        let mut offsets = vec![offset];
+        loop {
+            let next = move_caret_visually(text, direction, *offsets.last().unwrap(), to_right);
+            if next == *offsets.last().unwrap() || offsets.len() > text.len() + 1 {
+                return offsets;
+            }
+            offsets.push(next);
+        }
+    }
+
+    #[test]
+    fn paragraphs_are_reordered_separately() {
+        assert_eq!(vec![(0..4, 0), (4..10, 1)], runs("abc\nאבג", Direction::Auto));
+        assert_eq!(vec![(0..7, 1), (7..10, 0)], runs("אבג\nabc", Direction::Auto));
+        assert_eq!(vec![(0..4, 0), (4..10, 1)], runs("abc\nאבג", Direction::LeftToRight));
+        assert_eq!(vec![(0..7, 1), (7..10, 2)], runs("אבג\nabc", Direction::RightToLeft));
+    }
+
+    #[test]
+    fn nested_levels_are_in_visual_order() {
+        assert_eq!(
+            vec![(0..4, 0), (11..13, 2), (4..11, 1), (13..17, 0)],
+            runs("abc אבג 12 def", Direction::Auto)
+        );
+        assert_eq!(
+            vec![(11..14, 2), (10..11, 1), (7..10, 2), (0..7, 1)],
+            runs("אבג 123 abc", Direction::Auto)
+        );
+        assert_eq!(vec![(3..10, 1), (0..3, 2)], runs("abc אבג", Direction::RightToLeft));
+    }
+
+    #[test]
+    fn caret_moves_over_graphemes_in_ltr_text() {
+        let text = "ae\u{301}b";
+        assert_eq!(vec![0, 1, 4, 5], walk(text, Direction::Auto, 0, true));
+        assert_eq!(vec![5, 4, 1, 0], walk(text, Direction::Auto, 5, false));
+    }
+
+    #[test]
+    fn caret_moves_backwards_in_rtl_text() {
+        let text = "אבג";
+        assert_eq!(vec![6, 4, 2, 0], walk(text, Direction::Auto, 6, true));
+        assert_eq!(vec![0, 2, 4, 6], walk(text, Direction::Auto, 0, false));
+    }
+
+    #[test]
+    fn caret_crosses_rtl_run_in_ltr_paragraph() {
+        let text = "abc אבג";
+        assert_eq!(vec![0, 1, 2, 3, 4, 8, 6, 10], walk(text, Direction::Auto, 0, true));
+        assert_eq!(vec![10, 6, 8, 4, 3, 2, 1, 0], walk(text, Direction::Auto, 10, false));
+
+        let text = "אבג abc";
+        assert_eq!(vec![0, 4, 2, 6, 7, 8, 9, 10], walk(text, Direction::LeftToRight, 0, true));
+        assert_eq!(vec![10, 9, 8, 7, 6, 2, 4, 0], walk(text, Direction::LeftToRight, 10, false));
+    }
+
+    #[test]
+    fn caret_crosses_ltr_run_in_rtl_paragraph() {
+        let text = "אב cd";
+        assert_eq!(vec![0, 2, 4, 5, 6, 7], walk(text, Direction::Auto, 0, false));
+        assert_eq!(vec![7, 6, 5, 4, 2, 0], walk(text, Direction::Auto, 7, true));
+    }
+
+    #[test]
+    fn caret_inside_grapheme_moves_from_its_edges() {
+        let text = "ae\u{301}b";
+        assert_eq!(4, move_caret_visually(text, Direction::Auto, 2, true));
+        assert_eq!(0, move_caret_visually(text, Direction::Auto, 2, false));
+
+        let text = "abc אבג";
+        assert_eq!(6, move_caret_visually(text, Direction::Auto, 9, true));
+        assert_eq!(4, move_caret_visually(text, Direction::Auto, 9, false));
+    }
+
+    #[test]
+    fn caret_stays_at_text_ends() {
+        assert_eq!(0, move_caret_visually("abc", Direction::Auto, 0, false));
+        assert_eq!(3, move_caret_visually("abc", Direction::Auto, 3, true));
+        assert_eq!(0, move_caret_visually("אבג", Direction::Auto, 0, true));
+        assert_eq!(6, move_caret_visually("אבג", Direction::Auto, 6, false));
+        assert_eq!(0, move_caret_visually("", Direction::Auto, 0, true));
+    }
+}
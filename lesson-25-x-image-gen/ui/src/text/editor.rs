@@ -0,0 +1,594 @@
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::primitives::GlyphMeasurement;
+use super::bidi::{self, Direction};
+
+/// Undo steps kept by `TextEditor`, older steps are forgotten.
+const MAX_UNDO_STEPS: usize = 100;
+
+#[derive(Clone, Debug, PartialEq)]
+struct Snapshot {
+    text: String,
+    caret: usize,
+    anchor: Option<usize>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum EditKind {
+    Typing,
+    Deleting,
+}
+
+/// Editable text with a caret, selection and undo history.
+///
+/// Offsets are in bytes and always at grapheme boundaries.
+#[derive(Clone, Debug)]
+pub struct TextEditor {
+    text: String,
+    caret: usize,
+    /// Other end of the selection, the selection is empty if it equals the caret.
+    anchor: Option<usize>,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    /// Typing or deleting right after the same kind of edit is undone together with it.
+    last_edit: Option<EditKind>,
+}
+
+impl TextEditor {
+    pub fn new<P: ToString>(text: P) -> TextEditor {
+        let text = text.to_string();
+        let caret = text.len();
+
+        TextEditor {
+            text,
+            caret,
+            anchor: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            last_edit: None,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn caret(&self) -> usize {
+        self.caret
+    }
+
+    /// Selected bytes in logical order, empty if nothing is selected.
+    pub fn selection(&self) -> Range<usize> {
+        match self.anchor {
+            Some(anchor) if anchor < self.caret => anchor..self.caret,
+            Some(anchor) => self.caret..anchor,
+            None => self.caret..self.caret,
+        }
+    }
+
+    pub fn has_selection(&self) -> bool {
+        let selection = self.selection();
+        selection.start != selection.end
+    }
+
+    pub fn selected_text(&self) -> &str {
+        &self.text[self.selection()]
+    }
+
+    /// Moves the caret, `extend` keeps the other end of the selection in place.
+    pub fn move_caret(&mut self, offset: usize, extend: bool) {
+        let offset = grapheme_boundary(&self.text, offset);
+
+        if extend {
+            if self.anchor.is_none() {
+                self.anchor = Some(self.caret);
+            }
+        } else {
+            self.anchor = None;
+        }
+
+        self.caret = offset;
+        self.last_edit = None;
+    }
+
+    /// Selects the word at the offset, or the whitespace between words.
+    pub fn select_word(&mut self, offset: usize) {
+        let word = self.text.split_word_bound_indices()
+            .map(|(start, word)| start..start + word.len())
+            .find(|range| range.start <= offset && offset < range.end)
+            .unwrap_or(self.text.len()..self.text.len());
+
+        self.move_caret(word.start, false);
+        self.move_caret(word.end, true);
+    }
+
+    pub fn select_all(&mut self) {
+        let end = self.text.len();
+        self.move_caret(0, false);
+        self.move_caret(end, true);
+    }
+
+    /// Replaces the selection with text, or inserts it at the caret.
+    pub fn insert(&mut self, text: &str) {
+        if text.is_empty() && !self.has_selection() {
+            return;
+        }
+
+        // typing is undone a word at a time, replacing a selection or pasting separately
+        let continues_typing = !self.has_selection() && text.graphemes(true).count() == 1;
+        self.begin_edit(EditKind::Typing, continues_typing);
+
+        let selection = self.selection();
+        self.text.replace_range(selection.clone(), text);
+        self.caret = selection.start + text.len();
+        self.anchor = None;
+
+        if text.chars().all(char::is_whitespace) {
+            self.last_edit = None;
+        }
+    }
+
+    /// Deletes the selection, or the grapheme or word before the caret.
+    pub fn delete_backward(&mut self, by_word: bool) {
+        let range = if self.has_selection() {
+            self.selection()
+        } else if by_word {
+            previous_word(&self.text, self.caret)..self.caret
+        } else {
+            previous_grapheme(&self.text, self.caret)..self.caret
+        };
+
+        self.delete_range(range, !by_word);
+    }
+
+    /// Deletes the selection, or the grapheme or word after the caret.
+    pub fn delete_forward(&mut self, by_word: bool) {
+        let range = if self.has_selection() {
+            self.selection()
+        } else if by_word {
+            self.caret..next_word(&self.text, self.caret)
+        } else {
+            self.caret..next_grapheme(&self.text, self.caret)
+        };
+
+        self.delete_range(range, !by_word);
+    }
+
+    /// Removes the selected text and returns it, for copying to the clipboard.
+    pub fn cut(&mut self) -> Option<String> {
+        if !self.has_selection() {
+            return None;
+        }
+
+        let text = self.selected_text().to_string();
+        let selection = self.selection();
+        self.delete_range(selection, false);
+        Some(text)
+    }
+
+    /// Returns `false` if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.undo.pop() {
+            Some(snapshot) => {
+                let current = self.snapshot();
+                self.redo.push(current);
+                self.restore(snapshot);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Returns `false` if there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(snapshot) => {
+                let current = self.snapshot();
+                self.undo.push(current);
+                self.restore(snapshot);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Undo restores the caret and selection from before the deletion.
+    fn delete_range(&mut self, range: Range<usize>, continues_deleting: bool) {
+        if range.start == range.end {
+            self.anchor = None;
+            return;
+        }
+
+        self.begin_edit(EditKind::Deleting, continues_deleting);
+
+        self.text.replace_range(range.clone(), "");
+        self.caret = range.start;
+        self.anchor = None;
+    }
+
+    /// Saves the state for undo, unless the edit continues the previous one.
+    fn begin_edit(&mut self, kind: EditKind, continues: bool) {
+        if !continues || self.last_edit != Some(kind) {
+            let snapshot = self.snapshot();
+            self.undo.push(snapshot);
+            if self.undo.len() > MAX_UNDO_STEPS {
+                self.undo.remove(0);
+            }
+        }
+
+        self.redo.clear();
+        self.last_edit = if continues { Some(kind) } else { None };
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            text: self.text.clone(),
+            caret: self.caret,
+            anchor: self.anchor,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.text = snapshot.text;
+        self.caret = snapshot.caret;
+        self.anchor = snapshot.anchor;
+        self.last_edit = None;
+    }
+}
+
+/// Start of the grapheme before the offset, or 0.
+pub fn previous_grapheme(text: &str, offset: usize) -> usize {
+    text.grapheme_indices(true)
+        .map(|(start, _)| start)
+        .take_while(|&start| start < offset)
+        .last()
+        .unwrap_or(0)
+}
+
+/// End of the grapheme after the offset, or the end of the text.
+pub fn next_grapheme(text: &str, offset: usize) -> usize {
+    text.grapheme_indices(true)
+        .map(|(start, g)| start + g.len())
+        .find(|&end| end > offset)
+        .unwrap_or(text.len())
+}
+
+/// Start of the word before the offset, whitespace and punctuation are skipped.
+pub fn previous_word(text: &str, offset: usize) -> usize {
+    text.split_word_bound_indices()
+        .rev()
+        .find(|&(start, word)| start < offset && is_word(word))
+        .map(|(start, _)| start)
+        .unwrap_or(0)
+}
+
+/// End of the word after the offset, whitespace and punctuation are skipped.
+pub fn next_word(text: &str, offset: usize) -> usize {
+    text.split_word_bound_indices()
+        .map(|(start, word)| (start + word.len(), word))
+        .find(|&(end, word)| end > offset && is_word(word))
+        .map(|(end, _)| end)
+        .unwrap_or(text.len())
+}
+
+fn is_word(segment: &str) -> bool {
+    segment.chars().any(char::is_alphanumeric)
+}
+
+/// The closest grapheme boundary at or before the offset.
+fn grapheme_boundary(text: &str, offset: usize) -> usize {
+    if offset >= text.len() {
+        return text.len();
+    }
+
+    text.grapheme_indices(true)
+        .map(|(start, _)| start)
+        .take_while(|&start| start <= offset)
+        .last()
+        .unwrap_or(0)
+}
+
+/// Grapheme of a single line of shaped text, placed on screen.
+#[derive(Clone, Debug, PartialEq)]
+struct PlacedGrapheme {
+    range: Range<usize>,
+    left: f32,
+    right: f32,
+    rtl: bool,
+}
+
+/// Caret positions of a single line of shaped text, for drawing the caret and selection
+/// and for placing the caret under the pointer.
+///
+/// Positions are relative to the start of the line, in the units of glyph advances.
+#[derive(Clone, Debug)]
+pub struct CaretLayout {
+    /// Graphemes from left to right.
+    graphemes: Vec<PlacedGrapheme>,
+    text_len: usize,
+    rtl: bool,
+}
+
+impl CaretLayout {
+    /// `glyphs` are shaped glyphs of `text` in visual order, shaped with `direction`.
+    pub fn new(text: &str, glyphs: &[GlyphMeasurement], direction: Direction) -> CaretLayout {
+        let runs = bidi::visual_runs(text, direction);
+        let is_rtl_at = |offset: usize| runs.iter()
+            .find(|run| run.range.start <= offset && offset < run.range.end)
+            .map(|run| run.is_rtl())
+            .unwrap_or(false);
+
+        let mut graphemes = Vec::with_capacity(glyphs.len());
+        let mut x = 0.0;
+        let mut index = 0;
+
+        while index < glyphs.len() {
+            let start = glyphs[index].byte_offset as usize;
+            let end = (start + glyphs[index].len as usize).min(text.len());
+
+            // glyphs of one cluster follow each other
+            let mut width = 0.0;
+            while index < glyphs.len() && glyphs[index].byte_offset as usize == start {
+                width += glyphs[index].x_advance;
+                index += 1;
+            }
+
+            let rtl = is_rtl_at(start);
+            let cluster = text.get(start..end).unwrap_or("");
+            let cluster_graphemes: Vec<Range<usize>> = cluster.grapheme_indices(true)
+                .map(|(s, g)| start + s..start + s + g.len())
+                .collect();
+
+            // ligatures are split between their graphemes evenly
+            let count = cluster_graphemes.len().max(1);
+            let grapheme_width = width / count as f32;
+
+            let mut placed: Vec<PlacedGrapheme> = cluster_graphemes.into_iter()
+                .enumerate()
+                .map(|(i, range)| {
+                    // the first grapheme of a right-to-left cluster is on the right
+                    let left = if rtl {
+                        x + width - grapheme_width * (i + 1) as f32
+                    } else {
+                        x + grapheme_width * i as f32
+                    };
+                    PlacedGrapheme { range, left, right: left + grapheme_width, rtl }
+                })
+                .collect();
+
+            if rtl {
+                placed.reverse();
+            }
+
+            graphemes.extend(placed);
+            x += width;
+        }
+
+        CaretLayout {
+            graphemes,
+            text_len: text.len(),
+            rtl: bidi::is_rtl(text, direction),
+        }
+    }
+
+    /// Total advance of the line.
+    pub fn width(&self) -> f32 {
+        self.graphemes.last().map(|g| g.right).unwrap_or(0.0)
+    }
+
+    /// Position of the caret before the grapheme at the byte `offset`.
+    pub fn caret_x(&self, offset: usize) -> f32 {
+        if let Some(g) = self.graphemes.iter().find(|g| g.range.start == offset) {
+            return if g.rtl { g.right } else { g.left };
+        }
+
+        // after the last grapheme of a run
+        if let Some(g) = self.graphemes.iter().find(|g| g.range.end == offset) {
+            return if g.rtl { g.left } else { g.right };
+        }
+
+        match (offset >= self.text_len, self.rtl) {
+            (true, true) | (false, false) => 0.0,
+            (true, false) | (false, true) => self.width(),
+        }
+    }
+
+    /// Byte offset of the caret position closest to `x`.
+    pub fn hit_test(&self, x: f32) -> usize {
+        for g in self.graphemes.iter() {
+            if x < g.right {
+                let left_half = x < (g.left + g.right) / 2.0;
+                return match (left_half, g.rtl) {
+                    (true, false) | (false, true) => g.range.start,
+                    (true, true) | (false, false) => g.range.end,
+                };
+            }
+        }
+
+        match self.graphemes.last() {
+            Some(g) if g.rtl => g.range.start,
+            Some(g) => g.range.end,
+            None => 0,
+        }
+    }
+
+    /// Horizontal spans covered by selected bytes, from left to right.
+    pub fn selection_spans(&self, selection: Range<usize>) -> Vec<(f32, f32)> {
+        let mut spans: Vec<(f32, f32)> = Vec::new();
+
+        for g in self.graphemes.iter().filter(|g| g.range.start >= selection.start && g.range.end <= selection.end) {
+            match spans.last_mut() {
+                Some(span) if (span.1 - g.left).abs() < 0.001 => span.1 = g.right,
+                _ => spans.push((g.left, g.right)),
+            }
+        }
+
+        spans
+    }
+}
+
+#[cfg(test)]
+mod editor_tests {
+    use super::*;
+
+    fn type_text(editor: &mut TextEditor, text: &str) {
+        for grapheme in text.graphemes(true) {
+            editor.insert(grapheme);
+        }
+    }
+
+    #[test]
+    fn backspace_deletes_combining_marks_with_their_base() {
+        let mut editor = TextEditor::new("ae\u{301}");
+
+        editor.delete_backward(false);
+        assert_eq!("a", editor.text());
+        assert_eq!(1, editor.caret());
+    }
+
+    #[test]
+    fn delete_removes_whole_emoji_sequences() {
+        // thumbs up with skin tone, family joined with zero width joiners, flag
+        let mut editor = TextEditor::new("\u{1F44D}\u{1F3FD}\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{1F1EA}\u{1F1FA}");
+        editor.move_caret(0, false);
+
+        editor.delete_forward(false);
+        assert_eq!("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{1F1EA}\u{1F1FA}", editor.text());
+        editor.delete_forward(false);
+        assert_eq!("\u{1F1EA}\u{1F1FA}", editor.text());
+        editor.delete_forward(false);
+        assert_eq!("", editor.text());
+    }
+
+    #[test]
+    fn caret_is_moved_to_grapheme_boundary() {
+        let mut editor = TextEditor::new("e\u{301}x");
+
+        editor.move_caret(2, false);
+        assert_eq!(0, editor.caret());
+        editor.move_caret(3, true);
+        assert_eq!("e\u{301}", editor.selected_text());
+    }
+
+    #[test]
+    fn word_deletion_skips_whitespace_and_punctuation() {
+        let mut editor = TextEditor::new("hello, big world");
+
+        editor.delete_backward(true);
+        assert_eq!("hello, big ", editor.text());
+        editor.delete_backward(true);
+        assert_eq!("hello, ", editor.text());
+        editor.delete_backward(true);
+        assert_eq!("", editor.text());
+
+        let mut editor = TextEditor::new("hello, big world");
+        editor.move_caret(5, false);
+        editor.delete_forward(true);
+        assert_eq!("hello world", editor.text());
+        assert_eq!(5, editor.caret());
+    }
+
+    #[test]
+    fn deletion_removes_selection() {
+        let mut editor = TextEditor::new("hello world");
+        editor.select_word(1);
+
+        editor.delete_forward(true);
+        assert_eq!(" world", editor.text());
+        assert!(!editor.has_selection());
+        assert_eq!(0, editor.caret());
+    }
+
+    #[test]
+    fn typing_is_undone_a_word_at_a_time() {
+        let mut editor = TextEditor::new("");
+        type_text(&mut editor, "one two");
+
+        assert!(editor.undo());
+        assert_eq!("one ", editor.text());
+        assert_eq!(4, editor.caret());
+        assert!(editor.undo());
+        assert_eq!("", editor.text());
+        assert!(!editor.undo());
+
+        assert!(editor.redo());
+        assert_eq!("one ", editor.text());
+        assert!(editor.redo());
+        assert_eq!("one two", editor.text());
+        assert!(!editor.redo());
+    }
+
+    #[test]
+    fn deletions_are_undone_together() {
+        let mut editor = TextEditor::new("one two");
+
+        editor.delete_backward(false);
+        editor.delete_backward(false);
+        assert!(editor.undo());
+        assert_eq!("one two", editor.text());
+        assert!(!editor.has_selection());
+
+        // deleting words is undone one word at a time
+        editor.delete_backward(true);
+        editor.delete_backward(true);
+        assert!(editor.undo());
+        assert_eq!("one ", editor.text());
+    }
+
+    #[test]
+    fn moving_caret_ends_undo_group() {
+        let mut editor = TextEditor::new("");
+        type_text(&mut editor, "ab");
+        editor.move_caret(0, false);
+        type_text(&mut editor, "c");
+
+        assert!(editor.undo());
+        assert_eq!("ab", editor.text());
+        assert!(editor.undo());
+        assert_eq!("", editor.text());
+    }
+
+    #[test]
+    fn paste_and_typing_are_undone_separately() {
+        let mut editor = TextEditor::new("");
+        type_text(&mut editor, "a");
+        editor.insert("pasted");
+        type_text(&mut editor, "b");
+
+        assert!(editor.undo());
+        assert_eq!("apasted", editor.text());
+        assert!(editor.undo());
+        assert_eq!("a", editor.text());
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut editor = TextEditor::new("xy");
+        type_text(&mut editor, "ab");
+        assert!(editor.undo());
+        assert_eq!("xy", editor.text());
+
+        editor.insert("c");
+        assert!(!editor.redo());
+        assert_eq!("xyc", editor.text());
+
+        assert!(editor.undo());
+        editor.delete_backward(false);
+        assert!(!editor.redo());
+        assert_eq!("x", editor.text());
+    }
+
+    #[test]
+    fn cut_returns_selection_and_can_be_undone() {
+        let mut editor = TextEditor::new("hello world");
+        assert_eq!(None, editor.cut());
+
+        editor.select_word(7);
+        assert_eq!(Some("world".to_string()), editor.cut());
+        assert_eq!("hello ", editor.text());
+
+        assert!(editor.undo());
+        assert_eq!("hello world", editor.text());
+        assert_eq!(6..11, editor.selection());
+    }
+}
@@ -0,0 +1,157 @@
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use super::script::{Script, is_default_ignorable};
+
+/// Part of text drawn with one font and shaped as one script.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextRun {
+    /// Byte range in the itemized text, always on grapheme boundaries.
+    pub range: Range<usize>,
+    /// Index of the font in the fallback chain.
+    pub font_index: usize,
+    pub script: Script,
+}
+
+/// Splits text into runs of the same script and font.
+///
+/// Every grapheme gets the first of `font_count` fonts for which `covers(font_index, c)`
+/// is true for all its characters, or the first font if none does. Neutral characters,
+/// like spaces and punctuation, stay in the run before them if its font covers them.
+pub fn itemize<F>(text: &str, font_count: usize, mut covers: F) -> Vec<TextRun>
+    where F: FnMut(usize, char) -> bool
+{
+    let mut runs: Vec<TextRun> = Vec::new();
+
+    for (start, grapheme) in text.grapheme_indices(true) {
+        let script = grapheme.chars()
+            .map(Script::of)
+            .find(|s| !s.is_neutral())
+            .unwrap_or(Script::Common);
+
+        let previous = runs.last().map(|r| (r.font_index, r.script));
+
+        let mut grapheme_covered_by = |font_index: usize| grapheme.chars()
+            .filter(|c| !is_default_ignorable(*c))
+            .all(|c| covers(font_index, c));
+
+        let keep_previous_font = match previous {
+            Some((previous_font, _)) => script.is_neutral() && grapheme_covered_by(previous_font),
+            None => false,
+        };
+
+        let font_index = match previous {
+            Some((previous_font, _)) if keep_previous_font => previous_font,
+            _ => (0..font_count)
+                .find(|i| grapheme_covered_by(*i))
+                .unwrap_or(0),
+        };
+
+        let script = match previous {
+            Some((_, previous_script)) if script.is_neutral() => previous_script,
+            _ => script,
+        };
+
+        let end = start + grapheme.len();
+
+        let extends_previous = previous == Some((font_index, script));
+        if extends_previous {
+            if let Some(run) = runs.last_mut() {
+                run.range.end = end;
+            }
+        } else {
+            runs.push(TextRun {
+                range: start..end,
+                font_index,
+                script,
+            });
+        }
+    }
+
+    runs
+}
+
+#[cfg(test)]
+mod itemize_tests {
+    use super::*;
+
+    fn run(range: Range<usize>, font_index: usize, script: Script) -> TextRun {
+        TextRun { range, font_index, script }
+    }
+
+    fn covers_everything(_: usize, _: char) -> bool {
+        true
+    }
+
+    /// First font covers ASCII, the second everything.
+    fn ascii_then_everything(font_index: usize, c: char) -> bool {
+        font_index == 1 || c.is_ascii()
+    }
+
+    #[test]
+    fn empty_text_has_no_runs() {
+        assert_eq!(Vec::<TextRun>::new(), itemize("", 1, covers_everything));
+    }
+
+    #[test]
+    fn neutrals_stay_in_previous_run() {
+        assert_eq!(vec![run(0..9, 0, Script::Latin)], itemize("abc, def!", 1, covers_everything));
+
+        // "שלום, abc"
+        assert_eq!(
+            vec![run(0..10, 0, Script::Hebrew), run(10..13, 0, Script::Latin)],
+            itemize("\u{5e9}\u{5dc}\u{5d5}\u{5dd}, abc", 1, covers_everything)
+        );
+    }
+
+    #[test]
+    fn leading_neutrals_have_common_script() {
+        assert_eq!(
+            vec![run(0..2, 0, Script::Common), run(2..5, 0, Script::Latin)],
+            itemize("1 abc", 1, covers_everything)
+        );
+    }
+
+    #[test]
+    fn neutral_not_covered_by_previous_font_falls_back() {
+        let covers = |font_index: usize, c: char| font_index == 1 || c.is_alphabetic();
+
+        assert_eq!(
+            vec![run(0..2, 0, Script::Latin), run(2..3, 1, Script::Latin), run(3..4, 0, Script::Latin)],
+            itemize("ab!c", 2, covers)
+        );
+    }
+
+    #[test]
+    fn first_covering_font_is_picked() {
+        // "ab אב"
+        assert_eq!(
+            vec![run(0..3, 0, Script::Latin), run(3..7, 1, Script::Hebrew)],
+            itemize("ab \u{5d0}\u{5d1}", 2, ascii_then_everything)
+        );
+    }
+
+    #[test]
+    fn uncovered_graphemes_use_first_font() {
+        assert_eq!(
+            vec![run(0..1, 0, Script::Latin), run(1..4, 0, Script::Han)],
+            itemize("a\u{4e00}", 2, |_, c| c.is_ascii())
+        );
+    }
+
+    #[test]
+    fn combining_marks_are_covered_with_their_base() {
+        // the first font has "e", but not the accent
+        assert_eq!(
+            vec![run(0..1, 0, Script::Latin), run(1..4, 1, Script::Latin)],
+            itemize("ae\u{301}", 2, ascii_then_everything)
+        );
+    }
+
+    #[test]
+    fn default_ignorables_do_not_need_coverage() {
+        // man, zero width joiner, woman
+        let covers = |font_index: usize, c: char| font_index == 1 && c != '\u{200d}';
+
+        assert_eq!(vec![run(0..11, 1, Script::Common)], itemize("\u{1f468}\u{200d}\u{1f469}", 2, covers));
+    }
+}
@@ -0,0 +1,15 @@
+//! Text analysis, styled text and paragraph layout that do not depend on fonts being loaded.
+
+mod script;
+mod itemize;
+mod rich;
+mod paragraph;
+mod bidi;
+mod editor;
+
+pub use self::script::{Script, is_default_ignorable};
+pub use self::itemize::{itemize, TextRun};
+pub use self::bidi::{Direction, BidiRun, visual_runs, is_rtl, move_caret_visually};
+pub use self::rich::{RichText, Span, SpanStyle};
+pub use self::editor::{TextEditor, CaretLayout, previous_grapheme, next_grapheme, previous_word, next_word};
+pub use self::paragraph::{Paragraph, ParagraphStyle, TextAlign, LineHeight, Marker, Hyphenator, Line, LineSegment, LineEndMarker, CaretRect};
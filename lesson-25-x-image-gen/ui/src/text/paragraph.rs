@@ -0,0 +1,854 @@
+use std::ops::Range;
+use std::rc::Rc;
+use std::f32;
+use xi_unicode::LineBreakIterator;
+use crate::fonts::Measurement;
+use crate::primitives::GlyphMeasurement;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+    /// Stretches spaces so that lines fill the whole width, except the last line
+    /// of every paragraph.
+    Justify,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineHeight {
+    /// Height of the font, including its line gap.
+    Normal,
+    /// Height of the font multiplied by the factor.
+    Factor(f32),
+    Fixed(f32),
+}
+
+/// Text drawn where a line is cut, like a hyphen or an ellipsis, and its measured width.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Marker {
+    pub text: String,
+    pub width: f32,
+}
+
+/// Finds places where words can be split with a hyphen.
+pub trait Hyphenator {
+    /// Byte offsets inside `word`, between graphemes, where it can be split.
+    fn hyphenation_points(&self, word: &str) -> Vec<usize>;
+}
+
+#[derive(Clone)]
+pub struct ParagraphStyle {
+    pub align: TextAlign,
+    /// Lines longer than this are wrapped, `None` only breaks at line breaks in the text.
+    pub max_width: Option<f32>,
+    pub max_lines: Option<usize>,
+    pub line_height: LineHeight,
+    /// Drawn at the end of the last line when text is cut by `max_lines`.
+    pub ellipsis: Option<Marker>,
+    pub hyphenation: Option<(Rc<Hyphenator>, Marker)>,
+}
+
+impl ParagraphStyle {
+    pub fn new() -> ParagraphStyle {
+        ParagraphStyle {
+            align: TextAlign::Left,
+            max_width: None,
+            max_lines: None,
+            line_height: LineHeight::Normal,
+            ellipsis: None,
+            hyphenation: None,
+        }
+    }
+
+    pub fn align(mut self, align: TextAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn max_width(mut self, max_width: f32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    pub fn max_lines(mut self, max_lines: usize) -> Self {
+        self.max_lines = Some(max_lines);
+        self
+    }
+
+    pub fn line_height(mut self, line_height: LineHeight) -> Self {
+        self.line_height = line_height;
+        self
+    }
+
+    pub fn ellipsis<P: ToString>(mut self, text: P, width: f32) -> Self {
+        self.ellipsis = Some(Marker { text: text.to_string(), width });
+        self
+    }
+
+    pub fn hyphenation<H: Hyphenator + 'static, P: ToString>(mut self, hyphenator: H, hyphen: P, width: f32) -> Self {
+        self.hyphenation = Some((Rc::new(hyphenator) as Rc<Hyphenator>, Marker { text: hyphen.to_string(), width }));
+        self
+    }
+}
+
+impl Default for ParagraphStyle {
+    fn default() -> Self {
+        ParagraphStyle::new()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineEndMarker {
+    Hyphen { x: f32 },
+    Ellipsis { x: f32 },
+}
+
+/// Part of a line drawn in one piece. Lines have a single segment unless justified.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineSegment {
+    pub range: Range<usize>,
+    pub x: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    /// Bytes of the line, including trailing whitespace and the line break.
+    pub range: Range<usize>,
+    /// Indices of glyphs passed to `Paragraph::layout`. Glyphs of bidirectional text are in
+    /// visual order, so the range may include glyphs of other lines.
+    pub glyphs: Range<usize>,
+    pub x: f32,
+    pub top: f32,
+    pub baseline: f32,
+    pub height: f32,
+    /// Width without trailing whitespace.
+    pub width: f32,
+    pub segments: Vec<LineSegment>,
+    pub end_marker: Option<LineEndMarker>,
+}
+
+/// Grapheme cluster placed on a line.
+#[derive(Clone, Debug)]
+struct Cluster {
+    range: Range<usize>,
+    glyphs: Range<usize>,
+    width: f32,
+    whitespace: bool,
+    line_break: bool,
+    /// Set by layout.
+    x: f32,
+}
+
+/// Text broken into lines, aligned and positioned vertically from the top at zero.
+#[derive(Clone, Debug)]
+pub struct Paragraph {
+    lines: Vec<Line>,
+    clusters: Vec<Cluster>,
+    /// Clusters of every line.
+    line_clusters: Vec<Range<usize>>,
+    width: f32,
+    height: f32,
+    text_len: usize,
+}
+
+/// Position and height of a text cursor.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CaretRect {
+    pub line: usize,
+    pub x: f32,
+    pub top: f32,
+    pub height: f32,
+}
+
+impl Paragraph {
+    /// Breaks `text` into lines at line break opportunities of Unicode Standard Annex #14.
+    ///
+    /// `glyphs` are shaped glyphs of `text`, `metrics` are metrics of its font, both in
+    /// the same units as the style sizes. Lines are laid out in logical order, right-to-left
+    /// runs are expected to be reordered when every line is drawn.
+    pub fn layout(text: &str, glyphs: &[GlyphMeasurement], metrics: &Measurement, style: &ParagraphStyle) -> Paragraph {
+        let mut clusters = clusters(text, glyphs);
+        let breaks = break_opportunities(text, &clusters);
+
+        let mut breaker = LineBreaker {
+            text,
+            clusters: &clusters,
+            style,
+            max_width: style.max_width.unwrap_or(f32::INFINITY),
+            lines: Vec::new(),
+            line_start: 0,
+            line_end: 0,
+        };
+
+        let mut segment_start = 0;
+        for &(segment_end, hard) in breaks.iter() {
+            breaker.add_segment(segment_start, segment_end);
+            if hard {
+                breaker.end_line(None, true);
+            }
+            segment_start = segment_end;
+        }
+        breaker.finish();
+
+        let mut lines = breaker.lines;
+
+        let truncated = match style.max_lines.map(|m| m.max(1)) {
+            Some(max_lines) if lines.len() > max_lines => {
+                lines.truncate(max_lines);
+                true
+            },
+            _ => false,
+        };
+
+        if truncated {
+            if let (Some(ellipsis), Some(last)) = (style.ellipsis.as_ref(), lines.last_mut()) {
+                let max_width = style.max_width.unwrap_or(f32::INFINITY);
+                while last.clusters.end > last.clusters.start
+                    && content_width(&clusters, last.clusters.clone()) + ellipsis.width > max_width {
+                    last.clusters.end -= 1;
+                }
+                last.marker = Some(MarkerKind::Ellipsis);
+                last.hard_break = true;
+            }
+        }
+
+        let available_width = match style.max_width {
+            Some(max_width) => max_width,
+            None => lines.iter()
+                .map(|l| content_width(&clusters, l.clusters.clone()))
+                .fold(0.0, f32::max),
+        };
+
+        let line_height = match style.line_height {
+            LineHeight::Normal => metrics.height,
+            LineHeight::Factor(factor) => metrics.height * factor,
+            LineHeight::Fixed(height) => height,
+        };
+        let half_leading = (line_height - (metrics.ascent - metrics.descent)) / 2.0;
+
+        let mut placed_lines = Vec::with_capacity(lines.len());
+        let mut line_clusters = Vec::with_capacity(lines.len());
+        let mut width: f32 = 0.0;
+        let mut line_byte_start = 0;
+
+        for (index, line) in lines.iter().enumerate() {
+            let is_last = index + 1 == lines.len();
+            let content = content_end(&clusters, line.clusters.clone());
+            let line_width = content_width(&clusters, line.clusters.clone());
+
+            let justify_spaces = clusters[line.clusters.start..content].iter()
+                .filter(|c| c.whitespace)
+                .count();
+            let justify = style.align == TextAlign::Justify
+                && style.max_width.is_some()
+                && !line.hard_break
+                && !is_last
+                && justify_spaces > 0;
+            let space_extra = if justify {
+                (available_width - line_width).max(0.0) / justify_spaces as f32
+            } else {
+                0.0
+            };
+
+            let x = match style.align {
+                TextAlign::Left | TextAlign::Justify => 0.0,
+                TextAlign::Center => (available_width - line_width) / 2.0,
+                TextAlign::Right => available_width - line_width,
+            };
+
+            let mut segments = Vec::new();
+            let mut pen = x;
+            for ix in line.clusters.clone() {
+                let cluster = &mut clusters[ix];
+                cluster.x = pen;
+                pen += cluster.width;
+                if justify && ix < content && cluster.whitespace {
+                    pen += space_extra;
+                }
+            }
+
+            let line_range_end = if is_last && !truncated {
+                text.len()
+            } else {
+                line.clusters.end.checked_sub(1)
+                    .filter(|&i| i >= line.clusters.start)
+                    .map(|i| clusters[i].range.end)
+                    .unwrap_or(line_byte_start)
+                    .max(line_byte_start)
+            };
+
+            // stretched lines are drawn word by word
+            if justify {
+                let mut segment_start = line.clusters.start;
+                for ix in line.clusters.start..content {
+                    if clusters[ix].whitespace {
+                        segments.push(LineSegment {
+                            range: clusters[segment_start].range.start..clusters[ix].range.end,
+                            x: clusters[segment_start].x,
+                        });
+                        segment_start = ix + 1;
+                    }
+                }
+                if segment_start < content {
+                    segments.push(LineSegment {
+                        range: clusters[segment_start].range.start..clusters[content - 1].range.end,
+                        x: clusters[segment_start].x,
+                    });
+                }
+            } else if content > line.clusters.start {
+                segments.push(LineSegment {
+                    range: clusters[line.clusters.start].range.start..clusters[content - 1].range.end,
+                    x,
+                });
+            }
+
+            let end_marker = match line.marker {
+                Some(MarkerKind::Hyphen) => Some(LineEndMarker::Hyphen { x: x + line_width }),
+                Some(MarkerKind::Ellipsis) => Some(LineEndMarker::Ellipsis { x: x + line_width }),
+                None => None,
+            };
+
+            let marker_width = match line.marker {
+                Some(MarkerKind::Hyphen) => style.hyphenation.as_ref().map(|h| h.1.width).unwrap_or(0.0),
+                Some(MarkerKind::Ellipsis) => style.ellipsis.as_ref().map(|e| e.width).unwrap_or(0.0),
+                None => 0.0,
+            };
+
+            let glyphs = match (clusters[line.clusters.clone()].iter().map(|c| c.glyphs.start).min(),
+                                clusters[line.clusters.clone()].iter().map(|c| c.glyphs.end).max()) {
+                (Some(start), Some(end)) => start..end,
+                _ => 0..0,
+            };
+
+            let top = index as f32 * line_height;
+
+            placed_lines.push(Line {
+                range: line_byte_start..line_range_end,
+                glyphs,
+                x,
+                top,
+                baseline: top + half_leading + metrics.ascent,
+                height: line_height,
+                width: line_width,
+                segments,
+                end_marker,
+            });
+            line_clusters.push(line.clusters.clone());
+
+            width = width.max(if justify { available_width } else { line_width + marker_width });
+            line_byte_start = line_range_end;
+        }
+
+        Paragraph {
+            height: placed_lines.len() as f32 * line_height,
+            lines: placed_lines,
+            clusters,
+            line_clusters,
+            width,
+            text_len: text.len(),
+        }
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    /// Width of the longest line and the height of all lines.
+    pub fn size(&self) -> (f32, f32) {
+        (self.width, self.height)
+    }
+
+    /// True if lines were cut by `max_lines`.
+    pub fn is_truncated(&self) -> bool {
+        self.lines.last().map(|l| l.range.end < self.text_len).unwrap_or(false)
+    }
+
+    /// Byte offset of the caret position closest to the point.
+    pub fn hit_test(&self, x: f32, y: f32) -> usize {
+        let line_index = match self.lines.iter().position(|l| y < l.top + l.height) {
+            Some(index) => index,
+            None => match self.lines.len() {
+                0 => return 0,
+                len => len - 1,
+            },
+        };
+
+        let line = &self.lines[line_index];
+        let clusters = &self.clusters[self.line_clusters[line_index].clone()];
+
+        for cluster in clusters.iter() {
+            if cluster.line_break {
+                return cluster.range.start;
+            }
+            if x < cluster.x + cluster.width / 2.0 {
+                return cluster.range.start;
+            }
+        }
+
+        // stay on this line instead of moving to the start of the next one
+        match clusters.last() {
+            Some(last) if last.whitespace && line_index + 1 < self.lines.len() => last.range.start,
+            _ => line.range.end,
+        }
+    }
+
+    /// Caret before the grapheme at the byte `offset`, or at the end of the text.
+    pub fn caret_rect(&self, offset: usize) -> CaretRect {
+        let line_index = match self.lines.iter().position(|l| offset < l.range.end) {
+            Some(index) => index,
+            None => self.lines.len().saturating_sub(1),
+        };
+
+        let line = match self.lines.get(line_index) {
+            Some(line) => line,
+            None => return CaretRect { line: 0, x: 0.0, top: 0.0, height: 0.0 },
+        };
+
+        let clusters = &self.clusters[self.line_clusters[line_index].clone()];
+
+        let x = match clusters.iter().find(|c| c.range.end > offset) {
+            Some(cluster) => cluster.x,
+            None => clusters.last()
+                .map(|c| c.x + c.width)
+                .unwrap_or(line.x),
+        };
+
+        CaretRect {
+            line: line_index,
+            x,
+            top: line.top,
+            height: line.height,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum MarkerKind {
+    Hyphen,
+    Ellipsis,
+}
+
+struct BrokenLine {
+    clusters: Range<usize>,
+    marker: Option<MarkerKind>,
+    hard_break: bool,
+}
+
+struct LineBreaker<'a> {
+    text: &'a str,
+    clusters: &'a [Cluster],
+    style: &'a ParagraphStyle,
+    max_width: f32,
+    lines: Vec<BrokenLine>,
+    line_start: usize,
+    line_end: usize,
+}
+
+impl<'a> LineBreaker<'a> {
+    /// Adds clusters `start..end` that can not be broken, except with a hyphen
+    /// or when they do not fit on an empty line.
+    fn add_segment(&mut self, segment_start: usize, end: usize) {
+        let mut start = segment_start;
+        while start < end {
+            let word_end = content_end(self.clusters, start..end);
+            let word_width = width(self.clusters, start..word_end);
+            let line_width = width(self.clusters, self.line_start..self.line_end);
+
+            if line_width + word_width <= self.max_width {
+                self.line_end = end;
+                return;
+            }
+
+            let remaining = self.max_width - line_width;
+            if let Some(split) = self.hyphenation_split(segment_start, start, word_end, remaining) {
+                self.line_end = split;
+                self.end_line(Some(MarkerKind::Hyphen), false);
+                start = split;
+                continue;
+            }
+
+            if self.line_end > self.line_start {
+                self.end_line(None, false);
+                continue;
+            }
+
+            // the word does not fit on an empty line, break it between graphemes
+            let mut split = start + 1;
+            while split < word_end && width(self.clusters, start..split + 1) <= self.max_width {
+                split += 1;
+            }
+
+            if split >= word_end {
+                self.line_end = end;
+                return;
+            }
+
+            self.line_end = split;
+            self.end_line(None, false);
+            start = split;
+        }
+    }
+
+    /// Cluster index after `start` where the word can be hyphenated so that the part
+    /// from `start` fits into `remaining` width.
+    fn hyphenation_split(&self, word_start: usize, start: usize, word_end: usize, remaining: f32) -> Option<usize> {
+        let (hyphenator, hyphen) = self.style.hyphenation.as_ref()?;
+        if word_end <= start {
+            return None;
+        }
+
+        let word_start_byte = self.clusters[word_start].range.start;
+        let word = &self.text[word_start_byte..self.clusters[word_end - 1].range.end];
+
+        let mut points = hyphenator.hyphenation_points(word);
+        points.sort();
+
+        points.iter().rev()
+            .filter_map(|&point| {
+                let byte = word_start_byte + point;
+                self.clusters[word_start..word_end].iter()
+                    .position(|c| c.range.start == byte)
+                    .map(|index| word_start + index)
+            })
+            .filter(|&split| split > start)
+            .find(|&split| width(self.clusters, start..split) + hyphen.width <= remaining)
+    }
+
+    fn end_line(&mut self, marker: Option<MarkerKind>, hard_break: bool) {
+        self.lines.push(BrokenLine {
+            clusters: self.line_start..self.line_end,
+            marker,
+            hard_break,
+        });
+        self.line_start = self.line_end;
+    }
+
+    fn finish(&mut self) {
+        let ends_with_hard_break = self.lines.last().map(|l| l.hard_break).unwrap_or(true);
+        if self.line_end > self.line_start || ends_with_hard_break {
+            self.end_line(None, true);
+        }
+    }
+}
+
+/// Groups glyphs of the same grapheme into clusters, in logical order.
+fn clusters(text: &str, glyphs: &[GlyphMeasurement]) -> Vec<Cluster> {
+    let mut clusters: Vec<Cluster> = Vec::with_capacity(glyphs.len());
+
+    for (index, glyph) in glyphs.iter().enumerate() {
+        let start = (glyph.byte_offset as usize).min(text.len());
+        let end = ((glyph.byte_offset + glyph.len) as usize).min(text.len()).max(start);
+
+        let same_cluster = clusters.last().map(|c| c.range.start == start).unwrap_or(false);
+        if same_cluster {
+            let cluster = clusters.last_mut().unwrap();
+            cluster.glyphs.end = index + 1;
+            if !cluster.line_break {
+                cluster.width += glyph.x_advance;
+            }
+            continue;
+        }
+
+        let grapheme = text.get(start..end).unwrap_or("");
+        let line_break = grapheme.chars().any(is_line_break);
+        let whitespace = !grapheme.is_empty() && grapheme.chars().all(char::is_whitespace);
+
+        clusters.push(Cluster {
+            range: start..end,
+            glyphs: index..index + 1,
+            width: if line_break { 0.0 } else { glyph.x_advance },
+            whitespace,
+            line_break,
+            x: 0.0,
+        });
+    }
+
+    // right-to-left runs are reversed
+    clusters.sort_by_key(|c| c.range.start);
+
+    clusters
+}
+
+fn is_line_break(c: char) -> bool {
+    match c {
+        '\n' | '\r' | '\u{b}' | '\u{c}' | '\u{85}' | '\u{2028}' | '\u{2029}' => true,
+        _ => false,
+    }
+}
+
+/// Cluster indices after which lines can be broken, and whether the break is mandatory.
+fn break_opportunities(text: &str, clusters: &[Cluster]) -> Vec<(usize, bool)> {
+    let mut breaks: Vec<(usize, bool)> = Vec::new();
+    let mut cluster = 0;
+
+    for (byte, _) in LineBreakIterator::new(text) {
+        while cluster < clusters.len() && clusters[cluster].range.start < byte {
+            cluster += 1;
+        }
+
+        let hard = text[..byte].chars().next_back().map(is_line_break).unwrap_or(false);
+
+        match breaks.last_mut() {
+            Some(last) if last.0 == cluster => {
+                last.1 |= hard;
+                continue;
+            },
+            _ => (),
+        }
+
+        breaks.push((cluster, hard));
+    }
+
+    if breaks.last().map(|b| b.0) != Some(clusters.len()) {
+        breaks.push((clusters.len(), false));
+    }
+
+    breaks
+}
+
+fn width(clusters: &[Cluster], range: Range<usize>) -> f32 {
+    clusters[range].iter().map(|c| c.width).sum()
+}
+
+/// End of clusters without trailing whitespace and line breaks.
+fn content_end(clusters: &[Cluster], range: Range<usize>) -> usize {
+    let mut end = range.end;
+    while end > range.start && (clusters[end - 1].whitespace || clusters[end - 1].line_break) {
+        end -= 1;
+    }
+    end
+}
+
+fn content_width(clusters: &[Cluster], range: Range<usize>) -> f32 {
+    let end = content_end(clusters, range.clone());
+    width(clusters, range.start..end)
+}
+
+#[cfg(test)]
+mod paragraph_tests {
+    use super::*;
+
+    fn glyphs(text: &str) -> Vec<GlyphMeasurement> {
+        text.char_indices()
+            .map(|(offset, c)| GlyphMeasurement {
+                id: c as u32,
+                cluster: offset as u32,
+                byte_offset: offset as u32,
+                len: c.len_utf8() as u32,
+                x_advance: 10.0,
+                y_advance: 0.0,
+                x_offset: 0.0,
+                y_offset: 0.0,
+            })
+            .collect()
+    }
+
+    fn metrics() -> Measurement {
+        Measurement {
+            ascent: 8.0,
+            descent: -2.0,
+            width: 0.0,
+            cap_height: 7.0,
+            x_height: 5.0,
+            line_gap: 2.0,
+            height: 12.0,
+        }
+    }
+
+    fn layout(text: &str, style: &ParagraphStyle) -> Paragraph {
+        Paragraph::layout(text, &glyphs(text), &metrics(), style)
+    }
+
+    fn line_texts<'t>(text: &'t str, paragraph: &Paragraph) -> Vec<&'t str> {
+        paragraph.lines().iter().map(|l| &text[l.range.clone()]).collect()
+    }
+
+    struct SyllableHyphenator;
+
+    impl Hyphenator for SyllableHyphenator {
+        fn hyphenation_points(&self, word: &str) -> Vec<usize> {
+            match word {
+                "hyphenation" => vec![2, 6, 7],
+                _ => vec![],
+            }
+        }
+    }
+
+    #[test]
+    fn empty_text_has_one_empty_line() {
+        let paragraph = layout("", &ParagraphStyle::new());
+        assert_eq!(1, paragraph.lines().len());
+        assert_eq!(0..0, paragraph.lines()[0].range);
+        assert_eq!((0.0, 12.0), paragraph.size());
+    }
+
+    #[test]
+    fn wraps_at_spaces() {
+        let text = "aaa bbb ccc";
+        let paragraph = layout(text, &ParagraphStyle::new().max_width(75.0));
+        assert_eq!(vec!["aaa bbb ", "ccc"], line_texts(text, &paragraph));
+        assert_eq!(70.0, paragraph.lines()[0].width);
+        assert_eq!((70.0, 24.0), paragraph.size());
+    }
+
+    #[test]
+    fn trailing_space_does_not_wrap_line() {
+        let text = "aaa bbb";
+        let paragraph = layout(text, &ParagraphStyle::new().max_width(30.0));
+        assert_eq!(vec!["aaa ", "bbb"], line_texts(text, &paragraph));
+    }
+
+    #[test]
+    fn breaks_at_newlines() {
+        let text = "aaa\nbbb\n";
+        let paragraph = layout(text, &ParagraphStyle::new());
+        assert_eq!(vec!["aaa\n", "bbb\n", ""], line_texts(text, &paragraph));
+        assert_eq!(30.0, paragraph.lines()[0].width);
+    }
+
+    #[test]
+    fn lines_cover_all_text() {
+        let text = "one two  three\nfour five six seven";
+        let paragraph = layout(text, &ParagraphStyle::new().max_width(55.0));
+        let joined: String = line_texts(text, &paragraph).concat();
+        assert_eq!(text, joined);
+    }
+
+    #[test]
+    fn aligns_lines() {
+        let text = "aaaa bb";
+        let style = ParagraphStyle::new().max_width(45.0);
+
+        let right = layout(text, &style.clone().align(TextAlign::Right));
+        assert_eq!(5.0, right.lines()[0].x);
+        assert_eq!(25.0, right.lines()[1].x);
+
+        let center = layout(text, &style.align(TextAlign::Center));
+        assert_eq!(2.5, center.lines()[0].x);
+        assert_eq!(12.5, center.lines()[1].x);
+    }
+
+    #[test]
+    fn aligns_to_longest_line_without_max_width() {
+        let text = "aaaa\nbb";
+        let paragraph = layout(text, &ParagraphStyle::new().align(TextAlign::Center));
+        assert_eq!(0.0, paragraph.lines()[0].x);
+        assert_eq!(10.0, paragraph.lines()[1].x);
+    }
+
+    #[test]
+    fn justifies_all_lines_but_last() {
+        let text = "a b c dd";
+        let paragraph = layout(text, &ParagraphStyle::new().max_width(70.0).align(TextAlign::Justify));
+        assert_eq!(vec!["a b c ", "dd"], line_texts(text, &paragraph));
+
+        let first = &paragraph.lines()[0];
+        let positions: Vec<f32> = first.segments.iter().map(|s| s.x).collect();
+        assert_eq!(vec![0.0, 30.0, 60.0], positions);
+
+        let last = &paragraph.lines()[1];
+        assert_eq!(1, last.segments.len());
+        assert_eq!(0.0, last.segments[0].x);
+    }
+
+    #[test]
+    fn does_not_justify_before_newline() {
+        let text = "a b\nc d e f";
+        let paragraph = layout(text, &ParagraphStyle::new().max_width(100.0).align(TextAlign::Justify));
+        assert_eq!(1, paragraph.lines()[0].segments.len());
+    }
+
+    #[test]
+    fn truncates_with_ellipsis() {
+        let text = "aaa bbb ccc ddd";
+        let paragraph = layout(text, &ParagraphStyle::new().max_width(75.0).max_lines(1).ellipsis("…", 10.0));
+        assert_eq!(1, paragraph.lines().len());
+        assert!(paragraph.is_truncated());
+
+        let line = &paragraph.lines()[0];
+        assert_eq!("aaa bb", &text[line.range.clone()]);
+        assert_eq!(Some(LineEndMarker::Ellipsis { x: 60.0 }), line.end_marker);
+        assert_eq!(70.0, paragraph.size().0);
+    }
+
+    #[test]
+    fn truncates_without_ellipsis() {
+        let text = "a\nb\nc";
+        let paragraph = layout(text, &ParagraphStyle::new().max_lines(2));
+        assert_eq!(vec!["a\n", "b\n"], line_texts(text, &paragraph));
+        assert_eq!(None, paragraph.lines()[1].end_marker);
+    }
+
+    #[test]
+    fn hyphenates_long_words() {
+        let text = "a hyphenation";
+        let style = ParagraphStyle::new()
+            .max_width(90.0)
+            .hyphenation(SyllableHyphenator, "-", 10.0);
+        let paragraph = layout(text, &style);
+
+        assert_eq!(vec!["a hyphen", "ation"], line_texts(text, &paragraph));
+        assert_eq!(Some(LineEndMarker::Hyphen { x: 80.0 }), paragraph.lines()[0].end_marker);
+        assert_eq!(None, paragraph.lines()[1].end_marker);
+
+        let narrow = layout(text, &style.max_width(50.0));
+        assert_eq!(vec!["a hy", "phen", "ation"], line_texts(text, &narrow));
+    }
+
+    #[test]
+    fn breaks_words_that_do_not_fit() {
+        let text = "abcdefg hi";
+        let paragraph = layout(text, &ParagraphStyle::new().max_width(30.0));
+        assert_eq!(vec!["abc", "def", "g ", "hi"], line_texts(text, &paragraph));
+    }
+
+    #[test]
+    fn breaks_between_ideographs() {
+        let text = "漢字漢字";
+        let paragraph = layout(text, &ParagraphStyle::new().max_width(25.0));
+        assert_eq!(vec!["漢字", "漢字"], line_texts(text, &paragraph));
+    }
+
+    #[test]
+    fn positions_lines_by_line_height() {
+        let text = "a\nb";
+
+        let normal = layout(text, &ParagraphStyle::new());
+        assert_eq!(12.0, normal.lines()[1].top);
+        assert_eq!(9.0, normal.lines()[0].baseline);
+
+        let doubled = layout(text, &ParagraphStyle::new().line_height(LineHeight::Factor(2.0)));
+        assert_eq!(24.0, doubled.lines()[1].top);
+        assert_eq!(15.0, doubled.lines()[0].baseline);
+
+        let fixed = layout(text, &ParagraphStyle::new().line_height(LineHeight::Fixed(10.0)));
+        assert_eq!(20.0, fixed.size().1);
+        assert_eq!(8.0, fixed.lines()[0].baseline);
+    }
+
+    #[test]
+    fn hit_tests_points() {
+        let text = "aaa bbb\ncc";
+        let paragraph = layout(text, &ParagraphStyle::new().max_width(45.0));
+        assert_eq!(vec!["aaa ", "bbb\n", "cc"], line_texts(text, &paragraph));
+
+        assert_eq!(0, paragraph.hit_test(-5.0, 2.0));
+        assert_eq!(1, paragraph.hit_test(6.0, 2.0));
+        assert_eq!(3, paragraph.hit_test(100.0, 2.0));
+        assert_eq!(7, paragraph.hit_test(100.0, 14.0));
+        assert_eq!(9, paragraph.hit_test(14.0, 100.0));
+        assert_eq!(10, paragraph.hit_test(100.0, 100.0));
+    }
+
+    #[test]
+    fn finds_caret_rects() {
+        let text = "aaa bbb\ncc";
+        let paragraph = layout(text, &ParagraphStyle::new().max_width(45.0).align(TextAlign::Right));
+
+        assert_eq!(CaretRect { line: 0, x: 15.0, top: 0.0, height: 12.0 }, paragraph.caret_rect(0));
+        assert_eq!(CaretRect { line: 1, x: 15.0, top: 12.0, height: 12.0 }, paragraph.caret_rect(4));
+        assert_eq!(CaretRect { line: 1, x: 45.0, top: 12.0, height: 12.0 }, paragraph.caret_rect(7));
+        assert_eq!(CaretRect { line: 2, x: 45.0, top: 24.0, height: 12.0 }, paragraph.caret_rect(10));
+    }
+}
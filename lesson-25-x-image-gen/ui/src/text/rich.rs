@@ -0,0 +1,201 @@
+use crate::na;
+
+/// Style of a rich text span.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpanStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub monospaced: bool,
+    pub color: na::Vector4<u8>,
+    /// Font size, or `None` for the size of the whole text.
+    pub size: Option<f32>,
+}
+
+impl SpanStyle {
+    pub fn new() -> SpanStyle {
+        SpanStyle {
+            bold: false,
+            italic: false,
+            monospaced: false,
+            color: [0, 0, 0, 255].into(),
+            size: None,
+        }
+    }
+
+    pub fn bold(mut self, value: bool) -> SpanStyle {
+        self.bold = value;
+        self
+    }
+
+    pub fn italic(mut self, value: bool) -> SpanStyle {
+        self.italic = value;
+        self
+    }
+
+    pub fn monospaced(mut self, value: bool) -> SpanStyle {
+        self.monospaced = value;
+        self
+    }
+
+    pub fn color(mut self, color: na::Vector4<u8>) -> SpanStyle {
+        self.color = color;
+        self
+    }
+
+    pub fn size(mut self, size: f32) -> SpanStyle {
+        self.size = Some(size);
+        self
+    }
+}
+
+impl Default for SpanStyle {
+    fn default() -> Self {
+        SpanStyle::new()
+    }
+}
+
+impl From<na::Vector4<u8>> for SpanStyle {
+    fn from(color: na::Vector4<u8>) -> Self {
+        SpanStyle::new().color(color)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub style: SpanStyle,
+}
+
+/// Text made of spans with different styles, see `Primitives::rich_text`.
+///
+/// ```ignore
+/// let text = RichText::new()
+///     .span("fn", SpanStyle::new().bold(true))
+///     .span(" main", [0, 0, 255, 255].into());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RichText {
+    spans: Vec<Span>,
+}
+
+impl RichText {
+    pub fn new() -> RichText {
+        RichText {
+            spans: Vec::new(),
+        }
+    }
+
+    pub fn span<P: ToString, S: Into<SpanStyle>>(mut self, text: P, style: S) -> RichText {
+        self.push(text, style);
+        self
+    }
+
+    /// Appends a span, text with the same style as the last span is merged into it.
+    pub fn push<P: ToString, S: Into<SpanStyle>>(&mut self, text: P, style: S) {
+        let text = text.to_string();
+        let style = style.into();
+
+        if text.is_empty() {
+            return;
+        }
+
+        if let Some(last) = self.spans.last_mut() {
+            if last.style == style {
+                last.text.push_str(&text);
+                return;
+            }
+        }
+
+        self.spans.push(Span { text, style });
+    }
+
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// Spans together with their byte offsets in the whole text.
+    pub fn spans_with_offsets<'r>(&'r self) -> impl Iterator<Item=(usize, &'r Span)> + 'r {
+        self.spans.iter()
+            .scan(0, |offset, span| {
+                let span_offset = *offset;
+                *offset += span.text.len();
+                Some((span_offset, span))
+            })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    /// Text of all spans without styles.
+    pub fn to_plain_string(&self) -> String {
+        self.spans.iter().map(|s| &s.text[..]).collect()
+    }
+}
+
+#[cfg(test)]
+mod rich_tests {
+    use super::*;
+
+    fn bold() -> SpanStyle {
+        SpanStyle::new().bold(true)
+    }
+
+    #[test]
+    fn adjacent_spans_with_same_style_are_merged() {
+        let text = RichText::new()
+            .span("fn", bold())
+            .span(" ", bold())
+            .span("main", SpanStyle::new())
+            .span("()", SpanStyle::new());
+
+        assert_eq!(
+            vec![
+                Span { text: "fn ".into(), style: bold() },
+                Span { text: "main()".into(), style: SpanStyle::new() },
+            ],
+            text.spans()
+        );
+    }
+
+    #[test]
+    fn spans_with_different_style_are_not_merged() {
+        let text = RichText::new()
+            .span("a", SpanStyle::new())
+            .span("b", SpanStyle::new().size(20.0))
+            .span("c", SpanStyle::new().color([255, 0, 0, 255].into()))
+            .span("d", SpanStyle::new());
+
+        let texts: Vec<_> = text.spans().iter().map(|s| &s.text[..]).collect();
+        assert_eq!(vec!["a", "b", "c", "d"], texts);
+    }
+
+    #[test]
+    fn empty_span_does_not_split_spans_with_same_style() {
+        let text = RichText::new()
+            .span("a", SpanStyle::new())
+            .span("", bold())
+            .span("b", SpanStyle::new());
+
+        assert_eq!(1, text.spans().len());
+        assert_eq!("ab", text.to_plain_string());
+        assert!(RichText::new().span("", bold()).is_empty());
+    }
+
+    #[test]
+    fn offsets_are_in_bytes_of_whole_text() {
+        let text = RichText::new()
+            .span("ąž", SpanStyle::new())
+            .span("日本", bold())
+            .span("x", SpanStyle::new())
+            .span("🦀", bold());
+
+        let offsets: Vec<_> = text.spans_with_offsets().map(|(offset, span)| (offset, &span.text[..])).collect();
+        assert_eq!(vec![(0, "ąž"), (4, "日本"), (10, "x"), (11, "🦀")], offsets);
+
+        let plain = text.to_plain_string();
+        for (offset, span) in text.spans_with_offsets() {
+            assert_eq!(&span.text[..], &plain[offset..offset + span.text.len()]);
+        }
+    }
+}
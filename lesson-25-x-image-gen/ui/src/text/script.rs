@@ -0,0 +1,91 @@
+/// Writing system of a character, as far as it matters for choosing fonts and shaping.
+///
+/// Only scripts that need a separate font or shaper are told apart, the rest are `Common`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Script {
+    /// Punctuation, digits, symbols, emoji and scripts not listed here.
+    Common,
+    /// Combining marks and joiners, which take the script of the preceding character.
+    Inherited,
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Thai,
+    Hangul,
+    Hiragana,
+    Katakana,
+    Han,
+}
+
+impl Script {
+    pub fn of(c: char) -> Script {
+        let c = c as u32;
+
+        match c {
+            0x41..=0x5A | 0x61..=0x7A | 0xAA | 0xBA | 0xC0..=0xD6 | 0xD8..=0xF6 | 0xF8..=0x24F
+            | 0x1E00..=0x1EFF | 0x2C60..=0x2C7F | 0xA720..=0xA7FF | 0xFB00..=0xFB06 => Script::Latin,
+            0x300..=0x36F | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x200C..=0x200D | 0x20D0..=0x20FF
+            | 0xFE00..=0xFE0F | 0xFE20..=0xFE2F | 0xE0100..=0xE01EF => Script::Inherited,
+            0x370..=0x3FF | 0x1F00..=0x1FFF => Script::Greek,
+            0x400..=0x52F | 0x1C80..=0x1C8F | 0x2DE0..=0x2DFF | 0xA640..=0xA69F => Script::Cyrillic,
+            0x531..=0x58F | 0xFB13..=0xFB17 => Script::Armenian,
+            0x591..=0x5FF | 0xFB1D..=0xFB4F => Script::Hebrew,
+            0x600..=0x604 | 0x606..=0x60B | 0x60D..=0x61A | 0x61C..=0x61E | 0x620..=0x63F
+            | 0x641..=0x64A | 0x656..=0x66F | 0x671..=0x6DC | 0x6DE..=0x6FF | 0x750..=0x77F
+            | 0x8A0..=0x8FF | 0xFB50..=0xFDFF | 0xFE70..=0xFEFF => Script::Arabic,
+            0x900..=0x950 | 0x955..=0x963 | 0x966..=0x97F | 0xA8E0..=0xA8FF => Script::Devanagari,
+            0xE01..=0xE3A | 0xE40..=0xE5B => Script::Thai,
+            0x1100..=0x11FF | 0x3131..=0x318E | 0xA960..=0xA97F | 0xAC00..=0xD7AF | 0xD7B0..=0xD7FF
+            | 0xFFA0..=0xFFDC => Script::Hangul,
+            0x3041..=0x3096 | 0x309D..=0x309F => Script::Hiragana,
+            0x30A1..=0x30FA | 0x30FD..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF6F | 0xFF71..=0xFF9D => Script::Katakana,
+            0x2E80..=0x2FDF | 0x3005 | 0x3007 | 0x3021..=0x3029 | 0x3038..=0x303B | 0x3400..=0x4DBF
+            | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x2FA1F => Script::Han,
+            _ => Script::Common,
+        }
+    }
+
+    /// ISO 15924 code of the script, as used in OpenType.
+    pub fn iso15924(&self) -> &'static str {
+        match *self {
+            Script::Common => "Zyyy",
+            Script::Inherited => "Zinh",
+            Script::Latin => "Latn",
+            Script::Greek => "Grek",
+            Script::Cyrillic => "Cyrl",
+            Script::Armenian => "Armn",
+            Script::Hebrew => "Hebr",
+            Script::Arabic => "Arab",
+            Script::Devanagari => "Deva",
+            Script::Thai => "Thai",
+            Script::Hangul => "Hang",
+            Script::Hiragana => "Hira",
+            Script::Katakana => "Kana",
+            Script::Han => "Hani",
+        }
+    }
+
+    /// True for `Common` and `Inherited`, which take the script of surrounding text.
+    pub fn is_neutral(&self) -> bool {
+        match *self {
+            Script::Common | Script::Inherited => true,
+            _ => false,
+        }
+    }
+}
+
+/// Characters that are not drawn and do not need a glyph in the font, such as
+/// joiners, variation selectors and control characters.
+pub fn is_default_ignorable(c: char) -> bool {
+    let c = c as u32;
+
+    match c {
+        0x0..=0x1F | 0x7F..=0x9F | 0xAD | 0x34F | 0x61C | 0x180B..=0x180E | 0x200B..=0x200F
+        | 0x202A..=0x202E | 0x2060..=0x206F | 0xFE00..=0xFE0F | 0xFEFF | 0xE0000..=0xE0FFF => true,
+        _ => false,
+    }
+}
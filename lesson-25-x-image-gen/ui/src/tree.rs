@@ -141,22 +141,62 @@ mod shared {
             }
         }
 
-        pub fn enable_actions(&mut self, state: bool) {
-            if let Some(ref mut set) = self.container
-                .action_set
-                .as_mut() {
-                if state {
-                    set.insert(self.id);
-                } else {
-                    set.remove(&self.id);
-                }
+        /// Clips primitives of this element and all its descendants to the resolved box.
+        pub fn set_clip(&mut self, enabled: bool) {
+            if let Some(node) = self.container.nodes.get_mut(&self.id) {
+                node.clip = enabled;
+            }
+            self.container.queues.borrow_mut().send(Effect::Clip {
+                id: self.id,
+                enabled,
+            });
+        }
+
+        /// Receives pointer, key and text events, see `Element::pointer_down`.
+        pub fn enable_input(&mut self, state: bool) {
+            if state {
+                self.container.input_set.insert(self.id);
             } else {
-                self.container.action_set_actions.push_back(if state {
-                    SetAction::Add(self.id)
-                } else {
-                    SetAction::Remove(self.id)
-                })
+                self.container.input_set.remove(&self.id);
+            }
+        }
+
+        pub fn absolute_transform(&self) -> na::Projective3<f32> {
+            self.container.nodes.get(&self.id)
+                .map(|node| node.absolute_transform())
+                .unwrap_or_else(na::Projective3::identity)
+        }
+
+        /// Moves the focus to this element after the current event. Key and text events are
+        /// delivered to the focused element and its ancestors.
+        pub fn focus(&mut self) {
+            self.container.focus_request = Some(Some(self.id));
+        }
+
+        /// Moves the focus from this element to the closest ancestor with input enabled.
+        pub fn blur(&mut self) {
+            if !self.is_focused() {
+                return;
             }
+
+            let ancestor = self.container.nodes.get(&self.id)
+                .and_then(|node| node.parent_id)
+                .and_then(|parent_id| self.container.input_path(parent_id).last().cloned());
+            self.container.focus_request = Some(ancestor);
+        }
+
+        pub fn is_focused(&self) -> bool {
+            self.container.focused == Some(self.id)
+        }
+
+        /// The pointer is over this element or one of its descendants, if input is enabled.
+        pub fn is_hovered(&self) -> bool {
+            self.container.hovered.contains(&self.id)
+        }
+
+        /// System clipboard, if the tree has one.
+        pub fn clipboard(&self) -> Option<Rc<Clipboard>> {
+            self.container.clipboard.clone()
         }
 
         #[inline(always)]
@@ -282,6 +322,106 @@ mod shared {
             }
         }
 
+        /// Places children with their `FlexItem` properties, see `set_flex_item`.
+        ///
+        /// Children are first measured with `BoxSize::Auto`, and then resized to their final size.
+        pub fn layout_flex(&mut self, style: &FlexStyle) {
+            let size = match self.box_size() {
+                BoxSize::Hidden => return self.layout_empty(),
+                BoxSize::Auto => None,
+                BoxSize::Fixed { w, h } => Some(ResolvedSize { w, h }),
+            };
+
+            let items = self.measure_children(|child| child.flex_item);
+            let (resolved_size, placements) = layout::place_flex(style, size, &items);
+            self.place_children(&placements);
+            self.resolve_size(resolved_size);
+        }
+
+        /// Places children in cells of a grid with their `GridItem` properties, see `set_grid_item`.
+        pub fn layout_grid(&mut self, style: &GridStyle) {
+            let size = match self.box_size() {
+                BoxSize::Hidden => return self.layout_empty(),
+                BoxSize::Auto => None,
+                BoxSize::Fixed { w, h } => Some(ResolvedSize { w, h }),
+            };
+
+            let items = self.measure_children(|child| child.grid_item);
+            let (resolved_size, placements) = layout::place_grid(style, size, &items);
+            self.place_children(&placements);
+            self.resolve_size(resolved_size);
+        }
+
+        /// Places children over each other with their `StackItem` properties, see `set_stack_item`.
+        ///
+        /// Later children are drawn on top of earlier ones.
+        pub fn layout_stack(&mut self, padding: Edges) {
+            let size = match self.box_size() {
+                BoxSize::Hidden => return self.layout_empty(),
+                BoxSize::Auto => None,
+                BoxSize::Fixed { w, h } => Some(ResolvedSize { w, h }),
+            };
+
+            let items = self.measure_children(|child| child.stack_item);
+            let (resolved_size, placements) = layout::place_stack(padding, size, &items);
+            self.place_children(&placements);
+            self.resolve_size(resolved_size);
+        }
+
+        /// Resizes children with `BoxSize::Auto`, and returns their layout properties and sizes.
+        fn measure_children<T, F>(&mut self, properties: F) -> Vec<(T, Option<ResolvedSize>)>
+        where
+            F: Fn(&Child) -> T,
+        {
+            let mut items = Vec::with_capacity(self.children_len());
+            self.children_mut(|_, mut child| {
+                let measured = child.element_resize(BoxSize::Auto);
+                items.push((properties(child.child), measured));
+            });
+            items
+        }
+
+        /// Resizes and moves children to their placements, hides children placed at `None`.
+        pub fn place_children(&mut self, placements: &[Option<Placement>]) {
+            self.children_mut(|i, mut child| match placements.get(i) {
+                Some(&Some(placement)) => {
+                    child.element_resize(BoxSize::Fixed { w: placement.size.w, h: placement.size.h }); // layout ignores actual size
+                    child.set_translation(placement.offset);
+                }
+                _ => child.hide(),
+            });
+        }
+
+        /// Sets flex properties of a child added with `add`, used by `layout_flex`.
+        pub fn set_flex_item(&mut self, id: Ix, item: FlexItem) {
+            if let Some(child) = self.children.items.get_mut(&id) {
+                if child.flex_item != item {
+                    child.flex_item = item;
+                    self.invalidate_size();
+                }
+            }
+        }
+
+        /// Sets the grid cell of a child added with `add`, used by `layout_grid`.
+        pub fn set_grid_item(&mut self, id: Ix, item: GridItem) {
+            if let Some(child) = self.children.items.get_mut(&id) {
+                if child.grid_item != item {
+                    child.grid_item = item;
+                    self.invalidate_size();
+                }
+            }
+        }
+
+        /// Sets the anchor of a child added with `add`, used by `layout_stack`.
+        pub fn set_stack_item(&mut self, id: Ix, item: StackItem) {
+            if let Some(child) = self.children.items.get_mut(&id) {
+                if child.stack_item != item {
+                    child.stack_item = item;
+                    self.invalidate_size();
+                }
+            }
+        }
+
         #[inline(always)]
         pub fn children_len(&self) -> usize {
             self.children.items.len()
@@ -365,6 +505,17 @@ mod shared {
             self.child.transform_propagated = true;
         }
 
+        /// Fades the child and all its descendants, in `[0, 1]` range.
+        pub fn element_opacity(&mut self, opacity: f32) {
+            if self.child.opacity != opacity {
+                self.child.opacity = opacity;
+                self.container.queues.borrow_mut().send(Effect::Opacity {
+                    id: self.child.id,
+                    opacity,
+                });
+            }
+        }
+
         #[inline(always)]
         pub fn hide(&mut self) {
             self.container.hide(self.child.id, self.window_scale);
@@ -376,6 +527,10 @@ mod shared {
         translation2d: ResolvedSize,
         transform: na::Projective3<f32>,
         transform_propagated: bool,
+        opacity: f32,
+        flex_item: FlexItem,
+        grid_item: GridItem,
+        stack_item: StackItem,
     }
 
     impl Child {
@@ -385,6 +540,10 @@ mod shared {
                 translation2d: ResolvedSize { w: 0, h: 0 },
                 transform: na::Projective3::<f32>::identity(),
                 transform_propagated: false,
+                opacity: 1.0,
+                flex_item: FlexItem::new(),
+                grid_item: GridItem::new(),
+                stack_item: StackItem::new(),
             }
         }
 
@@ -425,94 +584,111 @@ mod shared {
             }
         }
 
-        pub fn hide_primitives(&mut self, queues: &mut RefMut<Queues>) {
+        pub fn recreate_font_text_buffers(&mut self, font_ids: &[usize]) {
             if let Some(ref mut primitives) = self.primitives {
                 let mut shared = primitives.shared.borrow_mut();
+                shared.recreate_font_text_buffers(font_ids);
+            }
+        }
 
-                for entry in shared.modified_buffers() {
-                    match entry {
-                        primitives::ModificationLogEntry::Added { buffer } => {
-                            queues.send(Effect::TextAdd {
-                                buffer: buffer.weak_ref()
-                            });
-                        }
-                        primitives::ModificationLogEntry::Removed { buffer_id } => {
-                            queues.send(Effect::TextRemove {
-                                buffer_id
-                            });
-                        }
-                    }
-                }
+        pub fn hide_primitives(&mut self, node_id: Ix, queues: &mut RefMut<Queues>) {
+            if let Some(ref mut primitives) = self.primitives {
+                let mut shared = primitives.shared.borrow_mut();
 
-                for buffer in shared.buffers_keep_invalidated() {
-                    queues.send(Effect::TextUpdate {
-                        buffer_id: buffer.id(),
-                        absolute_transform: None,
-                        color: [0, 0, 0, 255].into(),
-                    });
+                send_modification_log(node_id, &mut shared, queues);
+
+                for primitive in shared.primitives_keep_invalidated() {
+                    send_primitive_update(primitive, None, queues);
                 }
             }
         }
 
-        pub fn sync_primitives(&mut self, absolute_transform: &na::Projective3<f32>, queues: &mut RefMut<Queues>) {
+        pub fn sync_primitives(&mut self, node_id: Ix, absolute_transform: &na::Projective3<f32>, queues: &mut RefMut<Queues>) {
             if let Some(ref mut primitives) = self.primitives {
                 let mut shared = primitives.shared.borrow_mut();
 
-                for entry in shared.modified_buffers() {
-                    match entry {
-                        primitives::ModificationLogEntry::Added { buffer } => {
-                            queues.send(Effect::TextAdd {
-                                buffer: buffer.weak_ref()
-                            });
-                        }
-                        primitives::ModificationLogEntry::Removed { buffer_id } => {
-                            queues.send(Effect::TextRemove {
-                                buffer_id
-                            });
-                        }
-                    }
-                }
+                send_modification_log(node_id, &mut shared, queues);
 
-                for buffer in shared.buffers() {
-                    queues.send(Effect::TextUpdate {
-                        buffer_id: buffer.id(),
-                        absolute_transform: buffer.absolute_transform(absolute_transform),
-                        color: buffer.color(),
-                    });
+                for primitive in shared.primitives() {
+                    send_primitive_update(primitive, Some(absolute_transform), queues);
                 }
             }
         }
 
-        pub fn sync_invalidated_primitives(&mut self, absolute_transform: &na::Projective3<f32>, queues: &mut RefMut<Queues>) {
+        pub fn sync_invalidated_primitives(&mut self, node_id: Ix, absolute_transform: &na::Projective3<f32>, queues: &mut RefMut<Queues>) {
             if let Some(ref mut primitives) = self.primitives {
                 let mut shared = primitives.shared.borrow_mut();
 
-                for entry in shared.modified_buffers() {
-                    match entry {
-                        primitives::ModificationLogEntry::Added { buffer } => {
-                            queues.send(Effect::TextAdd {
-                                buffer: buffer.weak_ref()
-                            });
-                        }
-                        primitives::ModificationLogEntry::Removed { buffer_id } => {
-                            queues.send(Effect::TextRemove {
-                                buffer_id
-                            });
-                        }
-                    }
+                send_modification_log(node_id, &mut shared, queues);
+
+                for primitive in shared.only_invalidated_primitives() {
+                    send_primitive_update(primitive, Some(absolute_transform), queues);
                 }
+            }
+        }
+    }
 
-                for buffer in shared.only_invalidated_buffers() {
-                    queues.send(Effect::TextUpdate {
-                        buffer_id: buffer.id(),
-                        absolute_transform: buffer.absolute_transform(absolute_transform),
-                        color: buffer.color(),
+    fn send_modification_log(node_id: Ix, shared: &mut primitives::shared::InnerPrimitives, queues: &mut RefMut<Queues>) {
+        for entry in shared.modified_buffers() {
+            match entry {
+                primitives::ModificationLogEntry::Added { buffer } => {
+                    queues.send(Effect::TextAdd {
+                        node_id,
+                        buffer: buffer.weak_ref(),
+                        renderer: buffer.renderer(),
                     });
                 }
+                primitives::ModificationLogEntry::Removed { buffer_id } => {
+                    queues.send(Effect::TextRemove {
+                        buffer_id
+                    });
+                }
+                primitives::ModificationLogEntry::ShapeAdded { shape } => {
+                    queues.send(Effect::ShapeAdd {
+                        node_id,
+                        shape
+                    });
+                }
+                primitives::ModificationLogEntry::ShapeRemoved { shape_id } => {
+                    queues.send(Effect::ShapeRemove {
+                        shape_id
+                    });
+                }
+            }
+        }
+    }
+
+    /// Sends the primitive transform relative to the parent, `None` parent transform hides it.
+    fn send_primitive_update(primitive: &primitives::shared::PrimitiveKind, parent_absolute_transform: Option<&na::Projective3<f32>>, queues: &mut RefMut<Queues>) {
+        match *primitive {
+            primitives::shared::PrimitiveKind::TextBuffer(ref buffer) => {
+                queues.send(Effect::TextUpdate {
+                    buffer_id: buffer.id(),
+                    absolute_transform: parent_absolute_transform.and_then(|t| buffer.absolute_transform(t)),
+                    color: match parent_absolute_transform {
+                        Some(_) => buffer.color(),
+                        None => [0, 0, 0, 255].into(),
+                    },
+                });
+            }
+            primitives::shared::PrimitiveKind::Shape { ref shape, transform } => {
+                queues.send(Effect::ShapeUpdate {
+                    shape_id: shape.id(),
+                    absolute_transform: match (parent_absolute_transform, transform) {
+                        (Some(parent), Some(transform)) => Some(parent * transform),
+                        _ => None,
+                    },
+                });
             }
         }
     }
 
+    /// Pointer event in coordinates of the element, if the ray intersects its plane.
+    fn pointer_event(ray: &PointerRay, base: &Base, clicks: u8, modifiers: Modifiers, phase: EventPhase) -> Option<PointerEvent> {
+        ray.intersect(&base.absolute_transform())
+            .map(|(x, y, _)| PointerEvent { x, y, clicks, modifiers, phase })
+    }
+
     enum SetAction {
         Remove(Ix),
         Add(Ix),
@@ -528,17 +704,26 @@ mod shared {
         nodes: BTreeMap<Ix, NodeSkeleton>,
 
         update_set: Option<BTreeSet<Ix>>,
-        action_set: Option<BTreeSet<Ix>>,
-
         update_set_actions: VecDeque<SetAction>,
-        action_set_actions: VecDeque<SetAction>,
+
+        /// Elements that receive input, never iterated so it can change while handling input.
+        input_set: BTreeSet<Ix>,
+        focused: Option<Ix>,
+        /// Focus change requested while handling an event, applied after it.
+        focus_request: Option<Option<Ix>>,
+        /// Elements with input enabled under the pointer, root first.
+        hovered: Vec<Ix>,
+        /// Element that handled the last `pointer_down`, receives pointer events until `pointer_up`.
+        pointer_capture: Option<Ix>,
+
+        clipboard: Option<Rc<Clipboard>>,
     }
 
     impl Container {
         pub fn new(resources: &Resources) -> Container {
             Container {
                 queues: Rc::new(RefCell::new(Queues::new())),
-                _fonts: Fonts::new(),
+                _fonts: Fonts::new(resources),
                 _resources: resources.clone(),
 
                 next_id: Ix(0),
@@ -546,10 +731,15 @@ mod shared {
                 nodes: BTreeMap::new(),
 
                 update_set: Some(BTreeSet::new()),
-                action_set: Some(BTreeSet::new()),
-
                 update_set_actions: VecDeque::with_capacity(32),
-                action_set_actions: VecDeque::with_capacity(32),
+
+                input_set: BTreeSet::new(),
+                focused: None,
+                focus_request: None,
+                hovered: Vec::new(),
+                pointer_capture: None,
+
+                clipboard: None,
             }
         }
 
@@ -597,6 +787,15 @@ mod shared {
             if let Some(mut removed) = self.nodes.remove(&id) {
                 info!("delete node {:?}", id);
 
+                self.input_set.remove(&id);
+                if self.focused == Some(id) {
+                    self.focused = None;
+                }
+                if self.pointer_capture == Some(id) {
+                    self.pointer_capture = None;
+                }
+                self.hovered.retain(|&hovered| hovered != id);
+
                 let absolute_transform = removed.absolute_transform();
 
                 let mut body = removed.steal_body();
@@ -614,15 +813,23 @@ mod shared {
                     }
                 }
 
+                if self._root_id == Some(id) {
+                    self._root_id = self.nodes.iter()
+                        .find(|&(_, node)| node.parent_id.is_none())
+                        .map(|(&id, _)| id);
+                }
+
                 let mut children = body.children;
                 let el = body.el;
                 ::std::mem::drop(el);
-                children.sync_primitives(&absolute_transform, &mut self.queues.borrow_mut());
+                children.sync_primitives(id, &absolute_transform, &mut self.queues.borrow_mut());
 
                 self.queues.borrow_mut().send(Effect::Remove { id })
             }
         }
 
+        /// Adds a root next to the existing ones, for example an overlay drawn above the main root.
+        /// The first root stays the main root, nodes of other roots are removed when their `Leaf` drops.
         pub fn new_root(&mut self, element: Box<Element>, window_scale: f32) -> Ix {
             let root_id = self.next_id.inc();
 
@@ -635,9 +842,10 @@ mod shared {
                 NodeSkeleton::new(None,Children::empty(), &na::Projective3::identity(), element, window_scale);
             let mut body = skeleton.steal_body();
 
-            self.nodes.clear();
             self.nodes.insert(root_id, skeleton);
-            self._root_id = Some(root_id);
+            if self._root_id.is_none() {
+                self._root_id = Some(root_id);
+            }
 
             {
                 let mut base = Base::new(
@@ -708,6 +916,7 @@ mod shared {
             id
         }
 
+        /// The oldest root that still exists.
         #[inline(always)]
         pub fn root_id(&self) -> Option<Ix> {
             self._root_id
@@ -761,12 +970,12 @@ mod shared {
                     if !skip_update || new_window_scale.is_some() {
                         match resolved_size {
                             None => {
-                                skeleton.body.as_mut().map(|b| b.children.hide_primitives(&mut q.borrow_mut()));
+                                skeleton.body.as_mut().map(|b| b.children.hide_primitives(id, &mut q.borrow_mut()));
                             }
                             _ => {
                                 let absolute_transform = skeleton.absolute_transform();
                                 skeleton.body.as_mut().map(|b| {
-                                    b.children.sync_primitives(&absolute_transform, &mut q.borrow_mut())
+                                    b.children.sync_primitives(id, &absolute_transform, &mut q.borrow_mut())
                                 });
                             }
                         }
@@ -795,7 +1004,7 @@ mod shared {
                         for (child_id, _) in &body.children.items {
                             container.parent_transform(*child_id, &absolute_transform);
                         }
-                        body.children.sync_primitives(&absolute_transform, &mut container.queues.borrow_mut());
+                        body.children.sync_primitives(id, &absolute_transform, &mut container.queues.borrow_mut());
                         Some(absolute_transform)
                     } else {
                         None
@@ -823,7 +1032,7 @@ mod shared {
                         for (child_id, _) in &body.children.items {
                             container.parent_transform(*child_id, &absolute_transform);
                         }
-                        body.children.sync_primitives(&absolute_transform, &mut container.queues.borrow_mut());
+                        body.children.sync_primitives(id, &absolute_transform, &mut container.queues.borrow_mut());
                         Some(absolute_transform)
                     } else {
                         None
@@ -859,26 +1068,260 @@ mod shared {
             }
         }
 
-        pub fn send_action(&mut self, action: UiAction) {
-            let update_list = ::std::mem::replace(&mut self.action_set, None)
-                .expect("update: iteration reentry error");
+        pub fn pointer_down(&mut self, ray: &PointerRay, clicks: u8, modifiers: Modifiers) -> bool {
+            let path = self.hit_path(ray);
+            self.update_hover(&path);
 
-            self.update_template(&update_list, |el, base, arg| {
-                el.action(base, arg)
-            }, action);
+            // the focus is cleared unless an element takes it
+            self.focus_request = Some(None);
+
+            let handled_by = self.dispatch(&path, |el, base, phase| {
+                match pointer_event(ray, base, clicks, modifiers, phase) {
+                    Some(event) => el.pointer_down(base, &event),
+                    None => false,
+                }
+            });
 
-            ::std::mem::replace(&mut self.action_set, Some(update_list));
+            self.pointer_capture = handled_by;
+            self.apply_focus_request();
 
-            let set = self.action_set.as_mut().unwrap();
-            while let Some(a) = self.action_set_actions.pop_front() {
-                match a {
-                    SetAction::Add(ix) => set.insert(ix),
-                    SetAction::Remove(ix) => set.remove(&ix),
+            handled_by.is_some()
+        }
+
+        pub fn pointer_move(&mut self, ray: &PointerRay, modifiers: Modifiers) -> bool {
+            let path = self.hit_path(ray);
+            self.update_hover(&path);
+
+            let path = self.pointer_path(path);
+            let handled_by = self.dispatch(&path, |el, base, phase| {
+                match pointer_event(ray, base, 0, modifiers, phase) {
+                    Some(event) => el.pointer_move(base, &event),
+                    None => false,
+                }
+            });
+
+            self.apply_focus_request();
+
+            handled_by.is_some()
+        }
+
+        pub fn pointer_up(&mut self, ray: &PointerRay, modifiers: Modifiers) -> bool {
+            let path = self.hit_path(ray);
+            let path = self.pointer_path(path);
+            let handled_by = self.dispatch(&path, |el, base, phase| {
+                match pointer_event(ray, base, 0, modifiers, phase) {
+                    Some(event) => el.pointer_up(base, &event),
+                    None => false,
+                }
+            });
+
+            self.pointer_capture = None;
+            self.apply_focus_request();
+
+            handled_by.is_some()
+        }
+
+        pub fn key(&mut self, key: Key, modifiers: Modifiers) -> bool {
+            let path = self.focus_path();
+            let handled_by = self.dispatch(&path, |el, base, phase| {
+                el.key(base, &KeyEvent { key, modifiers, phase })
+            });
+
+            self.apply_focus_request();
+
+            handled_by.is_some()
+        }
+
+        pub fn text(&mut self, text: &str, composition_cursor: Option<usize>) -> bool {
+            let path = self.focus_path();
+            let handled_by = self.dispatch(&path, |el, base, phase| {
+                el.text(base, &TextEvent { text: text.to_string(), composition_cursor, phase })
+            });
+
+            self.apply_focus_request();
+
+            handled_by.is_some()
+        }
+
+        /// Delivers the event to elements on the path, in the order of `EventPhase`s,
+        /// and returns the element that handled it.
+        fn dispatch<F>(&mut self, path: &[Ix], mut fun: F) -> Option<Ix>
+            where F: FnMut(&mut Element, &mut Base, EventPhase) -> bool
+        {
+            let (&target, ancestors) = match path.split_last() {
+                Some(split) => split,
+                None => return None,
+            };
+
+            let phases = ancestors.iter().map(|&id| (id, EventPhase::Capture))
+                .chain(Some((target, EventPhase::Target)))
+                .chain(ancestors.iter().rev().map(|&id| (id, EventPhase::Bubble)));
+
+            for (id, phase) in phases {
+                // a previous handler may have removed the element
+                if !self.nodes.contains_key(&id) {
+                    continue;
+                }
+
+                let mut handled = false;
+                self.update_template(Some(&id), |el, base, phase| {
+                    handled = fun(el, base, phase);
+                }, phase);
+
+                if handled {
+                    return Some(id);
+                }
+            }
+
+            None
+        }
+
+        /// Elements with input enabled from the root to the closest element hit by the ray.
+        fn hit_path(&self, ray: &PointerRay) -> Vec<Ix> {
+            let mut hit = None;
+
+            for (&id, node) in self.nodes.iter() {
+                if node.parent_id.is_none() {
+                    self.hit_test(id, ray, &mut hit);
+                }
+            }
+
+            match hit {
+                Some((_, id)) => self.input_path(id),
+                None => Vec::new(),
+            }
+        }
+
+        fn hit_test(&self, id: Ix, ray: &PointerRay, hit: &mut Option<(f32, Ix)>) {
+            // distance within which elements drawn later are considered closer
+            const TOLERANCE: f32 = 0.01;
+
+            let node = match self.nodes.get(&id) {
+                Some(node) => node,
+                None => return,
+            };
+
+            let size = match node.resolved_size() {
+                Some(size) => size,
+                None => return,
+            };
+
+            let inside = match ray.intersect(&node.absolute_transform()) {
+                Some((x, y, distance)) => {
+                    let inside = x >= 0.0 && y >= 0.0 && x < size.w as f32 && y < size.h as f32;
+                    let closer = match *hit {
+                        Some((hit_distance, _)) => distance <= hit_distance + TOLERANCE,
+                        None => true,
+                    };
+
+                    if inside && closer {
+                        *hit = Some((distance, id));
+                    }
+
+                    inside
+                },
+                None => false,
+            };
+
+            if node.clip && !inside {
+                return;
+            }
+
+            if let Some(ref body) = node.body {
+                for (&child_id, child) in body.children.items.iter() {
+                    if child.opacity > 0.0 {
+                        self.hit_test(child_id, ray, hit);
+                    }
+                }
+            }
+        }
+
+        /// The element and its ancestors with input enabled, root first.
+        fn input_path(&self, id: Ix) -> Vec<Ix> {
+            let mut path = Vec::new();
+            let mut next = Some(id);
+
+            while let Some(id) = next {
+                let node = match self.nodes.get(&id) {
+                    Some(node) => node,
+                    None => break,
                 };
+
+                if self.input_set.contains(&id) {
+                    path.push(id);
+                }
+
+                next = node.parent_id;
+            }
+
+            path.reverse();
+            path
+        }
+
+        fn focus_path(&self) -> Vec<Ix> {
+            match self.focused {
+                Some(id) => self.input_path(id),
+                None => Vec::new(),
             }
         }
 
+        /// Path of a pointer event, which is only the capturing element if there is one.
+        fn pointer_path(&self, hit_path: Vec<Ix>) -> Vec<Ix> {
+            match self.pointer_capture {
+                Some(id) if self.nodes.contains_key(&id) => vec![id],
+                _ => hit_path,
+            }
+        }
+
+        fn update_hover(&mut self, path: &[Ix]) {
+            let previous = ::std::mem::replace(&mut self.hovered, path.to_vec());
+
+            for id in previous.iter().rev().filter(|&&id| !path.contains(&id)) {
+                if self.nodes.contains_key(id) {
+                    self.update_template(Some(id), |el, base, hovered| el.hover(base, hovered), false);
+                }
+            }
+
+            for id in path.iter().filter(|&&id| !previous.contains(&id)) {
+                self.update_template(Some(id), |el, base, hovered| el.hover(base, hovered), true);
+            }
+        }
+
+        fn apply_focus_request(&mut self) {
+            let focused = match self.focus_request.take() {
+                Some(focused) => focused,
+                None => return,
+            };
+
+            if focused == self.focused {
+                return;
+            }
+
+            let previous = ::std::mem::replace(&mut self.focused, focused);
+
+            if let Some(id) = previous {
+                if self.nodes.contains_key(&id) {
+                    self.update_template(Some(&id), |el, base, focused| el.focus(base, focused), false);
+                }
+            }
+
+            if let Some(id) = focused {
+                if self.nodes.contains_key(&id) {
+                    self.update_template(Some(&id), |el, base, focused| el.focus(base, focused), true);
+                }
+            }
+
+            // the focus is not moved again from focus handlers
+            self.focus_request = None;
+        }
+
+        pub fn set_clipboard(&mut self, clipboard: Option<Rc<Clipboard>>) {
+            self.clipboard = clipboard;
+        }
+
         pub fn update(&mut self, delta: f32) {
+            self.reload_modified_fonts();
+
             let update_list = ::std::mem::replace(&mut self.update_set, None)
                 .expect("update: iteration reentry error");
 
@@ -888,18 +1331,56 @@ mod shared {
 
             ::std::mem::replace(&mut self.update_set, Some(update_list));
 
-            let set = self.update_set.as_mut().unwrap();
-            while let Some(a) = self.update_set_actions.pop_front() {
-                match a {
-                    SetAction::Add(ix) => set.insert(ix),
-                    SetAction::Remove(ix) => set.remove(&ix),
-                };
+            {
+                let set = self.update_set.as_mut().unwrap();
+                while let Some(a) = self.update_set_actions.pop_front() {
+                    match a {
+                        SetAction::Add(ix) => set.insert(ix),
+                        SetAction::Remove(ix) => set.remove(&ix),
+                    };
+                }
+            }
+
+            self.apply_focus_request();
+        }
+
+        /// Sends text of reloaded fonts to the renderer again and lays out all roots,
+        /// because text measurements may have changed.
+        fn reload_modified_fonts(&mut self) {
+            let font_ids = self._fonts.reload_modified();
+            if font_ids.is_empty() {
+                return;
+            }
+
+            {
+                let mut queues = self.queues.borrow_mut();
+                for &font_id in font_ids.iter() {
+                    queues.send(Effect::FontReload { font_id });
+                }
+            }
+
+            let mut roots = Vec::new();
+
+            for (&id, skeleton) in self.nodes.iter_mut() {
+                if let Some(ref mut body) = skeleton.body {
+                    body.children.recreate_font_text_buffers(&font_ids);
+                }
+
+                if let (None, Some(last_resolved_size)) = (skeleton.parent_id, skeleton.last_resolved_size) {
+                    roots.push((id, last_resolved_size.to_box_size(), skeleton.window_scale));
+                }
+
+                skeleton.last_resolved_size = None;
+            }
+
+            for (id, box_size, window_scale) in roots {
+                self.resize(id, box_size, window_scale);
             }
         }
 
         #[inline(always)]
-        pub fn update_template<A, F>(&mut self, update_list: &BTreeSet<Ix>, mut fun: F, arg: A)
-            where F: FnMut(&mut Element, &mut Base, A), A: Copy
+        pub fn update_template<'l, L, A, F>(&mut self, update_list: L, mut fun: F, arg: A)
+            where L: IntoIterator<Item=&'l Ix>, F: FnMut(&mut Element, &mut Base, A), A: Copy
         {
             enum ResizeAction {
                 None,
@@ -949,7 +1430,7 @@ mod shared {
                             BoxSize::Hidden => (),
                             _ => {
                                 let absolute_transform = skeleton.absolute_transform();
-                                skeleton.body.as_mut().map(|b| b.children.sync_invalidated_primitives(&absolute_transform, &mut q.borrow_mut()));
+                                skeleton.body.as_mut().map(|b| b.children.sync_invalidated_primitives(*id, &absolute_transform, &mut q.borrow_mut()));
                             }
                         }
 
@@ -1008,6 +1489,7 @@ mod shared {
         parent_id: Option<Ix>,
         body: Option<NodeBody>,
         window_scale: f32,
+        clip: bool,
     }
 
     impl NodeSkeleton {
@@ -1027,7 +1509,8 @@ mod shared {
                     children,
                     el: element,
                 }),
-                window_scale
+                window_scale,
+                clip: false,
             }
         }
 
@@ -1047,6 +1530,14 @@ mod shared {
             }
         }
 
+        pub fn resolved_size(&self) -> Option<ResolvedSize> {
+            match self.last_resolved_size {
+                Some(LastResolvedSize::ElementSizeAuto(size)) => size,
+                Some(LastResolvedSize::ElementSizeFixed { size, .. }) => size,
+                _ => None,
+            }
+        }
+
         #[inline(always)]
         pub fn absolute_transform(&self) -> na::Projective3<f32> {
             &self.parent_transform * &self.relative_transform
@@ -1103,9 +1594,42 @@ impl Tree {
         self.shared.borrow_mut().update(delta)
     }
 
+    /// Returns `true` if an element handled the event, see `Element::pointer_down`.
+    #[inline(always)]
+    pub fn pointer_down(&self, ray: &PointerRay, clicks: u8, modifiers: Modifiers) -> bool {
+        self.shared.borrow_mut().pointer_down(ray, clicks, modifiers)
+    }
+
+    #[inline(always)]
+    pub fn pointer_move(&self, ray: &PointerRay, modifiers: Modifiers) -> bool {
+        self.shared.borrow_mut().pointer_move(ray, modifiers)
+    }
+
     #[inline(always)]
-    pub fn send_action(&self, action: UiAction) {
-        self.shared.borrow_mut().send_action(action)
+    pub fn pointer_up(&self, ray: &PointerRay, modifiers: Modifiers) -> bool {
+        self.shared.borrow_mut().pointer_up(ray, modifiers)
+    }
+
+    /// Returns `true` if the focused element or one of its ancestors handled the key.
+    #[inline(always)]
+    pub fn key(&self, key: Key, modifiers: Modifiers) -> bool {
+        self.shared.borrow_mut().key(key, modifiers)
+    }
+
+    /// Text typed or committed by an input method.
+    #[inline(always)]
+    pub fn text(&self, text: &str) -> bool {
+        self.shared.borrow_mut().text(text, None)
+    }
+
+    /// Text being composed by an input method, `cursor` is a byte offset in it.
+    #[inline(always)]
+    pub fn composition(&self, text: &str, cursor: usize) -> bool {
+        self.shared.borrow_mut().text(text, Some(cursor))
+    }
+
+    pub fn set_clipboard<C: Clipboard + 'static>(&self, clipboard: C) {
+        self.shared.borrow_mut().set_clipboard(Some(Rc::new(clipboard)));
     }
 
     #[inline(always)]
@@ -1154,10 +1678,6 @@ impl<T> Leaf<T> {
     pub fn resize(&self, size: BoxSize, window_scale: f32) -> Option<ResolvedSize> {
         self.shared.borrow_mut().resize(self.id, size, window_scale)
     }
-
-    pub fn send_action(&self, action: UiAction) {
-        self.shared.borrow_mut().send_action(action)
-    }
 }
 
 impl<T> Drop for Leaf<T> {
@@ -1165,3 +1685,305 @@ impl<T> Drop for Leaf<T> {
         self.shared.borrow_mut().delete_node(self.id);
     }
 }
+
+#[cfg(test)]
+mod tree_tests {
+    use super::*;
+    use crate::na;
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    /// Element with input enabled that records events it receives.
+    struct Recorder {
+        name: &'static str,
+        size: ResolvedSize,
+        children: Vec<Recorder>,
+        log: Log,
+        /// Phase in which pointer and key events are handled, others are passed on.
+        handles: Option<EventPhase>,
+        focus_on_pointer_down: bool,
+    }
+
+    impl Recorder {
+        fn new(name: &'static str, w: i32, h: i32, log: &Log) -> Recorder {
+            Recorder {
+                name,
+                size: ResolvedSize { w, h },
+                children: Vec::new(),
+                log: log.clone(),
+                handles: None,
+                focus_on_pointer_down: false,
+            }
+        }
+
+        fn child(mut self, child: Recorder) -> Self {
+            self.children.push(child);
+            self
+        }
+
+        fn handles(mut self, phase: EventPhase) -> Self {
+            self.handles = Some(phase);
+            self
+        }
+
+        fn focus_on_pointer_down(mut self) -> Self {
+            self.focus_on_pointer_down = true;
+            self
+        }
+
+        fn record(&self, event: &str, phase: EventPhase) -> bool {
+            self.log.borrow_mut().push(format!("{} {} {:?}", self.name, event, phase));
+            self.handles == Some(phase)
+        }
+    }
+
+    impl Element for Recorder {
+        fn inflate(&mut self, base: &mut Base) {
+            base.enable_input(true);
+            for child in self.children.drain(..) {
+                base.add(child);
+            }
+        }
+
+        fn resize(&mut self, base: &mut Base) {
+            if base.children_len() > 0 {
+                return base.layout_stack(Edges::new());
+            }
+
+            match base.box_size() {
+                BoxSize::Hidden => base.resolve_size(None),
+                _ => base.resolve_size(Some(self.size)),
+            }
+        }
+
+        fn pointer_down(&mut self, base: &mut Base, event: &PointerEvent) -> bool {
+            if self.focus_on_pointer_down && event.phase == EventPhase::Target {
+                base.focus();
+            }
+            self.record("down", event.phase)
+        }
+
+        fn pointer_move(&mut self, _base: &mut Base, event: &PointerEvent) -> bool {
+            self.record("move", event.phase)
+        }
+
+        fn pointer_up(&mut self, _base: &mut Base, event: &PointerEvent) -> bool {
+            self.record("up", event.phase)
+        }
+
+        fn hover(&mut self, _base: &mut Base, hovered: bool) {
+            self.log.borrow_mut().push(format!("{} hover {}", self.name, hovered));
+        }
+
+        fn focus(&mut self, _base: &mut Base, focused: bool) {
+            self.log.borrow_mut().push(format!("{} focus {}", self.name, focused));
+        }
+
+        fn key(&mut self, _base: &mut Base, event: &KeyEvent) -> bool {
+            self.record("key", event.phase)
+        }
+    }
+
+    /// Ray straight into the root plane at a point in root coordinates.
+    fn ray_at(x: f32, y: f32) -> PointerRay {
+        PointerRay::unproject(&na::Matrix4::identity(), x, y).unwrap()
+    }
+
+    fn drain(log: &Log) -> Vec<String> {
+        log.borrow_mut().drain(..).collect()
+    }
+
+    /// The `root` is 100x100, `middle` and its child `leaf` cover 20x20 in its corner.
+    fn with_tree<F>(leaf: Recorder, middle: Recorder, log: &Log, fun: F)
+        where F: FnOnce(&Tree)
+    {
+        let tree = Tree::new(&Resources::new());
+        let root = tree.create_root(Recorder::new("root", 100, 100, log).child(middle.child(leaf)), 1.0);
+        root.resize(BoxSize::Fixed { w: 100, h: 100 }, 1.0);
+        drain(log);
+
+        fun(&tree);
+    }
+
+    #[test]
+    fn event_goes_through_capture_target_and_bubble_phases() {
+        let log = Log::default();
+        let leaf = Recorder::new("leaf", 20, 20, &log);
+        let middle = Recorder::new("middle", 20, 20, &log);
+
+        with_tree(leaf, middle, &log, |tree| {
+            assert!(!tree.pointer_down(&ray_at(10.0, 10.0), 1, Modifiers::new()));
+        });
+
+        assert_eq!(
+            vec![
+                "root hover true", "middle hover true", "leaf hover true",
+                "root down Capture", "middle down Capture", "leaf down Target",
+                "middle down Bubble", "root down Bubble",
+            ],
+            drain(&log)
+        );
+    }
+
+    #[test]
+    fn handled_event_stops_propagation() {
+        let log = Log::default();
+        let leaf = Recorder::new("leaf", 20, 20, &log);
+        let middle = Recorder::new("middle", 20, 20, &log).handles(EventPhase::Capture);
+
+        with_tree(leaf, middle, &log, |tree| {
+            assert!(tree.pointer_down(&ray_at(10.0, 10.0), 1, Modifiers::new()));
+        });
+
+        assert_eq!(
+            vec![
+                "root hover true", "middle hover true", "leaf hover true",
+                "root down Capture", "middle down Capture",
+            ],
+            drain(&log)
+        );
+    }
+
+    #[test]
+    fn element_handling_pointer_down_captures_pointer_until_up() {
+        let log = Log::default();
+        let leaf = Recorder::new("leaf", 20, 20, &log);
+        let middle = Recorder::new("middle", 20, 20, &log).handles(EventPhase::Bubble);
+
+        with_tree(leaf, middle, &log, |tree| {
+            tree.pointer_down(&ray_at(10.0, 10.0), 1, Modifiers::new());
+            drain(&log);
+
+            tree.pointer_move(&ray_at(60.0, 60.0), Modifiers::new());
+            tree.pointer_up(&ray_at(60.0, 60.0), Modifiers::new());
+            tree.pointer_move(&ray_at(10.0, 10.0), Modifiers::new());
+        });
+
+        assert_eq!(
+            vec![
+                "leaf hover false", "middle hover false",
+                "middle move Target",
+                "middle up Target",
+                "middle hover true", "leaf hover true",
+                "root move Capture", "middle move Capture", "leaf move Target",
+                "middle move Bubble",
+            ],
+            drain(&log)
+        );
+    }
+
+    #[test]
+    fn pointer_outside_of_children_hits_root() {
+        let log = Log::default();
+        let leaf = Recorder::new("leaf", 20, 20, &log);
+        let middle = Recorder::new("middle", 20, 20, &log);
+
+        with_tree(leaf, middle, &log, |tree| {
+            tree.pointer_move(&ray_at(60.0, 60.0), Modifiers::new());
+            tree.pointer_move(&ray_at(160.0, 60.0), Modifiers::new());
+        });
+
+        assert_eq!(
+            vec!["root hover true", "root move Target", "root hover false"],
+            drain(&log)
+        );
+    }
+
+    #[test]
+    fn hover_enters_root_first_and_leaves_target_first() {
+        let log = Log::default();
+        let leaf = Recorder::new("leaf", 20, 20, &log);
+        let middle = Recorder::new("middle", 20, 20, &log).handles(EventPhase::Capture);
+
+        with_tree(leaf, middle, &log, |tree| {
+            tree.pointer_move(&ray_at(10.0, 10.0), Modifiers::new());
+            tree.pointer_move(&ray_at(15.0, 15.0), Modifiers::new());
+            tree.pointer_move(&ray_at(60.0, 60.0), Modifiers::new());
+        });
+
+        let hover: Vec<_> = drain(&log).into_iter().filter(|entry| entry.contains("hover")).collect();
+        assert_eq!(
+            vec![
+                "root hover true", "middle hover true", "leaf hover true",
+                "leaf hover false", "middle hover false",
+            ],
+            hover
+        );
+    }
+
+    #[test]
+    fn key_goes_to_focused_element_and_its_ancestors() {
+        let log = Log::default();
+        let leaf = Recorder::new("leaf", 20, 20, &log).focus_on_pointer_down();
+        let middle = Recorder::new("middle", 20, 20, &log);
+
+        with_tree(leaf, middle, &log, |tree| {
+            assert!(!tree.key(Key::Enter, Modifiers::new()));
+
+            tree.pointer_down(&ray_at(10.0, 10.0), 1, Modifiers::new());
+            tree.pointer_up(&ray_at(10.0, 10.0), Modifiers::new());
+            drain(&log);
+
+            assert!(!tree.key(Key::Enter, Modifiers::new()));
+        });
+
+        assert_eq!(
+            vec![
+                "root key Capture", "middle key Capture", "leaf key Target",
+                "middle key Bubble", "root key Bubble",
+            ],
+            drain(&log)
+        );
+    }
+
+    #[test]
+    fn focus_moves_on_pointer_down_and_is_cleared_outside() {
+        let log = Log::default();
+        let leaf = Recorder::new("leaf", 20, 20, &log).focus_on_pointer_down();
+        let middle = Recorder::new("middle", 20, 20, &log);
+
+        with_tree(leaf, middle, &log, |tree| {
+            tree.pointer_down(&ray_at(10.0, 10.0), 1, Modifiers::new());
+            tree.pointer_up(&ray_at(10.0, 10.0), Modifiers::new());
+            let focus_gained: Vec<_> = drain(&log).into_iter().filter(|entry| entry.contains("focus")).collect();
+            assert_eq!(vec!["leaf focus true"], focus_gained);
+
+            tree.pointer_down(&ray_at(60.0, 60.0), 1, Modifiers::new());
+            let focus_lost: Vec<_> = drain(&log).into_iter().filter(|entry| entry.contains("focus")).collect();
+            assert_eq!(vec!["leaf focus false"], focus_lost);
+        });
+    }
+
+    #[test]
+    fn second_root_is_added_next_to_first_root() {
+        let log = Log::default();
+        let tree = Tree::new(&Resources::new());
+        let main = tree.create_root(Recorder::new("main", 100, 100, &log), 1.0);
+        main.resize(BoxSize::Fixed { w: 100, h: 100 }, 1.0);
+        let overlay = tree.create_root(Recorder::new("overlay", 20, 20, &log), 1.0);
+        overlay.resize(BoxSize::Auto, 1.0);
+
+        assert_eq!(Some(main.id), tree.shared.borrow().root_id());
+
+        tree.pointer_move(&ray_at(60.0, 60.0), Modifiers::new());
+        assert_eq!(vec!["main hover true", "main move Target"], drain(&log));
+    }
+
+    #[test]
+    fn dropping_main_root_makes_next_root_main() {
+        let log = Log::default();
+        let tree = Tree::new(&Resources::new());
+        let main = tree.create_root(Recorder::new("main", 100, 100, &log), 1.0);
+        let overlay = tree.create_root(Recorder::new("overlay", 20, 20, &log), 1.0);
+        overlay.resize(BoxSize::Auto, 1.0);
+
+        ::std::mem::drop(main);
+        assert_eq!(Some(overlay.id), tree.shared.borrow().root_id());
+
+        tree.pointer_move(&ray_at(10.0, 10.0), Modifiers::new());
+        assert_eq!(vec!["overlay hover true", "overlay move Target"], drain(&log));
+
+        ::std::mem::drop(overlay);
+        assert_eq!(None, tree.shared.borrow().root_id());
+    }
+}
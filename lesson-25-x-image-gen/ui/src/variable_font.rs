@@ -0,0 +1,1177 @@
+//! Outlines and advances of variable TrueType fonts at any axis values.
+//!
+//! Deltas of the `gvar` table are applied to `glyf` outlines, fonts with CFF2 outlines
+//! are drawn with their default instance. Advances are changed by deltas of the `HVAR`
+//! table, or of `gvar` phantom points in fonts without it.
+
+use std::sync::Arc;
+use byteorder::{BigEndian, ByteOrder};
+use lyon_path::builder::PathBuilder;
+use lyon_path::math::{point, Point};
+use crate::fonts::FontVariation;
+
+/// Point in font units.
+type Point2 = (f32, f32);
+
+/// Nesting limit of composite glyphs.
+const MAX_COMPONENT_DEPTH: u8 = 8;
+
+#[derive(Debug, Clone)]
+struct Axis {
+    tag: [u8; 4],
+    min: f32,
+    default: f32,
+    max: f32,
+}
+
+pub struct VariableFont {
+    data: Arc<Vec<u8>>,
+    axes: Vec<Axis>,
+    /// Segment maps of `avar` for every axis, empty if the font has none.
+    axis_maps: Vec<Vec<(f32, f32)>>,
+    glyf: usize,
+    loca: usize,
+    long_loca: bool,
+    gvar: Gvar,
+    /// Offset of the `HVAR` table.
+    hvar: Option<usize>,
+}
+
+struct Gvar {
+    shared_tuples: usize,
+    glyph_count: usize,
+    long_offsets: bool,
+    offsets: usize,
+    data: usize,
+}
+
+#[derive(Default)]
+struct Outline {
+    points: Vec<(f32, f32)>,
+    on_curve: Vec<bool>,
+    contour_ends: Vec<usize>,
+}
+
+struct Component {
+    glyph_id: u16,
+    offset: (f32, f32),
+    /// 2x2 transform applied to component points before the offset.
+    transform: [f32; 4],
+}
+
+impl VariableFont {
+    /// Reads variation tables of the font at `font_index` of a font file or collection,
+    /// `None` if the font is not a variable TrueType font.
+    pub fn parse(data: Arc<Vec<u8>>, font_index: u32) -> Option<VariableFont> {
+        let font = {
+            let bytes = &data[..];
+
+            let offset = if bytes.get(0..4) == Some(&b"ttcf"[..]) {
+                u32_at(bytes, 12 + 4 * font_index as usize)? as usize
+            } else {
+                0
+            };
+
+            let table_count = u16_at(bytes, offset + 4)? as usize;
+            let table = |tag: &[u8]| find_table(bytes, offset, table_count, tag);
+
+            let fvar = table(b"fvar")?;
+            let gvar = table(b"gvar")?;
+            let head = table(b"head")?;
+
+            let axes_offset = fvar + u16_at(bytes, fvar + 4)? as usize;
+            let axis_count = u16_at(bytes, fvar + 8)? as usize;
+            let axis_size = u16_at(bytes, fvar + 10)? as usize;
+
+            let axes = (0..axis_count)
+                .map(|i| {
+                    let record = axes_offset + i * axis_size;
+                    Some(Axis {
+                        tag: tag_at(bytes, record)?,
+                        min: fixed_at(bytes, record + 4)?,
+                        default: fixed_at(bytes, record + 8)?,
+                        max: fixed_at(bytes, record + 12)?,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+
+            let axis_maps = table(b"avar")
+                .and_then(|avar| read_axis_maps(bytes, avar, axis_count))
+                .unwrap_or_else(|| vec![Vec::new(); axis_count]);
+
+            if u16_at(bytes, gvar + 4)? as usize != axis_count {
+                return None;
+            }
+
+            VariableFont {
+                data: data.clone(),
+                axes,
+                axis_maps,
+                glyf: table(b"glyf")?,
+                loca: table(b"loca")?,
+                long_loca: i16_at(bytes, head + 50)? != 0,
+                gvar: Gvar {
+                    shared_tuples: gvar + u32_at(bytes, gvar + 8)? as usize,
+                    glyph_count: u16_at(bytes, gvar + 12)? as usize,
+                    long_offsets: u16_at(bytes, gvar + 14)? & 1 != 0,
+                    offsets: gvar + 20,
+                    data: gvar + u32_at(bytes, gvar + 16)? as usize,
+                },
+                hvar: table(b"HVAR"),
+            }
+        };
+
+        Some(font)
+    }
+
+    /// Axis values in the -1..1 range used by variation tables, in order of font axes.
+    ///
+    /// Axes without a value are at their default, values outside the axis range are clamped.
+    pub fn normalized_coords(&self, variations: &[FontVariation]) -> Vec<f32> {
+        self.axes.iter()
+            .zip(self.axis_maps.iter())
+            .map(|(axis, map)| {
+                let value = variations.iter().rev()
+                    .find(|v| v.tag == axis.tag)
+                    .map(|v| v.value)
+                    .unwrap_or(axis.default)
+                    .max(axis.min)
+                    .min(axis.max);
+
+                let normalized = if value < axis.default {
+                    (value - axis.default) / (axis.default - axis.min)
+                } else if value > axis.default {
+                    (value - axis.default) / (axis.max - axis.default)
+                } else {
+                    0.0
+                };
+
+                map_segments(map, normalized)
+            })
+            .collect()
+    }
+
+    /// Builds the glyph outline at normalized coordinates, `None` if the glyph can not be read.
+    pub fn outline<B: PathBuilder>(&self, glyph_id: u32, coords: &[f32], builder: &mut B) -> Option<()> {
+        let mut outline = Outline::default();
+        self.instance_glyph(glyph_id as u16, coords, 0, &mut outline)?;
+
+        let mut start = 0;
+        for &end in outline.contour_ends.iter() {
+            build_contour(&outline.points[start..=end], &outline.on_curve[start..=end], builder);
+            start = end + 1;
+        }
+
+        Some(())
+    }
+
+    /// Change of the horizontal advance at normalized coordinates, in font units.
+    pub fn advance_delta(&self, glyph_id: u32, coords: &[f32]) -> f32 {
+        let glyph_id = glyph_id as u16;
+
+        if let Some(delta) = self.hvar.and_then(|hvar| self.hvar_advance_delta(hvar, glyph_id, coords)) {
+            return delta;
+        }
+
+        let point_count = match self.glyph_data(glyph_id) {
+            Some(&[]) => 0,
+            Some(data) => match i16_at(data, 0) {
+                Some(contours) if contours >= 0 => contour_ends(data, contours as usize)
+                    .and_then(|ends| ends.last().map(|&e| e + 1))
+                    .unwrap_or(0),
+                Some(_) => components(data).map(|c| c.len()).unwrap_or(0),
+                None => return 0.0,
+            },
+            None => return 0.0,
+        };
+
+        // phantom points are not on contours, so untouched points do not matter
+        let points = vec![(0.0, 0.0); point_count + 4];
+        self.glyph_deltas(glyph_id, coords, &points, &[])
+            .map(|deltas| deltas[point_count + 1].0 - deltas[point_count].0)
+            .unwrap_or(0.0)
+    }
+
+    fn hvar_advance_delta(&self, hvar: usize, glyph_id: u16, coords: &[f32]) -> Option<f32> {
+        let bytes = &self.data[..];
+        let store = hvar + u32_at(bytes, hvar + 4)? as usize;
+        let advance_map = u32_at(bytes, hvar + 8)? as usize;
+
+        // without a map, glyphs are items of the first item variation data
+        let (outer, inner) = if advance_map == 0 {
+            (0, glyph_id as usize)
+        } else {
+            delta_set_index(bytes, hvar + advance_map, glyph_id as usize)?
+        };
+
+        item_variation_delta(bytes, store, outer, inner, coords)
+    }
+
+    fn glyph_data(&self, glyph_id: u16) -> Option<&[u8]> {
+        let bytes = &self.data[..];
+        let index = glyph_id as usize;
+
+        let (start, end) = if self.long_loca {
+            (u32_at(bytes, self.loca + 4 * index)? as usize, u32_at(bytes, self.loca + 4 * index + 4)? as usize)
+        } else {
+            (u16_at(bytes, self.loca + 2 * index)? as usize * 2, u16_at(bytes, self.loca + 2 * index + 2)? as usize * 2)
+        };
+
+        bytes.get(self.glyf + start..self.glyf + end.max(start))
+    }
+
+    /// Appends points of the glyph with deltas applied, returns the advance delta.
+    fn instance_glyph(&self, glyph_id: u16, coords: &[f32], depth: u8, outline: &mut Outline) -> Option<f32> {
+        let data = self.glyph_data(glyph_id)?;
+
+        if data.is_empty() {
+            return Some(self.advance_delta(glyph_id as u32, coords));
+        }
+
+        let contours = i16_at(data, 0)?;
+
+        if contours >= 0 {
+            let ends = contour_ends(data, contours as usize)?;
+            let (points, on_curve) = simple_glyph_points(data, &ends)?;
+            let point_count = points.len();
+
+            let mut all_points = points;
+            all_points.extend_from_slice(&[(0.0, 0.0); 4]);
+            let deltas = self.glyph_deltas(glyph_id, coords, &all_points, &ends)?;
+
+            let first = outline.points.len();
+            outline.points.extend(
+                all_points[..point_count].iter().zip(deltas.iter())
+                    .map(|(p, d)| (p.0 + d.0, p.1 + d.1))
+            );
+            outline.on_curve.extend(on_curve);
+            outline.contour_ends.extend(ends.iter().map(|e| first + e));
+
+            return Some(deltas[point_count + 1].0 - deltas[point_count].0);
+        }
+
+        if depth >= MAX_COMPONENT_DEPTH {
+            return None;
+        }
+
+        let components = components(data)?;
+        let mut offsets: Vec<(f32, f32)> = components.iter().map(|c| c.offset).collect();
+        offsets.extend_from_slice(&[(0.0, 0.0); 4]);
+        let deltas = self.glyph_deltas(glyph_id, coords, &offsets, &[])?;
+
+        for (i, component) in components.iter().enumerate() {
+            let mut component_outline = Outline::default();
+            self.instance_glyph(component.glyph_id, coords, depth + 1, &mut component_outline)?;
+
+            let [a, b, c, d] = component.transform;
+            let (dx, dy) = (component.offset.0 + deltas[i].0, component.offset.1 + deltas[i].1);
+
+            let first = outline.points.len();
+            outline.points.extend(
+                component_outline.points.iter()
+                    .map(|&(x, y)| (a * x + c * y + dx, b * x + d * y + dy))
+            );
+            outline.on_curve.extend(component_outline.on_curve);
+            outline.contour_ends.extend(component_outline.contour_ends.iter().map(|e| first + e));
+        }
+
+        let count = components.len();
+        Some(deltas[count + 1].0 - deltas[count].0)
+    }
+
+    /// Deltas of glyph points, including 4 phantom points at the end, from all tuples of the glyph.
+    fn glyph_deltas(&self, glyph_id: u16, coords: &[f32], points: &[(f32, f32)], contour_ends: &[usize]) -> Option<Vec<(f32, f32)>> {
+        let bytes = &self.data[..];
+        let point_count = points.len();
+        let mut deltas = vec![(0.0, 0.0); point_count];
+
+        let index = glyph_id as usize;
+        if index >= self.gvar.glyph_count {
+            return Some(deltas);
+        }
+
+        let (start, end) = if self.gvar.long_offsets {
+            (u32_at(bytes, self.gvar.offsets + 4 * index)? as usize, u32_at(bytes, self.gvar.offsets + 4 * index + 4)? as usize)
+        } else {
+            (u16_at(bytes, self.gvar.offsets + 2 * index)? as usize * 2, u16_at(bytes, self.gvar.offsets + 2 * index + 2)? as usize * 2)
+        };
+
+        if end <= start {
+            return Some(deltas);
+        }
+
+        let data = bytes.get(self.gvar.data + start..self.gvar.data + end)?;
+        let axis_count = coords.len();
+
+        let tuple_count_field = u16_at(data, 0)?;
+        let tuple_count = (tuple_count_field & 0x0FFF) as usize;
+        let mut serialized = u16_at(data, 2)? as usize;
+
+        let shared_point_numbers = if tuple_count_field & 0x8000 != 0 {
+            let (numbers, size) = read_point_numbers(data, serialized)?;
+            serialized += size;
+            numbers
+        } else {
+            None
+        };
+
+        let mut header = 4;
+
+        for _ in 0..tuple_count {
+            let data_size = u16_at(data, header)? as usize;
+            let tuple_index = u16_at(data, header + 2)?;
+            header += 4;
+
+            let peak = if tuple_index & 0x8000 != 0 {
+                let peak = read_tuple(data, header, axis_count)?;
+                header += 2 * axis_count;
+                peak
+            } else {
+                let shared = self.gvar.shared_tuples + (tuple_index & 0x0FFF) as usize * 2 * axis_count;
+                read_tuple(bytes, shared, axis_count)?
+            };
+
+            let intermediate = if tuple_index & 0x4000 != 0 {
+                let start = read_tuple(data, header, axis_count)?;
+                let end = read_tuple(data, header + 2 * axis_count, axis_count)?;
+                header += 4 * axis_count;
+                Some((start, end))
+            } else {
+                None
+            };
+
+            let tuple_data = serialized;
+            serialized += data_size;
+
+            let scalar = tuple_scalar(coords, &peak, intermediate.as_ref());
+            if scalar == 0.0 {
+                continue;
+            }
+
+            let mut offset = tuple_data;
+            let point_numbers = if tuple_index & 0x2000 != 0 {
+                let (numbers, size) = read_point_numbers(data, offset)?;
+                offset += size;
+                numbers
+            } else {
+                shared_point_numbers.clone()
+            };
+
+            let count = point_numbers.as_ref().map(|n| n.len()).unwrap_or(point_count);
+            let (x_deltas, size) = read_deltas(data, offset, count)?;
+            let (y_deltas, _) = read_deltas(data, offset + size, count)?;
+
+            match point_numbers {
+                None => {
+                    for (delta, (x, y)) in deltas.iter_mut().zip(x_deltas.into_iter().zip(y_deltas)) {
+                        delta.0 += x * scalar;
+                        delta.1 += y * scalar;
+                    }
+                },
+                Some(numbers) => {
+                    let mut touched = vec![None; point_count];
+                    for (i, &number) in numbers.iter().enumerate() {
+                        if number < point_count {
+                            touched[number] = Some((x_deltas[i], y_deltas[i]));
+                        }
+                    }
+
+                    for (delta, tuple_delta) in deltas.iter_mut().zip(interpolate_untouched(&touched, points, contour_ends)) {
+                        delta.0 += tuple_delta.0 * scalar;
+                        delta.1 += tuple_delta.1 * scalar;
+                    }
+                },
+            }
+        }
+
+        Some(deltas)
+    }
+}
+
+fn find_table(bytes: &[u8], offset: usize, table_count: usize, tag: &[u8]) -> Option<usize> {
+    (0..table_count)
+        .map(|i| offset + 12 + 16 * i)
+        .find(|&record| bytes.get(record..record + 4) == Some(tag))
+        .and_then(|record| u32_at(bytes, record + 8))
+        .map(|table| table as usize)
+}
+
+fn read_axis_maps(bytes: &[u8], avar: usize, axis_count: usize) -> Option<Vec<Vec<(f32, f32)>>> {
+    if u16_at(bytes, avar + 6)? as usize != axis_count {
+        return None;
+    }
+
+    let mut offset = avar + 8;
+    let mut maps = Vec::with_capacity(axis_count);
+
+    for _ in 0..axis_count {
+        let count = u16_at(bytes, offset)? as usize;
+        offset += 2;
+
+        let map = (0..count)
+            .map(|i| Some((f2dot14_at(bytes, offset + 4 * i)?, f2dot14_at(bytes, offset + 4 * i + 2)?)))
+            .collect::<Option<Vec<_>>>()?;
+        offset += 4 * count;
+
+        maps.push(map);
+    }
+
+    Some(maps)
+}
+
+/// Maps a normalized coordinate with a segment map of `avar`.
+fn map_segments(map: &[(f32, f32)], value: f32) -> f32 {
+    let (first, last) = match (map.first(), map.last()) {
+        (Some(&first), Some(&last)) if map.len() >= 2 => (first, last),
+        _ => return value,
+    };
+
+    if value <= first.0 {
+        return first.1;
+    }
+
+    for pair in map.windows(2) {
+        let ((from0, to0), (from1, to1)) = (pair[0], pair[1]);
+        if value <= from1 {
+            if from1 <= from0 {
+                return to1;
+            }
+            return to0 + (to1 - to0) * (value - from0) / (from1 - from0);
+        }
+    }
+
+    last.1
+}
+
+/// Outer and inner index of an item in an item variation store, from a delta set index map.
+fn delta_set_index(bytes: &[u8], map: usize, index: usize) -> Option<(usize, usize)> {
+    let entry_format = *bytes.get(map + 1)?;
+    let (count, entries) = match *bytes.get(map)? {
+        0 => (u16_at(bytes, map + 2)? as usize, map + 4),
+        1 => (u32_at(bytes, map + 2)? as usize, map + 6),
+        _ => return None,
+    };
+
+    if count == 0 {
+        return None;
+    }
+
+    // glyphs past the end of the map use the last entry
+    let entry_size = ((entry_format & 0x30) >> 4) as usize + 1;
+    let inner_bits = (entry_format & 0x0F) as usize + 1;
+    let offset = entries + index.min(count - 1) * entry_size;
+    let entry = bytes.get(offset..offset + entry_size)?
+        .iter()
+        .fold(0usize, |entry, &byte| (entry << 8) | byte as usize);
+
+    Some((entry >> inner_bits, entry & ((1 << inner_bits) - 1)))
+}
+
+/// Sum of item deltas of all regions of an item variation store, scaled for the coordinates.
+fn item_variation_delta(bytes: &[u8], store: usize, outer: usize, inner: usize, coords: &[f32]) -> Option<f32> {
+    if u16_at(bytes, store)? != 1 || outer >= u16_at(bytes, store + 6)? as usize {
+        return None;
+    }
+
+    let regions = store + u32_at(bytes, store + 2)? as usize;
+    let axis_count = u16_at(bytes, regions)? as usize;
+    let region_count = u16_at(bytes, regions + 2)? as usize;
+    if axis_count != coords.len() {
+        return None;
+    }
+
+    let data = store + u32_at(bytes, store + 8 + 4 * outer)? as usize;
+    let item_count = u16_at(bytes, data)? as usize;
+    let word_delta_count = u16_at(bytes, data + 2)?;
+    let region_index_count = u16_at(bytes, data + 4)? as usize;
+    if inner >= item_count {
+        return None;
+    }
+
+    // the first deltas of a row are words, the rest are half as long
+    let long_words = word_delta_count & 0x8000 != 0;
+    let word_count = ((word_delta_count & 0x7FFF) as usize).min(region_index_count);
+    let (word_size, short_size) = if long_words { (4, 2) } else { (2, 1) };
+    let row_size = word_count * word_size + (region_index_count - word_count) * short_size;
+    let mut offset = data + 6 + 2 * region_index_count + inner * row_size;
+
+    let mut delta = 0.0;
+    for i in 0..region_index_count {
+        let value = match (i < word_count, long_words) {
+            (true, true) => i32_at(bytes, offset)? as f32,
+            (true, false) | (false, true) => i16_at(bytes, offset)? as f32,
+            (false, false) => *bytes.get(offset)? as i8 as f32,
+        };
+        offset += if i < word_count { word_size } else { short_size };
+
+        let region_index = u16_at(bytes, data + 6 + 2 * i)? as usize;
+        if region_index >= region_count {
+            return None;
+        }
+
+        let region = regions + 4 + region_index * axis_count * 6;
+        let axis_values = |value: usize| (0..axis_count)
+            .map(|axis| f2dot14_at(bytes, region + axis * 6 + value * 2))
+            .collect::<Option<Vec<_>>>();
+        let (start, peak, end) = (axis_values(0)?, axis_values(1)?, axis_values(2)?);
+
+        delta += value * tuple_scalar(coords, &peak, Some(&(start, end)));
+    }
+
+    Some(delta)
+}
+
+fn contour_ends(data: &[u8], contours: usize) -> Option<Vec<usize>> {
+    let ends = (0..contours)
+        .map(|i| u16_at(data, 10 + 2 * i).map(|e| e as usize))
+        .collect::<Option<Vec<_>>>()?;
+
+    // every contour has at least one point
+    if ends.windows(2).any(|pair| pair[1] <= pair[0]) {
+        return None;
+    }
+
+    Some(ends)
+}
+
+/// Points of a simple glyph and whether they are on the curve.
+fn simple_glyph_points(data: &[u8], ends: &[usize]) -> Option<(Vec<Point2>, Vec<bool>)> {
+    const ON_CURVE: u8 = 0x01;
+    const X_SHORT: u8 = 0x02;
+    const Y_SHORT: u8 = 0x04;
+    const REPEAT: u8 = 0x08;
+    const X_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+    let point_count = ends.last().map(|&e| e + 1).unwrap_or(0);
+    let instructions = u16_at(data, 10 + 2 * ends.len())? as usize;
+    let mut offset = 12 + 2 * ends.len() + instructions;
+
+    let mut flags = Vec::with_capacity(point_count);
+    while flags.len() < point_count {
+        let flag = *data.get(offset)?;
+        offset += 1;
+        flags.push(flag);
+
+        if flag & REPEAT != 0 {
+            let repeat = *data.get(offset)?;
+            offset += 1;
+            for _ in 0..repeat {
+                flags.push(flag);
+            }
+        }
+    }
+    flags.truncate(point_count);
+
+    let mut read_coords = |short: u8, same_or_positive: u8| -> Option<Vec<f32>> {
+        let mut value = 0i32;
+        let mut coords = Vec::with_capacity(point_count);
+
+        for &flag in flags.iter() {
+            if flag & short != 0 {
+                let delta = *data.get(offset)? as i32;
+                offset += 1;
+                value += if flag & same_or_positive != 0 { delta } else { -delta };
+            } else if flag & same_or_positive == 0 {
+                value += i16_at(data, offset)? as i32;
+                offset += 2;
+            }
+            coords.push(value as f32);
+        }
+
+        Some(coords)
+    };
+
+    let xs = read_coords(X_SHORT, X_SAME_OR_POSITIVE)?;
+    let ys = read_coords(Y_SHORT, Y_SAME_OR_POSITIVE)?;
+
+    Some((
+        xs.into_iter().zip(ys).collect(),
+        flags.iter().map(|f| f & ON_CURVE != 0).collect(),
+    ))
+}
+
+fn components(data: &[u8]) -> Option<Vec<Component>> {
+    const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+    const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+    const WE_HAVE_A_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+    const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+    let mut components = Vec::new();
+    let mut offset = 10;
+
+    loop {
+        let flags = u16_at(data, offset)?;
+        let glyph_id = u16_at(data, offset + 2)?;
+        offset += 4;
+
+        let (arg1, arg2) = if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+            let args = (i16_at(data, offset)? as f32, i16_at(data, offset + 2)? as f32);
+            offset += 4;
+            args
+        } else {
+            let args = (*data.get(offset)? as i8 as f32, *data.get(offset + 1)? as i8 as f32);
+            offset += 2;
+            args
+        };
+
+        // components placed by matching points are drawn without the offset
+        let component_offset = if flags & ARGS_ARE_XY_VALUES != 0 { (arg1, arg2) } else { (0.0, 0.0) };
+
+        let transform = if flags & WE_HAVE_A_SCALE != 0 {
+            let scale = f2dot14_at(data, offset)?;
+            offset += 2;
+            [scale, 0.0, 0.0, scale]
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            let scale = [f2dot14_at(data, offset)?, 0.0, 0.0, f2dot14_at(data, offset + 2)?];
+            offset += 4;
+            scale
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            let matrix = [
+                f2dot14_at(data, offset)?, f2dot14_at(data, offset + 2)?,
+                f2dot14_at(data, offset + 4)?, f2dot14_at(data, offset + 6)?,
+            ];
+            offset += 8;
+            matrix
+        } else {
+            [1.0, 0.0, 0.0, 1.0]
+        };
+
+        components.push(Component { glyph_id, offset: component_offset, transform });
+
+        if flags & MORE_COMPONENTS == 0 {
+            return Some(components);
+        }
+    }
+}
+
+/// Packed point numbers and their size in bytes, `None` numbers refer to all points.
+fn read_point_numbers(data: &[u8], offset: usize) -> Option<(Option<Vec<usize>>, usize)> {
+    let first = *data.get(offset)? as usize;
+    if first == 0 {
+        return Some((None, 1));
+    }
+
+    let (count, mut size) = if first & 0x80 != 0 {
+        (((first & 0x7F) << 8) | *data.get(offset + 1)? as usize, 2)
+    } else {
+        (first, 1)
+    };
+
+    let mut numbers = Vec::with_capacity(count);
+    let mut number = 0;
+
+    while numbers.len() < count {
+        let control = *data.get(offset + size)?;
+        size += 1;
+
+        let run = (control & 0x7F) as usize + 1;
+        for _ in 0..run {
+            if control & 0x80 != 0 {
+                number += u16_at(data, offset + size)? as usize;
+                size += 2;
+            } else {
+                number += *data.get(offset + size)? as usize;
+                size += 1;
+            }
+            numbers.push(number);
+        }
+    }
+
+    numbers.truncate(count);
+    Some((Some(numbers), size))
+}
+
+/// Packed deltas and their size in bytes.
+fn read_deltas(data: &[u8], offset: usize, count: usize) -> Option<(Vec<f32>, usize)> {
+    const DELTAS_ARE_ZERO: u8 = 0x80;
+    const DELTAS_ARE_WORDS: u8 = 0x40;
+
+    let mut deltas = Vec::with_capacity(count);
+    let mut size = 0;
+
+    while deltas.len() < count {
+        let control = *data.get(offset + size)?;
+        size += 1;
+
+        let run = (control & 0x3F) as usize + 1;
+        for _ in 0..run {
+            if control & DELTAS_ARE_ZERO != 0 {
+                deltas.push(0.0);
+            } else if control & DELTAS_ARE_WORDS != 0 {
+                deltas.push(i16_at(data, offset + size)? as f32);
+                size += 2;
+            } else {
+                deltas.push(*data.get(offset + size)? as i8 as f32);
+                size += 1;
+            }
+        }
+    }
+
+    deltas.truncate(count);
+    Some((deltas, size))
+}
+
+fn read_tuple(data: &[u8], offset: usize, axis_count: usize) -> Option<Vec<f32>> {
+    (0..axis_count)
+        .map(|i| f2dot14_at(data, offset + 2 * i))
+        .collect()
+}
+
+/// How much of a tuple variation applies at the coordinates.
+fn tuple_scalar(coords: &[f32], peak: &[f32], intermediate: Option<&(Vec<f32>, Vec<f32>)>) -> f32 {
+    let mut scalar = 1.0;
+
+    for (i, (&coord, &peak)) in coords.iter().zip(peak.iter()).enumerate() {
+        if peak == 0.0 || coord == peak {
+            continue;
+        }
+        if coord == 0.0 {
+            return 0.0;
+        }
+
+        match intermediate {
+            Some((start, end)) => {
+                let (start, end) = (start[i], end[i]);
+                // invalid regions do not limit the axis
+                if start > peak || peak > end || (start < 0.0 && end > 0.0) {
+                    continue;
+                }
+                if coord < start || coord > end {
+                    return 0.0;
+                }
+                scalar *= if coord < peak {
+                    (coord - start) / (peak - start)
+                } else {
+                    (end - coord) / (end - peak)
+                };
+            },
+            None => {
+                if coord < peak.min(0.0) || coord > peak.max(0.0) {
+                    return 0.0;
+                }
+                scalar *= coord / peak;
+            },
+        }
+    }
+
+    scalar
+}
+
+/// Infers deltas of points without explicit deltas from the nearest points on the same
+/// contour that have them. Points outside contours get no delta.
+fn interpolate_untouched(touched: &[Option<(f32, f32)>], points: &[(f32, f32)], contour_ends: &[usize]) -> Vec<(f32, f32)> {
+    let mut deltas: Vec<(f32, f32)> = touched.iter().map(|d| d.unwrap_or((0.0, 0.0))).collect();
+
+    let mut start = 0;
+    for &end in contour_ends {
+        if end >= points.len() {
+            break;
+        }
+
+        let touched_in_contour: Vec<usize> = (start..=end).filter(|&i| touched[i].is_some()).collect();
+
+        if let (Some(&first), Some(&last)) = (touched_in_contour.first(), touched_in_contour.last()) {
+            for i in start..=end {
+                if touched[i].is_some() {
+                    continue;
+                }
+
+                let next = touched_in_contour.iter().cloned().find(|&t| t > i).unwrap_or(first);
+                let previous = touched_in_contour.iter().cloned().rev().find(|&t| t < i).unwrap_or(last);
+
+                if let (Some(previous_delta), Some(next_delta)) = (touched[previous], touched[next]) {
+                    deltas[i] = (
+                        interpolate(points[i].0, points[previous].0, points[next].0, previous_delta.0, next_delta.0),
+                        interpolate(points[i].1, points[previous].1, points[next].1, previous_delta.1, next_delta.1),
+                    );
+                }
+            }
+        }
+
+        start = end + 1;
+    }
+
+    deltas
+}
+
+fn interpolate(value: f32, a: f32, b: f32, a_delta: f32, b_delta: f32) -> f32 {
+    if a == b {
+        return if a_delta == b_delta { a_delta } else { 0.0 };
+    }
+
+    let (low, high, low_delta, high_delta) = if a < b {
+        (a, b, a_delta, b_delta)
+    } else {
+        (b, a, b_delta, a_delta)
+    };
+
+    if value <= low {
+        low_delta
+    } else if value >= high {
+        high_delta
+    } else {
+        low_delta + (value - low) / (high - low) * (high_delta - low_delta)
+    }
+}
+
+/// Builds a closed contour of quadratic curves, where two off-curve points in a row
+/// have an implied on-curve point between them.
+fn build_contour<B: PathBuilder>(points: &[(f32, f32)], on_curve: &[bool], builder: &mut B) {
+    let count = points.len();
+    if count == 0 {
+        return;
+    }
+
+    let at = |i: usize| point(points[i % count].0, points[i % count].1);
+    let midpoint = |a: Point, b: Point| point((a.x + b.x) / 2.0, (a.y + b.y) / 2.0);
+
+    let (start, visits): (Point, Vec<(Point, bool)>) = match on_curve.iter().position(|&on| on) {
+        Some(first) => (
+            at(first),
+            (1..=count).map(|k| (first + k) % count).map(|i| (at(i), on_curve[i])).collect(),
+        ),
+        None => {
+            let start = midpoint(at(count - 1), at(0));
+            let mut visits: Vec<(Point, bool)> = (0..count).map(|i| (at(i), false)).collect();
+            visits.push((start, true));
+            (start, visits)
+        },
+    };
+
+    builder.move_to(start);
+
+    let mut control: Option<Point> = None;
+    for (p, on) in visits {
+        match (on, control) {
+            (true, Some(c)) => {
+                builder.quadratic_bezier_to(c, p);
+                control = None;
+            },
+            (true, None) => builder.line_to(p),
+            (false, Some(c)) => {
+                builder.quadratic_bezier_to(c, midpoint(c, p));
+                control = Some(p);
+            },
+            (false, None) => control = Some(p),
+        }
+    }
+
+    builder.close();
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(BigEndian::read_u16)
+}
+
+fn i16_at(data: &[u8], offset: usize) -> Option<i16> {
+    data.get(offset..offset + 2).map(BigEndian::read_i16)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(BigEndian::read_u32)
+}
+
+fn i32_at(data: &[u8], offset: usize) -> Option<i32> {
+    data.get(offset..offset + 4).map(BigEndian::read_i32)
+}
+
+fn fixed_at(data: &[u8], offset: usize) -> Option<f32> {
+    data.get(offset..offset + 4).map(|b| BigEndian::read_i32(b) as f32 / 65536.0)
+}
+
+fn f2dot14_at(data: &[u8], offset: usize) -> Option<f32> {
+    i16_at(data, offset).map(|v| v as f32 / 16384.0)
+}
+
+fn tag_at(data: &[u8], offset: usize) -> Option<[u8; 4]> {
+    data.get(offset..offset + 4).map(|t| [t[0], t[1], t[2], t[3]])
+}
+
+#[cfg(test)]
+mod variable_font_tests {
+    use super::*;
+    use lyon_path::default::Path;
+
+    fn assert_near(expected: f32, actual: f32) {
+        assert!((expected - actual).abs() < 0.0001, "expected {}, got {}", expected, actual);
+    }
+
+    /// Font with a single glyph and no variation data.
+    fn single_glyph_font(glyph: &[u8]) -> VariableFont {
+        let mut data = vec![0, 0, 0, (glyph.len() / 2) as u8];
+        data.extend_from_slice(glyph);
+
+        VariableFont {
+            data: Arc::new(data),
+            axes: Vec::new(),
+            axis_maps: Vec::new(),
+            glyf: 4,
+            loca: 0,
+            long_loca: false,
+            gvar: Gvar { shared_tuples: 0, glyph_count: 0, long_offsets: false, offsets: 0, data: 0 },
+            hvar: None,
+        }
+    }
+
+    /// Font with only an `HVAR` table, with or without an advance width map.
+    fn hvar_font(with_map: bool) -> VariableFont {
+        let map_offset = if with_map { 64 } else { 0 };
+        let mut data = vec![
+            // header, store at 20
+            0, 1, 0, 0, 0, 0, 0, 20, 0, 0, 0, map_offset, 0, 0, 0, 0, 0, 0, 0, 0,
+            // item variation store: format, region list at 12, one data at 28
+            0, 1, 0, 0, 0, 12, 0, 1, 0, 0, 0, 28,
+            // region list: one axis, two regions, 0..1 peaking at 1 and -1..0 peaking at -1
+            0, 1, 0, 2,
+            0x00, 0x00, 0x40, 0x00, 0x40, 0x00,
+            0xC0, 0x00, 0xC0, 0x00, 0x00, 0x00,
+            // item variation data: 2 items, 1 word delta, regions 0 and 1
+            0, 2, 0, 1, 0, 2, 0, 0, 0, 1,
+            // items: 100 and -10, -300 and 20
+            0, 100, 0xF6,
+            0xFE, 0xD4, 20,
+        ];
+        if with_map {
+            // format 0, 1 byte entries with 1 inner bit, glyph 0 is item 1 and glyph 1 is item 0
+            data.extend_from_slice(&[0, 0x00, 0, 2, 1, 0]);
+        }
+
+        VariableFont {
+            data: Arc::new(data),
+            axes: Vec::new(),
+            axis_maps: Vec::new(),
+            glyf: 0,
+            loca: 0,
+            long_loca: false,
+            gvar: Gvar { shared_tuples: 0, glyph_count: 0, long_offsets: false, offsets: 0, data: 0 },
+            hvar: Some(0),
+        }
+    }
+
+    /// Bytes that look nothing like font data, from a fixed seed.
+    fn garbage(len: usize) -> Vec<u8> {
+        let mut state: u32 = 0x1234_5678;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn zero_point_count_refers_to_all_points() {
+        assert_eq!(Some((None, 1)), read_point_numbers(&[0], 0));
+    }
+
+    #[test]
+    fn point_numbers_are_running_sums() {
+        // 3 points, one run of 3 byte increments
+        assert_eq!(Some((Some(vec![1, 3, 6]), 5)), read_point_numbers(&[3, 0x02, 1, 2, 3], 0));
+        // 2 points with a two byte count, one run of 2 word increments, read at an offset
+        assert_eq!(
+            Some((Some(vec![256, 261]), 7)),
+            read_point_numbers(&[0xFF, 0x80, 0x02, 0x81, 0x01, 0x00, 0x00, 0x05], 1)
+        );
+        // runs are split by their control bytes
+        assert_eq!(Some((Some(vec![4, 1004]), 6)), read_point_numbers(&[2, 0x00, 4, 0x80, 0x03, 0xE8], 0));
+    }
+
+    #[test]
+    fn deltas_are_bytes_words_or_zeros() {
+        assert_eq!(Some((vec![5.0, -5.0], 3)), read_deltas(&[0x01, 0x05, 0xFB], 0, 2));
+        assert_eq!(Some((vec![256.0, 0.0, 0.0], 4)), read_deltas(&[0x40, 0x01, 0x00, 0x81], 0, 3));
+        assert_eq!(Some((vec![-2.0], 3)), read_deltas(&[0xAA, 0x40, 0xFF, 0xFE], 1, 1));
+        // runs longer than the count are cut
+        assert_eq!(Some((vec![0.0, 0.0], 1)), read_deltas(&[0x85], 0, 2));
+    }
+
+    #[test]
+    fn truncated_packed_data_is_none() {
+        assert_eq!(None, read_point_numbers(&[], 0));
+        assert_eq!(None, read_point_numbers(&[3, 0x02, 1], 0));
+        assert_eq!(None, read_point_numbers(&[0x80], 0));
+        assert_eq!(None, read_point_numbers(&[1, 0x80, 0x01], 0));
+        assert_eq!(None, read_deltas(&[], 0, 1));
+        assert_eq!(None, read_deltas(&[0x41, 0x00, 0x01], 0, 2));
+        assert_eq!(None, read_deltas(&[0x01, 0x05], 0, 3));
+    }
+
+    #[test]
+    fn garbage_packed_data_does_not_panic() {
+        let data = garbage(512);
+        for offset in 0..data.len() + 2 {
+            let _ = read_point_numbers(&data, offset);
+            for count in 0..40 {
+                let _ = read_deltas(&data, offset, count);
+            }
+        }
+    }
+
+    #[test]
+    fn scalar_grows_towards_peak() {
+        assert_near(0.5, tuple_scalar(&[0.5], &[1.0], None));
+        assert_near(1.0, tuple_scalar(&[1.0], &[1.0], None));
+        assert_near(0.0, tuple_scalar(&[0.0], &[1.0], None));
+        assert_near(0.0, tuple_scalar(&[-0.5], &[1.0], None));
+        assert_near(0.25, tuple_scalar(&[-0.25], &[-1.0], None));
+    }
+
+    #[test]
+    fn scalar_multiplies_axes_and_skips_zero_peaks() {
+        assert_near(0.25, tuple_scalar(&[0.5, -0.5], &[1.0, -1.0], None));
+        assert_near(0.5, tuple_scalar(&[0.5, 0.7], &[1.0, 0.0], None));
+        assert_near(0.0, tuple_scalar(&[0.5, 0.0], &[1.0, 1.0], None));
+    }
+
+    #[test]
+    fn intermediate_region_limits_scalar() {
+        let region = (vec![0.2], vec![0.8]);
+
+        assert_near(1.0, tuple_scalar(&[0.5], &[0.5], Some(&region)));
+        assert_near(0.5, tuple_scalar(&[0.35], &[0.5], Some(&region)));
+        assert_near(0.5, tuple_scalar(&[0.65], &[0.5], Some(&region)));
+        assert_near(0.0, tuple_scalar(&[0.1], &[0.5], Some(&region)));
+        assert_near(0.0, tuple_scalar(&[0.9], &[0.5], Some(&region)));
+        assert_near(0.0, tuple_scalar(&[0.0], &[0.5], Some(&region)));
+    }
+
+    #[test]
+    fn invalid_intermediate_region_does_not_limit_axis() {
+        // region crosses zero
+        assert_near(1.0, tuple_scalar(&[0.9], &[0.5], Some(&(vec![-0.5], vec![1.0]))));
+        // peak outside of the region
+        assert_near(1.0, tuple_scalar(&[0.1], &[0.5], Some(&(vec![0.6], vec![0.8]))));
+    }
+
+    #[test]
+    fn segment_map_interpolates_between_pairs() {
+        let map = [(-1.0, -1.0), (0.0, 0.0), (0.5, 0.8), (1.0, 1.0)];
+
+        assert_near(0.4, map_segments(&map, 0.25));
+        assert_near(0.9, map_segments(&map, 0.75));
+        assert_near(-0.5, map_segments(&map, -0.5));
+        assert_near(0.8, map_segments(&map, 0.5));
+        assert_near(-1.0, map_segments(&map, -2.0));
+        assert_near(1.0, map_segments(&map, 2.0));
+    }
+
+    #[test]
+    fn short_segment_map_is_identity() {
+        assert_near(0.3, map_segments(&[], 0.3));
+        assert_near(0.3, map_segments(&[(0.0, 0.5)], 0.3));
+    }
+
+    #[test]
+    fn untouched_points_are_interpolated_on_their_contour() {
+        let points = [
+            // first contour
+            (0.0, 0.0), (5.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0),
+            // second contour
+            (20.0, 0.0), (30.0, 0.0),
+            // phantom point
+            (0.0, 0.0),
+        ];
+        let mut touched = vec![None; points.len()];
+        touched[0] = Some((2.0, 1.0));
+        touched[2] = Some((4.0, 1.0));
+        touched[7] = Some((3.0, 3.0));
+
+        let deltas = interpolate_untouched(&touched, &points, &[4, 6]);
+
+        assert_eq!(vec![
+            (2.0, 1.0),
+            // x is between touched neighbours, y of both neighbours is the same
+            (3.0, 1.0),
+            (4.0, 1.0),
+            // past the last touched point the contour wraps around to the first one
+            (4.0, 1.0),
+            (2.0, 1.0),
+            // contour without touched points
+            (0.0, 0.0),
+            (0.0, 0.0),
+            (3.0, 3.0),
+        ], deltas);
+    }
+
+    #[test]
+    fn garbage_fonts_are_not_parsed() {
+        assert!(VariableFont::parse(Arc::new(Vec::new()), 0).is_none());
+        assert!(VariableFont::parse(Arc::new(b"ttcf".to_vec()), 3).is_none());
+        assert!(VariableFont::parse(Arc::new(vec![0, 1, 0, 0, 0, 9]), 0).is_none());
+
+        let data = garbage(4096);
+        for len in (0..data.len()).step_by(61) {
+            assert!(VariableFont::parse(Arc::new(data[..len].to_vec()), 0).is_none());
+        }
+    }
+
+    #[test]
+    fn broken_glyphs_have_no_outline() {
+        let triangle = [
+            0, 1, 0, 0, 0, 0, 0, 10, 0, 10, // one contour and bounds
+            0, 2, // contour end
+            0, 0, // no instructions
+            0x37, 0x37, 0x33, // flags
+            5, 5, 5, // x bytes
+            5, 5, // y bytes
+        ];
+        assert!(single_glyph_font(&triangle).outline(0, &[], &mut Path::builder()).is_some());
+
+        for len in 10..triangle.len() {
+            assert!(single_glyph_font(&triangle[..len]).outline(0, &[], &mut Path::builder()).is_none());
+        }
+
+        // contour ends going backwards
+        let overlapping = [0, 2, 0, 0, 0, 0, 0, 10, 0, 10, 0, 2, 0, 1, 0, 0, 0x37, 0x37, 0x37, 5, 5, 5, 5, 5, 5];
+        assert!(single_glyph_font(&overlapping).outline(0, &[], &mut Path::builder()).is_none());
+
+        // component referring to itself
+        let recursive = [0xFF, 0xFF, 0, 0, 0, 0, 0, 10, 0, 10, 0, 0x02, 0, 0, 0, 0];
+        assert!(single_glyph_font(&recursive).outline(0, &[], &mut Path::builder()).is_none());
+
+        let data = garbage(64);
+        for len in 0..data.len() {
+            let _ = single_glyph_font(&data[..len & !1]).outline(0, &[], &mut Path::builder());
+        }
+    }
+
+    #[test]
+    fn advance_delta_sums_hvar_regions() {
+        let font = hvar_font(false);
+
+        assert_near(50.0, font.advance_delta(0, &[0.5]));
+        assert_near(100.0, font.advance_delta(0, &[1.0]));
+        assert_near(-10.0, font.advance_delta(0, &[-1.0]));
+        assert_near(-150.0, font.advance_delta(1, &[0.5]));
+        assert_near(10.0, font.advance_delta(1, &[-0.5]));
+        assert_near(0.0, font.advance_delta(0, &[0.0]));
+    }
+
+    #[test]
+    fn advance_map_picks_hvar_items() {
+        let font = hvar_font(true);
+
+        assert_near(-300.0, font.advance_delta(0, &[1.0]));
+        assert_near(100.0, font.advance_delta(1, &[1.0]));
+        // glyphs past the end of the map use its last entry
+        assert_near(100.0, font.advance_delta(7, &[1.0]));
+    }
+
+    #[test]
+    fn truncated_hvar_is_none() {
+        let font = hvar_font(true);
+        let bytes = &font.data[..];
+
+        // the last byte is the map entry of glyph 1
+        for len in 0..bytes.len() - 1 {
+            assert_eq!(None, VariableFont { data: Arc::new(bytes[..len].to_vec()), ..hvar_font(true) }
+                .hvar_advance_delta(0, 0, &[1.0]));
+        }
+        assert_eq!(None, font.hvar_advance_delta(0, 0, &[1.0, 0.0]));
+        assert_eq!(None, hvar_font(false).hvar_advance_delta(0, 2, &[1.0]));
+    }
+}
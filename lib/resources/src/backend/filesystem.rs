@@ -201,8 +201,18 @@ impl Backend for FileSystem {
         Ok(())
     }
 
-    fn write_from(&mut self, _path: &ResourcePath, _buffer: &mut io::Read) -> Result<(), Error> {
-        unimplemented!()
+    fn write_from(&mut self, path: &ResourcePath, buffer: &mut io::Read) -> Result<(), Error> {
+        if !self.can_write {
+            return Err(Error::NotWritable);
+        }
+
+        let path = resource_name_to_path(&self.root_path, path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut writer = io::BufWriter::new(fs::File::create(path)?);
+        io::copy(buffer, &mut writer)?;
+        Ok(())
     }
 }
 
@@ -215,3 +225,47 @@ fn resource_name_to_path(root_dir: &Path, location: &ResourcePath) -> PathBuf {
 
     path
 }
+
+#[cfg(test)]
+mod test {
+    use crate::backend::{Backend, FileSystem};
+    use crate::Error;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = env::temp_dir().join(format!("resources-filesystem-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&root);
+        root
+    }
+
+    #[test]
+    fn test_write_creates_missing_directories() {
+        let root = temp_root("write");
+        let mut be = FileSystem::from_path(&root).with_write();
+
+        be.write("a/b/x.txt".into(), b"hello world").unwrap();
+
+        assert_eq!(b"hello world", &fs::read(root.join("a").join("b").join("x.txt")).unwrap()[..]);
+        assert_eq!(b"hello world", &be.read_vec("a/b/x.txt".into()).unwrap()[..]);
+
+        be.write("a/b/x.txt".into(), b"hi").unwrap();
+        assert_eq!(b"hi", &be.read_vec("a/b/x.txt".into()).unwrap()[..]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_can_not_write_without_write_enabled() {
+        let root = temp_root("read_only");
+        let mut be = FileSystem::from_path(&root);
+
+        match be.write("x.txt".into(), b"hello world") {
+            Err(Error::NotWritable) => (),
+            other => panic!("expected not writable error, got {:?}", other),
+        }
+        assert!(!root.exists());
+    }
+}